@@ -0,0 +1,571 @@
+//! Ex-style commands which are typed in to the command line after pressing `:`.
+
+use crate::{
+    config::Key,
+    editor::autocmd::{self, Autocmd},
+    keymap,
+};
+use anyhow::{bail, Context};
+use std::{ops::Range, str::FromStr, time::Duration};
+
+/// A parsed command from the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Change some [`Settings`].
+    ///
+    /// [`Settings`]: crate::settings::Settings
+    Set(Vec<String>),
+    /// Replace the whole buffer with the output of a shell command which is given the buffer as
+    /// its input (`:%!cmd`).
+    Filter(String),
+    /// Map a sequence of keys to another in the [`Keymap`].
+    ///
+    /// [`Keymap`]: crate::keymap::Keymap
+    Map {
+        /// The keys being mapped.
+        lhs: Vec<Key>,
+        /// The keys typed instead of `lhs`.
+        rhs: Vec<Key>,
+        /// Whether `rhs` is checked for mappings too (`:map`, as opposed to `:noremap`).
+        recursive: bool,
+    },
+    /// List the mappings in the [`Keymap`] starting with `lhs`, or all of them when it is
+    /// [`None`] (`:map` with fewer arguments).
+    ///
+    /// With `verbose` (`:verbose map`), where each mapping came from is shown too, along with the
+    /// built-in meaning of `lhs` if it isn't mapped.
+    ///
+    /// [`Keymap`]: crate::keymap::Keymap
+    ListMaps {
+        /// The keys the listed mappings start with.
+        lhs: Option<Vec<Key>>,
+        /// Whether to show where the mappings came from.
+        verbose: bool,
+    },
+    /// Remove a mapping from the [`Keymap`].
+    ///
+    /// [`Keymap`]: crate::keymap::Keymap
+    Unmap(Vec<Key>),
+    /// List the contents of the registers.
+    Registers,
+    /// Go back through the undo history, regardless of branches.
+    Earlier(UndoStep),
+    /// Go forward through the undo history, regardless of branches.
+    Later(UndoStep),
+    /// Write the buffer, to the given file if there is one (`:w name`).
+    Write {
+        /// The file to write to instead of the buffer's own.
+        fname: Option<String>,
+        /// Whether to overwrite a file which has changed or already exists (`:w!`).
+        force: bool,
+    },
+    /// Close the focused window, quitting the editor if it is the last one (`:q`).
+    Quit {
+        /// Whether to quit even if a buffer is modified, throwing away the changes (`:q!`).
+        force: bool,
+    },
+    /// Write the buffer, then close the focused window like [`Command::Quit`] (`:wq`).
+    WriteQuit {
+        /// Whether to write like `:w!` does.
+        force: bool,
+    },
+    /// Add the buffer, or just some of its lines, to the end of a file (`:5,10w >> name`).
+    Append {
+        /// The file to add to, which is created if it doesn't exist.
+        fname: String,
+        /// The lines to add, counting from 0, or [`None`] for the whole buffer.
+        lines: Option<Range<usize>>,
+    },
+    /// Edit a new empty buffer with no file.
+    NewBuffer,
+    /// Read the selected buffer's file again, throwing away any changes (`:e!`).
+    Reload,
+    /// Close the selected buffer, even if it is modified when `force` is set (`:bd!`).
+    DeleteBuffer {
+        /// Whether changes to the buffer are thrown away rather than stopping it being closed.
+        force: bool,
+    },
+    /// Show the jump list to pick a position from.
+    Jumps,
+    /// Show the change list of the buffer to pick a position from.
+    Changes,
+    /// Show how the buffer differs from its file on disk.
+    Diff,
+    /// Split the focused window in two.
+    Split,
+    /// Close the focused window.
+    Close,
+    /// Close every window but the focused one.
+    Only,
+    /// Switch to the next buffer.
+    NextBuffer,
+    /// Switch to the previous buffer.
+    PrevBuffer,
+    /// Add an [`Autocmd`], or list them all when there isn't one (`:autocmd`).
+    Autocmd(Option<Autocmd>),
+    /// Delete every line containing `pattern` (`:g/pattern/d`), or every line without it when
+    /// `invert` is set (`:v/pattern/d` or `:g!/pattern/d`).
+    Global {
+        /// The text the lines are checked for.
+        pattern: String,
+        /// Whether the lines without the pattern are deleted instead.
+        invert: bool,
+    },
+    /// Go to a line, counting from 1 (`:42`). A line past the end goes to the last line.
+    GotoLine(usize),
+}
+
+/// How far to move through the undo history with `:earlier` and `:later`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoStep {
+    /// Move by this many changes.
+    Count(usize),
+    /// Move by this much time, like `:earlier 5m`.
+    Time(Duration),
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(cmd) = s.strip_prefix("%!") {
+            if cmd.trim().is_empty() {
+                bail!("No filter command given");
+            }
+            return Ok(Command::Filter(cmd.trim().to_owned()));
+        }
+        if let Some(command) = global(s)? {
+            return Ok(command);
+        }
+        let (lines, s) = line_range(s)?;
+        if let Some(fname) = ["write", "w"]
+            .into_iter()
+            .find_map(|name| s.strip_prefix(name)?.trim_start().strip_prefix(">>"))
+        {
+            let fname = fname.trim();
+            if fname.is_empty() {
+                bail!("No file name");
+            }
+            return Ok(Command::Append {
+                fname: fname.to_owned(),
+                lines,
+            });
+        }
+        if lines.is_some() {
+            bail!("Only :w >> can be given a range of lines");
+        }
+        let (name, args) = match s.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim_start()),
+            None => (s, ""),
+        };
+        Ok(match name {
+            "se" | "set" => Command::Set(args.split_whitespace().map(str::to_owned).collect()),
+            "map" => map(args, true)?,
+            "no" | "noremap" => map(args, false)?,
+            "verb" | "verbose" => match args.parse()? {
+                Command::ListMaps { lhs, .. } => Command::ListMaps { lhs, verbose: true },
+                _ => bail!("Only :map can be used with :verbose"),
+            },
+            "unm" | "unmap" => Command::Unmap(keymap::parse_keys(args)?),
+            "reg" | "registers" | "di" | "display" => Command::Registers,
+            "ea" | "earlier" => Command::Earlier(undo_step(args)?),
+            "lat" | "later" => Command::Later(undo_step(args)?),
+            "w" | "write" | "w!" | "write!" => Command::Write {
+                fname: Some(args)
+                    .filter(|args| !args.is_empty())
+                    .map(str::to_owned),
+                force: name.ends_with('!'),
+            },
+            "q" | "quit" | "q!" | "quit!" if args.is_empty() => Command::Quit {
+                force: name.ends_with('!'),
+            },
+            "wq" | "wq!" if args.is_empty() => Command::WriteQuit {
+                force: name.ends_with('!'),
+            },
+            "ene" | "enew" => Command::NewBuffer,
+            "e!" | "edit!" if args.is_empty() => Command::Reload,
+            "bd" | "bdelete" => Command::DeleteBuffer { force: false },
+            "bd!" | "bdelete!" => Command::DeleteBuffer { force: true },
+            "ju" | "jumps" => Command::Jumps,
+            "changes" => Command::Changes,
+            "diff" => Command::Diff,
+            "sp" | "split" => Command::Split,
+            "clo" | "close" => Command::Close,
+            "on" | "only" => Command::Only,
+            "bn" | "bnext" => Command::NextBuffer,
+            "bp" | "bprevious" | "bN" | "bNext" => Command::PrevBuffer,
+            "au" | "autocmd" => Command::Autocmd(autocmd_definition(args)?),
+            _ if !name.is_empty()
+                && name.bytes().all(|b| b.is_ascii_digit())
+                && args.is_empty() =>
+            {
+                // Only a number too big for a line of any buffer can fail to parse.
+                Command::GotoLine(name.parse().unwrap_or(usize::MAX))
+            }
+            "" => bail!("No command given"),
+            _ => bail!("Not an editor command: {s}"),
+        })
+    }
+}
+
+/// Parse the arguments to `:map` or `:noremap`, which are the keys being mapped and then what
+/// they are mapped to.
+///
+/// Without what they are mapped to, the mappings are listed instead.
+fn map(args: &str, recursive: bool) -> anyhow::Result<Command> {
+    let Some((lhs, rhs)) = args.split_once(char::is_whitespace) else {
+        let lhs = Some(args)
+            .filter(|args| !args.is_empty())
+            .map(keymap::parse_keys)
+            .transpose()?;
+        return Ok(Command::ListMaps {
+            lhs,
+            verbose: false,
+        });
+    };
+    Ok(Command::Map {
+        lhs: keymap::parse_keys(lhs)?,
+        rhs: keymap::parse_keys(rhs.trim_start())?,
+        recursive,
+    })
+}
+
+/// Parse the arguments to `:autocmd`, which are the event, the file pattern and then the command
+/// to run. With no arguments, there is no autocommand to add.
+fn autocmd_definition(args: &str) -> anyhow::Result<Option<Autocmd>> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    let Some((event, rest)) = args.split_once(char::is_whitespace) else {
+        bail!("Not enough arguments");
+    };
+    let Some((pattern, command)) = rest.trim_start().split_once(char::is_whitespace) else {
+        bail!("Not enough arguments");
+    };
+    let command = command.trim();
+    if command.is_empty() {
+        bail!("Not enough arguments");
+    }
+    Ok(Some(Autocmd {
+        event: autocmd::parse_event(event)?,
+        pattern: pattern.to_owned(),
+        command: command.to_owned(),
+    }))
+}
+
+/// Parse `:g/pattern/d`, `:g!/pattern/d` or `:v/pattern/d`, returning [`None`] if `s` isn't one
+/// of them.
+///
+/// Like vim, any punctuation can be used to delimit the pattern instead of `/`.
+fn global(s: &str) -> anyhow::Result<Option<Command>> {
+    let names = [
+        ("global!", true),
+        ("global", false),
+        ("vglobal", true),
+        ("g!", true),
+        ("g", false),
+        ("v", true),
+    ];
+    let Some((rest, invert)) = names.into_iter().find_map(|(name, invert)| {
+        let rest = s.strip_prefix(name)?;
+        rest.starts_with(|c: char| c.is_ascii_punctuation() && !matches!(c, '\\' | '"' | '|'))
+            .then_some((rest, invert))
+    }) else {
+        return Ok(None);
+    };
+    let mut chars = rest.chars();
+    let delimiter = chars.next().unwrap_or_default();
+    let Some((pattern, command)) = chars.as_str().split_once(delimiter) else {
+        bail!("Missing {delimiter} after the pattern");
+    };
+    if pattern.is_empty() {
+        bail!("No pattern given");
+    }
+    match command.trim() {
+        "d" | "delete" => {}
+        "" => bail!("No command given"),
+        command => bail!("Only d can be used with :global, not {command}"),
+    }
+    Ok(Some(Command::Global {
+        pattern: pattern.to_owned(),
+        invert,
+    }))
+}
+
+/// Split a range of lines like `5,10` off the start of `s`, which counts lines from 1 and
+/// includes both ends.
+///
+/// The range is returned counting from 0 and excluding the end, along with the rest of `s`.
+fn line_range(s: &str) -> anyhow::Result<(Option<Range<usize>>, &str)> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let first_len = digits(s);
+    let Some(rest) = s[first_len..].strip_prefix(',').filter(|_| first_len > 0) else {
+        return Ok((None, s));
+    };
+    let last_len = digits(rest);
+    let (first, last) = (&s[..first_len], &rest[..last_len]);
+    let first: usize = first
+        .parse()
+        .with_context(|| format!("Invalid line number: {first}"))?;
+    let last: usize = last
+        .parse()
+        .with_context(|| format!("Invalid line number: {last}"))?;
+    if first == 0 || last < first {
+        bail!("Invalid range");
+    }
+    Ok((Some(first - 1..last), rest[last_len..].trim_start()))
+}
+
+/// Parse the argument to `:earlier` or `:later`.
+///
+/// This is a count of changes, which defaults to 1, or an amount of time with a unit of `s`, `m`,
+/// `h` or `d`.
+fn undo_step(args: &str) -> anyhow::Result<UndoStep> {
+    if args.is_empty() {
+        return Ok(UndoStep::Count(1));
+    }
+    let unit = match args.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => {
+            let count = args
+                .parse()
+                .with_context(|| format!("Invalid count: {args}"))?;
+            return Ok(UndoStep::Count(count));
+        }
+    };
+    let amount: u64 = args[..args.len() - 1]
+        .parse()
+        .with_context(|| format!("Invalid time: {args}"))?;
+    Ok(UndoStep::Time(Duration::from_secs(
+        amount.saturating_mul(unit),
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing_set() {
+        assert_eq!(
+            "set spell nowrap".parse::<Command>().unwrap(),
+            Command::Set(vec!["spell".to_owned(), "nowrap".to_owned()])
+        );
+        assert_eq!(
+            "  se   spell ".parse::<Command>().unwrap(),
+            Command::Set(vec!["spell".to_owned()])
+        );
+        assert!("frobnicate".parse::<Command>().is_err());
+        assert!("".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parsing_filter() {
+        assert_eq!(
+            "%! sort -u".parse::<Command>().unwrap(),
+            Command::Filter("sort -u".to_owned())
+        );
+        assert!("%!  ".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parsing_counts() {
+        assert_eq!(
+            "earlier".parse::<Command>().unwrap(),
+            Command::Earlier(UndoStep::Count(1))
+        );
+        assert_eq!(
+            "ea 3".parse::<Command>().unwrap(),
+            Command::Earlier(UndoStep::Count(3))
+        );
+        assert_eq!(
+            "later 2".parse::<Command>().unwrap(),
+            Command::Later(UndoStep::Count(2))
+        );
+        assert!("later two".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parsing_times() {
+        assert_eq!(
+            "earlier 5m".parse::<Command>().unwrap(),
+            Command::Earlier(UndoStep::Time(Duration::from_secs(300)))
+        );
+        assert_eq!(
+            "lat 10s".parse::<Command>().unwrap(),
+            Command::Later(UndoStep::Time(Duration::from_secs(10)))
+        );
+        assert_eq!(
+            "ea 1h".parse::<Command>().unwrap(),
+            Command::Earlier(UndoStep::Time(Duration::from_secs(3600)))
+        );
+        assert!("earlier 5x".parse::<Command>().is_err());
+        assert!("earlier m".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parsing_global() {
+        assert_eq!(
+            "g/foo/d".parse::<Command>().unwrap(),
+            Command::Global {
+                pattern: "foo".to_owned(),
+                invert: false
+            }
+        );
+        assert_eq!(
+            "v#a/b#delete".parse::<Command>().unwrap(),
+            Command::Global {
+                pattern: "a/b".to_owned(),
+                invert: true
+            }
+        );
+        assert_eq!(
+            "global!/foo bar/d".parse::<Command>().unwrap(),
+            Command::Global {
+                pattern: "foo bar".to_owned(),
+                invert: true
+            }
+        );
+        assert!("g/foo".parse::<Command>().is_err());
+        assert!("g//d".parse::<Command>().is_err());
+        assert!("g/foo/s/a/b/".parse::<Command>().is_err());
+        // Commands which only start with g or v aren't mistaken for it.
+        assert!(matches!(
+            "verbose map".parse::<Command>().unwrap(),
+            Command::ListMaps { .. }
+        ));
+    }
+
+    #[test]
+    fn parsing_appends() {
+        assert_eq!(
+            "w >> notes.txt".parse::<Command>().unwrap(),
+            Command::Append {
+                fname: "notes.txt".to_owned(),
+                lines: None
+            }
+        );
+        assert_eq!(
+            "5,10write>>notes.txt".parse::<Command>().unwrap(),
+            Command::Append {
+                fname: "notes.txt".to_owned(),
+                lines: Some(4..10)
+            }
+        );
+        assert!("w >>".parse::<Command>().is_err());
+        assert!("10,5w >> notes.txt".parse::<Command>().is_err());
+        assert!("0,5w >> notes.txt".parse::<Command>().is_err());
+        assert!("1,2bn".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parsing_line_numbers() {
+        assert_eq!("42".parse::<Command>().unwrap(), Command::GotoLine(42));
+        assert_eq!("0".parse::<Command>().unwrap(), Command::GotoLine(0));
+        assert_eq!(
+            "99999999999999999999999".parse::<Command>().unwrap(),
+            Command::GotoLine(usize::MAX)
+        );
+        assert!("4 2".parse::<Command>().is_err());
+        assert!("4x".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parsing_buffer_commands() {
+        assert_eq!("bn".parse::<Command>().unwrap(), Command::NextBuffer);
+        assert_eq!("bNext".parse::<Command>().unwrap(), Command::PrevBuffer);
+        assert_eq!("enew".parse::<Command>().unwrap(), Command::NewBuffer);
+        assert_eq!("e!".parse::<Command>().unwrap(), Command::Reload);
+        assert_eq!(
+            "bd!".parse::<Command>().unwrap(),
+            Command::DeleteBuffer { force: true }
+        );
+        assert_eq!(
+            "w".parse::<Command>().unwrap(),
+            Command::Write {
+                fname: None,
+                force: false
+            }
+        );
+        assert_eq!(
+            "w! notes.txt".parse::<Command>().unwrap(),
+            Command::Write {
+                fname: Some("notes.txt".to_owned()),
+                force: true
+            }
+        );
+        assert_eq!(
+            "q".parse::<Command>().unwrap(),
+            Command::Quit { force: false }
+        );
+        assert_eq!(
+            "quit!".parse::<Command>().unwrap(),
+            Command::Quit { force: true }
+        );
+        assert_eq!(
+            "wq".parse::<Command>().unwrap(),
+            Command::WriteQuit { force: false }
+        );
+        assert!("q notes.txt".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parsing_maps() {
+        assert_eq!(
+            "noremap ,w :w<CR>".parse::<Command>().unwrap(),
+            Command::Map {
+                lhs: keymap::parse_keys(",w").unwrap(),
+                rhs: keymap::parse_keys(":w<CR>").unwrap(),
+                recursive: false,
+            }
+        );
+        assert!(matches!(
+            "map Q gq".parse::<Command>().unwrap(),
+            Command::Map {
+                recursive: true,
+                ..
+            }
+        ));
+        assert_eq!(
+            "map Q".parse::<Command>().unwrap(),
+            Command::ListMaps {
+                lhs: Some(vec![Key::from('Q')]),
+                verbose: false
+            }
+        );
+        assert_eq!(
+            "verbose map".parse::<Command>().unwrap(),
+            Command::ListMaps {
+                lhs: None,
+                verbose: true
+            }
+        );
+        assert!("verbose set spell".parse::<Command>().is_err());
+        assert_eq!(
+            "unmap Q".parse::<Command>().unwrap(),
+            Command::Unmap(vec![Key::from('Q')])
+        );
+    }
+
+    #[test]
+    fn parsing_autocmds() {
+        assert_eq!(
+            "au  BufWrite *.md   set spell".parse::<Command>().unwrap(),
+            Command::Autocmd(Some(Autocmd {
+                event: crate::editor::hooks::HookKind::BufWrite,
+                pattern: "*.md".to_owned(),
+                command: "set spell".to_owned(),
+            }))
+        );
+        assert_eq!(
+            "autocmd".parse::<Command>().unwrap(),
+            Command::Autocmd(None)
+        );
+        assert!("autocmd BufWrite *.md".parse::<Command>().is_err());
+        assert!("autocmd Frobnicate * set spell".parse::<Command>().is_err());
+    }
+}
@@ -4,32 +4,495 @@
 
 pub use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-use crate::editor::Mode;
+use crate::{
+    editor::{hooks::HookKind, Mode},
+    keymap::Keymap,
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-/// Read an event and translate it into a [`Message`].
+/// Translates [`Key`]s into [`Message`]s.
 ///
 /// This provides an easily-configurable layer in which to transform from user events to actions
 /// for the editor.
-pub fn translate_event(mode: Mode, key: Key) -> Message {
-    match mode {
-        Mode::Normal => normal_mode_event(key),
-        Mode::Insert => insert_mode_event(key),
-    }
+/// It keeps track of the keys typed so far so multi-key commands like `]s` can be recognized.
+///
+/// Keys are usually given to the [`Translator`] with [`Translator::push`] and then translated by
+/// [`Translator::next`], which applies any mappings in a [`Keymap`] first.
+#[derive(Debug)]
+pub struct Translator {
+    /// The keys which still need translating.
+    input: VecDeque<Input>,
+    /// The start of a multi-key command, if one has been typed.
+    pending: Option<Pending>,
+    /// The count typed before a normal mode command, like the 50 in `50%`.
+    count: Option<usize>,
+    /// A message translated along with the last one, which [`Translator::next`] returns next.
+    queued: Option<Message>,
+    /// The normal mode commands made of a prefix key followed by a second key.
+    ///
+    /// Maps each prefix to the commands which start with it. See [`Translator::register`].
+    prefixed: HashMap<char, HashMap<Key, Message>>,
+    /// Keys which are never treated as the start of a multi-key command.
+    ///
+    /// See [`Translator::set_immediate`].
+    immediate: HashSet<char>,
+    /// The two chars which leave insert mode when typed quickly one after the other.
+    ///
+    /// See [`Translator::set_insert_escape`].
+    insert_escape: Option<[char; 2]>,
+    /// When the first char of [`Translator::insert_escape`] was typed, if it was the last key
+    /// typed in insert mode.
+    escape_started: Option<Instant>,
+    /// Whether typing inserts text in normal mode too.
+    ///
+    /// See [`Translator::set_modeless`].
+    modeless: bool,
 }
 
-/// Translate a [`KeyEvent`] into a [`Message`] for normal mode.
-fn normal_mode_event(key: Key) -> Message {
-    match key {
-        Key {
-            code: KeyCode::Char('q'),
-            modifiers: KeyModifiers::NONE,
-        } => Message::Quit,
+/// The start of a multi-key command which is waiting for its next key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    /// A prefix of one of the commands in [`Translator::register`].
+    Prefix(char),
+    /// `"`, which is followed by the name of a register.
+    Register,
+    /// `Ctrl-r` in insert mode, which is followed by the name of a register to insert.
+    InsertRegister,
+    /// `r` in normal mode, which is followed by the char to replace the one under the cursor with.
+    Replace,
+    /// `Ctrl-w` in normal mode, which is followed by a window command.
+    Window,
+    /// `Esc` when editing modelessly, which is followed by a normal mode command.
+    Command,
+}
 
-        Key {
+/// A key waiting to be translated.
+#[derive(Debug, Clone, Copy)]
+struct Input {
+    /// The key itself.
+    key: Key,
+    /// Whether mappings apply to this key.
+    ///
+    /// This is `false` for keys which came from a non-recursive mapping.
+    remap: bool,
+    /// How many mappings were expanded to produce this key.
+    depth: usize,
+    /// When the key was typed, or when the key which was mapped to it was typed.
+    time: Instant,
+}
+
+impl Translator {
+    /// Queue `key` up to be translated by [`Translator::next`].
+    pub fn push(&mut self, key: Key) {
+        self.push_at(key, Instant::now());
+    }
+
+    /// Queue `key`, which was typed at `time`, up to be translated by [`Translator::next`].
+    pub fn push_at(&mut self, key: Key, time: Instant) {
+        self.input.push_back(Input {
+            key,
+            remap: true,
+            depth: 0,
+            time,
+        });
+    }
+
+    /// Translate the next key which was pushed into a [`Message`], applying the mappings in
+    /// `keymap` in normal and visual mode.
+    ///
+    /// Returns [`None`] when there's nothing left to translate, or when the keys so far could be
+    /// the start of a mapping and more are needed to tell. A mapping which keeps expanding in to
+    /// itself is stopped with [`Message::RecursiveMapping`], throwing away the rest of the keys.
+    pub fn next(&mut self, mode: Mode, keymap: &Keymap) -> Option<Message> {
+        if let Some(message) = self.queued.take() {
+            return Some(message);
+        }
+        loop {
+            let first = self.input.front()?;
+            let typing = self.modeless && mode == Mode::Normal;
+            if first.remap
+                && self.pending.is_none()
+                && !typing
+                && matches!(mode, Mode::Normal | Mode::Visual)
+            {
+                let keys: Vec<Key> = self
+                    .input
+                    .iter()
+                    .take_while(|input| input.remap)
+                    .map(|input| input.key)
+                    .collect();
+                if keys.len() == self.input.len() && keymap.is_prefix(&keys) {
+                    return None;
+                }
+                if let Some((len, mapping)) = keymap.find(&keys) {
+                    let time = first.time;
+                    let depth = self.input.drain(..len).map(|input| input.depth).max();
+                    let depth = depth.unwrap_or(0) + 1;
+                    if depth > MAX_MAP_DEPTH {
+                        self.input.clear();
+                        return Some(Message::RecursiveMapping);
+                    }
+                    for &key in mapping.rhs.iter().rev() {
+                        self.input.push_front(Input {
+                            key,
+                            remap: mapping.recursive,
+                            depth,
+                            time,
+                        });
+                    }
+                    continue;
+                }
+            }
+            let input = self.input.pop_front()?;
+            if mode == Mode::Insert && self.pending.is_none() && self.insert_escape(input) {
+                return Some(Message::Backspace);
+            }
+            return Some(self.translate(mode, input.key));
+        }
+    }
+
+    /// Check whether `input` finishes the [`Translator::insert_escape`] sequence in insert mode.
+    ///
+    /// If it does, the first char of the sequence has already been inserted, so it needs to be
+    /// deleted with a backspace before an `Esc` which is queued up here.
+    fn insert_escape(&mut self, input: Input) -> bool {
+        let started = self.escape_started.take();
+        let Some([first, second]) = self.insert_escape else {
+            return false;
+        };
+        let quick = started.is_some_and(|started| {
+            input.time.saturating_duration_since(started) <= INSERT_ESCAPE_TIMEOUT
+        });
+        if quick && input.key == Key::from(second) {
+            self.input.push_front(Input {
+                key: Key {
+                    code: KeyCode::Esc,
+                    modifiers: KeyModifiers::NONE,
+                },
+                remap: false,
+                ..input
+            });
+            return true;
+        }
+        if input.key == Key::from(first) {
+            self.escape_started = Some(input.time);
+        }
+        false
+    }
+
+    /// Read an event and translate it into a [`Message`].
+    ///
+    /// This only knows about the built-in meanings of keys; see [`Translator::next`] for
+    /// mappings.
+    ///
+    /// Keys which start a multi-key command produce [`Message::None`] until the command is
+    /// finished.
+    pub fn translate(&mut self, mode: Mode, key: Key) -> Message {
+        if let Some(pending) = self.pending.take() {
+            let typed = match key {
+                Key {
+                    code: KeyCode::Char(c),
+                    modifiers: KeyModifiers::NONE,
+                } => Some(c),
+                _ => None,
+            };
+            return match pending {
+                Pending::Prefix(prefix) => self.prefixed_event(prefix, key),
+                Pending::Register => typed.map_or(Message::None, Message::SelectRegister),
+                Pending::InsertRegister => typed.map_or(Message::None, Message::InsertRegister),
+                Pending::Replace => typed.map_or(Message::None, Message::ReplaceChar),
+                Pending::Window => window_event(key),
+                Pending::Command => self.normal_key(key),
+            };
+        }
+        if mode != Mode::Normal {
+            self.count = None;
+        }
+        if self.modeless {
+            if let Some(message) = self.modeless_event(mode, key) {
+                return message;
+            }
+        }
+        if mode == Mode::Visual && key == Key::from('"') {
+            self.pending = Some(Pending::Register);
+            return Message::None;
+        }
+        // So `g Ctrl-g` can tell how much is selected.
+        if mode == Mode::Visual && key == Key::from('g') {
+            self.pending = Some(Pending::Prefix('g'));
+            return Message::None;
+        }
+        let ctrl_r = Key {
+            code: KeyCode::Char('r'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        if mode == Mode::Insert && key == ctrl_r {
+            self.pending = Some(Pending::InsertRegister);
+            return Message::None;
+        }
+        match mode {
+            Mode::Normal => self.normal_key(key),
+            Mode::Insert => insert_mode_event(key),
+            Mode::Visual => visual_mode_event(key),
+            Mode::Command | Mode::Search => command_mode_event(key),
+            Mode::List => list_mode_event(key),
+            Mode::Confirm => confirm_mode_event(key),
+        }
+    }
+
+    /// Translate `key` in normal mode, starting a multi-key command if it is the first key of one.
+    ///
+    /// Digits are a count for the next key, which is dropped if that key doesn't take one. A
+    /// `0` which doesn't follow another digit isn't part of a count. Motions which take a count
+    /// are given it with a [`Message::Count`] just before them.
+    fn normal_key(&mut self, key: Key) -> Message {
+        let ctrl_w = Key {
             code: KeyCode::Char('w'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        let count = self.count.take();
+        match key {
+            Key {
+                code: KeyCode::Char(c @ '0'..='9'),
+                modifiers: KeyModifiers::NONE,
+            } if c != '0' || count.is_some() => {
+                let digit = c.to_digit(10).unwrap_or_default() as usize;
+                self.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                Message::None
+            }
+            Key {
+                code: KeyCode::Char('%'),
+                modifiers: KeyModifiers::NONE,
+            } if count.is_some() => Message::GotoPercent(count.unwrap_or_default()),
+            Key {
+                code: KeyCode::Char('"'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.pending = Some(Pending::Register);
+                Message::None
+            }
+            _ if key == ctrl_w => {
+                self.pending = Some(Pending::Window);
+                Message::None
+            }
+            Key {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.pending = Some(Pending::Replace);
+                Message::None
+            }
+            Key {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+            } if self.prefixed.contains_key(&c) && !self.immediate.contains(&c) => {
+                self.pending = Some(Pending::Prefix(c));
+                Message::None
+            }
+            _ => {
+                let message = normal_mode_event(key);
+                match count {
+                    Some(count) if takes_count(message) => {
+                        self.queued = Some(message);
+                        Message::Count(count)
+                    }
+                    _ => message,
+                }
+            }
+        }
+    }
+
+    /// Translate `key` when editing modelessly, or return [`None`] if it means what it usually
+    /// does in `mode`.
+    ///
+    /// In normal mode, keys which type text type it and `Ctrl-a` selects everything, as it does
+    /// in a conventional editor. `Esc` makes the next key a normal mode command instead, leaving
+    /// insert mode first if needed.
+    fn modeless_event(&mut self, mode: Mode, key: Key) -> Option<Message> {
+        let esc = Key {
+            code: KeyCode::Esc,
             modifiers: KeyModifiers::NONE,
-        } => Message::Write,
+        };
+        let ctrl_a = Key {
+            code: KeyCode::Char('a'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        match mode {
+            Mode::Normal | Mode::Insert if key == esc => {
+                self.pending = Some(Pending::Command);
+                Some(match mode {
+                    Mode::Insert => Message::Mode(Mode::Normal),
+                    _ => Message::None,
+                })
+            }
+            Mode::Normal if key == ctrl_a => Some(Message::SelectAll),
+            Mode::Normal => match insert_mode_event(key) {
+                message @ (Message::Char(_) | Message::Enter | Message::Backspace) => Some(message),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Set whether typing inserts text in normal mode too, like in a conventional editor.
+    ///
+    /// Once text is typed, the editor is in insert mode. `Esc` followed by a key then runs that
+    /// key as a normal mode command, such as `Esc :` to open the command line. Mappings don't
+    /// apply to keys typed in normal mode.
+    pub fn set_modeless(&mut self, modeless: bool) {
+        self.modeless = modeless;
+    }
+
+    /// Make typing `prefix` followed by `key` in normal mode produce `message`.
+    ///
+    /// Once a command has been registered for it, `prefix` on its own no longer does anything.
+    pub fn register(&mut self, prefix: char, key: impl Into<Key>, message: Message) {
+        self.prefixed
+            .entry(prefix)
+            .or_default()
+            .insert(key.into(), message);
+    }
+
+    /// Set whether `key` resolves immediately in normal mode.
+    ///
+    /// An immediate key is never treated as a prefix, so it does whatever it does on its own
+    /// straight away and any commands registered with it as their prefix can't be typed.
+    pub fn set_immediate(&mut self, key: char, immediate: bool) {
+        if immediate {
+            self.immediate.insert(key);
+        } else {
+            self.immediate.remove(&key);
+        }
+    }
+
+    /// Set the two chars which leave insert mode when the second is typed within
+    /// [`INSERT_ESCAPE_TIMEOUT`] of the first, like mapping `jk` to `<Esc>` in vim.
+    ///
+    /// The first char is inserted as normal until the second is typed, when it is deleted again.
+    pub fn set_insert_escape(&mut self, sequence: Option<[char; 2]>) {
+        self.insert_escape = sequence;
+        self.escape_started = None;
+    }
+
+    /// Translate the second key of a normal mode multi-key command into a [`Message`].
+    ///
+    /// Anything which isn't a registered command cancels it.
+    fn prefixed_event(&self, prefix: char, key: Key) -> Message {
+        self.prefixed
+            .get(&prefix)
+            .and_then(|commands| commands.get(&key))
+            .copied()
+            .unwrap_or(Message::None)
+    }
+}
+
+impl Default for Translator {
+    fn default() -> Self {
+        let mut translator = Self {
+            input: VecDeque::new(),
+            pending: None,
+            count: None,
+            queued: None,
+            prefixed: HashMap::new(),
+            immediate: IMMEDIATE_KEYS.iter().copied().collect(),
+            insert_escape: INSERT_ESCAPE,
+            escape_started: None,
+            modeless: MODELESS,
+        };
+        translator.register(']', 's', Message::NextMisspelling);
+        translator.register('[', 's', Message::PrevMisspelling);
+        translator.register('z', 'g', Message::SpellGood);
+        translator.register('z', 'w', Message::SpellBad);
+        translator.register('g', 'd', Message::GoToDefinition);
+        translator.register(
+            'g',
+            Key {
+                code: KeyCode::Char('g'),
+                modifiers: KeyModifiers::CONTROL,
+            },
+            Message::CursorInfo,
+        );
+        translator.register('g', 'g', Message::GotoTop);
+        translator.register('y', 'y', Message::YankLine);
+        translator.register('d', 'd', Message::DeleteLine);
+        translator.register('g', '-', Message::Earlier);
+        translator.register('g', '+', Message::Later);
+        translator
+    }
+}
+
+/// The built-in meaning of typing `keys` in normal mode, ignoring any mappings.
+///
+/// This is [`Message::None`] if the keys don't finish a command. If they make up several, this is
+/// the last of them.
+pub fn builtin_binding(keys: &[Key]) -> Message {
+    let mut translator = Translator::default();
+    keys.iter().fold(Message::None, |_, &key| {
+        translator.translate(Mode::Normal, key)
+    })
+}
+
+/// How many mappings may be expanded in to each other before they are assumed to be recursing
+/// forever.
+pub const MAX_MAP_DEPTH: usize = 1000;
+
+/// Keys which are never treated as the start of a multi-key command, even if commands have been
+/// registered with them as their prefix.
+pub const IMMEDIATE_KEYS: &[char] = &[];
 
+/// The mode the editor starts in.
+pub const INITIAL_MODE: Mode = Mode::Normal;
+
+/// Whether typing inserts text without entering insert mode first, like a conventional editor.
+///
+/// See [`Translator::set_modeless`].
+pub const MODELESS: bool = false;
+
+/// The autocommands the editor starts with, as the event, file pattern and command of each, like
+/// `(HookKind::BufOpen, "*.py", "set expandtab")`.
+///
+/// More can be added with `:autocmd`. See [`Autocmd`] for how they are matched.
+///
+/// [`Autocmd`]: crate::editor::autocmd::Autocmd
+pub const AUTOCMDS: &[(HookKind, &str, &str)] = &[];
+
+/// The key mappings the editor starts with, as the keys, what they are mapped to and whether the
+/// mapping is recursive, like `(",w", ":w<CR>", false)`.
+///
+/// The keys are written like vim's, as described in [`keymap`](crate::keymap). More can be added
+/// with `:map` and `:noremap`.
+pub const MAPPINGS: &[(&str, &str, bool)] = &[];
+
+/// Two chars which leave insert mode when typed quickly one after the other, like `jk`.
+///
+/// See [`Translator::set_insert_escape`].
+pub const INSERT_ESCAPE: Option<[char; 2]> = None;
+
+/// The longest gap between the two chars of [`INSERT_ESCAPE`] for them to leave insert mode.
+pub const INSERT_ESCAPE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Whether `message` is a command which a count typed before it applies to, like the 3 in `3j`.
+fn takes_count(message: Message) -> bool {
+    matches!(
+        message,
+        Message::Left
+            | Message::Right
+            | Message::Up
+            | Message::Down
+            | Message::WordStart
+            | Message::WordEnd
+            | Message::GotoBottom
+            | Message::DuplicateLine
+    )
+}
+
+/// Translate a [`KeyEvent`] into a [`Message`] for normal mode.
+fn normal_mode_event(key: Key) -> Message {
+    match key {
         Key {
             code: KeyCode::Left | KeyCode::Char('h'),
             modifiers: KeyModifiers::NONE,
@@ -55,6 +518,240 @@ fn normal_mode_event(key: Key) -> Message {
             modifiers: KeyModifiers::NONE,
         } => Message::Mode(Mode::Insert),
 
+        Key {
+            code: KeyCode::Char(':'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Mode(Mode::Command),
+
+        Key {
+            code: KeyCode::Char('/'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Mode(Mode::Search),
+
+        Key {
+            code: KeyCode::Char('v'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Mode(Mode::Visual),
+
+        Key {
+            code: KeyCode::Char('V'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::VisualLine,
+
+        Key {
+            code: KeyCode::Char('g'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::FileInfo,
+
+        Key {
+            code: KeyCode::Char('t'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::TransposeChars,
+
+        Key {
+            code: KeyCode::Char('%'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::MatchBracket,
+
+        Key {
+            code: KeyCode::Char('e'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::WordEnd,
+
+        Key {
+            code: KeyCode::Char('w'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::WordStart,
+
+        Key {
+            code: KeyCode::Char('0'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::LineStart,
+
+        Key {
+            code: KeyCode::Char('G'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::GotoBottom,
+
+        Key {
+            code: KeyCode::Char('x'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::DeleteChar,
+
+        Key {
+            code: KeyCode::Char('o'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::OpenBelow,
+
+        Key {
+            code: KeyCode::Char('a'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Append,
+
+        Key {
+            code: KeyCode::Char('A'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::AppendEnd,
+
+        Key {
+            code: KeyCode::Char('I'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::InsertLineStart,
+
+        Key {
+            code: KeyCode::Char('J'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::JoinLines,
+
+        Key {
+            code: KeyCode::Char('.'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Repeat,
+
+        Key {
+            code: KeyCode::Char('O'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::OpenAbove,
+
+        Key {
+            code: KeyCode::Char('^'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::FirstNonBlank,
+
+        Key {
+            code: KeyCode::Char('$'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::LineEnd,
+
+        Key {
+            code: KeyCode::Char('d'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::HalfPageDown,
+
+        Key {
+            code: KeyCode::Char('u'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::HalfPageUp,
+
+        Key {
+            code: KeyCode::Char('u'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Undo,
+
+        Key {
+            code: KeyCode::Char('p'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Paste,
+
+        Key {
+            code: KeyCode::Char('*'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::SearchWordForward,
+
+        Key {
+            code: KeyCode::Char('#'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::SearchWordBackward,
+
+        Key {
+            code: KeyCode::Char('n'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::SearchNext,
+
+        Key {
+            code: KeyCode::Char('N'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::SearchPrev,
+
+        Key {
+            code: KeyCode::Char('r'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::Redo,
+
+        DUPLICATE_LINE_KEY => Message::DuplicateLine,
+
+        Key {
+            code: KeyCode::Char('j') | KeyCode::Down,
+            modifiers: KeyModifiers::ALT,
+        } => Message::MoveLineDown,
+
+        Key {
+            code: KeyCode::Char('k') | KeyCode::Up,
+            modifiers: KeyModifiers::ALT,
+        } => Message::MoveLineUp,
+
+        _ => Message::None,
+    }
+}
+
+/// The key which duplicates the line the cursor is on in normal mode.
+pub const DUPLICATE_LINE_KEY: Key = Key {
+    code: KeyCode::Char('d'),
+    modifiers: KeyModifiers::ALT,
+};
+
+/// Translate the key after `Ctrl-w` in normal mode into a [`Message`].
+///
+/// Like vim, the key can be typed with or without `Ctrl` held.
+fn window_event(key: Key) -> Message {
+    if !matches!(key.modifiers, KeyModifiers::NONE | KeyModifiers::CONTROL) {
+        return Message::None;
+    }
+    match key.code {
+        KeyCode::Char('s') => Message::SplitWindow,
+        KeyCode::Char('c') => Message::CloseWindow,
+        KeyCode::Char('o') => Message::OnlyWindow,
+        KeyCode::Char('q') => Message::QuitWindow,
+        KeyCode::Char('w') => Message::NextWindow,
+        KeyCode::Char('j') | KeyCode::Down => Message::WindowDown,
+        KeyCode::Char('k') | KeyCode::Up => Message::WindowUp,
+        KeyCode::Char('=') => Message::EqualizeWindows,
+        KeyCode::Char('+') => Message::GrowWindow,
+        KeyCode::Char('-') => Message::ShrinkWindow,
+        _ => Message::None,
+    }
+}
+
+/// Translate a [`KeyEvent`] into a [`Message`] for list mode.
+fn list_mode_event(key: Key) -> Message {
+    match key {
+        Key {
+            code: KeyCode::Up | KeyCode::Char('k'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::ListUp,
+
+        Key {
+            code: KeyCode::Down | KeyCode::Char('j'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::ListDown,
+
+        Key {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        } => Message::ListPick,
+
+        Key {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Mode(Mode::Normal),
+
+        _ => Message::None,
+    }
+}
+
+/// Translate a [`KeyEvent`] into a [`Message`] for confirm mode.
+fn confirm_mode_event(key: Key) -> Message {
+    match key {
+        Key {
+            code: KeyCode::Char('y' | 'Y'),
+            modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+        } => Message::Confirm(true),
+
+        Key {
+            code: KeyCode::Char('n' | 'N') | KeyCode::Esc,
+            modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+        } => Message::Confirm(false),
+
         _ => Message::None,
     }
 }
@@ -92,9 +789,28 @@ fn insert_mode_event(key: Key) -> Message {
             modifiers: KeyModifiers::NONE,
         } => Message::Down,
 
+        Key {
+            code: KeyCode::Char('a'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::LineStart,
+
+        Key {
+            code: KeyCode::Char('e'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::LineEnd,
+
+        Key {
+            code: KeyCode::Char('t'),
+            modifiers: KeyModifiers::CONTROL,
+        } => Message::TransposeChars,
+
         Key {
             code: KeyCode::Esc,
             modifiers: KeyModifiers::NONE,
+        }
+        | Key {
+            code: KeyCode::Char('['),
+            modifiers: KeyModifiers::CONTROL,
         } => Message::Mode(Mode::Normal),
 
         Key {
@@ -106,8 +822,112 @@ fn insert_mode_event(key: Key) -> Message {
     }
 }
 
+/// Translate a [`KeyEvent`] into a [`Message`] for visual mode.
+fn visual_mode_event(key: Key) -> Message {
+    match key {
+        Key {
+            code: KeyCode::Left | KeyCode::Char('h'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Left,
+
+        Key {
+            code: KeyCode::Right | KeyCode::Char('l'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Right,
+
+        Key {
+            code: KeyCode::Up | KeyCode::Char('k'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Up,
+
+        Key {
+            code: KeyCode::Down | KeyCode::Char('j'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Down,
+
+        Key {
+            code: KeyCode::Esc | KeyCode::Char('v'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::Mode(Mode::Normal),
+
+        Key {
+            code: KeyCode::Char('V'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::VisualLine,
+
+        Key {
+            code: KeyCode::Char('e'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::WordEnd,
+
+        Key {
+            code: KeyCode::Char('w'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::WordStart,
+
+        Key {
+            code: KeyCode::Char('d' | 'x'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::DeleteSelection,
+
+        Key {
+            code: KeyCode::Char('s'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::ReplaceSelection,
+
+        Key {
+            code: KeyCode::Char('y'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::YankSelection,
+
+        Key {
+            code: KeyCode::Char('K'),
+            modifiers: KeyModifiers::NONE,
+        } => Message::KeepSelection,
+
+        Key {
+            code: KeyCode::Char('j') | KeyCode::Down,
+            modifiers: KeyModifiers::ALT,
+        } => Message::MoveLineDown,
+
+        Key {
+            code: KeyCode::Char('k') | KeyCode::Up,
+            modifiers: KeyModifiers::ALT,
+        } => Message::MoveLineUp,
+
+        _ => Message::None,
+    }
+}
+
+/// Translate a [`KeyEvent`] into a [`Message`] for command mode.
+fn command_mode_event(key: Key) -> Message {
+    match key {
+        Key {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        } => Message::RunCommand,
+
+        Key {
+            code: KeyCode::Backspace,
+            modifiers: KeyModifiers::NONE,
+        } => Message::CommandBackspace,
+
+        Key {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+        } => Message::Mode(Mode::Normal),
+
+        Key {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+        } => Message::CommandChar(c),
+
+        _ => Message::None,
+    }
+}
+
 /// An enumeration of all possible actions the editor could take.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Message {
     /// Quit the editor.
     Quit,
@@ -125,10 +945,162 @@ pub enum Message {
     Up,
     /// Move the cursor down.
     Down,
+    /// Move the cursor to the line this percentage of the way through the buffer.
+    GotoPercent(usize),
+    /// Use this count for the motion which comes next, which moves this many times instead of
+    /// once.
+    Count(usize),
+    /// Move the cursor to the first line of the buffer.
+    GotoTop,
+    /// Move the cursor to the last line of the buffer, or to line [`Message::Count`] if one was
+    /// typed first.
+    GotoBottom,
+    /// Move the cursor to the start of the next word.
+    WordStart,
+    /// Move the cursor to the end of the word, or of the next word if it is already there.
+    WordEnd,
+    /// Move the cursor to the bracket matching the one under or after it on the line.
+    MatchBracket,
+    /// Scroll the window and the cursor down by [`Settings::scroll`] lines.
+    ///
+    /// [`Settings::scroll`]: crate::settings::Settings::scroll
+    HalfPageDown,
+    /// Scroll the window and the cursor up by [`Settings::scroll`] lines.
+    ///
+    /// [`Settings::scroll`]: crate::settings::Settings::scroll
+    HalfPageUp,
+    /// Move the cursor to the start of the line.
+    LineStart,
+    /// Move the cursor to the first character on the line which isn't a space or tab.
+    FirstNonBlank,
+    /// Move the cursor to the end of the line, or onto its last character outside of insert mode.
+    LineEnd,
     /// Insert a character.
     Char(char),
     /// Enter a given [`Mode`].
     Mode(Mode),
+    /// Select whole lines in [`Mode::Visual`], or stop if they already are.
+    VisualLine,
+    /// Select every line of the buffer in [`Mode::Visual`].
+    SelectAll,
+    /// Type a character in to the command line.
+    CommandChar(char),
+    /// Delete the last character of the command line.
+    CommandBackspace,
+    /// Run what has been typed in to the command line.
+    RunCommand,
+    /// Move the cursor to the next misspelled word.
+    NextMisspelling,
+    /// Move the cursor to the previous misspelled word.
+    PrevMisspelling,
+    /// Add the word under the cursor to the user's spell checking dictionary.
+    SpellGood,
+    /// Mark the word under the cursor as misspelled in the user's spell checking dictionary.
+    SpellBad,
+    /// Go to the definition of what is under the cursor, as found by the language server.
+    GoToDefinition,
+    /// Show a summary of the current file.
+    FileInfo,
+    /// Show where the cursor is in the current file.
+    CursorInfo,
+    /// Undo the last change.
+    Undo,
+    /// Redo the last change which was undone.
+    Redo,
+    /// Go back to the previous state of the buffer in time, even if it is on another branch of
+    /// the undo tree.
+    Earlier,
+    /// Search forwards for the word under the cursor.
+    SearchWordForward,
+    /// Search backwards for the word under the cursor.
+    SearchWordBackward,
+    /// Go to the next match of the last search.
+    SearchNext,
+    /// Go to the next match of the last search, in the opposite direction.
+    SearchPrev,
+    /// A mapping kept expanding in to itself and was stopped.
+    RecursiveMapping,
+    /// The terminal changed to the given width and height.
+    Resize(u16, u16),
+    /// The terminal lost focus.
+    FocusLost,
+    /// Split the focused window in two.
+    SplitWindow,
+    /// Close the focused window.
+    CloseWindow,
+    /// Close every window but the focused one.
+    OnlyWindow,
+    /// Close the focused window, quitting if it is the last one.
+    QuitWindow,
+    /// Focus the next window, wrapping around to the first.
+    NextWindow,
+    /// Focus the window below.
+    WindowDown,
+    /// Focus the window above.
+    WindowUp,
+    /// Make every window the same size.
+    EqualizeWindows,
+    /// Make the focused window a row taller.
+    GrowWindow,
+    /// Make the focused window a row shorter.
+    ShrinkWindow,
+    /// Select the entry above in the list.
+    ListUp,
+    /// Select the entry below in the list.
+    ListDown,
+    /// Go to the selected entry in the list.
+    ListPick,
+    /// Answer the question asked before running a command, running it if the answer is yes.
+    Confirm(bool),
+    /// The terminal gained focus.
+    FocusGained,
+    /// Delete the selected text.
+    DeleteSelection,
+    /// Delete everything but the selected text.
+    KeepSelection,
+    /// Delete the selected text and start inserting in its place.
+    ReplaceSelection,
+    /// Copy the selected text in to a register.
+    YankSelection,
+    /// Copy the line the cursor is on in to a register.
+    YankLine,
+    /// Delete the line the cursor is on, putting it in a register.
+    DeleteLine,
+    /// Delete the character under the cursor, putting it in a register.
+    DeleteChar,
+    /// Start inserting on a new line below the cursor.
+    OpenBelow,
+    /// Start inserting on a new line above the cursor.
+    OpenAbove,
+    /// Start inserting after the character under the cursor.
+    Append,
+    /// Start inserting at the end of the line.
+    AppendEnd,
+    /// Start inserting before the first character on the line which isn't a space or tab.
+    InsertLineStart,
+    /// Join the line below on to the end of the line with a space.
+    JoinLines,
+    /// Swap the character under the cursor with the one before it.
+    TransposeChars,
+    /// Put a copy of the line the cursor is on below it.
+    DuplicateLine,
+    /// Swap the line the cursor is on, or the selected lines, with the line below.
+    MoveLineDown,
+    /// Swap the line the cursor is on, or the selected lines, with the line above.
+    MoveLineUp,
+    /// Put the contents of a register after the cursor.
+    Paste,
+    /// Use the given register for the next delete, yank or paste.
+    SelectRegister(char),
+    /// Insert the contents of the given register at the cursor.
+    InsertRegister(char),
+    /// Replace the char under the cursor with this one.
+    ReplaceChar(char),
+    /// Make the last change again, at the cursor.
+    Repeat,
+    /// Go forward to the next state of the buffer in time, even if it is on another branch of
+    /// the undo tree.
+    Later,
     /// Do nothing.
     None,
 }
@@ -136,8 +1108,138 @@ pub enum Message {
 /// The configured wrap mode for the editor.
 pub const WRAP_MODE: WrapMode = WrapMode::NoWrap(Some('>'));
 
+/// Whether spell checking is on when the editor starts.
+pub const SPELL: bool = false;
+
+/// Whether to warn about lines which mix tabs and spaces in their indentation.
+pub const MIXED_INDENT: bool = true;
+
+/// Whether modified buffers are written when switching away from them or when the terminal loses
+/// focus.
+pub const AUTOWRITE: bool = false;
+
+/// The most positions kept in the jump list and each buffer's change list.
+pub const POSITION_HISTORY_LENGTH: usize = 100;
+
+/// Whether new lines start with the same indentation as the line before them.
+pub const AUTOINDENT: bool = false;
+
+/// The pairs of chars `%` jumps between, and which are closed with `autopairs`.
+pub const MATCH_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Whether typing the start of one of [`MATCH_PAIRS`] types the end of it too when the editor
+/// starts.
+pub const AUTOPAIRS: bool = false;
+
+/// How many unchanged lines are shown around each change by `:diff`.
+pub const DIFF_CONTEXT: usize = 3;
+
+/// Whether backspacing at the start of a line joins it on to the line before when the editor
+/// starts.
+pub const BACKSPACE_JOINS_LINES: bool = false;
+
+/// Whether `J` leaves the cursor in the column it was in when the editor starts, rather than
+/// where the lines were joined.
+pub const JOIN_KEEPS_COLUMN: bool = false;
+
+/// The motions which wrap on to the next or previous line at the ends of a line when the editor
+/// starts, given by their keys: `h` for moving left and `l` for moving right.
+pub const WHICH_WRAP: &[char] = &[];
+
+/// Whether indent guides are drawn when the editor starts.
+pub const INDENT_GUIDES: bool = false;
+
+/// How many columns of spaces make up a level of indentation.
+pub const TAB_WIDTH: usize = 4;
+
+/// Whether lines are shown with their line numbers when the editor starts.
+pub const NUMBER: bool = false;
+
+/// Whether lines are shown with how far they are from the cursor when the editor starts.
+pub const RELATIVE_NUMBER: bool = false;
+
+/// The fewest columns the line numbers take up, including the space after them, like vim's
+/// `numberwidth`.
+pub const MIN_NUMBER_WIDTH: u16 = 4;
+
+/// How many keys in a row have to arrive quickly for them to be treated as pasted text.
+pub const PASTE_BURST_KEYS: usize = 8;
+
+/// The longest gap between keys which can be part of a paste.
+pub const PASTE_BURST_GAP: Duration = Duration::from_millis(10);
+
+/// Whether the cursor is hidden while the terminal doesn't have focus.
+pub const HIDE_UNFOCUSED_CURSOR: bool = true;
+
+/// How long a message is shown in the status bar before it goes away on its own.
+pub const MESSAGE_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// The fewest rows a window can be shrunk to, counting the bar with its file name.
+pub const MIN_WINDOW_HEIGHT: u16 = 2;
+
+/// The file words are loaded from for spell checking. It should have one word per line.
+pub const SPELL_DICTIONARY: &str = "/usr/share/dict/words";
+
+/// The file in the [`data_dir`] where words added to the spell checking dictionary are saved.
+pub const SPELL_USER_DICTIONARY: &str = "spell.add";
+
+/// The most output (in bytes) a filter command like `:%!` may produce before it is aborted.
+pub const FILTER_LIMIT: usize = 64 * 1024 * 1024;
+
+/// How many lines a command like `:g/pattern/d` may change before it asks for confirmation first.
+/// At 0, it never asks.
+pub const CONFIRM_LINES: usize = 0;
+
+/// How many bytes at the start of a file are checked for a null byte, which means the file is
+/// opened as binary.
+pub const BINARY_CHECK_LEN: usize = 8 * 1024;
+
+/// Whether binary files are shown as hex, as if `:set hex` was on for them.
+pub const HEX_BINARY: bool = true;
+
+/// Whether the version and a few keys to get started are shown in the middle of the screen while
+/// the only buffer is a blank one, like when the editor is started without a file.
+pub const START_SCREEN: bool = true;
+
+/// The language server to start for files with each extension, as `(extension, language ID,
+/// command)`. Only used when built with the `lsp` feature.
+pub const LANGUAGE_SERVERS: &[(&str, &str, &str)] = &[("rs", "rust", "rust-analyzer")];
+
+/// How often to check for messages from the language server while waiting for keys.
+pub const LANGUAGE_SERVER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The directory the editor keeps its own files in.
+///
+/// This is `$XDG_DATA_HOME/not-vim`, falling back to `~/.local/share/not-vim`.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("not-vim"))
+}
+
+/// The directory in the [`state_dir`] where the undo history of each file is saved.
+pub const UNDO_DIRECTORY: &str = "undo";
+
+/// The directory the editor keeps state which should persist between sessions in.
+///
+/// This is `$XDG_STATE_HOME/not-vim`, falling back to `~/.local/state/not-vim`. Tests keep their
+/// state in the temporary directory instead.
+pub fn state_dir() -> Option<PathBuf> {
+    if cfg!(test) {
+        // The files tests keep state for are unique to each run, so runs can share this.
+        return Some(std::env::temp_dir().join("not-vim-test-state"));
+    }
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join("not-vim"))
+}
+
 /// A keybind for a specific action.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Key {
     /// Which key was pressed.
     pub code: KeyCode,
@@ -146,10 +1248,28 @@ pub struct Key {
 }
 
 impl From<KeyEvent> for Key {
+    /// The key pressed in `value`.
+    ///
+    /// Shift is dropped from char keys because it is already part of the char, so `+` is the
+    /// same key whether or not the keyboard needs shift to type it.
     fn from(value: KeyEvent) -> Self {
+        let modifiers = match value.code {
+            KeyCode::Char(_) => value.modifiers - KeyModifiers::SHIFT,
+            _ => value.modifiers,
+        };
         Self {
             code: value.code,
-            modifiers: value.modifiers,
+            modifiers,
+        }
+    }
+}
+
+impl From<char> for Key {
+    /// An unmodified press of the key for `c`.
+    fn from(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE,
         }
     }
 }
@@ -166,3 +1286,584 @@ pub enum WrapMode {
     /// [`None`] then nothing will be displayed to signify line continuance.
     NoWrap(Option<char>),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keymap::parse_keys;
+
+    /// An unmodified press of `c`.
+    fn key(c: char) -> Key {
+        c.into()
+    }
+
+    #[test]
+    fn multi_key_commands() {
+        let mut translator = Translator::default();
+        assert_eq!(translator.translate(Mode::Normal, key(']')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('s')),
+            Message::NextMisspelling
+        );
+        assert_eq!(translator.translate(Mode::Normal, key('[')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('s')),
+            Message::PrevMisspelling
+        );
+
+        // An unknown second key cancels the command without doing anything else.
+        assert_eq!(translator.translate(Mode::Normal, key(']')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('j')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('j')), Message::Down);
+    }
+
+    #[test]
+    fn registering_commands() {
+        let mut translator = Translator::default();
+        // Not a prefix yet.
+        assert_eq!(translator.translate(Mode::Normal, key('Q')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('j')), Message::Down);
+
+        translator.register('Q', 'x', Message::Quit);
+        assert_eq!(translator.translate(Mode::Normal, key('Q')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('x')), Message::Quit);
+
+        // An unregistered command is cancelled without the second key doing anything.
+        assert_eq!(translator.translate(Mode::Normal, key('Q')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('j')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('j')), Message::Down);
+
+        // Modified keys are distinct from unmodified ones.
+        let ctrl_x = Key {
+            code: KeyCode::Char('x'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        translator.register('Q', ctrl_x, Message::Write);
+        assert_eq!(translator.translate(Mode::Normal, key('Q')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, ctrl_x), Message::Write);
+    }
+
+    #[test]
+    fn immediate_keys() {
+        let mut translator = Translator::default();
+        translator.register('g', 'g', Message::Quit);
+        translator.set_immediate('g', true);
+
+        // `g` doesn't wait for another key, so the `j` is a command of its own.
+        assert_eq!(translator.translate(Mode::Normal, key('g')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('j')), Message::Down);
+        // `gg` can't be typed.
+        assert_eq!(translator.translate(Mode::Normal, key('g')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('g')), Message::None);
+        // Other prefixes are unaffected.
+        assert_eq!(translator.translate(Mode::Normal, key(']')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('s')),
+            Message::NextMisspelling
+        );
+
+        translator.set_immediate('g', false);
+        assert_eq!(translator.translate(Mode::Normal, key('g')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('g')), Message::Quit);
+    }
+
+    /// Push all of `keys` in to `translator` and translate as many as possible in normal mode.
+    fn run(translator: &mut Translator, keymap: &Keymap, keys: &str) -> Vec<Message> {
+        for key in parse_keys(keys).unwrap() {
+            translator.push(key);
+        }
+        std::iter::from_fn(|| translator.next(Mode::Normal, keymap)).collect()
+    }
+
+    #[test]
+    fn recursive_and_non_recursive_maps() {
+        let mut translator = Translator::default();
+        let mut keymap = Keymap::default();
+        keymap.map(parse_keys("L").unwrap(), parse_keys("j").unwrap(), true);
+        keymap.map(parse_keys("a").unwrap(), parse_keys("L").unwrap(), true);
+        keymap.map(parse_keys("b").unwrap(), parse_keys("Ll").unwrap(), false);
+        keymap.map(parse_keys("l").unwrap(), parse_keys("k").unwrap(), false);
+
+        // `a` chains through the mapping for `L`.
+        assert_eq!(run(&mut translator, &keymap, "a"), [Message::Down]);
+        // `b` uses the built-in meanings, even though `l` is mapped too.
+        assert_eq!(
+            run(&mut translator, &keymap, "b"),
+            [Message::None, Message::Right]
+        );
+        assert_eq!(run(&mut translator, &keymap, "l"), [Message::Up]);
+    }
+
+    #[test]
+    fn multi_key_maps() {
+        let mut translator = Translator::default();
+        let mut keymap = Keymap::default();
+        keymap.map(parse_keys(",d").unwrap(), parse_keys("jj").unwrap(), false);
+
+        // `,` waits to see if it's the start of `,d`.
+        assert_eq!(run(&mut translator, &keymap, ","), []);
+        assert_eq!(
+            run(&mut translator, &keymap, "d"),
+            [Message::Down, Message::Down]
+        );
+        // Anything else gets its built-in meaning.
+        assert_eq!(
+            run(&mut translator, &keymap, ",j"),
+            [Message::None, Message::Down]
+        );
+    }
+
+    #[test]
+    fn recursive_maps_are_stopped() {
+        let mut translator = Translator::default();
+        let mut keymap = Keymap::default();
+        keymap.map(parse_keys("a").unwrap(), parse_keys("b").unwrap(), true);
+        keymap.map(parse_keys("b").unwrap(), parse_keys("a").unwrap(), true);
+        assert_eq!(
+            run(&mut translator, &keymap, "aj"),
+            [Message::RecursiveMapping]
+        );
+        // The translator still works afterwards.
+        assert_eq!(run(&mut translator, &keymap, "j"), [Message::Down]);
+    }
+
+    #[test]
+    fn selecting_registers() {
+        let mut translator = Translator::default();
+        assert_eq!(translator.translate(Mode::Normal, key('"')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('a')),
+            Message::SelectRegister('a')
+        );
+        assert_eq!(translator.translate(Mode::Normal, key('p')), Message::Paste);
+        assert_eq!(translator.translate(Mode::Visual, key('"')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Visual, key('"')),
+            Message::SelectRegister('"')
+        );
+    }
+
+    #[test]
+    fn replacing_chars() {
+        let mut translator = Translator::default();
+        assert_eq!(translator.translate(Mode::Normal, key('r')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('x')),
+            Message::ReplaceChar('x')
+        );
+        // `Esc` cancels the replacement without doing anything else.
+        let esc = Key {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+        };
+        assert_eq!(translator.translate(Mode::Normal, key('r')), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, esc), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('x')),
+            Message::DeleteChar
+        );
+    }
+
+    #[test]
+    fn confirming() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Confirm, key('y')),
+            Message::Confirm(true)
+        );
+        assert_eq!(
+            translator.translate(Mode::Confirm, key('n')),
+            Message::Confirm(false)
+        );
+        let esc = Key {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+        };
+        assert_eq!(
+            translator.translate(Mode::Confirm, esc),
+            Message::Confirm(false)
+        );
+        assert_eq!(translator.translate(Mode::Confirm, key('d')), Message::None);
+    }
+
+    #[test]
+    fn searching() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('/')),
+            Message::Mode(Mode::Search)
+        );
+        // The query is typed like a command.
+        assert_eq!(
+            translator.translate(Mode::Search, key('n')),
+            Message::CommandChar('n')
+        );
+        let enter = Key {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        };
+        assert_eq!(
+            translator.translate(Mode::Search, enter),
+            Message::RunCommand
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, key('n')),
+            Message::SearchNext
+        );
+    }
+
+    #[test]
+    fn inserting_registers() {
+        let ctrl_r = Key {
+            code: KeyCode::Char('r'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        let mut translator = Translator::default();
+        assert_eq!(translator.translate(Mode::Insert, ctrl_r), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Insert, key('a')),
+            Message::InsertRegister('a')
+        );
+        assert_eq!(
+            translator.translate(Mode::Insert, key('a')),
+            Message::Char('a')
+        );
+    }
+
+    #[test]
+    fn window_commands() {
+        let mut translator = Translator::default();
+        let mut window_command = |second| {
+            let ctrl_w = parse_keys("<C-w>").unwrap()[0];
+            assert_eq!(translator.translate(Mode::Normal, ctrl_w), Message::None);
+            translator.translate(Mode::Normal, parse_keys(second).unwrap()[0])
+        };
+        assert_eq!(window_command("q"), Message::QuitWindow);
+        assert_eq!(window_command("o"), Message::OnlyWindow);
+        assert_eq!(window_command("<C-w>"), Message::NextWindow);
+        assert_eq!(window_command("<Down>"), Message::WindowDown);
+        assert_eq!(window_command("+"), Message::GrowWindow);
+        assert_eq!(window_command("x"), Message::None);
+        // Quitting is left to `:q`.
+        assert_eq!(translator.translate(Mode::Normal, key('q')), Message::None);
+    }
+
+    #[test]
+    fn leaving_insert_mode() {
+        let mut translator = Translator::default();
+        let ctrl_bracket = parse_keys("<C-[>").unwrap()[0];
+        assert_eq!(
+            translator.translate(Mode::Insert, ctrl_bracket),
+            Message::Mode(Mode::Normal)
+        );
+
+        translator.set_insert_escape(Some(['j', 'k']));
+        let keymap = Keymap::default();
+        let now = Instant::now();
+        let mut type_at = |key, millis| {
+            translator.push_at(Key::from(key), now + Duration::from_millis(millis));
+            std::iter::from_fn(|| translator.next(Mode::Insert, &keymap)).collect::<Vec<_>>()
+        };
+        assert_eq!(type_at('j', 0), [Message::Char('j')]);
+        assert_eq!(
+            type_at('k', 50),
+            [Message::Backspace, Message::Mode(Mode::Normal)]
+        );
+        // Typed slowly, they are just chars.
+        assert_eq!(type_at('j', 1000), [Message::Char('j')]);
+        assert_eq!(type_at('k', 2000), [Message::Char('k')]);
+        assert_eq!(type_at('k', 2010), [Message::Char('k')]);
+    }
+
+    #[test]
+    fn counts() {
+        let mut translator = Translator::default();
+        for c in "150".chars() {
+            assert_eq!(translator.translate(Mode::Normal, key(c)), Message::None);
+        }
+        assert_eq!(
+            translator.translate(Mode::Normal, key('%')),
+            Message::GotoPercent(150)
+        );
+        // The count is used up, and `%` without one matches brackets instead.
+        assert_eq!(
+            translator.translate(Mode::Normal, key('%')),
+            Message::MatchBracket
+        );
+
+        // A count is dropped by keys which don't take one.
+        assert_eq!(translator.translate(Mode::Normal, key('5')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('x')),
+            Message::DeleteChar
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, key('%')),
+            Message::MatchBracket
+        );
+
+        // Motions are given their count just before them.
+        let keymap = Keymap::default();
+        let mut type_keys = |keys: &str| {
+            for c in keys.chars() {
+                translator.push(Key::from(c));
+            }
+            std::iter::from_fn(|| translator.next(Mode::Normal, &keymap)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            type_keys("3j"),
+            [Message::None, Message::Count(3), Message::Down]
+        );
+        assert_eq!(
+            type_keys("12w"),
+            [
+                Message::None,
+                Message::None,
+                Message::Count(12),
+                Message::WordStart
+            ]
+        );
+        assert_eq!(type_keys("j"), [Message::Down]);
+        // A leading 0 is still a motion of its own.
+        assert_eq!(type_keys("0l"), [Message::LineStart, Message::Right]);
+        // Commands which can be done several times are given their count too.
+        assert_eq!(translator.translate(Mode::Normal, key('3')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, DUPLICATE_LINE_KEY),
+            Message::Count(3)
+        );
+        assert_eq!(
+            translator.next(Mode::Normal, &keymap),
+            Some(Message::DuplicateLine)
+        );
+
+        // A huge count doesn't overflow.
+        for _ in 0..30 {
+            translator.translate(Mode::Normal, key('9'));
+        }
+        assert_eq!(
+            translator.translate(Mode::Normal, key('%')),
+            Message::GotoPercent(usize::MAX)
+        );
+    }
+
+    #[test]
+    fn moving_along_lines_in_insert_mode() {
+        let mut translator = Translator::default();
+        let ctrl = |keys| parse_keys(keys).unwrap()[0];
+        assert_eq!(
+            translator.translate(Mode::Insert, ctrl("<C-a>")),
+            Message::LineStart
+        );
+        assert_eq!(
+            translator.translate(Mode::Insert, ctrl("<C-e>")),
+            Message::LineEnd
+        );
+    }
+
+    #[test]
+    fn line_start_and_end_motions() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('0')),
+            Message::LineStart
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, key('$')),
+            Message::LineEnd
+        );
+        // A 0 after other digits is part of a count instead.
+        translator.translate(Mode::Normal, key('1'));
+        assert_eq!(translator.translate(Mode::Normal, key('0')), Message::None);
+    }
+
+    #[test]
+    fn going_to_top_and_bottom() {
+        let mut translator = Translator::default();
+        assert_eq!(translator.translate(Mode::Normal, key('g')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('g')),
+            Message::GotoTop
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, key('G')),
+            Message::GotoBottom
+        );
+    }
+
+    #[test]
+    fn opening_lines() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('o')),
+            Message::OpenBelow
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, key('O')),
+            Message::OpenAbove
+        );
+    }
+
+    #[test]
+    fn appending() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('a')),
+            Message::Append
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, key('A')),
+            Message::AppendEnd
+        );
+    }
+
+    #[test]
+    fn transposing_chars() {
+        let mut translator = Translator::default();
+        let ctrl_t = parse_keys("<C-t>").unwrap()[0];
+        assert_eq!(
+            translator.translate(Mode::Insert, ctrl_t),
+            Message::TransposeChars
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, ctrl_t),
+            Message::TransposeChars
+        );
+    }
+
+    #[test]
+    fn deleting_lines() {
+        let mut translator = Translator::default();
+        assert_eq!(translator.translate(Mode::Normal, key('d')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('d')),
+            Message::DeleteLine
+        );
+    }
+
+    #[test]
+    fn first_non_blank_motion() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('^')),
+            Message::FirstNonBlank
+        );
+    }
+
+    #[test]
+    fn deleting_chars() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('x')),
+            Message::DeleteChar
+        );
+    }
+
+    #[test]
+    fn joining_lines() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('J')),
+            Message::JoinLines
+        );
+    }
+
+    #[test]
+    fn repeating_changes() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('.')),
+            Message::Repeat
+        );
+    }
+
+    #[test]
+    fn selecting_all() {
+        let ctrl_a = parse_keys("<C-a>").unwrap()[0];
+        let mut translator = Translator::default();
+        assert_eq!(translator.translate(Mode::Normal, ctrl_a), Message::None);
+
+        translator.set_modeless(true);
+        assert_eq!(
+            translator.translate(Mode::Normal, ctrl_a),
+            Message::SelectAll
+        );
+        assert_eq!(
+            translator.translate(Mode::Insert, ctrl_a),
+            Message::LineStart
+        );
+    }
+
+    #[test]
+    fn inserting_at_first_non_blank() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, key('I')),
+            Message::InsertLineStart
+        );
+    }
+
+    #[test]
+    fn duplicating_lines() {
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, DUPLICATE_LINE_KEY),
+            Message::DuplicateLine
+        );
+    }
+
+    #[test]
+    fn modeless_typing() {
+        let mut translator = Translator::default();
+        translator.set_modeless(true);
+        let esc = parse_keys("<Esc>").unwrap()[0];
+        assert_eq!(
+            translator.translate(Mode::Normal, key('x')),
+            Message::Char('x')
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, parse_keys("<Down>").unwrap()[0]),
+            Message::Down
+        );
+        assert_eq!(
+            translator.translate(Mode::Insert, esc),
+            Message::Mode(Mode::Normal)
+        );
+        assert_eq!(
+            translator.translate(Mode::Normal, key(':')),
+            Message::Mode(Mode::Command)
+        );
+        assert_eq!(translator.translate(Mode::Normal, esc), Message::None);
+        assert_eq!(translator.translate(Mode::Normal, key('u')), Message::Undo);
+        assert_eq!(
+            translator.translate(Mode::Normal, key('u')),
+            Message::Char('u')
+        );
+
+        translator.set_modeless(false);
+        assert_eq!(translator.translate(Mode::Normal, key('u')), Message::Undo);
+    }
+
+    #[test]
+    fn info_commands() {
+        let ctrl_g = Key {
+            code: KeyCode::Char('g'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        let mut translator = Translator::default();
+        assert_eq!(
+            translator.translate(Mode::Normal, ctrl_g),
+            Message::FileInfo
+        );
+        assert_eq!(translator.translate(Mode::Normal, key('g')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Normal, ctrl_g),
+            Message::CursorInfo
+        );
+        assert_eq!(translator.translate(Mode::Visual, key('g')), Message::None);
+        assert_eq!(
+            translator.translate(Mode::Visual, ctrl_g),
+            Message::CursorInfo
+        );
+    }
+}
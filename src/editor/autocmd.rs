@@ -0,0 +1,115 @@
+//! Autocommands run a command when an event happens to a file matching a pattern, like
+//! `:autocmd BufWrite *.md set spell`.
+//!
+//! They are the configurable counterpart to [`hooks`], and are run after the hooks for the same
+//! event.
+//!
+//! [`hooks`]: super::hooks
+
+use super::hooks::HookKind;
+use anyhow::bail;
+
+/// A command to run when an event happens to a file matching a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Autocmd {
+    /// The kind of event the command is run for.
+    pub event: HookKind,
+    /// The glob pattern the file has to match, where `*` matches any characters and `?` matches
+    /// one.
+    ///
+    /// A pattern with a `/` in it is matched against the whole file name, otherwise only the
+    /// last part of the path is.
+    pub pattern: String,
+    /// The command to run, as it would be typed on the command line.
+    pub command: String,
+}
+
+impl Autocmd {
+    /// Whether the autocommand should be run for an event of `kind` happening to `fname`.
+    pub fn matches(&self, kind: HookKind, fname: &str) -> bool {
+        if self.event != kind {
+            return false;
+        }
+        let name = if self.pattern.contains('/') {
+            fname
+        } else {
+            fname.rsplit('/').next().unwrap_or(fname)
+        };
+        glob_match(&self.pattern, name)
+    }
+}
+
+/// Parse the name of an event, as used by `:autocmd`. Case is ignored, like in vim.
+pub fn parse_event(name: &str) -> anyhow::Result<HookKind> {
+    Ok(match name.to_ascii_lowercase().as_str() {
+        "bufwrite" => HookKind::BufWrite,
+        "bufopen" => HookKind::BufOpen,
+        "modechange" => HookKind::ModeChange,
+        "cursormove" => HookKind::CursorMove,
+        _ => bail!("No such event: {name}"),
+    })
+}
+
+/// Whether all of `text` matches the glob `pattern`, where `*` matches any number of characters
+/// and `?` matches exactly one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where to go back to if what follows the last `*` fails to match: the index after the `*`
+    // and the index in the text it is matched up to.
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the `*` match one more character and try again.
+                Some((after, matched)) => {
+                    star = Some((after, matched + 1));
+                    p = after;
+                    t = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matching_globs() {
+        assert!(glob_match("*.md", "README.md"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("*a*b", "xxaxxab"));
+        assert!(!glob_match("*.md", "main.rs"));
+        assert!(!glob_match("*.md", "notes.md.bak"));
+        assert!(!glob_match("a?c", "ac"));
+
+        let autocmd = Autocmd {
+            event: HookKind::BufWrite,
+            pattern: "*.md".to_owned(),
+            command: "set spell".to_owned(),
+        };
+        assert!(autocmd.matches(HookKind::BufWrite, "docs/notes.md"));
+        assert!(!autocmd.matches(HookKind::BufOpen, "docs/notes.md"));
+        let autocmd = Autocmd {
+            pattern: "docs/*".to_owned(),
+            ..autocmd
+        };
+        assert!(autocmd.matches(HookKind::BufWrite, "docs/notes.md"));
+        assert!(!autocmd.matches(HookKind::BufWrite, "src/notes.md"));
+    }
+}
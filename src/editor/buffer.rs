@@ -4,8 +4,24 @@
 //!
 //! A buffer contains both the content of the buffer and the file which it refers to.
 
-use anyhow::Context;
+use super::{
+    diagnostic::Diagnostic,
+    jumps::History,
+    line_count, trim_newlines,
+    undo::{self, UndoTree},
+};
+use crate::{
+    config,
+    progress::{Progress, ProgressReader},
+};
+use anyhow::{bail, Context};
 use ropey::{iter::Lines, Rope};
+use std::{
+    hash::{DefaultHasher, Hasher},
+    io::{BufRead, BufReader, Read, Write},
+    ops::Range,
+    time::SystemTime,
+};
 
 /// A single buffer of text. May refer to a specific file or be a free-floating buffer.
 /// See the [module] level documentation for more.
@@ -17,77 +33,672 @@ pub struct Buffer {
     pub text: Rope,
     /// The path to the file on disk (if the buffer references one).
     pub file: Option<String>,
+    /// Every change which has been made to the buffer.
+    pub undo: UndoTree,
+    /// Whether the text has changed since the file was last written (or opened).
+    pub modified: bool,
+    /// Where the cursor was when another buffer was last selected.
+    pub cursor: (usize, usize),
+    /// Where each change to the buffer was made, like vim's change list.
+    pub changes: History<(usize, usize)>,
+    /// The problems reported with the text, such as by a language server.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The bytes of the file if it is binary rather than text, in which case it can't be written.
+    ///
+    /// See [`Buffer::open_with_progress`].
+    pub binary: Option<Vec<u8>>,
+    /// When the file was last modified, as of it being opened or written by the editor.
+    mtime: Option<SystemTime>,
+    /// A hash of the file's contents, as of it being opened or written by the editor.
+    hash: Option<u64>,
+    /// When the file was last modified by something else, as of the last time that was noticed.
+    noticed_mtime: Option<SystemTime>,
+    /// The state of [`Buffer::undo`] which matches the file, if any does.
+    ///
+    /// Undoing back to this state means the buffer is no longer modified.
+    saved: Option<usize>,
 }
 
 impl Buffer {
-    pub fn empty() -> Self {
+    /// Create a [`Buffer`] containing `text`, with no changes made to it yet.
+    pub fn new(text: Rope, file: Option<String>) -> Self {
         Self {
-            text: Rope::new(),
-            file: None,
+            undo: UndoTree::new(text.clone()),
+            text,
+            file,
+            modified: false,
+            cursor: (0, 0),
+            changes: History::default(),
+            diagnostics: Vec::new(),
+            binary: None,
+            mtime: None,
+            hash: None,
+            noticed_mtime: None,
+            saved: Some(0),
         }
     }
 
+    /// Create an empty [`Buffer`] with no file, for scratch editing.
+    pub fn empty() -> Self {
+        Self::new(Rope::new(), None)
+    }
+
     /// Open a file and read its contents to the buffer.
     pub fn open(fname: &str) -> anyhow::Result<Self> {
+        Self::open_with_progress(fname, |_| {})
+    }
+
+    /// Open a file and read its contents to the buffer, calling `report` as it is read in.
+    ///
+    /// The undo history saved when the file was last written is picked back up, as long as the
+    /// file hasn't been changed since.
+    ///
+    /// A file with a null byte in its first [`config::BINARY_CHECK_LEN`] bytes is opened as
+    /// [`Buffer::binary`]. Its text then has a char for each byte, with `·` for any byte which
+    /// isn't printable ASCII, and it can't be written since that would lose the original bytes.
+    pub fn open_with_progress(fname: &str, report: impl FnMut(Progress)) -> anyhow::Result<Self> {
         let file = std::fs::File::open(fname)
             .with_context(|| format!("Opening file `{fname}` failed."))?;
-        let rope = Rope::from_reader(file)?;
+        let metadata = file.metadata().ok();
+        let len = metadata.as_ref().map_or(0, |metadata| metadata.len());
+        let mtime = metadata.and_then(|metadata| metadata.modified().ok());
+        let mut reader = BufReader::with_capacity(
+            config::BINARY_CHECK_LEN,
+            ProgressReader::new(file, len, report),
+        );
+        if reader.fill_buf()?.contains(&0) {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            return Ok(Self {
+                mtime,
+                hash: Some(hash_chunks([bytes.as_slice()])),
+                ..Self::new(binary_text(&bytes), Some(fname.to_owned()))
+            }
+            .with_bytes(bytes));
+        }
+        let rope = Rope::from_reader(reader)?;
+        let history = undo::file_for(fname).and_then(|path| UndoTree::load(&path, &rope).ok()?);
+        let undo = history.unwrap_or_else(|| UndoTree::new(rope.clone()));
         Ok(Self {
+            saved: Some(undo.current_index()),
+            undo,
+            hash: Some(hash_chunks(rope.chunks().map(str::as_bytes))),
             text: rope,
             file: Some(fname.to_owned()),
+            modified: false,
+            cursor: (0, 0),
+            changes: History::default(),
+            diagnostics: Vec::new(),
+            binary: None,
+            mtime,
+            noticed_mtime: None,
         })
     }
 
+    /// Set the bytes of the binary file the buffer's text was made from.
+    fn with_bytes(self, bytes: Vec<u8>) -> Self {
+        Self {
+            binary: Some(bytes),
+            ..self
+        }
+    }
+
+    /// Whether the buffer has no text at all.
+    pub fn is_empty(&self) -> bool {
+        self.text.len_chars() == 0
+    }
+
+    /// The bytes of the buffer in `range`, as they would be written to its file.
+    ///
+    /// For a binary file, these are the original bytes rather than those of the text. A range
+    /// past the end is cut short.
+    pub fn bytes(&self, range: Range<usize>) -> Vec<u8> {
+        let len = self.len_bytes();
+        let range = range.start.min(len)..range.end.min(len);
+        match &self.binary {
+            Some(bytes) => bytes[range].to_vec(),
+            None => self.text.bytes_at(range.start).take(range.len()).collect(),
+        }
+    }
+
+    /// How many bytes long the buffer is. See [`Buffer::bytes`].
+    pub fn len_bytes(&self) -> usize {
+        match &self.binary {
+            Some(bytes) => bytes.len(),
+            None => self.text.len_bytes(),
+        }
+    }
+
+    /// The index of the byte at char index `idx` of the text. See [`Buffer::bytes`].
+    pub fn char_to_byte(&self, idx: usize) -> usize {
+        match &self.binary {
+            // Each byte of a binary file is a char of the text.
+            Some(_) => idx,
+            None => self.text.char_to_byte(idx),
+        }
+    }
+
+    /// Convert an `(x, y)` position into a char index of the text.
+    ///
+    /// A position past the end of its line is the end of the line, which is the index of the
+    /// line's newline if it has one. A line past the end of the text is the last line.
+    pub fn pos_to_char(&self, x: usize, y: usize) -> usize {
+        let y = y.min(self.text.len_lines() - 1);
+        let line_len = trim_newlines(self.text.line(y)).len_chars();
+        self.text.line_to_char(y) + x.min(line_len)
+    }
+
+    /// Convert a char index of the text into an `(x, y)` position.
+    ///
+    /// Every char of a line's newline (both chars of a `\r\n`) is at the end of that line. An
+    /// index past the end of the text is the end of the text.
+    pub fn char_to_pos(&self, idx: usize) -> (usize, usize) {
+        let idx = idx.min(self.text.len_chars());
+        let y = self.text.char_to_line(idx);
+        let line_len = trim_newlines(self.text.line(y)).len_chars();
+        ((idx - self.text.line_to_char(y)).min(line_len), y)
+    }
+
+    /// The column of the `(x, y)` position counted in UTF-16 code units, which is how language
+    /// servers count columns unless told otherwise.
+    ///
+    /// Chars outside the Basic Multilingual Plane, like most emoji, are two code units.
+    #[cfg_attr(not(feature = "lsp"), allow(dead_code))]
+    pub fn char_to_utf16_col(&self, x: usize, y: usize) -> usize {
+        let idx = self.pos_to_char(x, y);
+        let start = self.pos_to_char(0, y);
+        self.text.char_to_utf16_cu(idx) - self.text.char_to_utf16_cu(start)
+    }
+
+    /// The x position of UTF-16 column `col` of line `y`, the reverse of
+    /// [`Buffer::char_to_utf16_col`].
+    ///
+    /// A column between the two code units of a char is that char. Columns and lines past the end
+    /// are clamped like in [`Buffer::pos_to_char`].
+    #[cfg_attr(not(feature = "lsp"), allow(dead_code))]
+    pub fn utf16_col_to_char(&self, col: usize, y: usize) -> usize {
+        let y = y.min(self.text.len_lines() - 1);
+        let line = trim_newlines(self.text.line(y));
+        line.utf16_cu_to_char(col.min(line.len_utf16_cu()))
+    }
+
+    /// The column of the `(x, y)` position counted in bytes of UTF-8.
+    #[cfg_attr(not(feature = "lsp"), allow(dead_code))]
+    pub fn char_to_byte_col(&self, x: usize, y: usize) -> usize {
+        let idx = self.pos_to_char(x, y);
+        let start = self.pos_to_char(0, y);
+        self.text.char_to_byte(idx) - self.text.char_to_byte(start)
+    }
+
+    /// The x position of UTF-8 byte column `col` of line `y`, the reverse of
+    /// [`Buffer::char_to_byte_col`].
+    ///
+    /// A column in the middle of a char is that char. Columns and lines past the end are clamped
+    /// like in [`Buffer::pos_to_char`].
+    #[cfg_attr(not(feature = "lsp"), allow(dead_code))]
+    pub fn byte_col_to_char(&self, col: usize, y: usize) -> usize {
+        let y = y.min(self.text.len_lines() - 1);
+        let line = trim_newlines(self.text.line(y));
+        line.byte_to_char(col.min(line.len_bytes()))
+    }
+
     /// Append a single character to the [`Buffer`] at the provided coordinates.
     pub fn push(&mut self, c: char, (x, y): &mut (usize, usize)) {
-        let char_idx = self.text.line_to_char(*y) + *x;
+        let char_idx = self.pos_to_char(*x, *y);
         self.text.insert_char(char_idx, c);
+        self.modified = true;
         *x += 1;
     }
 
+    /// Replace the character at the provided coordinates with `c`, leaving them where they are.
+    ///
+    /// Nothing happens if there is no character there before the end of the line.
+    pub fn replace_char(&mut self, c: char, (x, y): (usize, usize)) {
+        if x >= trim_newlines(self.text.line(y)).len_chars() {
+            return;
+        }
+        let char_idx = self.pos_to_char(x, y);
+        self.text.remove(char_idx..=char_idx);
+        self.text.insert_char(char_idx, c);
+        self.modified = true;
+    }
+
+    /// Swap the character at the provided coordinates with the one before it, like readline's
+    /// `Ctrl-t`, moving the coordinates after them. At the end of the line, the last two
+    /// characters are swapped instead and the coordinates stay at the end.
+    ///
+    /// Nothing happens at the start of a line, or on a line with fewer than two characters.
+    pub fn transpose_chars(&mut self, (x, y): &mut (usize, usize)) {
+        let len = trim_newlines(self.text.line(*y)).len_chars();
+        if *x == 0 || len < 2 {
+            return;
+        }
+        let second_x = (*x).min(len - 1);
+        let second = self.pos_to_char(second_x, *y);
+        let c = self.text.char(second);
+        self.text.remove(second..=second);
+        self.text.insert_char(second - 1, c);
+        self.modified = true;
+        *x = second_x + 1;
+    }
+
     /// Remove the character in the [`Buffer`] right before the provided coordinates.
     pub fn backspace(&mut self, (x, y): &mut (usize, usize)) {
         if *x == 0 {
             return;
         }
-        let char_idx = self.text.line_to_char(*y) + *x - 1;
+        let char_idx = self.pos_to_char(*x - 1, *y);
         self.text.remove(char_idx..=char_idx);
-        // if *x == 0 {
-        //     if *y != 0 {
-        //         *x = original_len;
-        //         *y -= 1;
-        //     }
-        //     return;
-        // }
+        self.modified = true;
         *x -= 1;
     }
 
+    /// Join line `y` on to the end of the line before it, as if its line break were backspaced
+    /// over, moving the coordinates to where the lines were joined.
+    pub fn join_with_previous(&mut self, (x, y): &mut (usize, usize)) {
+        if *y == 0 || *y >= self.text.len_lines() {
+            return;
+        }
+        let prev_len = trim_newlines(self.text.line(*y - 1)).len_chars();
+        let start = self.pos_to_char(prev_len, *y - 1);
+        self.text.remove(start..self.text.line_to_char(*y));
+        self.modified = true;
+        *x = prev_len;
+        *y -= 1;
+    }
+
+    /// Join the line after line `y` on to the end of it with a single space, dropping the
+    /// indentation of the joined line, like vim's `J`. The coordinates are moved to the space.
+    ///
+    /// Nothing happens on the last line.
+    pub fn join_lines(&mut self, (x, y): &mut (usize, usize)) {
+        if *y + 1 >= line_count(self.text.slice(..)) {
+            return;
+        }
+        let len = trim_newlines(self.text.line(*y)).len_chars();
+        let start = self.pos_to_char(len, *y);
+        let next = self.text.line_to_char(*y + 1);
+        let indent = self
+            .text
+            .line(*y + 1)
+            .chars()
+            .take_while(|&c| c == ' ' || c == '\t')
+            .count();
+        self.text.remove(start..next + indent);
+        self.text.insert_char(start, ' ');
+        self.modified = true;
+        *x = len;
+    }
+
     /// Adds a new line where the cursor is.
     ///
     /// This may split a line into two if the cursor is in the middle of a line.
     pub fn newline(&mut self, (x, y): &mut (usize, usize)) {
-        let char_idx = self.text.line_to_char(*y) + *x;
+        let char_idx = self.pos_to_char(*x, *y);
         self.text.insert_char(char_idx, '\n');
+        self.modified = true;
         *x = 0;
         *y += 1;
     }
 
+    /// Work out whether the buffer is modified from where [`Buffer::undo`] is, once every change
+    /// has been recorded in it.
+    pub fn update_modified(&mut self) {
+        self.modified = self.saved != Some(self.undo.current_index());
+    }
+
     /// Write the current contents of the buffer to the file it came from.
-    pub fn write(&self) -> anyhow::Result<()> {
-        if let Some(file) = &self.file {
-            let file = std::fs::OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(file)
-                .with_context(|| format!("Opening file `{file}` failed."))?;
-            self.text.write_to(file)?;
+    ///
+    /// The undo history is saved alongside it, and the buffer is no longer modified. The current
+    /// state of the undo history should match the text.
+    ///
+    /// If something else has changed the file since it was opened or written, it isn't
+    /// overwritten unless `force` is set. A buffer without a file can't be written, see
+    /// [`Buffer::write_as`].
+    pub fn write(&mut self, force: bool) -> anyhow::Result<()> {
+        if self.binary.is_some() {
+            bail!("Binary files can't be written");
+        }
+        if !force && self.changed_since_read() {
+            bail!("The file has been changed since reading it (add ! to override)");
+        }
+        let Some(fname) = &self.file else {
+            bail!("No file name");
+        };
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(fname)
+            .with_context(|| format!("Opening file `{fname}` failed."))?;
+        self.text.write_to(file)?;
+        self.saved = Some(self.undo.current_index());
+        self.modified = false;
+        self.mtime = disk_mtime(fname);
+        self.hash = Some(hash_chunks(self.text.chunks().map(str::as_bytes)));
+        // The file itself has been written, so failing to keep its history isn't worth
+        // reporting the write as failed.
+        if let Some(path) = undo::file_for(fname) {
+            let _ = self.undo.save(&path);
         }
         Ok(())
     }
 
+    /// Write the text to `fname` rather than the buffer's own file.
+    ///
+    /// A buffer without a file takes `fname` as its file. Otherwise the buffer is left as it is,
+    /// since its own file hasn't been written. An existing file is only overwritten if `force` is
+    /// set.
+    pub fn write_as(&mut self, fname: &str, force: bool) -> anyhow::Result<()> {
+        if self.binary.is_some() {
+            bail!("Binary files can't be written");
+        }
+        if self.file.as_deref() == Some(fname) {
+            return self.write(force);
+        }
+        if !force && std::path::Path::new(fname).exists() {
+            bail!("File exists (add ! to override)");
+        }
+        if self.file.is_none() {
+            self.file = Some(fname.to_owned());
+            return self.write(force);
+        }
+        let file = std::fs::File::create(fname)
+            .with_context(|| format!("Opening file `{fname}` failed."))?;
+        self.text.write_to(file)?;
+        Ok(())
+    }
+
+    /// Add the text to the end of `fname`, creating it if it doesn't exist, like vim's `:w >>`.
+    ///
+    /// Only `lines` of the text are added if given, counting from 0. The buffer itself is left as
+    /// it is, since its own file hasn't been written.
+    pub fn append_to_path(&self, fname: &str, lines: Option<Range<usize>>) -> anyhow::Result<()> {
+        if self.binary.is_some() {
+            bail!("Binary files can't be written");
+        }
+        let text = match lines {
+            Some(lines) if lines.end > line_count(self.text.slice(..)) => bail!("Invalid range"),
+            Some(lines) => self
+                .text
+                .slice(self.text.line_to_char(lines.start)..self.text.line_to_char(lines.end)),
+            None => self.text.slice(..),
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(fname)
+            .with_context(|| format!("Opening file `{fname}` failed."))?;
+        for chunk in text.chunks() {
+            file.write_all(chunk.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Check whether the file has been changed by something else since it was opened or written,
+    /// or since the last time this returned `true`.
+    ///
+    /// A file which can't be checked, such as one which has been deleted, isn't counted as
+    /// changed.
+    pub fn check_changed_on_disk(&mut self) -> bool {
+        let Some(mtime) = self.file.as_deref().and_then(disk_mtime) else {
+            return false;
+        };
+        if self.mtime == Some(mtime) || self.noticed_mtime == Some(mtime) {
+            return false;
+        }
+        self.noticed_mtime = Some(mtime);
+        true
+    }
+
+    /// Whether the contents of the file have been changed by something else since it was opened
+    /// or written.
+    ///
+    /// A file which has only been touched, keeping the same contents, isn't counted as changed.
+    /// Neither is one which the buffer hasn't read or written yet, or one which can't be read.
+    pub fn changed_since_read(&self) -> bool {
+        let (Some(fname), Some(mtime)) = (self.file.as_deref(), self.mtime) else {
+            return false;
+        };
+        if disk_mtime(fname).is_none_or(|disk| disk == mtime) {
+            return false;
+        }
+        match std::fs::read(fname) {
+            Ok(contents) => self.hash != Some(hash_chunks([contents.as_slice()])),
+            Err(_) => false,
+        }
+    }
+
+    /// Read the file back in from disk, throwing away any changes which haven't been written.
+    ///
+    /// The buffer is then just as if the file had been opened again, except the cursor is left
+    /// where it was, so it may need clamping to the new text. If the file can't be read, such as
+    /// when it has been deleted, the buffer is left unchanged.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let Some(fname) = &self.file else {
+            bail!("No file name");
+        };
+        *self = Self {
+            cursor: self.cursor,
+            ..Self::open(fname)?
+        };
+        Ok(())
+    }
+
     /// Returns a reference to the lines of this [`Buffer`].
-    pub fn lines(&self) -> Lines {
+    pub fn lines(&self) -> Lines<'_> {
         self.text.lines()
     }
 }
+
+/// The text shown for the bytes of a binary file, with one char for each byte.
+///
+/// Printable ASCII, newlines and tabs are kept, and every other byte becomes `·` so control
+/// characters never reach the terminal.
+fn binary_text(bytes: &[u8]) -> Rope {
+    let text: String = bytes
+        .iter()
+        .map(|&byte| match byte {
+            b'\n' | b'\t' | b' '..=b'~' => byte as char,
+            _ => '·',
+        })
+        .collect();
+    Rope::from_str(&text)
+}
+
+/// Hash the text of a file, given in `chunks`, to tell whether it still has the same contents.
+///
+/// The hash only depends on the bytes of the text, not how it is split into chunks.
+fn hash_chunks<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for chunk in chunks {
+        hasher.write(chunk);
+    }
+    hasher.finish()
+}
+
+/// When the file `fname` was last modified, if that can be found out.
+fn disk_mtime(fname: &str) -> Option<SystemTime> {
+    std::fs::metadata(fname).ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempFile;
+
+    #[test]
+    fn empty_buffers() {
+        let mut buffer = Buffer::empty();
+        assert!(buffer.is_empty());
+        buffer.push('a', &mut (0, 0));
+        assert!(!buffer.is_empty());
+        assert!(!Buffer::new(Rope::from_str("\n"), None).is_empty());
+    }
+
+    #[test]
+    fn transposing_chars() {
+        let mut buffer = Buffer::new(Rope::from_str("abc\n"), None);
+        let mut pos = (1, 0);
+        buffer.transpose_chars(&mut pos);
+        assert_eq!(buffer.text, "bac\n");
+        assert_eq!(pos, (2, 0));
+        // At the end of the line, the last two chars are swapped.
+        let mut pos = (3, 0);
+        buffer.transpose_chars(&mut pos);
+        assert_eq!(buffer.text, "bca\n");
+        assert_eq!(pos, (3, 0));
+
+        let mut pos = (0, 0);
+        buffer.transpose_chars(&mut pos);
+        assert_eq!(buffer.text, "bca\n");
+        assert_eq!(pos, (0, 0));
+        let mut buffer = Buffer::new(Rope::from_str("a\n\nb"), None);
+        for mut pos in [(1, 0), (0, 1), (1, 2)] {
+            buffer.transpose_chars(&mut pos);
+        }
+        assert_eq!(buffer.text, "a\n\nb");
+        assert!(!buffer.modified);
+    }
+
+    #[test]
+    fn joining_lines() {
+        let mut buffer = Buffer::new(Rope::from_str("one\r\n    two\n\tthree"), None);
+        let mut pos = (1, 0);
+        buffer.join_lines(&mut pos);
+        assert_eq!(buffer.text, "one two\n\tthree");
+        assert_eq!(pos, (3, 0));
+        buffer.join_lines(&mut pos);
+        assert_eq!(buffer.text, "one two three");
+        assert_eq!(pos, (7, 0));
+
+        // The last line has nothing to join.
+        buffer.join_lines(&mut pos);
+        assert_eq!(buffer.text, "one two three");
+        assert_eq!(pos, (7, 0));
+        let mut buffer = Buffer::new(Rope::from_str("one\n"), None);
+        let mut pos = (0, 0);
+        buffer.join_lines(&mut pos);
+        assert_eq!(buffer.text, "one\n");
+        assert_eq!(pos, (0, 0));
+    }
+
+    #[test]
+    fn converting_positions() {
+        let buffer = Buffer::new(Rope::from_str("ab\ncd\r\n\nef"), None);
+        for (idx, pos) in [
+            (0, (0, 0)),
+            (2, (2, 0)),
+            (3, (0, 1)),
+            (5, (2, 1)),
+            (7, (0, 2)),
+            (8, (0, 3)),
+            (10, (2, 3)),
+        ] {
+            assert_eq!(buffer.char_to_pos(idx), pos);
+            assert_eq!(buffer.pos_to_char(pos.0, pos.1), idx);
+        }
+
+        // Both chars of a `\r\n` are the end of the line.
+        assert_eq!(buffer.char_to_pos(6), (2, 1));
+        // Positions past the end of a line or the text are clamped.
+        assert_eq!(buffer.pos_to_char(10, 0), 2);
+        assert_eq!(buffer.pos_to_char(1, 10), 9);
+        assert_eq!(buffer.char_to_pos(100), (2, 3));
+    }
+
+    #[test]
+    fn opening_binary_files() {
+        let file = TempFile::new("binary");
+        let fname = file.path();
+        std::fs::write(fname, b"ELF\0\x01\x1b[2J\n\xff\tok").unwrap();
+        let mut buffer = Buffer::open(fname).unwrap();
+        assert_eq!(buffer.text, "ELF···[2J\n·\tok");
+        assert_eq!(buffer.bytes(3..5), b"\0\x01");
+        assert_eq!(buffer.char_to_byte(10), 10);
+        assert!(!buffer.changed_since_read());
+        assert!(buffer.write(true).is_err());
+        assert!(buffer.write_as(&format!("{fname}.copy"), true).is_err());
+        assert_eq!(std::fs::read(fname).unwrap(), b"ELF\0\x01\x1b[2J\n\xff\tok");
+
+        std::fs::write(fname, "plain text\n").unwrap();
+        assert!(Buffer::open(fname).unwrap().binary.is_none());
+    }
+
+    #[test]
+    fn appending_to_files() {
+        let file = TempFile::new("append");
+        let fname = file.path();
+        let buffer = Buffer::new(Rope::from_str("one\ntwo\nthree\n"), None);
+        buffer.append_to_path(fname, Some(1..3)).unwrap();
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "two\nthree\n");
+        buffer.append_to_path(fname, None).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(fname).unwrap(),
+            "two\nthree\none\ntwo\nthree\n"
+        );
+        assert!(buffer.append_to_path(fname, Some(2..4)).is_err());
+    }
+
+    #[test]
+    fn reloading() {
+        let file = TempFile::new("reload");
+        let fname = file.path();
+        std::fs::write(fname, "one\n").unwrap();
+        let mut buffer = Buffer::open(fname).unwrap();
+        buffer.text.insert(0, "zero\n");
+        buffer.modified = true;
+        buffer.cursor = (0, 1);
+
+        std::fs::write(fname, "one\ntwo\n").unwrap();
+        buffer.reload().unwrap();
+        assert_eq!(buffer.text, "one\ntwo\n");
+        assert!(!buffer.modified);
+        assert!(!buffer.changed_since_read());
+        assert_eq!(buffer.cursor, (0, 1));
+
+        std::fs::remove_file(fname).unwrap();
+        assert!(buffer.reload().is_err());
+        assert_eq!(buffer.text, "one\ntwo\n");
+        assert!(Buffer::new(Rope::new(), None).reload().is_err());
+    }
+
+    #[test]
+    fn reading_bytes() {
+        let buffer = Buffer::new(Rope::from_str("aé\nb"), None);
+        assert_eq!(buffer.len_bytes(), 5);
+        assert_eq!(buffer.bytes(1..4), "é\n".as_bytes());
+        assert_eq!(buffer.bytes(3..10), b"\nb");
+        assert_eq!(buffer.char_to_byte(2), 3);
+    }
+
+    #[test]
+    fn converting_columns() {
+        let buffer = Buffer::new(Rope::from_str("x\na😀b\n"), None);
+        // The emoji is two UTF-16 code units and four bytes.
+        for (x, utf16, byte) in [(0, 0, 0), (1, 1, 1), (2, 3, 5), (3, 4, 6)] {
+            assert_eq!(buffer.char_to_utf16_col(x, 1), utf16);
+            assert_eq!(buffer.utf16_col_to_char(utf16, 1), x);
+            assert_eq!(buffer.char_to_byte_col(x, 1), byte);
+            assert_eq!(buffer.byte_col_to_char(byte, 1), x);
+        }
+
+        // Columns inside the emoji are the emoji.
+        assert_eq!(buffer.utf16_col_to_char(2, 1), 1);
+        assert_eq!(buffer.byte_col_to_char(3, 1), 1);
+        // Columns past the end of the line don't reach the next one.
+        assert_eq!(buffer.utf16_col_to_char(10, 1), 3);
+        assert_eq!(buffer.char_to_utf16_col(10, 1), 4);
+        assert_eq!(buffer.utf16_col_to_char(1, 5), 0);
+    }
+
+    #[test]
+    fn round_trips() {
+        let buffer = Buffer::new(Rope::from_str("one\r\ntwo\n\nthree\n"), None);
+        for idx in 0..=buffer.text.len_chars() {
+            let (x, y) = buffer.char_to_pos(idx);
+            assert_eq!(buffer.char_to_pos(buffer.pos_to_char(x, y)), (x, y));
+        }
+    }
+}
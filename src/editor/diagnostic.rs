@@ -0,0 +1,89 @@
+//! Diagnostics are problems with the text reported by something outside the editor, such as a
+//! language server.
+//!
+//! Each line with a diagnostic gets a sign in the gutter showing the most severe one on it.
+
+use std::collections::BTreeMap;
+
+/// How serious a [`Diagnostic`] is, from most to least.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    /// Something which is wrong.
+    Error,
+    /// Something which is probably a mistake.
+    Warning,
+    /// Something worth knowing.
+    Information,
+    /// A suggestion.
+    Hint,
+}
+
+impl DiagnosticSeverity {
+    /// The sign shown in the gutter for a line whose most severe diagnostic is this severe.
+    pub fn sign(self) -> char {
+        match self {
+            Self::Error => 'E',
+            Self::Warning => 'W',
+            Self::Information => 'I',
+            Self::Hint => 'H',
+        }
+    }
+}
+
+/// A problem with part of the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The `(x, y)` position where the problem starts.
+    pub start: (usize, usize),
+    /// The `(x, y)` position just after the problem ends.
+    pub end: (usize, usize),
+    /// How serious the problem is.
+    pub severity: DiagnosticSeverity,
+    /// What the problem is.
+    pub message: String,
+}
+
+/// The sign for each line with a diagnostic on it, for the most severe diagnostic on that line.
+///
+/// A diagnostic covering several lines only gets a sign on its first line.
+pub fn signs(diagnostics: &[Diagnostic]) -> BTreeMap<usize, DiagnosticSeverity> {
+    let mut signs = BTreeMap::new();
+    for diagnostic in diagnostics {
+        signs
+            .entry(diagnostic.start.1)
+            .and_modify(|severity: &mut DiagnosticSeverity| {
+                *severity = (*severity).min(diagnostic.severity)
+            })
+            .or_insert(diagnostic.severity);
+    }
+    signs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn choosing_signs() {
+        let diagnostic = |y, severity| Diagnostic {
+            start: (2, y),
+            end: (0, y + 1),
+            severity,
+            message: String::new(),
+        };
+        let signs = signs(&[
+            diagnostic(3, DiagnosticSeverity::Hint),
+            diagnostic(1, DiagnosticSeverity::Warning),
+            diagnostic(3, DiagnosticSeverity::Error),
+            diagnostic(3, DiagnosticSeverity::Information),
+        ]);
+        assert_eq!(
+            signs.into_iter().collect::<Vec<_>>(),
+            [
+                (1, DiagnosticSeverity::Warning),
+                (3, DiagnosticSeverity::Error)
+            ]
+        );
+        assert_eq!(DiagnosticSeverity::Error.sign(), 'E');
+    }
+}
@@ -0,0 +1,138 @@
+//! Working out which lines differ between two versions of a text, for `:diff`.
+
+use super::{line_count, trim_newlines};
+use ropey::RopeSlice;
+
+/// How a line differs between the old and new versions of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// The line is in both.
+    Same,
+    /// The line is only in the new text.
+    Added,
+    /// The line is only in the old text.
+    Removed,
+}
+
+/// A line of a [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    /// Whether the line is in the old text, the new text or both.
+    pub change: Change,
+    /// The text of the line, without its newline.
+    pub text: String,
+    /// The line of the new text this is, or which a removed line would come before.
+    pub line: usize,
+}
+
+/// The lines of `old` and `new` in order, marked with whether they are in both or only one.
+///
+/// As few lines as possible are marked as changed, by keeping the longest common subsequence of
+/// lines the same. Where lines were replaced, the removed lines come before the added ones.
+pub fn diff(old: RopeSlice, new: RopeSlice) -> Vec<DiffLine> {
+    let (old, new) = (lines(old), lines(new));
+    // Matching lines at the start and end are kept out of the table, which is quadratic.
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    // `lcs[i][j]` is the length of the longest common subsequence of `a[i..]` and `b[j..]`.
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |change, text: &String, line| DiffLine {
+        change,
+        text: text.clone(),
+        line,
+    };
+    let mut diff: Vec<DiffLine> = (0..prefix)
+        .map(|y| line(Change::Same, &new[y], y))
+        .collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            diff.push(line(Change::Same, &b[j], prefix + j));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(line(Change::Removed, &a[i], prefix + j));
+            i += 1;
+        } else {
+            diff.push(line(Change::Added, &b[j], prefix + j));
+            j += 1;
+        }
+    }
+    diff.extend((new.len() - suffix..new.len()).map(|y| line(Change::Same, &new[y], y)));
+    diff
+}
+
+/// Only the lines of `diff` which have changed, along with up to `context` unchanged lines on
+/// either side of each of them.
+pub fn hunks(diff: Vec<DiffLine>, context: usize) -> Vec<DiffLine> {
+    let changed: Vec<usize> = (0..diff.len())
+        .filter(|&i| diff[i].change != Change::Same)
+        .collect();
+    diff.into_iter()
+        .enumerate()
+        .filter(|&(i, _)| {
+            let next = changed.partition_point(|&c| c + context < i);
+            changed.get(next).is_some_and(|&c| c <= i + context)
+        })
+        .map(|(_, line)| line)
+        .collect()
+}
+
+/// The text of each line of `text`, without the newlines.
+fn lines(text: RopeSlice) -> Vec<String> {
+    (0..line_count(text))
+        .map(|y| trim_newlines(text.line(y)).to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ropey::Rope;
+
+    #[test]
+    fn diffing_lines() {
+        let old = Rope::from_str("a\nb\nc\nd\ne\nf\ng\n");
+        let new = Rope::from_str("a\nB\nc\nd\ne\nf\ng\nh\n");
+        let lines = diff(old.slice(..), new.slice(..));
+        let changes: Vec<(Change, &str, usize)> = lines
+            .iter()
+            .map(|line| (line.change, line.text.as_str(), line.line))
+            .collect();
+        assert_eq!(
+            changes[..4],
+            [
+                (Change::Same, "a", 0),
+                (Change::Removed, "b", 1),
+                (Change::Added, "B", 1),
+                (Change::Same, "c", 2),
+            ]
+        );
+        assert_eq!(changes[8], (Change::Added, "h", 7));
+        assert_eq!(changes.len(), 9);
+
+        // Only the changes are kept, with a line of context around them.
+        let texts: Vec<String> = hunks(lines, 1).into_iter().map(|line| line.text).collect();
+        assert_eq!(texts, ["a", "b", "B", "c", "g", "h"]);
+
+        let same = diff(old.slice(..), old.slice(..));
+        assert!(hunks(same, 3).is_empty());
+    }
+}
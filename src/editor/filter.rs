@@ -0,0 +1,103 @@
+//! Running text through an external shell command, like vim's `:%!`.
+
+use anyhow::{bail, Context};
+use ropey::{Rope, RopeSlice};
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+};
+
+/// Run `command` in the shell with `input` as its standard input and collect its output.
+///
+/// The output is streamed straight in to a [`Rope`]. If the command produces more than `limit`
+/// bytes of output, it is killed and an error is returned instead.
+pub fn filter(command: &str, input: RopeSlice, limit: usize) -> anyhow::Result<Rope> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Running `{command}` failed."))?;
+
+    // Feed the input from another thread so a command which writes before it has read all of its
+    // input can't deadlock with us.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || {
+        // The command is free to stop reading early, so a broken pipe isn't an error.
+        let _ = stdin.write_all(input.as_bytes());
+    });
+
+    let mut stdout = CountingReader {
+        inner: child
+            .stdout
+            .take()
+            .expect("stdout is piped")
+            .take(limit as u64 + 1),
+        count: 0,
+    };
+    let output = Rope::from_reader(&mut stdout);
+    // Cutting the output off might have split a char, so check the count rather than the output.
+    let too_long = stdout.count > limit;
+    if too_long || output.is_err() {
+        let _ = child.kill();
+    }
+    let status = child.wait()?;
+    let _ = writer.join();
+
+    if too_long {
+        bail!("Output of `{command}` is larger than filterlimit ({limit} bytes); buffer left unchanged");
+    }
+    let output = output.with_context(|| format!("Reading the output of `{command}` failed."))?;
+    if !status.success() {
+        bail!("`{command}` failed ({status}); buffer left unchanged");
+    }
+    Ok(output)
+}
+
+/// A reader which keeps track of how many bytes have been read through it.
+struct CountingReader<R> {
+    /// The reader being counted.
+    inner: R,
+    /// The number of bytes read so far.
+    count: usize,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filtering_text() {
+        let input = Rope::from_str("banana\napple\n");
+        let output = filter("sort", input.slice(..), 100).unwrap();
+        assert_eq!(output, "apple\nbanana\n");
+    }
+
+    #[test]
+    fn too_much_output() {
+        let input = Rope::from_str("hi\n");
+        assert!(filter("yes", input.slice(..), 1000).is_err());
+        assert!(filter("head -c 1001 /dev/zero", input.slice(..), 1000).is_err());
+        assert!(filter("head -c 1000 /dev/zero", input.slice(..), 1000).is_ok());
+        // The limit landing in the middle of a char is still reported as being too long.
+        let err = filter("yes é", input.slice(..), 1000).unwrap_err();
+        assert!(err.to_string().contains("filterlimit"), "{err}");
+    }
+
+    #[test]
+    fn failing_command() {
+        let input = Rope::from_str("hi\n");
+        assert!(filter("exit 3", input.slice(..), 1000).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Hooks are callbacks run when something happens in the editor, like vim's autocommands.
+//!
+//! They are what scripts, tests and plugins use to react to the editor. Each hook is given the
+//! editor, which it can look at but not change, along with what happened.
+
+use super::{Editor, Mode};
+use std::fmt;
+
+/// The kinds of [`HookEvent`] a hook can be added for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+    /// See [`HookEvent::BufWrite`].
+    BufWrite,
+    /// See [`HookEvent::BufOpen`].
+    BufOpen,
+    /// See [`HookEvent::ModeChange`].
+    ModeChange,
+    /// See [`HookEvent::CursorMove`].
+    CursorMove,
+}
+
+/// Something which happened in the editor, given to the hooks added for its [`HookKind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookEvent {
+    /// A buffer was written to the file `fname`.
+    BufWrite {
+        /// The file which was written.
+        fname: String,
+    },
+    /// The file `fname` was opened in a new buffer.
+    ///
+    /// This is fired for the file the editor is created with too, though only autocommands see
+    /// it then, since no hooks can have been added yet.
+    BufOpen {
+        /// The file which was opened.
+        fname: String,
+    },
+    /// The editor changed mode.
+    ModeChange {
+        /// The mode the editor was in.
+        from: Mode,
+        /// The mode the editor is now in.
+        to: Mode,
+    },
+    /// The cursor was moved by a motion, rather than by editing the text.
+    CursorMove {
+        /// The `(x, y)` position the cursor was at.
+        from: (usize, usize),
+        /// The `(x, y)` position the cursor is now at.
+        to: (usize, usize),
+    },
+}
+
+impl HookEvent {
+    /// The kind of hooks which are run for the event.
+    pub fn kind(&self) -> HookKind {
+        match self {
+            Self::BufWrite { .. } => HookKind::BufWrite,
+            Self::BufOpen { .. } => HookKind::BufOpen,
+            Self::ModeChange { .. } => HookKind::ModeChange,
+            Self::CursorMove { .. } => HookKind::CursorMove,
+        }
+    }
+}
+
+/// A callback run for a [`HookEvent`].
+pub type Hook = Box<dyn FnMut(&Editor, &HookEvent) + Send>;
+
+/// The hooks added to an [`Editor`], in the order they were added.
+#[derive(Default)]
+pub struct Hooks {
+    /// Each hook along with the kind of event it is run for.
+    hooks: Vec<(HookKind, Hook)>,
+}
+
+impl Hooks {
+    /// Run `hook` whenever an event of `kind` happens.
+    pub fn add(&mut self, kind: HookKind, hook: impl FnMut(&Editor, &HookEvent) + Send + 'static) {
+        self.hooks.push((kind, Box::new(hook)));
+    }
+
+    /// Run every hook added for the kind of `event`, in the order they were added.
+    pub fn run(&mut self, editor: &Editor, event: &HookEvent) {
+        for (kind, hook) in &mut self.hooks {
+            if *kind == event.kind() {
+                hook(editor, event);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.hooks.iter().map(|(kind, _)| kind))
+            .finish()
+    }
+}
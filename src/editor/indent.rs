@@ -0,0 +1,142 @@
+//! Checking the indentation of a buffer.
+
+use super::trim_newlines;
+use ropey::RopeSlice;
+use std::ops::Range;
+
+/// What a line is indented with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Indent {
+    /// Only tabs, possibly followed by spaces to line things up.
+    Tabs,
+    /// Only spaces.
+    Spaces,
+    /// Spaces followed by tabs, which is wrong whichever style is expected.
+    Mixed,
+}
+
+/// Find the first line of `text` which is indented differently to the rest.
+///
+/// The first indented line decides whether tabs or spaces are expected. Tabs followed by spaces
+/// are allowed for lining things up, but spaces followed by tabs never are.
+pub fn mixed_indentation(text: RopeSlice) -> Option<usize> {
+    let mut expected = None;
+    for (y, line) in text.lines().enumerate() {
+        let Some(indent) = indent(line) else {
+            continue;
+        };
+        match expected {
+            _ if indent == Indent::Mixed => return Some(y),
+            None => expected = Some(indent),
+            Some(expected) if expected != indent => return Some(y),
+            Some(_) => {}
+        }
+    }
+    None
+}
+
+/// Work out what `line` is indented with, if it is indented at all.
+///
+/// Blank lines aren't counted as indented.
+fn indent(line: RopeSlice) -> Option<Indent> {
+    let mut seen_space = false;
+    let mut seen_tab = false;
+    for c in line.chars() {
+        match c {
+            ' ' => seen_space = true,
+            '\t' if seen_space => return Some(Indent::Mixed),
+            '\t' => seen_tab = true,
+            '\n' | '\r' => return None,
+            _ => break,
+        }
+    }
+    match (seen_tab, seen_space) {
+        (true, _) => Some(Indent::Tabs),
+        (false, true) => Some(Indent::Spaces),
+        (false, false) => None,
+    }
+}
+
+/// The columns an indent guide is drawn at for each line of `text` in `lines`, one for each level
+/// of indentation.
+///
+/// Each tab is a level, as is every `tab_width` columns of spaces. Blank lines get the guides of
+/// the nearest lines above or below which aren't blank, whichever has fewer, so the guides carry
+/// on through them.
+pub fn guides(text: RopeSlice, lines: Range<usize>, tab_width: usize) -> Vec<Vec<usize>> {
+    let lines = lines.start.min(text.len_lines())..lines.end.min(text.len_lines());
+    if tab_width == 0 {
+        return vec![Vec::new(); lines.len()];
+    }
+    let is_blank = |y: usize| trim_newlines(text.line(y)).chars().all(char::is_whitespace);
+    lines
+        .map(|y| {
+            if !is_blank(y) {
+                return line_guides(text.line(y), tab_width);
+            }
+            let above = (0..y).rev().find(|&y| !is_blank(y));
+            let below = (y + 1..text.len_lines()).find(|&y| !is_blank(y));
+            let mut neighbours = [above, below]
+                .into_iter()
+                .flatten()
+                .map(|y| line_guides(text.line(y), tab_width));
+            let first = neighbours.next().unwrap_or_default();
+            match neighbours.next() {
+                Some(second) if second.len() < first.len() => second,
+                _ => first,
+            }
+        })
+        .collect()
+}
+
+/// The columns of the indentation of `line` where an indent guide is drawn. See [`guides`].
+fn line_guides(line: RopeSlice, tab_width: usize) -> Vec<usize> {
+    let mut columns = Vec::new();
+    let mut spaces = 0;
+    for (x, c) in line.chars().enumerate() {
+        match c {
+            '\t' => {
+                columns.push(x);
+                spaces = 0;
+            }
+            ' ' => {
+                if spaces % tab_width == 0 {
+                    columns.push(x);
+                }
+                spaces += 1;
+            }
+            _ => break,
+        }
+    }
+    columns
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ropey::Rope;
+
+    #[test]
+    fn finding_mixed_indentation() {
+        let check = |text: &str| mixed_indentation(Rope::from_str(text).slice(..));
+        assert_eq!(check("fn a() {\n\tone\n    two\n\tthree\n}\n"), Some(2));
+        assert_eq!(check("fn a() {\n    one\n\ttwo\n}\n"), Some(2));
+        assert_eq!(check("a\n \tb\n"), Some(1));
+        // Tabs followed by spaces to line things up are fine, as are blank lines.
+        assert_eq!(check("\tone(a,\n\t    b)\n    \n\n"), None);
+        assert_eq!(check("a\n  b\n    c\n"), None);
+    }
+
+    #[test]
+    fn placing_guides() {
+        let text = Rope::from_str("a\n        b\n\n    c\n\t\td\n");
+        let guides = guides(text.slice(..), 0..10, 4);
+        assert_eq!(guides[1], [0, 4]);
+        // The blank line takes the guides of the less indented line below it.
+        assert_eq!(guides[2], [0]);
+        assert_eq!(guides[3], [0]);
+        assert_eq!(guides[4], [0, 1]);
+        assert!(guides[0].is_empty());
+        assert_eq!(guides.len(), 6);
+    }
+}
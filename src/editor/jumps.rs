@@ -0,0 +1,88 @@
+//! Lists of places the cursor has been, like vim's jump list and change list.
+//!
+//! The jump list remembers where the cursor was before each jump, such as to a search match. The
+//! change list remembers where each change to a buffer was made.
+
+use crate::config::POSITION_HISTORY_LENGTH;
+
+/// A list of positions, oldest first, with one of them being the current one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History<T> {
+    /// The positions, oldest first.
+    entries: Vec<T>,
+    /// The index of the current entry.
+    current: usize,
+}
+
+impl<T> History<T> {
+    /// Add `entry` as the newest position and make it the current one.
+    ///
+    /// Any older entries which `same` says are at the same place are removed first, so each
+    /// place is only in the list once. The oldest entries are dropped to keep at most
+    /// [`POSITION_HISTORY_LENGTH`].
+    pub fn push(&mut self, entry: T, same: impl Fn(&T, &T) -> bool) {
+        self.entries.retain(|old| !same(old, &entry));
+        self.entries.push(entry);
+        let excess = self.entries.len().saturating_sub(POSITION_HISTORY_LENGTH);
+        self.entries.drain(..excess);
+        self.current = self.entries.len() - 1;
+    }
+
+    /// All the entries, oldest first.
+    pub fn entries(&self) -> &[T] {
+        &self.entries
+    }
+
+    /// The index of the current entry in [`History::entries`].
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Make the entry at `index` the current one, returning it.
+    pub fn go_to(&mut self, index: usize) -> Option<&T> {
+        let entry = self.entries.get(index)?;
+        self.current = index;
+        Some(entry)
+    }
+
+    /// Remove every entry which doesn't satisfy `keep`.
+    pub fn retain(&mut self, keep: impl Fn(&T) -> bool) {
+        self.entries.retain(keep);
+        self.current = self.current.min(self.entries.len().saturating_sub(1));
+    }
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            current: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeping_history() {
+        let same_line = |a: &(usize, usize), b: &(usize, usize)| a.1 == b.1;
+        let mut history = History::default();
+        history.push((0, 0), same_line);
+        history.push((3, 4), same_line);
+        history.push((1, 0), same_line);
+        assert_eq!(history.entries(), [(3, 4), (1, 0)]);
+        assert_eq!(history.current(), 1);
+
+        assert_eq!(history.go_to(0), Some(&(3, 4)));
+        assert_eq!(history.current(), 0);
+        assert_eq!(history.go_to(2), None);
+
+        for y in 0..POSITION_HISTORY_LENGTH + 10 {
+            history.push((0, y), same_line);
+        }
+        assert_eq!(history.entries().len(), POSITION_HISTORY_LENGTH);
+        assert_eq!(history.entries()[0], (0, 10));
+    }
+}
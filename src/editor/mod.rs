@@ -1,10 +1,48 @@
 //! All the code relating to the [`Editor`] lives here.
 
+#[cfg(feature = "lsp")]
+use crate::lsp::protocol::PositionEncoding;
+use crate::{
+    command::{Command, UndoStep},
+    config::{self, Key, Message},
+    keymap::{self, Keymap},
+    progress::Progress,
+    settings::Settings,
+};
+use anyhow::{bail, Context};
+use autocmd::Autocmd;
 use buffer::Buffer;
-use ropey::{iter::Lines, RopeSlice};
-use std::collections::BTreeMap;
+use diagnostic::Diagnostic;
+use diff::{Change, DiffLine};
+use hooks::{HookEvent, HookKind, Hooks};
+use jumps::History;
+use registers::{Register, Registers};
+use ropey::{iter::Lines, Rope, RopeSlice};
+use search::Search;
+use spell::Dictionary;
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    ops::Range,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use window::Layout;
 
+pub mod autocmd;
 mod buffer;
+pub mod diagnostic;
+pub mod diff;
+mod filter;
+pub mod hooks;
+mod indent;
+pub mod jumps;
+pub mod registers;
+pub mod search;
+pub mod spell;
+pub mod undo;
+pub mod window;
 
 /// Documents are indexed by a unique usize.
 type DocumentID = usize;
@@ -17,6 +55,10 @@ type DocumentID = usize;
 pub struct Editor {
     /// All the buffers in the editor.
     buffers: BTreeMap<DocumentID, Buffer>,
+    /// The ID the next buffer opened will get.
+    ///
+    /// IDs only ever go up, so an ID of a closed buffer is never given to a different one.
+    next_id: DocumentID,
     /// Which of the buffers is currently selected.
     ///
     /// This is a key into [`buffers`].
@@ -27,66 +69,674 @@ pub struct Editor {
     ///
     /// This is a position in the buffer, not necessarilly on the screen.
     selected_pos: (usize, usize),
+    /// The column the cursor is trying to stay in while moving up and down, along with where the
+    /// cursor was left by the last of those moves.
+    ///
+    /// The column can be past the end of the line the cursor is on, so that moving through short
+    /// lines and back again returns to it. It is forgotten as soon as the cursor is anywhere else.
+    /// See [`Editor::virtual_column`].
+    virtual_column: Option<(usize, (usize, usize))>,
     /// The current mode of the editor.
     pub mode: Mode,
+    /// The char index where the selection started in [`Mode::Visual`].
+    ///
+    /// The selection runs from here to the cursor.
+    anchor: usize,
+    /// Whether the selection in [`Mode::Visual`] is of chars or whole lines.
+    selection_kind: SelectionKind,
+    /// The text which has been deleted or yanked.
+    registers: Registers,
+    /// The register the next delete, yank or paste uses, chosen by typing `"` and its name.
+    pending_register: Option<char>,
+    /// The count typed before the next motion, like the 3 in `3j`.
+    count: Option<usize>,
+    /// Output from the last command run which should be shown to the user.
+    output: Option<String>,
+    /// The text typed in to the command line so far.
+    command_line: String,
+    /// The current runtime settings.
+    pub settings: Settings,
+    /// The key mappings added with `:map` and `:noremap`.
+    pub keymap: Keymap,
+    /// The last search, which `n` and `N` repeat.
+    search: Option<Search>,
+    /// Where the cursor was before each jump, like vim's jump list.
+    jumps: History<Jump>,
+    /// The list being picked from in [`Mode::List`].
+    list: Option<List>,
+    /// The change waiting to be confirmed in [`Mode::Confirm`], and the question asked about it.
+    confirming: Option<(Confirmable, String)>,
+    /// Whether a command like `:q` has asked for the editor to quit.
+    quitting: bool,
+    /// The messages which made the last change, which [`Message::Repeat`] replays.
+    last_change: Vec<Message>,
+    /// The messages of the change being made in [`Mode::Insert`], which becomes the last change
+    /// once insert mode is left.
+    recording: Option<Vec<Message>>,
+    /// The windows showing the buffers. The focused one shows the selected buffer.
+    layout: Layout,
+    /// The first line of the selected buffer with indentation which doesn't match the rest.
+    ///
+    /// This is checked whenever a change is made. See [`Editor::mixed_indentation`].
+    mixed_indentation: Option<usize>,
+    /// Whether the keys coming in are pasted text rather than typing.
+    ///
+    /// Auto-indent is turned off while pasting, since the text already has its indentation.
+    pasting: bool,
+    /// The words considered correct when spell checking.
+    ///
+    /// This is loaded the first time spell checking is turned on.
+    dictionary: Option<Dictionary>,
+    /// The callbacks run when things happen in the editor.
+    hooks: Hooks,
+    /// The autocommands, in the order they are run.
+    autocmds: Vec<Autocmd>,
+    /// Whether autocommands are being run, so the commands they run don't set off more.
+    running_autocmds: bool,
+    /// The language server for the kind of file being edited, if one is running.
+    #[cfg(feature = "lsp")]
+    language_server: Option<crate::lsp::Client>,
 }
 
 impl Editor {
-    pub fn new() -> Self {
-        let mut buffers = BTreeMap::new();
-        buffers.insert(0, Buffer::empty());
-        Self {
-            buffers,
-            selected_buf: 0,
-            selected_pos: (0, 0),
-            mode: Mode::Normal,
-        }
+    /// Create an [`Editor`] with a single empty buffer which has no file, ready to be typed in to.
+    ///
+    /// This is what the editor starts with when it isn't given a file to open.
+    pub fn new_empty() -> Self {
+        Self::with_buffer(Buffer::empty())
     }
+
     /// Open a file and read its contents to the buffer.
+    ///
+    /// [`HookEvent::BufOpen`] is fired for the file, so autocommands for it run.
     pub fn open(fname: &str) -> anyhow::Result<Self> {
+        Self::open_with_progress(fname, |_| {})
+    }
+
+    /// Open a file and read its contents to the buffer, calling `report` as it is read in.
+    ///
+    /// [`HookEvent::BufOpen`] is fired for the file, so autocommands for it run.
+    pub fn open_with_progress(fname: &str, report: impl FnMut(Progress)) -> anyhow::Result<Self> {
+        let mut editor = Self::with_buffer(Buffer::open_with_progress(fname, report)?);
+        editor.fire(HookEvent::BufOpen {
+            fname: fname.to_owned(),
+        });
+        Ok(editor)
+    }
+
+    /// Read all of `reader` in to a buffer with no file, like `-` on the command line reading
+    /// from stdin.
+    pub fn from_reader(reader: impl Read) -> anyhow::Result<Self> {
+        let text = Rope::from_reader(reader).context("Reading the text to edit failed")?;
+        Ok(Self::with_buffer(Buffer::new(text, None)))
+    }
+
+    /// Create an [`Editor`] editing only `buffer`.
+    fn with_buffer(buffer: Buffer) -> Self {
         let mut buffers = BTreeMap::new();
-        buffers.insert(0, Buffer::open(fname)?);
-        Ok(Self {
+        buffers.insert(0, buffer);
+        Self {
             buffers,
+            next_id: 1,
             selected_buf: 0,
             selected_pos: (0, 0),
+            virtual_column: None,
             mode: Mode::Normal,
-        })
+            anchor: 0,
+            selection_kind: SelectionKind::Char,
+            registers: Registers::default(),
+            pending_register: None,
+            count: None,
+            output: None,
+            command_line: String::new(),
+            settings: Settings::default(),
+            keymap: Keymap::from_config(),
+            search: None,
+            jumps: History::default(),
+            list: None,
+            confirming: None,
+            quitting: false,
+            last_change: Vec::new(),
+            recording: None,
+            layout: Layout::new(0),
+            mixed_indentation: None,
+            pasting: false,
+            dictionary: None,
+            hooks: Hooks::default(),
+            autocmds: config::AUTOCMDS
+                .iter()
+                .map(|&(event, pattern, command)| Autocmd {
+                    event,
+                    pattern: pattern.to_owned(),
+                    command: command.to_owned(),
+                })
+                .collect(),
+            running_autocmds: false,
+            #[cfg(feature = "lsp")]
+            language_server: None,
+        }
+        .checked_indentation()
+    }
+
+    /// Check the indentation of the selected buffer, returning the editor for convenience.
+    fn checked_indentation(mut self) -> Self {
+        self.check_indentation();
+        self
     }
 
     /// Append a single character to the [`Editor`].
+    ///
+    /// With [`Settings::autopairs`] on, typing the first char of one of [`Settings::match_pairs`]
+    /// types the second after the cursor too, and typing the second when it is already under the
+    /// cursor moves past it instead. Pasted text is left alone.
     pub fn push(&mut self, c: char) {
+        let autopairs = self.settings.autopairs && !self.pasting;
+        let pair = self.settings.match_pairs.iter().find(|pair| pair.0 == c);
+        let close = pair.filter(|_| autopairs).map(|pair| pair.1);
+        let closing = self.settings.match_pairs.iter().any(|pair| pair.1 == c);
+        if autopairs && closing && self.text().get_char(self.cursor_char()) == Some(c) {
+            self.selected_pos.0 += 1;
+            return;
+        }
         if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
             buf.push(c, &mut self.selected_pos);
+            if let Some(close) = close {
+                buf.push(close, &mut self.selected_pos);
+                self.selected_pos.0 -= 1;
+            }
         }
     }
 
     /// Remove the last character in the [`Editor`].
+    ///
+    /// At the start of a line, this joins it on to the line before if
+    /// [`Settings::backspace_joins_lines`] is on, and does nothing otherwise.
     pub fn backspace(&mut self) {
+        let join = self.selected_pos.0 == 0 && self.settings.backspace_joins_lines;
         if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
-            buf.backspace(&mut self.selected_pos);
+            if join {
+                buf.join_with_previous(&mut self.selected_pos);
+            } else {
+                buf.backspace(&mut self.selected_pos);
+            }
         }
     }
 
     /// Adds a new line where the cursor is.
+    ///
+    /// With [`Settings::autoindent`] on, the new line starts with the indentation of the line the
+    /// cursor was on, unless text is being pasted.
     pub fn newline(&mut self) {
+        let (x, y) = self.selected_pos;
+        let indent = self.new_line_indent(x, y);
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.newline(&mut self.selected_pos);
+        }
+        self.insert_str(&indent);
+    }
+
+    /// The indentation a new line split off at `(x, y)` starts with, which is whatever of line
+    /// `y`'s indentation comes before `x` if [`Settings::autoindent`] is on.
+    fn new_line_indent(&self, x: usize, y: usize) -> String {
+        if !self.settings.autoindent || self.pasting {
+            return String::new();
+        }
+        self.text()
+            .line(y)
+            .chars()
+            .take(x)
+            .take_while(|&c| c == ' ' || c == '\t')
+            .collect()
+    }
+
+    /// Start inserting after the char under the cursor, like vim's `a`.
+    ///
+    /// On an empty line, or with the cursor already past the end of the line, this inserts where
+    /// the cursor is.
+    pub fn append(&mut self) {
+        self.set_mode(Mode::Insert);
+        let from = self.selected_pos;
+        let (x, y) = self.selected_pos;
+        self.selected_pos.0 = grapheme_end(trim_newlines(self.text().line(y)), x);
+        self.cursor_moved(from);
+    }
+
+    /// Start inserting at the end of the line the cursor is on, like vim's `A`.
+    pub fn append_at_line_end(&mut self) {
+        self.set_mode(Mode::Insert);
+        self.move_to_line_end();
+    }
+
+    /// Start inserting before the first char of the line which isn't a space or tab, like vim's
+    /// `I`. See [`Editor::move_to_first_non_blank`].
+    pub fn insert_at_first_non_blank(&mut self) {
+        self.set_mode(Mode::Insert);
+        self.move_to_first_non_blank();
+    }
+
+    /// Start a new line below the one the cursor is on and start inserting on it, like vim's `o`.
+    ///
+    /// With [`Settings::autoindent`] on, it starts with the same indentation.
+    pub fn open_below(&mut self) {
+        self.set_mode(Mode::Insert);
+        let y = self.selected_pos.1;
+        self.selected_pos.0 = trim_newlines(self.text().line(y)).len_chars();
+        self.newline();
+    }
+
+    /// Start a new line above the one the cursor is on and start inserting on it, like vim's `O`.
+    ///
+    /// With [`Settings::autoindent`] on, it starts with the same indentation.
+    pub fn open_above(&mut self) {
+        self.set_mode(Mode::Insert);
+        let y = self.selected_pos.1;
+        let indent = self.new_line_indent(usize::MAX, y);
+        self.selected_pos = (0, y);
         if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
             buf.newline(&mut self.selected_pos);
         }
+        self.selected_pos = (0, y);
+        self.insert_str(&indent);
+    }
+
+    /// Set whether the keys coming in are pasted text rather than typing, such as by a
+    /// [`PasteDetector`].
+    ///
+    /// [`PasteDetector`]: crate::paste::PasteDetector
+    pub fn set_pasting(&mut self, pasting: bool) {
+        self.pasting = pasting;
     }
 
     /// Write the current contents of the buffer to the file it came from.
-    pub fn write(&self) -> anyhow::Result<()> {
-        self.buffers[&self.selected_buf].write()
+    ///
+    /// Any changes not yet in the undo history are recorded first, so the saved history ends at
+    /// what was written. See [`Buffer::write`] for when `force` is needed.
+    pub fn write(&mut self, force: bool) -> anyhow::Result<()> {
+        self.commit();
+        if self.settings.readonly {
+            bail!("The buffer is read-only (see :set noreadonly)");
+        }
+        let Some(buf) = self.buffers.get_mut(&self.selected_buf) else {
+            return Ok(());
+        };
+        buf.write(force)?;
+        if let Some(fname) = buf.file.clone() {
+            self.fire(HookEvent::BufWrite { fname });
+        }
+        Ok(())
+    }
+
+    /// Select the buffer after the selected one, wrapping around to the first.
+    ///
+    /// See [`Editor::select_buffer`].
+    pub fn next_buffer(&mut self) -> anyhow::Result<()> {
+        let next = self
+            .buffers
+            .range(self.selected_buf + 1..)
+            .chain(&self.buffers)
+            .next()
+            .map(|(&id, _)| id);
+        match next {
+            Some(id) => self.select_buffer(id),
+            None => Ok(()),
+        }
+    }
+
+    /// Select the buffer before the selected one, wrapping around to the last.
+    ///
+    /// See [`Editor::select_buffer`].
+    pub fn prev_buffer(&mut self) -> anyhow::Result<()> {
+        let prev = self
+            .buffers
+            .range(..self.selected_buf)
+            .next_back()
+            .or(self.buffers.last_key_value())
+            .map(|(&id, _)| id);
+        match prev {
+            Some(id) => self.select_buffer(id),
+            None => Ok(()),
+        }
+    }
+
+    /// Switch to the buffer `id`, putting the cursor back where it was when it was left.
+    ///
+    /// With [`Settings::autowrite`] on, the buffer being left is written first if it is modified.
+    /// Failing to write it doesn't stop the switch, but the error is returned.
+    fn select_buffer(&mut self, id: DocumentID) -> anyhow::Result<()> {
+        self.set_mode(Mode::Normal);
+        self.commit();
+        let written = self.autowrite(self.selected_buf);
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.cursor = self.selected_pos;
+        }
+        self.enter_buffer(id);
+        written
+    }
+
+    /// Make `id` the selected buffer, putting the cursor back where it was when it was left.
+    fn enter_buffer(&mut self, id: DocumentID) {
+        self.selected_buf = id;
+        self.layout.focused_window().buffer = id;
+        self.selected_pos = self.buffers[&id].cursor;
+        self.clamp_cursor();
+        self.check_indentation();
+    }
+
+    /// Open the file `fname` in a new buffer, without selecting it.
+    fn open_buffer(&mut self, fname: &str) -> anyhow::Result<DocumentID> {
+        let id = self.add_buffer(Buffer::open(fname)?);
+        self.fire(HookEvent::BufOpen {
+            fname: fname.to_owned(),
+        });
+        Ok(id)
+    }
+
+    /// Add `buffer` to the editor with a new ID, without selecting it.
+    fn add_buffer(&mut self, buffer: Buffer) -> DocumentID {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.buffers.insert(id, buffer);
+        id
+    }
+
+    /// Close the buffer `id`, throwing away any changes to it.
+    ///
+    /// If it was selected, the buffer after it is selected instead, or the one before if it was
+    /// the last. Closing the only buffer leaves an empty one with no file in its place.
+    pub fn close_buffer(&mut self, id: DocumentID) -> anyhow::Result<()> {
+        if self.buffers.remove(&id).is_none() {
+            bail!("No buffer {id}");
+        }
+        self.jumps.retain(|jump| jump.buffer != id);
+        let neighbour = self
+            .buffers
+            .range(id..)
+            .next()
+            .or_else(|| self.buffers.range(..id).next_back())
+            .map(|(&id, _)| id);
+        let neighbour = neighbour.unwrap_or_else(|| self.add_buffer(Buffer::empty()));
+        // Other windows showing the buffer show its replacement instead.
+        let cursor = self.buffers[&neighbour].cursor;
+        self.layout.replace_buffer(id, neighbour, cursor);
+        if id == self.selected_buf {
+            self.set_mode(Mode::Normal);
+            self.enter_buffer(neighbour);
+        }
+        Ok(())
+    }
+
+    /// The windows showing the buffers, and which of them is focused.
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// The whole text of the buffer `id`, for showing in a window which isn't focused.
+    pub fn buffer_text(&self, id: usize) -> Option<RopeSlice<'_>> {
+        Some(self.buffers.get(&id)?.text.slice(..))
+    }
+
+    /// The file of the buffer `id`, if it has one.
+    pub fn buffer_fname(&self, id: usize) -> Option<&str> {
+        self.buffers.get(&id)?.file.as_deref()
+    }
+
+    /// Split the focused window in two, like vim's `:split`.
+    ///
+    /// See [`Layout::split`].
+    pub fn split_window(&mut self) {
+        self.layout.focused_window().cursor = self.selected_pos;
+        self.layout.split();
+    }
+
+    /// Close the focused window, like vim's `:close`, keeping the buffer it showed.
+    ///
+    /// See [`Layout::close`] for which window is focused afterwards.
+    pub fn close_window(&mut self) -> anyhow::Result<()> {
+        self.set_mode(Mode::Normal);
+        self.layout.close()?;
+        self.enter_window();
+        Ok(())
+    }
+
+    /// Close every window but the focused one, like vim's `:only`, keeping the buffers they
+    /// showed.
+    pub fn only_window(&mut self) {
+        self.set_mode(Mode::Normal);
+        self.layout.only();
+    }
+
+    /// Close the focused window like [`Editor::close_window`], or if it is the last window, check
+    /// that the editor can quit, like vim's `Ctrl-w q`.
+    ///
+    /// Returns whether the editor should quit, which it can't while any buffer is modified.
+    pub fn quit_window(&mut self) -> anyhow::Result<bool> {
+        if self.layout.windows().len() > 1 {
+            self.close_window()?;
+            return Ok(false);
+        }
+        self.commit();
+        if self.any_modified() {
+            bail!("No write since last change (add ! to override)");
+        }
+        Ok(true)
+    }
+
+    /// Close the focused window like [`Editor::quit_window`], or quit the editor if it is the
+    /// last one, like vim's `:q`. With `force`, modified buffers don't stop it quitting.
+    ///
+    /// The editor doesn't stop running by itself, so whatever runs it should check
+    /// [`Editor::quitting`].
+    pub fn quit(&mut self, force: bool) -> anyhow::Result<()> {
+        self.quitting = if force && self.layout.windows().len() == 1 {
+            true
+        } else {
+            self.quit_window()?
+        };
+        Ok(())
+    }
+
+    /// Whether a command like `:q` has asked for the editor to quit.
+    pub fn quitting(&self) -> bool {
+        self.quitting
+    }
+
+    /// Focus the window at `index` in [`Layout::windows`], leaving the cursor of the window
+    /// being left where it was.
+    pub fn focus_window(&mut self, index: usize) {
+        self.set_mode(Mode::Normal);
+        self.layout.focused_window().cursor = self.selected_pos;
+        self.layout.focus(index);
+        self.enter_window();
+    }
+
+    /// Set how much of the screen each window gets, from top to bottom.
+    ///
+    /// See [`Layout::set_weights`].
+    pub fn set_window_weights(&mut self, weights: &[usize]) {
+        self.layout.set_weights(weights);
+    }
+
+    /// Give every window the same amount of the screen, like vim's `Ctrl-w =`.
+    pub fn equalize_windows(&mut self) {
+        self.layout.equalize();
+    }
+
+    /// Focus the window below the focused one, wrapping around to the top one.
+    pub fn next_window(&mut self) {
+        let next = (self.layout.focused() + 1) % self.layout.windows().len();
+        self.focus_window(next);
+    }
+
+    /// Select the buffer of the focused window, with the cursor where the window had it.
+    fn enter_window(&mut self) {
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.cursor = self.selected_pos;
+        }
+        let window = *self.layout.focused_window();
+        self.enter_buffer(window.buffer);
+        self.selected_pos = window.cursor;
+        self.clamp_cursor();
+    }
+
+    /// Write every modified buffer which has a file, if [`Settings::autowrite`] is on.
+    ///
+    /// This is done when the terminal loses focus. Every buffer is tried even if some can't be
+    /// written.
+    pub fn autowrite_all(&mut self) -> anyhow::Result<()> {
+        self.commit();
+        let ids: Vec<DocumentID> = self.buffers.keys().copied().collect();
+        let errors: Vec<String> = ids
+            .into_iter()
+            .filter_map(|id| self.autowrite(id).err())
+            .map(|err| format!("{err:#}"))
+            .collect();
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+        Ok(())
+    }
+
+    /// Write the buffer `id` if [`Settings::autowrite`] is on and it is modified.
+    ///
+    /// Buffers without a file have nowhere to be written, so they are left alone.
+    fn autowrite(&mut self, id: DocumentID) -> anyhow::Result<()> {
+        if !self.settings.autowrite || self.settings.readonly {
+            return Ok(());
+        }
+        let Some(buf) = self.buffers.get_mut(&id) else {
+            return Ok(());
+        };
+        let Some(fname) = buf.file.clone().filter(|_| buf.modified) else {
+            return Ok(());
+        };
+        buf.write(false)
+            .with_context(|| format!("Could not write to file {fname}"))?;
+        self.fire(HookEvent::BufWrite { fname });
+        Ok(())
+    }
+
+    /// A warning if the file of the selected buffer has been changed by something else since it
+    /// was opened or written, like vim's check when the terminal gains focus.
+    ///
+    /// Each change is only warned about once.
+    pub fn check_changed_on_disk(&mut self) -> Option<String> {
+        let buf = self.buffers.get_mut(&self.selected_buf)?;
+        if !buf.check_changed_on_disk() {
+            return None;
+        }
+        let fname = buf.file.as_deref().unwrap_or_default();
+        Some(format!("\"{fname}\" has changed on disk since it was read"))
+    }
+
+    /// Write the selected buffer to `fname`, which becomes its file if it doesn't have one, like
+    /// vim's `:w name`.
+    ///
+    /// An existing file is only overwritten if `force` is set, like `:w! name`.
+    pub fn write_as(&mut self, fname: &str, force: bool) -> anyhow::Result<()> {
+        self.commit();
+        if self.settings.readonly {
+            bail!("The buffer is read-only (see :set noreadonly)");
+        }
+        let Some(buf) = self.buffers.get_mut(&self.selected_buf) else {
+            return Ok(());
+        };
+        buf.write_as(fname, force)?;
+        self.fire(HookEvent::BufWrite {
+            fname: fname.to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Create an empty buffer with no file and select it, like vim's `:enew`.
+    ///
+    /// See [`Editor::select_buffer`].
+    pub fn new_buffer(&mut self) -> anyhow::Result<()> {
+        let id = self.add_buffer(Buffer::empty());
+        self.select_buffer(id)
+    }
+
+    /// Throw away the changes to the selected buffer and read its file again, like vim's `:e!`.
+    ///
+    /// The cursor stays where it was, as far as the new text allows.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.reload()?;
+        }
+        self.clamp_cursor();
+        self.check_indentation();
+        Ok(())
+    }
+
+    /// Close the selected buffer, like vim's `:bd`.
+    ///
+    /// A modified buffer is only closed if `force` is set, throwing away the changes. See
+    /// [`Editor::close_buffer`] for which buffer is selected afterwards.
+    pub fn delete_buffer(&mut self, force: bool) -> anyhow::Result<()> {
+        self.commit();
+        if !force && self.is_modified() {
+            bail!(
+                "No write since last change for buffer {} (add ! to override)",
+                self.selected_buf
+            );
+        }
+        self.close_buffer(self.selected_buf)
+    }
+
+    /// Whether the selected buffer has changed since it was last written.
+    pub fn is_modified(&self) -> bool {
+        self.buffers[&self.selected_buf].modified
+    }
+
+    /// Whether the only buffer is an empty one with no file which hasn't been changed, like when
+    /// the editor is started without a file.
+    pub fn is_blank(&self) -> bool {
+        let mut buffers = self.buffers.values();
+        match (buffers.next(), buffers.next()) {
+            (Some(buffer), None) => buffer.is_empty() && !buffer.modified && buffer.file.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Whether the selected buffer is of a binary file, which is shown read-only. See
+    /// [`Buffer::open_with_progress`].
+    pub fn is_binary(&self) -> bool {
+        self.buffers[&self.selected_buf].binary.is_some()
+    }
+
+    /// Whether the selected buffer is shown as hex rather than text, because of
+    /// [`Settings::hex`] or because it is binary and [`config::HEX_BINARY`] is set.
+    pub fn hex_view(&self) -> bool {
+        self.settings.hex || (config::HEX_BINARY && self.is_binary())
+    }
+
+    /// The bytes of the selected buffer in `range`, as shown by the hex view. A range past the
+    /// end is cut short.
+    pub fn bytes(&self, range: Range<usize>) -> Vec<u8> {
+        self.buffers[&self.selected_buf].bytes(range)
+    }
+
+    /// How many bytes long the selected buffer is. See [`Editor::bytes`].
+    pub fn len_bytes(&self) -> usize {
+        self.buffers[&self.selected_buf].len_bytes()
+    }
+
+    /// The index of the byte the cursor is on. See [`Editor::bytes`].
+    pub fn cursor_byte(&self) -> usize {
+        self.buffers[&self.selected_buf].char_to_byte(self.cursor_char())
+    }
+
+    /// Whether any buffer has changed since it was last written.
+    pub fn any_modified(&self) -> bool {
+        self.buffers.values().any(|buf| buf.modified)
     }
 
     /// Returns a reference to the lines of this [`Editor`].
-    pub fn lines(&self) -> Lines {
+    pub fn lines(&self) -> Lines<'_> {
         self.buffers[&self.selected_buf].lines()
     }
 
     /// Returns a reference to the whole text of this [`Editor`].
-    pub fn text(&self) -> RopeSlice {
+    pub fn text(&self) -> RopeSlice<'_> {
         self.buffers[&self.selected_buf].text.slice(..)
     }
 
@@ -95,21 +745,108 @@ impl Editor {
         self.selected_pos
     }
 
+    /// The char index of the cursor in [`Editor::text`].
+    pub fn cursor_char(&self) -> usize {
+        let (x, y) = self.selected_pos;
+        self.buffers[&self.selected_buf].pos_to_char(x, y)
+    }
+
+    /// Move the cursor to the char index `idx` of [`Editor::text`].
+    ///
+    /// An index past the end of the buffer moves the cursor to the end of the buffer, and an
+    /// index on the newline at the end of a line moves it to the end of that line.
+    pub fn set_cursor_char(&mut self, idx: usize) {
+        let from = self.selected_pos;
+        self.selected_pos = self.buffers[&self.selected_buf].char_to_pos(idx);
+        self.cursor_moved(from);
+    }
+
+    /// Move the cursor to the start of line `y`, or the last line if there aren't that many.
+    pub fn go_to_line(&mut self, y: usize) {
+        let from = self.selected_pos;
+        self.selected_pos = (0, y.min(self.line_count().saturating_sub(1)));
+        self.cursor_moved(from);
+    }
+
+    /// Run the [`HookKind::CursorMove`] hooks if the cursor isn't at `from` any more.
+    fn cursor_moved(&mut self, from: (usize, usize)) {
+        if self.selected_pos != from {
+            self.fire(HookEvent::CursorMove {
+                from,
+                to: self.selected_pos,
+            });
+        }
+    }
+
+    /// Run `hook` whenever an event of `kind` happens. See [`hooks`] for more.
+    pub fn add_hook(
+        &mut self,
+        kind: HookKind,
+        hook: impl FnMut(&Editor, &HookEvent) + Send + 'static,
+    ) {
+        self.hooks.add(kind, hook);
+    }
+
+    /// Run the hooks for `event`, followed by the autocommands which match it.
+    fn fire(&mut self, event: HookEvent) {
+        // The hooks can only look at the editor, so none can be added while they run.
+        let mut hooks = std::mem::take(&mut self.hooks);
+        hooks.run(self, &event);
+        self.hooks = hooks;
+        self.run_autocmds(&event);
+    }
+
+    /// Run the commands of the autocommands matching `event`, in the order they were added.
+    ///
+    /// Events with no file of their own are matched against the selected buffer's file. Nothing
+    /// is run for events caused by autocommands, so `:w` on `BufWrite` doesn't repeat forever.
+    /// Errors are given as the command output, since there is no command to fail.
+    fn run_autocmds(&mut self, event: &HookEvent) {
+        if self.running_autocmds {
+            return;
+        }
+        let fname = match event {
+            HookEvent::BufWrite { fname } | HookEvent::BufOpen { fname } => fname.as_str(),
+            _ => self.active_fname().unwrap_or_default(),
+        };
+        let commands: Vec<String> = self
+            .autocmds
+            .iter()
+            .filter(|autocmd| autocmd.matches(event.kind(), fname))
+            .map(|autocmd| autocmd.command.clone())
+            .collect();
+        self.running_autocmds = true;
+        for command in commands {
+            if let Err(err) = command.parse().and_then(|command| self.execute(command)) {
+                self.output = Some(format!("Error running autocommand {command}: {err:#}"));
+            }
+        }
+        self.running_autocmds = false;
+    }
+
     /// Move the cursor left by one character.
     ///
     /// Does not move the cursor beyond the end of the line.
-    /// Will not wrap to the previous line if the cursor is at the start of a line.
+    /// Will not wrap to the previous line if the cursor is at the start of a line, unless `h` is
+    /// in [`Settings::which_wrap`], when it goes to the end of the previous line.
     pub fn move_left(&mut self) {
+        let from = self.selected_pos;
         if self.selected_pos.0 != 0 {
             self.selected_pos.0 -= 1;
+        } else if self.selected_pos.1 != 0 && self.settings.which_wrap.contains(&'h') {
+            let y = self.selected_pos.1 - 1;
+            self.selected_pos = (trim_newlines(self.text().line(y)).len_chars(), y);
         }
+        self.cursor_moved(from);
     }
 
     /// Move the cursor right by one character.
     ///
     /// Does not move the cursor beyond the end of the line.
-    /// Will not wrap to the previous line if the cursor is at the end of a line.
+    /// Will not wrap to the next line if the cursor is at the end of a line, unless `l` is in
+    /// [`Settings::which_wrap`], when it goes to the start of the next line.
     pub fn move_right(&mut self) {
+        let from = self.selected_pos;
         if self.selected_pos.0
             < trim_newlines(
                 self.lines()
@@ -119,46 +856,135 @@ impl Editor {
             .len_chars()
         {
             self.selected_pos.0 += 1;
+        } else if self.selected_pos.1 + 1 < self.line_count()
+            && self.settings.which_wrap.contains(&'l')
+        {
+            self.selected_pos = (0, self.selected_pos.1 + 1);
+        }
+        self.cursor_moved(from);
+    }
+
+    /// Move the cursor to the start of the line, like `Ctrl-a` in insert mode or `0` in normal
+    /// mode.
+    pub fn move_to_line_start(&mut self) {
+        let from = self.selected_pos;
+        self.selected_pos.0 = 0;
+        self.cursor_moved(from);
+    }
+
+    /// Move the cursor to the first character on the line which isn't a space or tab, like vim's
+    /// `^`.
+    ///
+    /// A line with nothing else on it leaves the cursor at the start.
+    pub fn move_to_first_non_blank(&mut self) {
+        let from = self.selected_pos;
+        let line = trim_newlines(self.text().line(self.selected_pos.1));
+        self.selected_pos.0 = line
+            .chars()
+            .position(|c| c != ' ' && c != '\t')
+            .unwrap_or(0);
+        self.cursor_moved(from);
+    }
+
+    /// Move the cursor to the end of the line, after its last character, like `Ctrl-e` in insert
+    /// mode.
+    ///
+    /// Outside of insert mode the cursor goes on the last character instead, like vim's `$`. An
+    /// empty line leaves it at the start.
+    pub fn move_to_line_end(&mut self) {
+        let from = self.selected_pos;
+        let len = trim_newlines(self.text().line(self.selected_pos.1)).len_chars();
+        self.selected_pos.0 = match self.mode {
+            Mode::Insert => len,
+            _ => len.saturating_sub(1),
+        };
+        self.cursor_moved(from);
+    }
+
+    /// Move the cursor to the end of the word it is in, or of the next word if it is already at
+    /// the end of one, like vim's `e`. See [`search::word_end`] for what counts as a word.
+    pub fn word_end(&mut self) {
+        let idx = search::word_end(self.text(), self.cursor_char());
+        self.set_cursor_char(idx);
+    }
+
+    /// Move the cursor to the start of the next word, like vim's `w`. See [`search::word_start`]
+    /// for what counts as a word.
+    pub fn word_start(&mut self) {
+        let idx = search::word_start(self.text(), self.cursor_char());
+        self.set_cursor_char(idx);
+    }
+
+    /// Move the cursor with `motion` `count` times, like typing a count before it in vim.
+    ///
+    /// It stops as soon as `motion` doesn't move the cursor, so a count bigger than the buffer
+    /// only goes as far as the buffer does.
+    pub fn repeat_motion(&mut self, count: usize, motion: fn(&mut Self)) {
+        for _ in 0..count {
+            let from = self.selected_pos;
+            motion(self);
+            if self.selected_pos == from {
+                break;
+            }
         }
     }
 
     /// Move the cursor down by one line.
     ///
     /// If the line below is shorter than where the cursor currently is, the cursor will move back
-    /// to the end of the line.
+    /// to the end of the line. It goes back to the [`Editor::virtual_column`] on longer lines.
     pub fn move_down(&mut self) {
-        if self.selected_pos.1 == self.lines().len() - 1 {
-            return;
-        }
-        self.selected_pos.1 += 1;
-        let line_len = trim_newlines(
-            self.lines()
-                .nth(self.selected_pos.1)
-                .expect("invalid selected position"),
-        )
-        .len_chars();
+        self.move_down_by(1);
+    }
 
-        if self.selected_pos.0 > line_len {
-            self.selected_pos.0 = line_len;
+    /// Move the cursor down by `count` lines, or to the last line if there aren't that many.
+    ///
+    /// The column is kept like [`Editor::move_down`] keeps it.
+    pub fn move_down_by(&mut self, count: usize) {
+        let last = self.lines().len() - 1;
+        let y = self.selected_pos.1.saturating_add(count).min(last);
+        if y != self.selected_pos.1 {
+            self.move_vertically(y);
         }
     }
 
     /// Move the cursor up by one line.
     ///
     /// If the line above is shorter than where the cursor currently is, the cursor will move back
-    /// to the end of the line.
+    /// to the end of the line. It goes back to the [`Editor::virtual_column`] on longer lines.
     pub fn move_up(&mut self) {
-        if self.selected_pos.1 != 0 {
-            self.selected_pos.1 -= 1;
-            let line_len = trim_newlines(
-                self.lines()
-                    .nth(self.selected_pos.1)
-                    .expect("invalid selected position"),
-            )
-            .len_chars();
-            if self.selected_pos.0 > line_len {
-                self.selected_pos.0 = line_len;
-            }
+        self.move_up_by(1);
+    }
+
+    /// Move the cursor up by `count` lines, or to the first line if there aren't that many.
+    ///
+    /// The column is kept like [`Editor::move_up`] keeps it.
+    pub fn move_up_by(&mut self, count: usize) {
+        let y = self.selected_pos.1.saturating_sub(count);
+        if y != self.selected_pos.1 {
+            self.move_vertically(y);
+        }
+    }
+
+    /// Move the cursor to line `y`, as close to the [`Editor::virtual_column`] as the line allows.
+    fn move_vertically(&mut self, y: usize) {
+        let from = self.selected_pos;
+        let column = self.virtual_column();
+        let line_len =
+            trim_newlines(self.lines().nth(y).expect("invalid selected position")).len_chars();
+        self.selected_pos = (column.min(line_len), y);
+        self.virtual_column = Some((column, self.selected_pos));
+        self.cursor_moved(from);
+    }
+
+    /// The column the cursor would be in if its line were long enough.
+    ///
+    /// This is the cursor's own column unless it was last moved up or down from a longer line,
+    /// in which case it is the column it was in there.
+    pub fn virtual_column(&self) -> usize {
+        match self.virtual_column {
+            Some((column, pos)) if pos == self.selected_pos => column,
+            _ => self.selected_pos.0,
         }
     }
 
@@ -167,50 +993,3681 @@ impl Editor {
             .get(&self.selected_buf)
             .and_then(|buf| buf.file.as_deref())
     }
-}
 
-impl Default for Editor {
-    fn default() -> Self {
-        Self::new()
+    /// A one-line summary of the selected buffer, like vim's `Ctrl-g`.
+    ///
+    /// This is formatted as `"path" N lines --P%--`, where `P` is how far through the buffer the
+    /// cursor is. A binary file has `[binary]` after its path.
+    pub fn file_info(&self) -> String {
+        let name = self.active_fname().unwrap_or("[No Name]");
+        let binary = if self.is_binary() { " [binary]" } else { "" };
+        let lines = self.line_count();
+        if lines == 0 {
+            return format!("\"{name}\"{binary} --No lines in buffer--");
+        }
+        let percent = ((self.selected_pos.1 + 1) * 100 / lines).min(100);
+        let plural = if lines == 1 { "" } else { "s" };
+        format!("\"{name}\"{binary} {lines} line{plural} --{percent}%--")
     }
-}
 
-/// Remove the newline character(s) from the end of a [`RopeSlice`].
-///
-/// This is necessary because [`RopeSlice::lines`] includes the trailing newline characters.
-///
-/// [`RopeSlice`]: ropey::RopeSlice
-/// [`RopeSlice::lines`]: ropey::RopeSlice::lines
-pub fn trim_newlines(line: RopeSlice) -> RopeSlice {
-    let mut num_newline_chars = 0;
-    for c in line.chars_at(line.len_chars()).reversed() {
-        if matches!(
-            c,
-            '\u{000A}'|// Line Feed
-            '\u{000D}'|// Carriage Return
-            '\u{000B}'|// Vertical Tab
-            '\u{000C}'|// Form Feed
-            '\u{0085}'|// Next Line
-            '\u{2028}'|// Line Separator
-            '\u{2029}' // Paragraph Separator
-        ) {
-            num_newline_chars += 1;
-        } else {
-            break;
+    /// Where the cursor is in the selected buffer, like vim's `g Ctrl-g`.
+    ///
+    /// This gives the column, line, word and char the cursor is on, along with their totals. In
+    /// [`Mode::Visual`], it gives how many lines, words and chars are selected instead.
+    pub fn cursor_info(&self) -> String {
+        if let Some(selection) = self.selection() {
+            let selected = self.stats_in_range(selection);
+            let total = self.stats_in_range(0..self.text().len_chars());
+            return format!(
+                "Selected {} of {} Lines; {} of {} Words; {} of {} Chars",
+                selected.lines,
+                total.lines,
+                selected.words,
+                total.words,
+                selected.chars,
+                total.chars,
+            );
         }
+        let text = self.text();
+        let (x, y) = self.selected_pos;
+        let cursor = self.cursor_char();
+        let columns = trim_newlines(text.line(y)).len_chars();
+        let words = count_words(text);
+        // Count the words which have started before the cursor, including the one under it.
+        let word = count_words(text.slice(..(cursor + 1).min(text.len_chars())));
+        format!(
+            "Col {} of {columns}; Line {} of {}; Word {word} of {words}; Char {} of {}",
+            x + 1,
+            y + 1,
+            self.line_count(),
+            cursor + 1,
+            text.len_chars(),
+        )
     }
-    line.slice(..line.len_chars() - num_newline_chars)
-}
 
-/// An enumeration of possible editor modes.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum Mode {
-    /// Normal mode.
+    /// How many words, chars and lines there are in the chars `range` of the selected buffer.
     ///
-    /// This mode is mainly for navigation and editing text.
-    Normal,
-    /// Insert mode.
+    /// Words and lines which are only partly in `range` count too.
+    pub fn stats_in_range(&self, range: Range<usize>) -> Stats {
+        let text = self.text();
+        let end = range.end.min(text.len_chars());
+        let start = range.start.min(end);
+        let lines = match end > start {
+            true => text.char_to_line(end - 1) - text.char_to_line(start) + 1,
+            false => 0,
+        };
+        Stats {
+            words: count_words(text.slice(start..end)),
+            chars: end - start,
+            lines,
+        }
+    }
+
+    /// The number of lines in the selected buffer. See [`line_count`].
+    pub fn line_count(&self) -> usize {
+        line_count(self.text())
+    }
+
+    /// Switch to a different [`Mode`].
     ///
-    /// This mode is specifically for inserting text into the buffer.
-    Insert,
+    /// Entering [`Mode::Command`] or [`Mode::Search`] starts off with an empty command line and
+    /// entering
+    /// [`Mode::Visual`] starts a selection of chars at the cursor. Leaving [`Mode::Insert`]
+    /// records everything typed as a single change in the undo history.
+    pub fn set_mode(&mut self, mode: Mode) {
+        if matches!(mode, Mode::Command | Mode::Search) {
+            self.command_line.clear();
+        }
+        if mode == Mode::Visual && self.mode != Mode::Visual {
+            self.anchor = self.cursor_char();
+            self.selection_kind = SelectionKind::Char;
+        }
+        if self.mode == Mode::Insert && mode != Mode::Insert {
+            self.commit();
+        }
+        if mode != Mode::List {
+            self.list = None;
+        }
+        if mode != Mode::Confirm {
+            self.confirming = None;
+        }
+        let from = std::mem::replace(&mut self.mode, mode);
+        if from != mode {
+            self.fire(HookEvent::ModeChange { from, to: mode });
+        }
+    }
+
+    /// Keep track of `message`, which is about to be handled, in case it is part of a change for
+    /// [`Message::Repeat`] to replay.
+    ///
+    /// A change is a single message like `x` or `dd` in normal mode, or everything from entering
+    /// insert mode to leaving it. Messages replaying a change shouldn't be recorded.
+    pub fn record(&mut self, message: Message) {
+        if let Some(recording) = &mut self.recording {
+            if self.mode == Mode::Insert {
+                recording.push(message);
+                return;
+            }
+            self.last_change = self.recording.take().unwrap_or_default();
+        }
+        if self.mode != Mode::Normal {
+            return;
+        }
+        match message {
+            Message::Mode(Mode::Insert)
+            | Message::Append
+            | Message::AppendEnd
+            | Message::InsertLineStart
+            | Message::OpenBelow
+            | Message::OpenAbove
+            // These start inserting too, from normal mode.
+            | Message::Char(_)
+            | Message::Enter
+            | Message::Backspace => self.recording = Some(vec![message]),
+            Message::DeleteChar
+            | Message::DeleteLine
+            | Message::ReplaceChar(_)
+            | Message::JoinLines
+            | Message::TransposeChars
+            | Message::DuplicateLine
+            | Message::MoveLineDown
+            | Message::MoveLineUp
+            | Message::Paste => self.last_change = vec![message],
+            _ => {}
+        }
+    }
+
+    /// The messages which made the last change, for [`Message::Repeat`] to replay. See
+    /// [`Editor::record`].
+    pub fn last_change(&self) -> &[Message] {
+        &self.last_change
+    }
+
+    /// Record the current state of the selected buffer in its undo history, if it has changed.
+    ///
+    /// The cursor is moved back to the start of its grapheme cluster first, in case the change
+    /// left it in the middle of one.
+    pub fn commit(&mut self) {
+        self.snap_to_grapheme();
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            let changed = buf
+                .undo
+                .record(buf.text.clone(), self.selected_pos, SystemTime::now());
+            buf.update_modified();
+            if changed {
+                buf.changes
+                    .push(self.selected_pos, |old, new| old.1 == new.1);
+                self.check_indentation();
+            }
+        }
+    }
+
+    /// Undo the last change to the selected buffer.
+    pub fn undo(&mut self) -> anyhow::Result<()> {
+        self.travel(|undo| undo.undo().is_some(), "Already at oldest change")
+    }
+
+    /// Redo the last change to the selected buffer which was undone.
+    pub fn redo(&mut self) -> anyhow::Result<()> {
+        self.travel(|undo| undo.redo().is_some(), "Already at newest change")
+    }
+
+    /// Go back `count` states of the selected buffer in the order they were made, even onto
+    /// branches of the undo history which have been undone and then edited over.
+    pub fn earlier(&mut self, count: usize) -> anyhow::Result<()> {
+        self.travel(
+            |undo| undo.earlier(count).is_some(),
+            "Already at oldest change",
+        )
+    }
+
+    /// Go forward `count` states of the selected buffer in the order they were made.
+    pub fn later(&mut self, count: usize) -> anyhow::Result<()> {
+        self.travel(
+            |undo| undo.later(count).is_some(),
+            "Already at newest change",
+        )
+    }
+
+    /// Go back to the state the selected buffer was in `duration` before its current state was
+    /// made, like `:earlier 5m`.
+    pub fn earlier_by(&mut self, duration: Duration) -> anyhow::Result<()> {
+        self.travel(
+            |undo| undo.earlier_by(duration).is_some(),
+            "Already at oldest change",
+        )
+    }
+
+    /// Go forward to the state the selected buffer was in `duration` after its current state was
+    /// made, like `:later 5m`.
+    pub fn later_by(&mut self, duration: Duration) -> anyhow::Result<()> {
+        self.travel(
+            |undo| undo.later_by(duration).is_some(),
+            "Already at newest change",
+        )
+    }
+
+    /// Move through the undo history of the selected buffer with `step`, which returns whether
+    /// it moved, and restore the state it ends up at.
+    ///
+    /// Any changes not yet in the history are recorded first so they can't be lost.
+    fn travel(
+        &mut self,
+        step: impl FnOnce(&mut undo::UndoTree) -> bool,
+        err: &str,
+    ) -> anyhow::Result<()> {
+        self.commit();
+        let Some(buf) = self.buffers.get_mut(&self.selected_buf) else {
+            return Ok(());
+        };
+        if !step(&mut buf.undo) {
+            bail!("{err}");
+        }
+        let state = buf.undo.current();
+        buf.text = state.text.clone();
+        self.selected_pos = state.cursor;
+        buf.update_modified();
+        self.clamp_cursor();
+        self.check_indentation();
+        Ok(())
+    }
+
+    /// The first line of the selected buffer which mixes tabs and spaces in its indentation, or
+    /// which is indented with different ones to the lines above it.
+    ///
+    /// This is only updated when a change is recorded in the undo history, and is always
+    /// [`None`] if [`Settings::mixed_indent`] is off.
+    pub fn mixed_indentation(&self) -> Option<usize> {
+        self.mixed_indentation
+            .filter(|_| self.settings.mixed_indent)
+    }
+
+    /// A warning about the first line found by [`Editor::mixed_indentation`], if there is one.
+    pub fn indentation_warning(&self) -> Option<String> {
+        let line = self.mixed_indentation()?;
+        Some(format!(
+            "Mixed tabs and spaces in indentation, first at line {}",
+            line + 1
+        ))
+    }
+
+    /// Look for mixed indentation in the selected buffer again.
+    fn check_indentation(&mut self) {
+        self.mixed_indentation = indent::mixed_indentation(self.text());
+    }
+
+    /// Start selecting whole lines in [`Mode::Visual`], like vim's `V`.
+    ///
+    /// If chars are already being selected, the selection becomes whole lines instead. If whole
+    /// lines are, visual mode is left.
+    pub fn visual_line_mode(&mut self) {
+        match (self.mode, self.selection_kind) {
+            (Mode::Visual, SelectionKind::Line) => self.set_mode(Mode::Normal),
+            _ => {
+                self.set_mode(Mode::Visual);
+                self.selection_kind = SelectionKind::Line;
+            }
+        }
+    }
+
+    /// Select every line of the buffer in [`Mode::Visual`], from the start to the cursor at the
+    /// end of the last line.
+    pub fn select_all(&mut self) {
+        self.set_mode(Mode::Visual);
+        self.selection_kind = SelectionKind::Line;
+        self.anchor = 0;
+        self.selected_pos = (0, self.line_count().saturating_sub(1));
+        self.move_to_line_end();
+    }
+
+    /// Whether the selection in [`Mode::Visual`] is of chars or whole lines.
+    pub fn selection_kind(&self) -> SelectionKind {
+        self.selection_kind
+    }
+
+    /// The first and last lines with part of the selection on them in [`Mode::Visual`].
+    pub fn selected_lines(&self) -> Option<(usize, usize)> {
+        if self.mode != Mode::Visual {
+            return None;
+        }
+        let y = self.selected_pos.1;
+        let anchor = self.buffers[&self.selected_buf].char_to_pos(self.anchor).1;
+        Some((y.min(anchor), y.max(anchor)))
+    }
+
+    /// The columns indent guides are drawn at on each of `lines` of the selected buffer, for
+    /// [`Settings::indent_guides`].
+    ///
+    /// See [`indent::guides`] for where they go.
+    pub fn indent_guides(&self, lines: Range<usize>) -> Vec<Vec<usize>> {
+        indent::guides(self.text(), lines, self.settings.tab_width)
+    }
+
+    /// The char range of [`Editor::text`] which is selected in [`Mode::Visual`].
+    ///
+    /// Like vim, the selection includes the chars under both the cursor and where it started.
+    /// A selection of whole lines includes the newline of the last of them.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        if self.mode != Mode::Visual {
+            return None;
+        }
+        if self.selection_kind == SelectionKind::Line {
+            let (first, last) = self.selected_lines()?;
+            let text = self.text();
+            return Some(text.line_to_char(first)..text.line_to_char(last + 1));
+        }
+        let cursor = self.cursor_char();
+        let end = (self.anchor.max(cursor) + 1).min(self.text().len_chars());
+        Some(self.anchor.min(cursor).min(end)..end)
+    }
+
+    /// The contents of the register `name`, if anything has been put in it.
+    pub fn register(&self, name: char) -> Option<&str> {
+        self.registers
+            .get(name)
+            .map(|register| register.text.as_str())
+    }
+
+    /// Use the register `name` for the next delete, yank or paste, like typing `"` and `name` in
+    /// vim.
+    ///
+    /// See [`registers`] for the names of registers.
+    pub fn select_register(&mut self, name: char) -> anyhow::Result<()> {
+        if !registers::is_valid_name(name) {
+            bail!("Invalid register name: {name}");
+        }
+        self.pending_register = Some(name);
+        Ok(())
+    }
+
+    /// Use `count` for the next motion, like typing it before the motion in vim.
+    pub fn set_count(&mut self, count: usize) {
+        self.count = Some(count);
+    }
+
+    /// Take the count given with [`Editor::set_count`], if there is one, so it only applies to
+    /// one command.
+    pub fn take_count(&mut self) -> Option<usize> {
+        self.count.take()
+    }
+
+    /// Take the register chosen with [`Editor::select_register`], defaulting to the unnamed
+    /// register.
+    fn take_register(&mut self) -> char {
+        self.pending_register.take().unwrap_or(registers::UNNAMED)
+    }
+
+    /// Put `text` in the chosen register.
+    fn store_register(&mut self, text: String, linewise: bool) {
+        let name = self.take_register();
+        self.registers.store(name, Register { text, linewise });
+    }
+
+    /// Delete the chars in `range` of [`Editor::text`], putting them in the chosen register.
+    ///
+    /// The cursor is left where the deleted text was.
+    pub fn delete_range(&mut self, range: Range<usize>) {
+        let deleted = self.text().slice(range.clone()).to_string();
+        self.store_register(deleted, false);
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.text.remove(range.clone());
+            buf.modified = true;
+        }
+        self.set_cursor_char(range.start);
+        self.clamp_cursor();
+    }
+
+    /// Copy the selection in to the chosen register, returning to [`Mode::Normal`] with the
+    /// cursor at the start of the selection.
+    ///
+    /// Whole lines are yanked as lines, so they are put back on lines of their own.
+    pub fn yank_selection(&mut self) {
+        if let Some(range) = self.selection() {
+            match self.selection_kind {
+                SelectionKind::Char => {
+                    let text = self.text().slice(range.clone()).to_string();
+                    self.store_register(text, false);
+                }
+                SelectionKind::Line => self.yank_lines(range.clone()),
+            }
+            self.set_mode(Mode::Normal);
+            self.set_cursor_char(range.start);
+        }
+    }
+
+    /// Copy the line the cursor is on in to the chosen register, like vim's `yy`.
+    pub fn yank_line(&mut self) {
+        let y = self.selected_pos.1;
+        let text = self.text();
+        self.yank_lines(text.line_to_char(y)..text.line_to_char(y + 1));
+    }
+
+    /// Delete the char under the cursor, putting it in the chosen register, like vim's `x`.
+    ///
+    /// The newline at the end of the line is never deleted. If the last char of the line is, the
+    /// cursor moves back on to the new last char.
+    pub fn delete_char(&mut self) {
+        let (x, y) = self.selected_pos;
+        let line_len = trim_newlines(self.text().line(y)).len_chars();
+        if x >= line_len {
+            return;
+        }
+        let idx = self.cursor_char();
+        self.delete_range(idx..idx + 1);
+        if x + 1 == line_len {
+            self.selected_pos.0 = x.saturating_sub(1);
+        }
+        self.commit();
+    }
+
+    /// Replace the char under the cursor with `c`, like vim's `r`, leaving the cursor on it.
+    pub fn replace_char(&mut self, c: char) {
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.replace_char(c, self.selected_pos);
+        }
+        self.commit();
+    }
+
+    /// Swap the char under the cursor with the one before it, moving the cursor after them, like
+    /// readline's `Ctrl-t`. See [`Buffer::transpose_chars`].
+    ///
+    /// Outside of insert mode this is a change of its own in the undo history.
+    pub fn transpose_chars(&mut self) {
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.transpose_chars(&mut self.selected_pos);
+        }
+        if self.mode != Mode::Insert {
+            self.commit();
+        }
+    }
+
+    /// Join the line below on to the one the cursor is on with a space, like vim's `J`. See
+    /// [`Buffer::join_lines`].
+    ///
+    /// The cursor goes to where the lines were joined, or stays in its column if
+    /// [`Settings::join_keeps_column`] is on.
+    pub fn join_lines(&mut self) {
+        let x = self.selected_pos.0;
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.join_lines(&mut self.selected_pos);
+        }
+        if self.settings.join_keeps_column {
+            self.selected_pos.0 = x;
+            self.clamp_cursor();
+        }
+        self.commit();
+    }
+
+    /// Delete the line the cursor is on, putting it in the chosen register, like vim's `dd`.
+    ///
+    /// The cursor is left at the start of the line after it, or the one before if it was the last.
+    pub fn delete_line(&mut self) {
+        if self.text().len_chars() == 0 {
+            return;
+        }
+        let y = self.selected_pos.1;
+        let text = self.text();
+        self.delete_lines(text.line_to_char(y)..text.line_to_char(y + 1));
+        self.commit();
+    }
+
+    /// Copy the whole lines in `range` in to the chosen register as lines.
+    fn yank_lines(&mut self, range: Range<usize>) {
+        let mut lines = self.text().slice(range).to_string();
+        if !lines.ends_with('\n') {
+            lines.push('\n');
+        }
+        self.store_register(lines, true);
+    }
+
+    /// Delete the whole lines in `range` of [`Editor::text`], putting them in the chosen register
+    /// as lines.
+    ///
+    /// If the last line of the buffer is deleted, so is the newline before it. The cursor is left
+    /// at the start of the line after the deleted ones, or the one before if there isn't one.
+    fn delete_lines(&mut self, range: Range<usize>) {
+        self.yank_lines(range.clone());
+        let buf = &self.buffers[&self.selected_buf];
+        let first = buf.char_to_pos(range.start).1;
+        let text = buf.text.slice(..);
+        let mut range = range;
+        let no_final_newline = trim_newlines(text).len_chars() == text.len_chars();
+        if range.end == text.len_chars() && no_final_newline && first > 0 {
+            range.start = buf.pos_to_char(usize::MAX, first - 1);
+        }
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.text.remove(range);
+            buf.modified = true;
+        }
+        self.selected_pos = (0, first);
+        self.clamp_cursor();
+    }
+
+    /// Put the contents of the chosen register after the cursor, like vim's `p`.
+    ///
+    /// The cursor is left on the last char put in. Whole lines are put below the line the cursor
+    /// is on instead, with the cursor left at the start of the first of them.
+    pub fn paste(&mut self) -> anyhow::Result<()> {
+        let name = self.take_register();
+        let Some(Register { text, linewise }) = self.registers.get(name).cloned() else {
+            bail!("Nothing in register {name}");
+        };
+        if linewise {
+            self.paste_lines(&text);
+            return Ok(());
+        }
+        let (x, y) = self.selected_pos;
+        let line_len = trim_newlines(self.text().line(y)).len_chars();
+        let start = self.cursor_char() + usize::from(x < line_len);
+        self.set_cursor_char(start);
+        self.insert_str(&text);
+        self.set_cursor_char((start + text.chars().count()).saturating_sub(1).max(start));
+        self.commit();
+        Ok(())
+    }
+
+    /// Put `count` copies of the line the cursor is on below it.
+    ///
+    /// The cursor is left on the last copy, in the same column. Unlike `yyp`, no register is
+    /// changed.
+    pub fn duplicate_line(&mut self, count: usize) {
+        let (x, y) = self.selected_pos;
+        let mut line = self.text().line(y).to_string();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        self.insert_lines_below(&line.repeat(count));
+        self.selected_pos = (x, y + count);
+        self.clamp_cursor();
+        self.commit();
+    }
+
+    /// Swap the line the cursor is on with the line below it, keeping the cursor on the moved
+    /// line. In [`Mode::Visual`], every line of the selection is moved together.
+    ///
+    /// Does nothing on the last line.
+    pub fn move_line_down(&mut self) {
+        self.move_lines(true);
+    }
+
+    /// Swap the line the cursor is on with the line above it, keeping the cursor on the moved
+    /// line. In [`Mode::Visual`], every line of the selection is moved together.
+    ///
+    /// Does nothing on the first line.
+    pub fn move_line_up(&mut self) {
+        self.move_lines(false);
+    }
+
+    /// Move the line the cursor is on, or the selected lines, past the next line in the
+    /// direction given.
+    ///
+    /// Each line keeps its contents, but the newlines stay where they were so the last line of
+    /// the buffer still has no newline if it didn't before.
+    fn move_lines(&mut self, down: bool) {
+        let buf = &self.buffers[&self.selected_buf];
+        let (x, y) = self.selected_pos;
+        let anchor = buf.char_to_pos(self.anchor);
+        let (first, last) = match self.mode {
+            Mode::Visual => (y.min(anchor.1), y.max(anchor.1)),
+            _ => (y, y),
+        };
+        if (down && last + 1 >= self.line_count()) || (!down && first == 0) {
+            return;
+        }
+        let (start, end) = if down {
+            (first, last + 1)
+        } else {
+            (first - 1, last)
+        };
+
+        let text = self.text();
+        let lines: Vec<RopeSlice> = (start..=end).map(|i| text.line(i)).collect();
+        let mut contents: Vec<String> = lines
+            .iter()
+            .map(|&line| trim_newlines(line).to_string())
+            .collect();
+        if down {
+            contents.rotate_right(1);
+        } else {
+            contents.rotate_left(1);
+        }
+        let moved: String = contents
+            .iter()
+            .zip(&lines)
+            .map(|(content, line)| {
+                let newline = line.slice(trim_newlines(*line).len_chars()..);
+                format!("{content}{newline}")
+            })
+            .collect();
+        let range = text.line_to_char(start)..text.line_to_char(end) + text.line(end).len_chars();
+
+        let shift = |line: usize| if down { line + 1 } else { line - 1 };
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.text.remove(range.clone());
+            buf.text.insert(range.start, &moved);
+            buf.modified = true;
+            self.anchor = buf.pos_to_char(anchor.0, shift(anchor.1));
+        }
+        self.selected_pos = (x, shift(y));
+        self.commit();
+    }
+
+    /// Put the whole lines in `text` below the line the cursor is on.
+    fn paste_lines(&mut self, text: &str) {
+        let y = self.selected_pos.1;
+        self.insert_lines_below(text);
+        self.selected_pos = (0, y + 1);
+        self.commit();
+    }
+
+    /// Insert the whole lines in `text` below the line the cursor is on, leaving the cursor
+    /// wherever the insertion ends.
+    fn insert_lines_below(&mut self, text: &str) {
+        let y = self.selected_pos.1;
+        let buffer_text = self.text();
+        if y + 1 < buffer_text.len_lines() {
+            self.set_cursor_char(buffer_text.line_to_char(y + 1));
+            self.insert_str(text);
+        } else {
+            // The last line has no newline to put the text after, so it needs one of its own.
+            self.set_cursor_char(buffer_text.len_chars());
+            self.insert_str("\n");
+            self.insert_str(text.strip_suffix('\n').unwrap_or(text));
+        }
+    }
+
+    /// Insert the contents of the register `name` at the cursor, like vim's `Ctrl-r` in insert
+    /// mode.
+    ///
+    /// The cursor is left after the inserted text. A register with nothing in it inserts nothing.
+    pub fn insert_register(&mut self, name: char) {
+        if let Some(register) = self.registers.get(name) {
+            let text = register.text.clone();
+            self.insert_str(&text);
+        }
+    }
+
+    /// Insert `s` at the cursor, leaving the cursor after it.
+    pub fn insert_str(&mut self, s: &str) {
+        let idx = self.cursor_char();
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.text.insert(idx, s);
+            buf.modified = true;
+        }
+        self.set_cursor_char(idx + s.chars().count());
+    }
+
+    /// Delete the selection, returning to [`Mode::Normal`].
+    ///
+    /// If it covers more lines than [`Settings::confirm_lines`], it is held back in
+    /// [`Mode::Confirm`] like a command run with [`Editor::execute`].
+    pub fn delete_selection(&mut self) {
+        let (Some(range), Some((first, last))) = (self.selection(), self.selected_lines()) else {
+            return;
+        };
+        let kind = self.selection_kind;
+        let lines = last - first + 1;
+        let limit = self.settings.confirm_lines;
+        if limit != 0 && lines > limit {
+            self.set_mode(Mode::Confirm);
+            let question = format!("Delete {lines} lines? (y/n)");
+            self.confirming = Some((Confirmable::DeleteSelection(range, kind), question));
+            return;
+        }
+        self.delete_selected(range, kind);
+    }
+
+    /// Delete `range`, which was selected as `kind`, returning to [`Mode::Normal`].
+    fn delete_selected(&mut self, range: Range<usize>, kind: SelectionKind) {
+        match kind {
+            SelectionKind::Char => self.delete_range(range),
+            SelectionKind::Line => self.delete_lines(range),
+        }
+        self.set_mode(Mode::Normal);
+        self.commit();
+    }
+
+    /// Delete everything but the selection, leaving it as the whole buffer with the cursor at the
+    /// start.
+    ///
+    /// What comes after the selection is deleted first, so the chosen register is left holding
+    /// what came before it.
+    pub fn keep_selection(&mut self) {
+        if let Some(range) = self.selection() {
+            self.delete_range(range.end..self.text().len_chars());
+            self.delete_range(0..range.start);
+            self.set_mode(Mode::Normal);
+            self.selected_pos = (0, 0);
+            self.commit();
+        }
+    }
+
+    /// Delete the selection and start inserting where it was, so whatever is typed next replaces
+    /// it.
+    ///
+    /// The deletion and the typing are undone together.
+    ///
+    /// Replacing whole lines keeps the newline of the last of them, so the typing goes on a line
+    /// of its own.
+    pub fn replace_all_selection(&mut self) {
+        if let Some(mut range) = self.selection() {
+            if let (SelectionKind::Line, Some((_, last))) =
+                (self.selection_kind, self.selected_lines())
+            {
+                range.end = self.buffers[&self.selected_buf].pos_to_char(usize::MAX, last);
+            }
+            self.delete_range(range);
+            self.set_mode(Mode::Insert);
+        }
+    }
+
+    /// Returns the text typed in to the command line so far.
+    pub fn command_line(&self) -> &str {
+        &self.command_line
+    }
+
+    /// Append a single character to the command line.
+    pub fn command_push(&mut self, c: char) {
+        self.command_line.push(c);
+    }
+
+    /// Remove the last character of the command line, or go back to [`Mode::Normal`] if it is
+    /// already empty.
+    pub fn command_backspace(&mut self) {
+        if self.command_line.pop().is_none() {
+            self.set_mode(Mode::Normal);
+        }
+    }
+
+    /// Take the output of the last command run, if it had any.
+    pub fn take_output(&mut self) -> Option<String> {
+        self.output.take()
+    }
+
+    /// A listing of the mappings starting with `lhs` for `:map`. See [`Command::ListMaps`].
+    fn list_maps(&self, lhs: Option<&[Key]>, verbose: bool) -> String {
+        let listing = self.keymap.list(lhs, verbose);
+        match lhs {
+            Some(lhs) if verbose && self.keymap.get(lhs).is_none() => {
+                let binding = match config::builtin_binding(lhs) {
+                    Message::None => "nothing".to_owned(),
+                    message => format!("{message:?}"),
+                };
+                format!(
+                    "{listing}\n{} is built in as {binding}",
+                    keymap::format_keys(lhs)
+                )
+            }
+            _ => listing,
+        }
+    }
+
+    /// A listing of the autocommands for `:autocmd`, one per line.
+    fn list_autocmds(&self) -> String {
+        let mut listing = String::from("--- Autocommands ---");
+        for autocmd in &self.autocmds {
+            let event = format!("{:?}", autocmd.event);
+            listing.push_str(&format!(
+                "\n{event:<10} {}  {}",
+                autocmd.pattern, autocmd.command
+            ));
+        }
+        listing
+    }
+
+    /// Parse and run the contents of the command line, returning to [`Mode::Normal`].
+    ///
+    /// In [`Mode::Search`], the contents are searched for instead, with [`Editor::search_for`].
+    pub fn run_command_line(&mut self) -> anyhow::Result<()> {
+        let line = std::mem::take(&mut self.command_line);
+        let searching = self.mode == Mode::Search;
+        self.set_mode(Mode::Normal);
+        if searching {
+            return self.search_for(line);
+        }
+        self.execute(line.parse()?)
+    }
+
+    /// Run a single [`Command`].
+    ///
+    /// If it would change more lines than [`Settings::confirm_lines`], it is held back in
+    /// [`Mode::Confirm`] until [`Editor::confirm`] answers the [`Editor::question`] about it.
+    pub fn execute(&mut self, command: Command) -> anyhow::Result<()> {
+        if let Some(question) = self.confirmation(&command) {
+            self.set_mode(Mode::Confirm);
+            self.confirming = Some((Confirmable::Command(command), question));
+            return Ok(());
+        }
+        self.run(command)
+    }
+
+    /// The question to ask before running `command`, if it changes too many lines to run straight
+    /// away.
+    fn confirmation(&self, command: &Command) -> Option<String> {
+        let limit = self.settings.confirm_lines;
+        if limit == 0 {
+            return None;
+        }
+        let (lines, question) = match command {
+            Command::Global { pattern, invert } => {
+                let lines = self.matching_lines(pattern, *invert).len();
+                (lines, format!("Delete {lines} lines? (y/n)"))
+            }
+            Command::Filter(command) => {
+                let lines = self.line_count();
+                (
+                    lines,
+                    format!("Replace all {lines} lines with the output of `{command}`? (y/n)"),
+                )
+            }
+            _ => return None,
+        };
+        (lines > limit).then_some(question)
+    }
+
+    /// The question being asked in [`Mode::Confirm`], if there is one.
+    pub fn question(&self) -> Option<&str> {
+        self.confirming
+            .as_ref()
+            .map(|(_, question)| question.as_str())
+    }
+
+    /// Answer the [`Editor::question`], making the change it is about if `yes` is set. Either
+    /// way, this goes back to [`Mode::Normal`].
+    pub fn confirm(&mut self, yes: bool) -> anyhow::Result<()> {
+        let confirming = self.confirming.take();
+        self.set_mode(Mode::Normal);
+        match confirming {
+            Some((Confirmable::Command(command), _)) if yes => self.run(command),
+            Some((Confirmable::DeleteSelection(range, kind), _)) if yes => {
+                self.delete_selected(range, kind);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `command` without asking first. See [`Editor::execute`].
+    fn run(&mut self, command: Command) -> anyhow::Result<()> {
+        match command {
+            Command::Set(args) => {
+                for arg in args {
+                    self.settings.set(&arg)?;
+                }
+                if self.settings.spell && self.dictionary.is_none() {
+                    match load_dictionary() {
+                        Ok(dictionary) => self.dictionary = Some(dictionary),
+                        Err(err) => {
+                            self.settings.spell = false;
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+            Command::Filter(command) => self.filter(&command)?,
+            Command::Map {
+                lhs,
+                rhs,
+                recursive,
+            } => self.keymap.map(lhs, rhs, recursive),
+            Command::ListMaps { lhs, verbose } => {
+                self.output = Some(self.list_maps(lhs.as_deref(), verbose));
+            }
+            Command::Unmap(lhs) => self.keymap.unmap(&lhs)?,
+            Command::Registers => self.output = Some(self.registers.list()),
+            Command::Earlier(UndoStep::Count(count)) => self.earlier(count)?,
+            Command::Later(UndoStep::Count(count)) => self.later(count)?,
+            Command::Earlier(UndoStep::Time(duration)) => self.earlier_by(duration)?,
+            Command::Later(UndoStep::Time(duration)) => self.later_by(duration)?,
+            Command::Write { fname: None, force } => self.write(force)?,
+            Command::Write {
+                fname: Some(fname),
+                force,
+            } => self.write_as(&fname, force)?,
+            Command::Append { fname, lines } => {
+                self.buffers[&self.selected_buf].append_to_path(&fname, lines)?
+            }
+            Command::NewBuffer => self.new_buffer()?,
+            Command::Reload => self.reload()?,
+            Command::DeleteBuffer { force } => self.delete_buffer(force)?,
+            Command::Jumps => self.show_jumps()?,
+            Command::Changes => self.show_changes()?,
+            Command::Diff => self.show_diff()?,
+            Command::Split => self.split_window(),
+            Command::Quit { force } => self.quit(force)?,
+            Command::WriteQuit { force } => {
+                self.write(force)?;
+                self.quit(false)?;
+            }
+            Command::Close => self.close_window()?,
+            Command::Only => self.only_window(),
+            Command::NextBuffer => self.next_buffer()?,
+            Command::PrevBuffer => self.prev_buffer()?,
+            Command::Autocmd(Some(autocmd)) => self.autocmds.push(autocmd),
+            Command::Autocmd(None) => self.output = Some(self.list_autocmds()),
+            Command::Global { pattern, invert } => self.delete_matching_lines(&pattern, invert)?,
+            Command::GotoLine(line) => self.goto_line_number(line),
+        }
+        Ok(())
+    }
+
+    /// Replace the whole of the selected buffer with the output of the shell command `command`,
+    /// which is given the buffer as its input.
+    ///
+    /// If the command fails or its output is larger than [`Settings::filter_limit`], the buffer is
+    /// left unchanged.
+    pub fn filter(&mut self, command: &str) -> anyhow::Result<()> {
+        let text = filter::filter(command, self.text(), self.settings.filter_limit)?;
+        if let Some(buf) = self.buffers.get_mut(&self.selected_buf) {
+            buf.text = text;
+            buf.modified = true;
+        }
+        self.clamp_cursor();
+        self.commit();
+        Ok(())
+    }
+
+    /// Delete every line containing `pattern`, or every line without it when `invert` is set, like
+    /// vim's `:g/pattern/d` and `:v/pattern/d`.
+    ///
+    /// The lines are all deleted in one change, which fails if there are none to delete.
+    pub fn delete_matching_lines(&mut self, pattern: &str, invert: bool) -> anyhow::Result<()> {
+        let lines = self.matching_lines(pattern, invert);
+        if lines.is_empty() {
+            match invert {
+                false => bail!("Pattern not found: {pattern}"),
+                true => bail!("Pattern found in every line: {pattern}"),
+            }
+        }
+        // Going from the bottom up leaves the lines still to delete where they were.
+        for &y in lines.iter().rev() {
+            let text = self.text();
+            self.delete_lines(text.line_to_char(y)..text.line_to_char(y + 1));
+        }
+        self.commit();
+        Ok(())
+    }
+
+    /// The lines containing `pattern`, or without it when `invert` is set.
+    fn matching_lines(&self, pattern: &str, invert: bool) -> Vec<usize> {
+        let text = self.text();
+        (0..self.line_count())
+            .filter(|&y| trim_newlines(text.line(y)).to_string().contains(pattern) != invert)
+            .collect()
+    }
+
+    /// The last search, if there has been one.
+    pub fn search(&self) -> Option<&Search> {
+        self.search.as_ref()
+    }
+
+    /// Search for the whole word under the cursor, like vim's `*`, or backwards like `#`.
+    ///
+    /// Does nothing if the cursor isn't on a word.
+    pub fn search_word_under_cursor(&mut self, backward: bool) -> anyhow::Result<()> {
+        let Some(range) = search::word_at(self.text(), self.cursor_char()) else {
+            return Ok(());
+        };
+        self.search = Some(Search {
+            query: self.text().slice(range).to_string(),
+            whole_word: true,
+            backward,
+        });
+        self.search_next()
+    }
+
+    /// Search forward for `query` from the cursor, like vim's `/`. An empty query searches for
+    /// the last one again.
+    pub fn search_for(&mut self, query: String) -> anyhow::Result<()> {
+        if query.is_empty() {
+            let Some(search) = &mut self.search else {
+                bail!("No previous search");
+            };
+            search.backward = false;
+        } else {
+            self.search = Some(Search {
+                query,
+                whole_word: false,
+                backward: false,
+            });
+        }
+        self.search_next()
+    }
+
+    /// Move the cursor to the next match of the last search, like vim's `n`.
+    pub fn search_next(&mut self) -> anyhow::Result<()> {
+        self.jump_to_match(false)
+    }
+
+    /// Move the cursor to the next match of the last search in the opposite direction, like
+    /// vim's `N`.
+    pub fn search_prev(&mut self) -> anyhow::Result<()> {
+        self.jump_to_match(true)
+    }
+
+    /// Move the cursor to the next match of the last search, going the other way if `reverse`
+    /// is set.
+    ///
+    /// If the search wraps around an end of the buffer to find it, that is given as the output.
+    fn jump_to_match(&mut self, reverse: bool) -> anyhow::Result<()> {
+        let Some(search) = &self.search else {
+            bail!("No previous search");
+        };
+        let cursor = self.cursor_char();
+        let Some(range) = search.next(self.text(), cursor, reverse) else {
+            bail!("Pattern not found: {}", search.query);
+        };
+        if search.backward == reverse && range.start <= cursor {
+            self.output = Some(String::from("Search hit BOTTOM, continuing at TOP"));
+        } else if search.backward != reverse && range.start >= cursor {
+            self.output = Some(String::from("Search hit TOP, continuing at BOTTOM"));
+        }
+        self.record_jump();
+        self.set_cursor_char(range.start);
+        Ok(())
+    }
+
+    /// The char index of the bracket matching the first of [`Settings::match_pairs`] under or
+    /// after the cursor on its line, like vim's `%` uses.
+    ///
+    /// Brackets of the same pair in between are skipped over in nested pairs.
+    pub fn match_bracket(&self) -> Option<usize> {
+        let text = self.text();
+        let pairs = &self.settings.match_pairs;
+        let start = self.cursor_char();
+        let y = self.selected_pos.1.min(text.len_lines() - 1);
+        let line_end = text.line_to_char(y) + trim_newlines(text.line(y)).len_chars();
+        let (idx, c) = (start..line_end)
+            .zip(text.chars_at(start))
+            .find(|&(_, c)| pairs.iter().any(|&(open, close)| c == open || c == close))?;
+        let mut depth = 0usize;
+        if let Some(&(open, close)) = pairs.iter().find(|pair| pair.0 == c) {
+            for (i, c) in (idx..).zip(text.chars_at(idx)) {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+            }
+        } else {
+            let &(open, close) = pairs.iter().find(|pair| pair.1 == c)?;
+            for (i, c) in (0..=idx).rev().zip(text.chars_at(idx + 1).reversed()) {
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Move the cursor to the [`Editor::match_bracket`], if there is one, like vim's `%`.
+    ///
+    /// The jump is remembered in the jump list.
+    pub fn go_to_matching_bracket(&mut self) {
+        if let Some(idx) = self.match_bracket() {
+            self.record_jump();
+            self.set_cursor_char(idx);
+        }
+    }
+
+    /// Move the cursor to the start of the line `percent` of the way through the buffer, like
+    /// vim's `N%`. The line is clamped to the buffer, so `200%` goes to the last line.
+    ///
+    /// The jump is remembered in the jump list.
+    pub fn goto_percent(&mut self, percent: usize) {
+        let lines = self.line_count();
+        let line = (percent.saturating_mul(lines) / 100).clamp(1, lines.max(1));
+        self.record_jump();
+        self.go_to_line(line - 1);
+    }
+
+    /// Move the cursor to the start of line `line`, counting from 1, like vim's `NG`. The line is
+    /// clamped to the buffer, so a line past the end goes to the last line.
+    ///
+    /// The jump is remembered in the jump list.
+    pub fn goto_line_number(&mut self, line: usize) {
+        self.record_jump();
+        self.go_to_line(line.saturating_sub(1));
+    }
+
+    /// Move the cursor to the first line of the buffer, like vim's `gg`, keeping it in the same
+    /// column if the line is long enough.
+    ///
+    /// The jump is remembered in the jump list.
+    pub fn goto_top(&mut self) {
+        self.goto_line_keeping_column(0);
+    }
+
+    /// Move the cursor to the last line of the buffer, like vim's `G`, keeping it in the same
+    /// column if the line is long enough.
+    ///
+    /// The jump is remembered in the jump list.
+    pub fn goto_bottom(&mut self) {
+        self.goto_line_keeping_column(self.line_count().saturating_sub(1));
+    }
+
+    /// Move the cursor to line `y`, recording the jump, and clamp its column to the line.
+    fn goto_line_keeping_column(&mut self, y: usize) {
+        self.record_jump();
+        let from = self.selected_pos;
+        self.selected_pos.1 = y;
+        self.clamp_cursor();
+        self.cursor_moved(from);
+    }
+
+    /// Remember where the cursor is in the jump list, before jumping somewhere else.
+    fn record_jump(&mut self) {
+        let jump = Jump {
+            buffer: self.selected_buf,
+            pos: self.selected_pos,
+        };
+        self.jumps.push(jump, |old, new| {
+            old.buffer == new.buffer && old.pos.1 == new.pos.1
+        });
+    }
+
+    /// Move the cursor to `pos` in the file `fname`, opening it if no buffer has it open yet.
+    ///
+    /// The jump is remembered in the jump list.
+    pub fn go_to_location(&mut self, fname: &str, pos: (usize, usize)) -> anyhow::Result<()> {
+        let open = self.buffer_with_path(&std::path::absolute(fname)?);
+        self.record_jump();
+        let id = match open {
+            Some(id) => id,
+            None => self.open_buffer(fname)?,
+        };
+        if id != self.selected_buf {
+            self.select_buffer(id)?;
+        }
+        let from = self.selected_pos;
+        self.selected_pos = pos;
+        self.clamp_cursor();
+        self.cursor_moved(from);
+        Ok(())
+    }
+
+    /// The diagnostics for the selected buffer.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.buffers[&self.selected_buf].diagnostics
+    }
+
+    /// Replace the diagnostics for the buffer with the file at `path`, if there is one.
+    pub fn set_diagnostics(&mut self, path: &Path, diagnostics: Vec<Diagnostic>) {
+        if let Some(id) = self.buffer_with_path(path) {
+            self.buffers
+                .get_mut(&id)
+                .expect("the buffer exists")
+                .diagnostics = diagnostics;
+        }
+    }
+
+    /// The buffer with the file at the absolute `path`, if there is one.
+    fn buffer_with_path(&self, path: &Path) -> Option<DocumentID> {
+        self.buffers.iter().find_map(|(&id, buf)| {
+            let file = buf.file.as_deref()?;
+            (std::path::absolute(file).ok()? == path).then_some(id)
+        })
+    }
+
+    /// Start the language server for the kind of file in the selected buffer, as set in
+    /// [`config::LANGUAGE_SERVERS`].
+    ///
+    /// Does nothing if there is no server for it, or if the editor was built without the `lsp`
+    /// feature.
+    pub fn start_language_server(&mut self) -> anyhow::Result<()> {
+        #[cfg(feature = "lsp")]
+        if let Some(&(_, language_id, command)) = self.active_fname().and_then(language_server) {
+            let root = std::env::current_dir()?;
+            self.language_server = Some(crate::lsp::Client::start(command, language_id, &root)?);
+        }
+        Ok(())
+    }
+
+    /// Whether a language server is running, so [`Editor::poll_language_server`] should be
+    /// called regularly.
+    pub fn language_server_running(&self) -> bool {
+        #[cfg(feature = "lsp")]
+        return self.language_server.is_some();
+        #[cfg(not(feature = "lsp"))]
+        false
+    }
+
+    /// Tell the language server about any changes to the selected buffer, and act on anything it
+    /// has sent, returning whether anything changed.
+    ///
+    /// If the server has stopped, it is forgotten about and the error is returned.
+    pub fn poll_language_server(&mut self) -> anyhow::Result<bool> {
+        #[cfg(feature = "lsp")]
+        {
+            use crate::lsp::Event;
+
+            let Some(client) = &mut self.language_server else {
+                return Ok(false);
+            };
+            let buf = &self.buffers[&self.selected_buf];
+            let language = buf.file.as_deref().and_then(language_server);
+            let synced = match &buf.file {
+                Some(fname) if language.map(|server| server.1) == Some(client.language_id()) => {
+                    client.sync(&std::path::absolute(fname)?, &buf.text)
+                }
+                _ => Ok(()),
+            };
+            let events = synced.and_then(|()| client.events());
+            let events = match events {
+                Ok(events) => events,
+                Err(err) => {
+                    self.language_server = None;
+                    return Err(err);
+                }
+            };
+            let encoding = client.position_encoding();
+            let changed = !events.is_empty();
+            for event in events {
+                match event {
+                    Event::Diagnostics(path, mut diagnostics) => {
+                        let Some(id) = self.buffer_with_path(&path) else {
+                            continue;
+                        };
+                        let buf = &self.buffers[&id];
+                        for diagnostic in &mut diagnostics {
+                            diagnostic.start = from_server_pos(buf, encoding, diagnostic.start);
+                            diagnostic.end = from_server_pos(buf, encoding, diagnostic.end);
+                        }
+                        self.set_diagnostics(&path, diagnostics);
+                    }
+                    Event::Definition(locations) => {
+                        let Some((path, pos)) = locations.into_iter().next() else {
+                            bail!("No definition found");
+                        };
+                        self.go_to_location(&path.to_string_lossy(), (0, pos.1))?;
+                        let buf = &self.buffers[&self.selected_buf];
+                        self.selected_pos = from_server_pos(buf, encoding, pos);
+                        self.clamp_cursor();
+                    }
+                }
+            }
+            Ok(changed)
+        }
+        #[cfg(not(feature = "lsp"))]
+        Ok(false)
+    }
+
+    /// Ask the language server where the definition of what is under the cursor is, like vim's
+    /// `gd`.
+    ///
+    /// The cursor moves there once the server answers, in [`Editor::poll_language_server`].
+    pub fn go_to_definition(&mut self) -> anyhow::Result<()> {
+        #[cfg(feature = "lsp")]
+        {
+            let Some(client) = &mut self.language_server else {
+                bail!("No language server is running");
+            };
+            let buf = &self.buffers[&self.selected_buf];
+            let Some(fname) = &buf.file else {
+                bail!("No file name");
+            };
+            let pos = to_server_pos(buf, client.position_encoding(), self.selected_pos);
+            client.request_definition(&std::path::absolute(fname)?, &buf.text, pos)
+        }
+        #[cfg(not(feature = "lsp"))]
+        bail!("Built without language server support")
+    }
+
+    /// Show the jump list to pick from in [`Mode::List`], like vim's `:jumps`.
+    pub fn show_jumps(&mut self) -> anyhow::Result<()> {
+        if self.jumps.entries().is_empty() {
+            bail!("The jump list is empty");
+        }
+        let items = self
+            .jumps
+            .entries()
+            .iter()
+            .map(|jump| {
+                let buf = &self.buffers[&jump.buffer];
+                let text = match (&buf.file, jump.buffer == self.selected_buf) {
+                    (Some(fname), false) => fname.clone(),
+                    _ => line_snippet(buf, jump.pos.1),
+                };
+                (jump.pos, text)
+            })
+            .collect();
+        self.show_list(
+            "jump line  col file/text",
+            items,
+            self.jumps.current(),
+            ListKind::Jumps,
+        );
+        Ok(())
+    }
+
+    /// Show the change list of the selected buffer to pick from in [`Mode::List`], like vim's
+    /// `:changes`.
+    pub fn show_changes(&mut self) -> anyhow::Result<()> {
+        let buf = &self.buffers[&self.selected_buf];
+        if buf.changes.entries().is_empty() {
+            bail!("The change list is empty");
+        }
+        let items = buf
+            .changes
+            .entries()
+            .iter()
+            .map(|&pos| (pos, line_snippet(buf, pos.1)))
+            .collect();
+        let current = buf.changes.current();
+        self.show_list("change line  col text", items, current, ListKind::Changes);
+        Ok(())
+    }
+
+    /// Show how the selected buffer differs from its file on disk in [`Mode::List`], with added
+    /// lines marked `+` and removed ones `-`.
+    ///
+    /// Only the changed lines are shown, with [`config::DIFF_CONTEXT`] lines around them. Picking
+    /// a line goes to where it is in the buffer.
+    pub fn show_diff(&mut self) -> anyhow::Result<()> {
+        let buf = &self.buffers[&self.selected_buf];
+        let Some(fname) = &buf.file else {
+            bail!("No file name");
+        };
+        let disk = std::fs::read_to_string(fname)
+            .with_context(|| format!("Reading file `{fname}` failed."))?;
+        let disk = Rope::from_str(&disk);
+        let lines = diff::hunks(
+            diff::diff(disk.slice(..), buf.text.slice(..)),
+            config::DIFF_CONTEXT,
+        );
+        if lines.is_empty() {
+            self.output = Some("No changes from the file on disk".to_owned());
+            return Ok(());
+        }
+        let items = lines
+            .iter()
+            .map(|line| {
+                let sign = match line.change {
+                    Change::Same => ' ',
+                    Change::Added => '+',
+                    Change::Removed => '-',
+                };
+                format!("{sign} {}", line.text)
+            })
+            .collect();
+        let title = format!(" diff {fname}");
+        self.set_mode(Mode::List);
+        self.list = Some(List {
+            title,
+            items,
+            selected: 0,
+            kind: ListKind::Diff(lines),
+        });
+        Ok(())
+    }
+
+    /// Start picking from a list of positions in [`Mode::List`], with `current` marked and
+    /// selected.
+    fn show_list(
+        &mut self,
+        title: &str,
+        items: Vec<((usize, usize), String)>,
+        current: usize,
+        kind: ListKind,
+    ) {
+        let items = items
+            .into_iter()
+            .enumerate()
+            .map(|(i, ((x, y), text))| {
+                let marker = if i == current { '>' } else { ' ' };
+                // Like vim, entries are numbered by how far they are from the current one.
+                let distance = i.abs_diff(current);
+                format!("{marker}{distance:>3} {:>5} {x:>4} {text}", y + 1)
+            })
+            .collect();
+        self.set_mode(Mode::List);
+        self.list = Some(List {
+            title: format!(" {title}"),
+            items,
+            selected: current,
+            kind,
+        });
+    }
+
+    /// The list being picked from in [`Mode::List`].
+    pub fn list(&self) -> Option<&List> {
+        self.list.as_ref()
+    }
+
+    /// Move the selection in the list down by one, or up if `up` is set.
+    ///
+    /// The selection stops at the ends of the list.
+    pub fn move_in_list(&mut self, up: bool) {
+        if let Some(list) = &mut self.list {
+            list.selected = if up {
+                list.selected.saturating_sub(1)
+            } else {
+                (list.selected + 1).min(list.items.len().saturating_sub(1))
+            };
+        }
+    }
+
+    /// Go to the position selected in the list, leaving [`Mode::List`].
+    pub fn pick_from_list(&mut self) -> anyhow::Result<()> {
+        let Some(List { selected, kind, .. }) = self.list.take() else {
+            return Ok(());
+        };
+        self.set_mode(Mode::Normal);
+        match kind {
+            ListKind::Jumps => {
+                let Some(&jump) = self.jumps.go_to(selected) else {
+                    return Ok(());
+                };
+                if jump.buffer != self.selected_buf {
+                    self.select_buffer(jump.buffer)?;
+                }
+                self.selected_pos = jump.pos;
+            }
+            ListKind::Changes => {
+                let Some(buf) = self.buffers.get_mut(&self.selected_buf) else {
+                    return Ok(());
+                };
+                if let Some(&pos) = buf.changes.go_to(selected) {
+                    self.selected_pos = pos;
+                }
+            }
+            ListKind::Diff(lines) => {
+                if let Some(line) = lines.get(selected) {
+                    self.selected_pos = (0, line.line);
+                }
+            }
+        }
+        self.clamp_cursor();
+        Ok(())
+    }
+
+    /// Use `dictionary` for spell checking instead of the configured one.
+    pub fn set_dictionary(&mut self, dictionary: Dictionary) {
+        self.dictionary = Some(dictionary);
+    }
+
+    /// Find all the misspelled words within `lines`.
+    ///
+    /// The ranges returned are char indices into the whole of [`Editor::text`].
+    /// If spell checking is off, nothing is misspelled.
+    pub fn misspellings(&self, lines: Range<usize>) -> Vec<Range<usize>> {
+        let Some(dictionary) = self.dictionary.as_ref().filter(|_| self.settings.spell) else {
+            return Vec::new();
+        };
+        let text = self.text();
+        let end = lines.end.min(text.len_lines());
+        let start = lines.start.min(end);
+        let offset = text.line_to_char(start);
+        dictionary
+            .misspellings(text.slice(offset..text.line_to_char(end)))
+            .into_iter()
+            .map(|range| range.start + offset..range.end + offset)
+            .collect()
+    }
+
+    /// Move the cursor to the start of the next misspelled word, wrapping around to the start of
+    /// the buffer.
+    pub fn next_misspelling(&mut self) {
+        let cursor = self.cursor_char();
+        let misspellings = self.misspellings(0..usize::MAX);
+        if let Some(range) = misspellings
+            .iter()
+            .find(|range| range.start > cursor)
+            .or(misspellings.first())
+        {
+            self.set_cursor_char(range.start);
+        }
+    }
+
+    /// Move the cursor to the start of the previous misspelled word, wrapping around to the end
+    /// of the buffer.
+    pub fn prev_misspelling(&mut self) {
+        let cursor = self.cursor_char();
+        let misspellings = self.misspellings(0..usize::MAX);
+        if let Some(range) = misspellings
+            .iter()
+            .rev()
+            .find(|range| range.start < cursor)
+            .or(misspellings.last())
+        {
+            self.set_cursor_char(range.start);
+        }
+    }
+
+    /// Find the word under the cursor, as spell checking sees words.
+    ///
+    /// The range returned is char indices into the whole of [`Editor::text`].
+    pub fn word_under_cursor(&self) -> Option<Range<usize>> {
+        let (x, y) = self.selected_pos;
+        let text = self.text();
+        let line = trim_newlines(text.line(y));
+        let offset = text.line_to_char(y);
+        spell::words(line)
+            .find(|range| range.contains(&x))
+            .map(|range| range.start + offset..range.end + offset)
+    }
+
+    /// Add the word under the cursor to the user's spell checking dictionary.
+    pub fn spell_good(&mut self) -> anyhow::Result<()> {
+        self.update_dictionary(Dictionary::add_good)
+    }
+
+    /// Mark the word under the cursor as misspelled in the user's spell checking dictionary.
+    pub fn spell_bad(&mut self) -> anyhow::Result<()> {
+        self.update_dictionary(Dictionary::add_bad)
+    }
+
+    /// Call `update` on the dictionary with the word under the cursor.
+    ///
+    /// Does nothing if the cursor isn't on a word.
+    fn update_dictionary(
+        &mut self,
+        update: impl FnOnce(&mut Dictionary, &str) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let Some(range) = self.word_under_cursor() else {
+            return Ok(());
+        };
+        let word = self.text().slice(range).to_string();
+        let Some(dictionary) = &mut self.dictionary else {
+            bail!("Spell checking has not been turned on");
+        };
+        update(dictionary, &word)
+    }
+
+    /// Move the cursor back inside of the text if it has ended up past the end of its line or
+    /// the end of the buffer.
+    fn clamp_cursor(&mut self) {
+        let text = self.text();
+        let y = self.selected_pos.1.min(text.len_lines() - 1);
+        let line_len = trim_newlines(text.line(y)).len_chars();
+        self.selected_pos = (self.selected_pos.0.min(line_len), y);
+        self.snap_to_grapheme();
+    }
+
+    /// Move the cursor back to the start of the grapheme cluster it is in the middle of, if it is.
+    /// See [`grapheme_start`].
+    fn snap_to_grapheme(&mut self) {
+        let (x, y) = self.selected_pos;
+        let text = self.text();
+        if y < text.len_lines() {
+            self.selected_pos.0 = grapheme_start(trim_newlines(text.line(y)), x);
+        }
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new_empty()
+    }
+}
+
+/// The entry in [`config::LANGUAGE_SERVERS`] for the file `fname`, if there is one.
+#[cfg(feature = "lsp")]
+fn language_server(fname: &str) -> Option<&'static (&'static str, &'static str, &'static str)> {
+    let extension = Path::new(fname).extension()?;
+    config::LANGUAGE_SERVERS
+        .iter()
+        .find(|(ext, _, _)| extension == *ext)
+}
+
+/// The `(column, line)` position a language server uses for the `(x, y)` position `pos` in `buf`,
+/// with its columns counted in `encoding`.
+#[cfg(feature = "lsp")]
+fn to_server_pos(
+    buf: &Buffer,
+    encoding: PositionEncoding,
+    (x, y): (usize, usize),
+) -> (usize, usize) {
+    let column = match encoding {
+        PositionEncoding::Utf8 => buf.char_to_byte_col(x, y),
+        PositionEncoding::Utf16 => buf.char_to_utf16_col(x, y),
+    };
+    (column, y)
+}
+
+/// The `(x, y)` position in `buf` of the `(column, line)` position `pos` from a language server,
+/// the reverse of [`to_server_pos`].
+#[cfg(feature = "lsp")]
+fn from_server_pos(
+    buf: &Buffer,
+    encoding: PositionEncoding,
+    (column, line): (usize, usize),
+) -> (usize, usize) {
+    let x = match encoding {
+        PositionEncoding::Utf8 => buf.byte_col_to_char(column, line),
+        PositionEncoding::Utf16 => buf.utf16_col_to_char(column, line),
+    };
+    (x, line)
+}
+
+/// Load the configured spell checking dictionary along with the user's dictionary.
+fn load_dictionary() -> anyhow::Result<Dictionary> {
+    let dictionary = Dictionary::load(config::SPELL_DICTIONARY)?;
+    match config::data_dir() {
+        Some(dir) => dictionary.with_user_file(dir.join(config::SPELL_USER_DICTIONARY)),
+        None => Ok(dictionary),
+    }
+}
+
+/// Count the whitespace-separated words in `text`.
+fn count_words(text: RopeSlice) -> usize {
+    let mut words = 0;
+    let mut in_word = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            in_word = true;
+            words += 1;
+        }
+    }
+    words
+}
+
+/// The number of lines in `text`.
+///
+/// Unlike [`RopeSlice::len_lines`], the empty line after a final newline isn't counted.
+///
+/// [`RopeSlice::len_lines`]: ropey::RopeSlice::len_lines
+pub fn line_count(text: RopeSlice) -> usize {
+    let lines = text.len_lines();
+    if text.len_chars() == 0 || trim_newlines(text.line(lines - 1)).len_chars() == 0 {
+        lines - 1
+    } else {
+        lines
+    }
+}
+
+/// Remove the newline character(s) from the end of a [`RopeSlice`].
+///
+/// This is necessary because [`RopeSlice::lines`] includes the trailing newline characters.
+///
+/// [`RopeSlice`]: ropey::RopeSlice
+/// [`RopeSlice::lines`]: ropey::RopeSlice::lines
+pub fn trim_newlines(line: RopeSlice) -> RopeSlice {
+    let mut num_newline_chars = 0;
+    for c in line.chars_at(line.len_chars()).reversed() {
+        if matches!(
+            c,
+            '\u{000A}'|// Line Feed
+            '\u{000D}'|// Carriage Return
+            '\u{000B}'|// Vertical Tab
+            '\u{000C}'|// Form Feed
+            '\u{0085}'|// Next Line
+            '\u{2028}'|// Line Separator
+            '\u{2029}' // Paragraph Separator
+        ) {
+            num_newline_chars += 1;
+        } else {
+            break;
+        }
+    }
+    line.slice(..line.len_chars() - num_newline_chars)
+}
+
+/// The column where the grapheme cluster that column `x` of `line` is in starts, so that a
+/// cursor there isn't left between the chars of a single grapheme like `e` and a combining accent.
+///
+/// `line` shouldn't include its newline. Columns past the end of it are left as they are.
+pub fn grapheme_start(line: RopeSlice, x: usize) -> usize {
+    graphemes_around(line, x).map_or(x, |grapheme| grapheme.start)
+}
+
+/// The column just after the grapheme cluster that column `x` of `line` is in.
+///
+/// `line` shouldn't include its newline. Columns past the end of it are left as they are.
+pub fn grapheme_end(line: RopeSlice, x: usize) -> usize {
+    graphemes_around(line, x).map_or(x, |grapheme| grapheme.end)
+}
+
+/// The columns of the grapheme cluster that column `x` of `line` is in, if it is in one.
+fn graphemes_around(line: RopeSlice, x: usize) -> Option<Range<usize>> {
+    let mut column = 0;
+    for grapheme in line.to_string().graphemes(true) {
+        let len = grapheme.chars().count();
+        if x < column + len {
+            return Some(column..column + len);
+        }
+        column += len;
+    }
+    None
+}
+
+/// What is selected in [`Mode::Visual`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+    /// Every char from where the selection started to the cursor, like vim's `v`.
+    Char,
+    /// Every line from where the selection started to the cursor, like vim's `V`.
+    Line,
+}
+
+/// An enumeration of possible editor modes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    /// Normal mode.
+    ///
+    /// This mode is mainly for navigation and editing text.
+    Normal,
+    /// Insert mode.
+    ///
+    /// This mode is specifically for inserting text into the buffer.
+    Insert,
+    /// Visual mode.
+    ///
+    /// This mode is for selecting a region of text to act on.
+    Visual,
+    /// Command mode.
+    ///
+    /// This mode is for typing in commands to the command line.
+    Command,
+    /// Search mode.
+    ///
+    /// This mode is for typing in text to search forward for, after `/`. It shares the command
+    /// line with [`Mode::Command`].
+    Search,
+    /// List mode.
+    ///
+    /// This mode is for picking an entry from a [`List`], like the one shown by `:jumps`.
+    List,
+    /// Confirm mode.
+    ///
+    /// This mode is for answering yes or no to the [`Editor::question`] asked before a command
+    /// which changes a lot of lines.
+    Confirm,
+}
+
+/// A change held back in [`Mode::Confirm`] until the [`Editor::question`] about it is answered.
+#[derive(Debug)]
+enum Confirmable {
+    /// A command run with [`Editor::execute`].
+    Command(Command),
+    /// Deleting the selection, as the chars it covered and how they were selected.
+    DeleteSelection(Range<usize>, SelectionKind),
+}
+
+/// A place the cursor was before a jump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Jump {
+    /// The buffer the cursor was in.
+    buffer: DocumentID,
+    /// Where the cursor was in the buffer.
+    pos: (usize, usize),
+}
+
+/// How many words, chars and lines are in part of a buffer, from [`Editor::stats_in_range`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of words, counting any run of chars which aren't whitespace as a word.
+    pub words: usize,
+    /// The number of chars, including newlines.
+    pub chars: usize,
+    /// The number of lines.
+    pub lines: usize,
+}
+
+/// A read-only list of entries to pick one from in [`Mode::List`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct List {
+    /// The heading shown above the entries.
+    pub title: String,
+    /// Each entry, as it is shown.
+    pub items: Vec<String>,
+    /// The index of the selected entry.
+    pub selected: usize,
+    /// What the entries are, which decides what picking one does.
+    kind: ListKind,
+}
+
+impl List {
+    /// How each entry differs from the file on disk, for the list from [`Editor::show_diff`].
+    /// Other lists have no changes.
+    pub fn changes(&self) -> Vec<Change> {
+        match &self.kind {
+            ListKind::Diff(lines) => lines.iter().map(|line| line.change).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// What the entries of a [`List`] are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ListKind {
+    /// The jump list.
+    Jumps,
+    /// The change list of the selected buffer.
+    Changes,
+    /// The lines of a diff of the selected buffer against its file.
+    Diff(Vec<DiffLine>),
+}
+
+/// The text of line `y` of `buf`, trimmed to fit in a list.
+fn line_snippet(buf: &Buffer, y: usize) -> String {
+    match buf.text.get_line(y) {
+        Some(line) => trim_newlines(line)
+            .chars()
+            .take(60)
+            .collect::<String>()
+            .trim()
+            .to_owned(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{paste::PasteDetector, test_util::TempFile};
+    use ropey::Rope;
+    use std::time::Instant;
+
+    /// Create an [`Editor`] with a single buffer containing `text`.
+    fn editor(text: &str) -> Editor {
+        Editor::with_buffer(Buffer::new(Rope::from_str(text), None))
+    }
+
+    #[test]
+    fn starting_empty() {
+        let mut editor = Editor::new_empty();
+        assert_eq!(editor.buffers.len(), 1);
+        assert!(editor.buffers.contains_key(&editor.selected_buf));
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.active_fname(), None);
+
+        editor.set_mode(Mode::Insert);
+        type_text(&mut editor, "hi");
+        assert_eq!(editor.text(), "hi");
+        assert_eq!(editor.selected_pos(), (2, 0));
+    }
+
+    #[test]
+    fn spell_checking() {
+        let mut editor = editor("a speling\nmistaek here\n");
+        editor.set_dictionary(["a", "here"].into_iter().collect());
+        assert!(editor.misspellings(0..2).is_empty());
+
+        editor.execute("set spell".parse().unwrap()).unwrap();
+        assert_eq!(editor.misspellings(0..2), [2..9, 10..17]);
+        assert_eq!(editor.misspellings(1..2), vec![10..17]);
+
+        editor.next_misspelling();
+        assert_eq!(editor.selected_pos(), (2, 0));
+        editor.next_misspelling();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.next_misspelling();
+        assert_eq!(editor.selected_pos(), (2, 0));
+        editor.prev_misspelling();
+        assert_eq!(editor.selected_pos(), (0, 1));
+    }
+
+    #[test]
+    fn adding_words_to_the_dictionary() {
+        let file = TempFile::new("zg.add");
+        let mut editor = editor("good baad\n");
+        editor.set_dictionary(
+            ["good"]
+                .into_iter()
+                .collect::<Dictionary>()
+                .with_user_file(file.path().into())
+                .unwrap(),
+        );
+        editor.execute("set spell".parse().unwrap()).unwrap();
+        assert_eq!(editor.misspellings(0..1), vec![5..9]);
+
+        editor.next_misspelling();
+        editor.spell_good().unwrap();
+        assert!(editor.misspellings(0..1).is_empty());
+
+        editor.selected_pos = (2, 0);
+        editor.spell_bad().unwrap();
+        assert_eq!(editor.misspellings(0..1), vec![0..4]);
+
+        // Not on a word.
+        editor.selected_pos = (4, 0);
+        editor.spell_good().unwrap();
+        assert_eq!(editor.misspellings(0..1), vec![0..4]);
+    }
+
+    #[test]
+    fn deleting_matching_lines() {
+        let mut editor = editor("foo one\ntwo\nthree foo\nfour\nfoo");
+        editor.execute("g/foo/d".parse().unwrap()).unwrap();
+        assert_eq!(editor.text(), "two\nfour");
+        assert_eq!(editor.selected_pos(), (0, 0));
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "foo one\ntwo\nthree foo\nfour\nfoo");
+
+        editor.execute("v/foo/d".parse().unwrap()).unwrap();
+        assert_eq!(editor.text(), "foo one\nthree foo\nfoo");
+        assert!(editor.execute("v/foo/d".parse().unwrap()).is_err());
+        assert!(editor.execute("g/bar/d".parse().unwrap()).is_err());
+        assert_eq!(editor.text(), "foo one\nthree foo\nfoo");
+    }
+
+    #[test]
+    fn confirming_big_changes() {
+        let text = "foo\n".repeat(1000);
+        let mut editor = editor(&text);
+        editor.settings.confirm_lines = 100;
+        editor.execute("g/foo/d".parse().unwrap()).unwrap();
+        assert_eq!(editor.mode, Mode::Confirm);
+        assert_eq!(editor.question(), Some("Delete 1000 lines? (y/n)"));
+        assert_eq!(editor.text(), text.as_str());
+
+        // Declining leaves the buffer as it was.
+        editor.confirm(false).unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.question(), None);
+        assert_eq!(editor.text(), text.as_str());
+
+        editor.execute("g/foo/d".parse().unwrap()).unwrap();
+        editor.confirm(true).unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.text(), "");
+
+        // Small changes don't ask.
+        let mut editor = self::editor("foo\nbar\nfoo");
+        editor.settings.confirm_lines = 100;
+        editor.execute("g/foo/d".parse().unwrap()).unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.text(), "bar");
+    }
+
+    #[test]
+    fn confirming_big_deletions() {
+        let text = "foo\n".repeat(1000);
+        let mut editor = editor(&text);
+        editor.settings.confirm_lines = 100;
+        editor.select_all();
+        editor.delete_selection();
+        assert_eq!(editor.mode, Mode::Confirm);
+        assert_eq!(editor.question(), Some("Delete 1000 lines? (y/n)"));
+        assert_eq!(editor.text(), text.as_str());
+
+        // Declining leaves the buffer as it was.
+        editor.confirm(false).unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.text(), text.as_str());
+
+        editor.select_all();
+        editor.delete_selection();
+        editor.confirm(true).unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.text(), "");
+
+        // Small selections don't ask.
+        let mut editor = self::editor("foo\nbar\nfoo");
+        editor.settings.confirm_lines = 100;
+        editor.select_all();
+        editor.delete_selection();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.text(), "");
+    }
+
+    #[test]
+    fn matching_brackets() {
+        let mut editor = editor("f(a[0], <b>)\n{\n  g()\n}");
+        editor.go_to_matching_bracket();
+        assert_eq!(editor.selected_pos(), (11, 0));
+        editor.go_to_matching_bracket();
+        assert_eq!(editor.selected_pos(), (1, 0));
+        // `<` isn't a bracket until it is added to the pairs.
+        editor.selected_pos = (7, 0);
+        assert_eq!(editor.match_bracket(), Some(1));
+        editor
+            .execute("set matchpairs=(:),[:],{:},<:>".parse().unwrap())
+            .unwrap();
+        assert_eq!(editor.match_bracket(), Some(10));
+        editor.selected_pos = (0, 1);
+        editor.go_to_matching_bracket();
+        assert_eq!(editor.selected_pos(), (0, 3));
+        editor.selected_pos = (0, 2);
+        assert_eq!(editor.match_bracket(), Some(19));
+
+        // Typing the start of a pair closes it, and typing the end steps over it.
+        let mut editor = self::editor("");
+        editor.settings.autopairs = true;
+        editor
+            .execute("set matchpairs=(:),<:>".parse().unwrap())
+            .unwrap();
+        editor.set_mode(Mode::Insert);
+        for c in "<a(b".chars() {
+            editor.push(c);
+        }
+        assert_eq!(editor.text(), "<a(b)>");
+        assert_eq!(editor.selected_pos(), (4, 0));
+        for c in ")>c".chars() {
+            editor.push(c);
+        }
+        assert_eq!(editor.text(), "<a(b)>c");
+        assert_eq!(editor.selected_pos(), (7, 0));
+    }
+
+    #[test]
+    fn going_to_a_percentage() {
+        let text: String = (1..=100).map(|i| format!("line {i}\n")).collect();
+        let mut editor = editor(&text);
+        editor.goto_percent(50);
+        assert_eq!(editor.selected_pos(), (0, 49));
+        editor.goto_percent(200);
+        assert_eq!(editor.selected_pos(), (0, 99));
+        editor.goto_percent(0);
+        assert_eq!(editor.selected_pos(), (0, 0));
+        assert_eq!(editor.jumps.entries().len(), 3);
+
+        let mut editor = self::editor("");
+        editor.goto_percent(50);
+        assert_eq!(editor.selected_pos(), (0, 0));
+    }
+
+    #[test]
+    fn going_to_the_top_and_bottom() {
+        let mut editor = editor("first line\nsecond\nlast line\n");
+        editor.selected_pos = (8, 0);
+        editor.goto_bottom();
+        assert_eq!(editor.selected_pos(), (8, 2));
+        editor.move_up();
+        editor.goto_top();
+        assert_eq!(editor.selected_pos(), (6, 0));
+        assert_eq!(editor.jumps.entries().len(), 2);
+
+        let mut editor = self::editor("");
+        editor.goto_bottom();
+        assert_eq!(editor.selected_pos(), (0, 0));
+    }
+
+    #[test]
+    fn filtering_the_buffer() {
+        let mut editor = editor("one\ntwo\nthree\n");
+        editor.selected_pos = (4, 2);
+        editor.execute("%!head -n 1".parse().unwrap()).unwrap();
+        assert_eq!(editor.text(), "one\n");
+        assert_eq!(editor.selected_pos(), (0, 1));
+
+        editor
+            .execute("set filterlimit=100".parse().unwrap())
+            .unwrap();
+        assert!(editor.execute("%!yes".parse().unwrap()).is_err());
+        assert_eq!(editor.text(), "one\n");
+    }
+
+    #[test]
+    fn file_info() {
+        let mut notes = editor("one two\nthree\nfour\nfive\n");
+        assert_eq!(notes.file_info(), "\"[No Name]\" 4 lines --25%--");
+        notes.buffers.get_mut(&0).unwrap().file = Some("notes.txt".to_owned());
+        notes.selected_pos = (0, 2);
+        assert_eq!(notes.file_info(), "\"notes.txt\" 4 lines --75%--");
+        notes.selected_pos = (0, 4);
+        assert_eq!(notes.file_info(), "\"notes.txt\" 4 lines --100%--");
+
+        assert_eq!(
+            editor("").file_info(),
+            "\"[No Name]\" --No lines in buffer--"
+        );
+        assert_eq!(editor("solo").file_info(), "\"[No Name]\" 1 line --100%--");
+    }
+
+    #[test]
+    fn cursor_info() {
+        let mut editor = editor("one two\nthree\n");
+        editor.selected_pos = (5, 0);
+        assert_eq!(
+            editor.cursor_info(),
+            "Col 6 of 7; Line 1 of 2; Word 2 of 3; Char 6 of 14"
+        );
+        editor.selected_pos = (3, 0);
+        assert_eq!(
+            editor.cursor_info(),
+            "Col 4 of 7; Line 1 of 2; Word 1 of 3; Char 4 of 14"
+        );
+    }
+
+    #[test]
+    fn selection_stats() {
+        let mut editor = editor("one two\nthree four five\nsix seven\n");
+        assert_eq!(
+            editor.stats_in_range(0..editor.text().len_chars()),
+            Stats {
+                words: 7,
+                chars: 34,
+                lines: 3,
+            }
+        );
+        // From the "o" of "two" to the "i" of "six".
+        assert_eq!(
+            editor.stats_in_range(5..26),
+            Stats {
+                words: 5,
+                chars: 21,
+                lines: 3,
+            }
+        );
+        assert_eq!(
+            editor.stats_in_range(8..8),
+            Stats {
+                words: 0,
+                chars: 0,
+                lines: 0,
+            }
+        );
+
+        editor.selected_pos = (5, 0);
+        editor.set_mode(Mode::Visual);
+        editor.selected_pos = (1, 2);
+        assert_eq!(
+            editor.cursor_info(),
+            "Selected 3 of 3 Lines; 5 of 7 Words; 21 of 34 Chars"
+        );
+    }
+
+    /// Type `text` in insert mode and then return to normal mode.
+    fn type_text(editor: &mut Editor, text: &str) {
+        editor.set_mode(Mode::Insert);
+        for c in text.chars() {
+            editor.push(c);
+        }
+        editor.set_mode(Mode::Normal);
+    }
+
+    #[test]
+    fn undo_tree() {
+        let mut editor = editor("");
+        type_text(&mut editor, "one");
+        type_text(&mut editor, " two");
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "one");
+        assert_eq!(editor.selected_pos(), (3, 0));
+
+        // Editing now starts a new branch.
+        type_text(&mut editor, " three");
+        assert_eq!(editor.text(), "one three");
+        editor.undo().unwrap();
+        editor.redo().unwrap();
+        assert_eq!(editor.text(), "one three");
+        assert!(editor.redo().is_err());
+
+        // Going back in time recovers the abandoned branch.
+        editor.earlier(1).unwrap();
+        assert_eq!(editor.text(), "one two");
+        editor
+            .execute(Command::Earlier(UndoStep::Count(5)))
+            .unwrap();
+        assert_eq!(editor.text(), "");
+        assert!(editor.undo().is_err());
+        editor.execute(Command::Later(UndoStep::Count(3))).unwrap();
+        assert_eq!(editor.text(), "one three");
+    }
+
+    #[test]
+    fn recording_changes() {
+        let mut editor = editor("one");
+        editor.record(Message::DeleteChar);
+        assert_eq!(editor.last_change(), [Message::DeleteChar]);
+        // Moving isn't a change.
+        editor.record(Message::Down);
+        assert_eq!(editor.last_change(), [Message::DeleteChar]);
+
+        let insert = [
+            Message::Append,
+            Message::Char('a'),
+            Message::Enter,
+            Message::Mode(Mode::Normal),
+        ];
+        for message in insert {
+            editor.record(message);
+            match message {
+                Message::Append => editor.append(),
+                Message::Mode(mode) => editor.set_mode(mode),
+                _ => {}
+            }
+        }
+        // The insert only becomes the last change once it is over.
+        assert_eq!(editor.last_change(), [Message::DeleteChar]);
+        editor.record(Message::Left);
+        assert_eq!(editor.last_change(), insert);
+    }
+
+    #[test]
+    fn undoing_a_whole_insert() {
+        let mut editor = editor("one");
+        editor.selected_pos = (3, 0);
+        editor.set_mode(Mode::Insert);
+        editor.push(' ');
+        editor.push('x');
+        editor.backspace();
+        editor.newline();
+        for c in "two".chars() {
+            editor.push(c);
+        }
+        editor.set_mode(Mode::Normal);
+        assert_eq!(editor.text(), "one \ntwo");
+        let end = editor.selected_pos();
+
+        // Everything typed before leaving insert mode goes in one go.
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "one");
+        assert!(editor.undo().is_err());
+        editor.redo().unwrap();
+        assert_eq!(editor.text(), "one \ntwo");
+        assert_eq!(editor.selected_pos(), end);
+    }
+
+    #[test]
+    fn replacing_the_selection() {
+        let mut editor = editor("one two three");
+        editor.selected_pos = (4, 0);
+        editor.set_mode(Mode::Visual);
+        for _ in 0..2 {
+            editor.move_right();
+        }
+        assert_eq!(editor.selection(), Some(4..7));
+        editor.replace_all_selection();
+        assert_eq!(editor.mode, Mode::Insert);
+        assert_eq!(editor.text(), "one  three");
+        assert_eq!(editor.register('"'), Some("two"));
+
+        type_text(&mut editor, "2");
+        assert_eq!(editor.text(), "one 2 three");
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "one two three");
+    }
+
+    #[test]
+    fn replacing_a_multi_line_selection() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.selected_pos = (1, 0);
+        editor.set_mode(Mode::Visual);
+        editor.move_down();
+        editor.replace_all_selection();
+        type_text(&mut editor, "X");
+        assert_eq!(editor.text(), "oXo\nthree");
+        assert_eq!(editor.register('"'), Some("ne\ntw"));
+    }
+
+    #[test]
+    fn deleting_the_selection() {
+        let mut editor = editor("one two");
+        editor.selected_pos = (6, 0);
+        editor.set_mode(Mode::Visual);
+        editor.move_left();
+        editor.delete_selection();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.text(), "one t");
+        assert_eq!(editor.selected_pos(), (5, 0));
+    }
+
+    #[test]
+    fn selecting_everything() {
+        let mut editor = editor("one\ntwo\nthree\n");
+        editor.move_down();
+        editor.select_all();
+        assert_eq!(editor.mode, Mode::Visual);
+        assert_eq!(editor.selected_pos(), (4, 2));
+        editor.yank_selection();
+        assert_eq!(editor.register('"'), Some("one\ntwo\nthree\n"));
+        assert_eq!(editor.selected_pos(), (0, 0));
+
+        editor.select_all();
+        editor.delete_selection();
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.mode, Mode::Normal);
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree\n");
+
+        // An empty buffer has nothing to select but its one empty line.
+        let mut editor = self::editor("");
+        editor.select_all();
+        assert_eq!(editor.selection(), Some(0..0));
+    }
+
+    #[test]
+    fn selecting_lines() {
+        let mut editor = editor("one\ntwo\nthree\nfour");
+        editor.move_right();
+        editor.visual_line_mode();
+        editor.move_down();
+        assert_eq!(editor.selection(), Some(0..8));
+        editor.delete_selection();
+        assert_eq!(editor.text(), "three\nfour");
+        assert_eq!(editor.selected_pos(), (0, 0));
+        assert_eq!(editor.register('"'), Some("one\ntwo\n"));
+
+        // Deleting the last line takes the newline before it too.
+        editor.move_down();
+        editor.visual_line_mode();
+        editor.delete_selection();
+        assert_eq!(editor.text(), "three");
+        assert_eq!(editor.register('"'), Some("four\n"));
+
+        let mut editor = self::editor("one\ntwo");
+        editor.visual_line_mode();
+        editor.yank_selection();
+        assert_eq!(editor.mode, Mode::Normal);
+        editor.move_down();
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "one\ntwo\none");
+        assert_eq!(editor.selected_pos(), (0, 2));
+
+        // Pressing `V` again leaves visual mode, and `v` starts selecting chars again.
+        editor.visual_line_mode();
+        editor.visual_line_mode();
+        assert_eq!(editor.mode, Mode::Normal);
+        editor.set_mode(Mode::Visual);
+        assert_eq!(editor.selection_kind(), SelectionKind::Char);
+
+        // Replacing lines leaves a line to type on.
+        let mut editor = self::editor("one\ntwo");
+        editor.visual_line_mode();
+        editor.replace_all_selection();
+        editor.push('x');
+        assert_eq!(editor.text(), "x\ntwo");
+    }
+
+    #[test]
+    fn keeping_the_selection() {
+        let text: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+        let mut editor = editor(&text);
+        editor.go_to_line(2);
+        editor.visual_line_mode();
+        editor.move_down();
+        editor.move_down();
+        editor.keep_selection();
+        assert_eq!(editor.text(), "line 3\nline 4\nline 5\n");
+        assert_eq!(editor.selected_pos(), (0, 0));
+        assert_eq!(editor.mode, Mode::Normal);
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), text.as_str());
+
+        // Parts of lines can be kept too.
+        let mut editor = self::editor("one two three");
+        editor.selected_pos = (4, 0);
+        editor.set_mode(Mode::Visual);
+        editor.move_right();
+        editor.move_right();
+        editor.keep_selection();
+        assert_eq!(editor.text(), "two");
+    }
+
+    #[test]
+    fn backspacing_at_the_start_of_lines() {
+        let mut editor = editor("one\r\ntwo\nthree");
+        editor.settings.backspace_joins_lines = false;
+        editor.set_mode(Mode::Insert);
+        editor.move_down();
+        editor.backspace();
+        assert_eq!(editor.text(), "one\r\ntwo\nthree");
+        assert_eq!(editor.selected_pos(), (0, 1));
+
+        editor.settings.backspace_joins_lines = true;
+        editor.backspace();
+        assert_eq!(editor.text(), "onetwo\nthree");
+        assert_eq!(editor.selected_pos(), (3, 0));
+        editor.backspace();
+        assert_eq!(editor.text(), "ontwo\nthree");
+        // There is nothing before the first line to join it to.
+        editor.move_to_line_start();
+        editor.backspace();
+        assert_eq!(editor.text(), "ontwo\nthree");
+        assert_eq!(editor.selected_pos(), (0, 0));
+    }
+
+    #[test]
+    fn wrapping_left_and_right() {
+        let mut editor = editor("ab\ncd");
+        editor.settings.which_wrap = vec![];
+        editor.selected_pos = (2, 0);
+        editor.move_right();
+        assert_eq!(editor.selected_pos(), (2, 0));
+        editor.selected_pos = (0, 1);
+        editor.move_left();
+        assert_eq!(editor.selected_pos(), (0, 1));
+
+        editor.settings.which_wrap = vec!['h'];
+        editor.move_left();
+        assert_eq!(editor.selected_pos(), (2, 0));
+        editor.move_right();
+        assert_eq!(editor.selected_pos(), (2, 0));
+        // There is no line before the first to wrap to.
+        editor.selected_pos = (0, 0);
+        editor.move_left();
+        assert_eq!(editor.selected_pos(), (0, 0));
+
+        editor.settings.which_wrap = vec!['l'];
+        editor.selected_pos = (2, 0);
+        editor.move_right();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.move_left();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.selected_pos = (2, 1);
+        editor.move_right();
+        assert_eq!(editor.selected_pos(), (2, 1));
+    }
+
+    #[test]
+    fn keeping_the_virtual_column() {
+        let mut editor = editor("a long line\nab\n\nanother long line");
+        editor.selected_pos = (7, 0);
+        editor.move_down();
+        assert_eq!(editor.selected_pos(), (2, 1));
+        assert_eq!(editor.virtual_column(), 7);
+        editor.move_down();
+        assert_eq!(editor.selected_pos(), (0, 2));
+        editor.move_down();
+        assert_eq!(editor.selected_pos(), (7, 3));
+        editor.move_up();
+        editor.move_up();
+        editor.move_up();
+        assert_eq!(editor.selected_pos(), (7, 0));
+
+        // Moving along the line forgets the old column.
+        editor.move_down();
+        editor.move_left();
+        assert_eq!(editor.virtual_column(), 1);
+        editor.move_up();
+        assert_eq!(editor.selected_pos(), (1, 0));
+    }
+
+    #[test]
+    fn moving_to_the_first_non_blank() {
+        let mut editor = editor("  \tfoo bar\n\t \nbaz");
+        editor.move_to_line_end();
+        editor.move_to_first_non_blank();
+        assert_eq!(editor.selected_pos(), (3, 0));
+        editor.move_down();
+        editor.move_to_first_non_blank();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.move_down();
+        editor.move_to_first_non_blank();
+        assert_eq!(editor.selected_pos(), (0, 2));
+    }
+
+    #[test]
+    fn moving_to_line_ends_in_normal_mode() {
+        let mut editor = editor("one two\r\n\nthree");
+        editor.move_right();
+        editor.move_to_line_end();
+        assert_eq!(editor.selected_pos(), (6, 0));
+        editor.move_to_line_start();
+        assert_eq!(editor.selected_pos(), (0, 0));
+
+        // An empty line has nowhere to go.
+        editor.move_down();
+        editor.move_to_line_end();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.move_down();
+        editor.move_to_line_end();
+        assert_eq!(editor.selected_pos(), (4, 2));
+    }
+
+    #[test]
+    fn moving_to_word_ends() {
+        let mut editor = editor("let x = foo.bar;\n\n  end");
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (2, 0));
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (4, 0));
+        editor.selected_pos = (8, 0);
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (10, 0));
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (11, 0));
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (14, 0));
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (15, 0));
+        // The rest of the line and the blank line after it are skipped.
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (4, 2));
+        editor.word_end();
+        assert_eq!(editor.selected_pos(), (4, 2));
+    }
+
+    #[test]
+    fn moving_to_line_ends() {
+        let mut editor = editor("one two\r\n\nthree");
+        editor.set_mode(Mode::Insert);
+        editor.move_right();
+        editor.move_to_line_end();
+        assert_eq!(editor.selected_pos(), (7, 0));
+        editor.push('!');
+        editor.move_to_line_start();
+        editor.push('>');
+        assert_eq!(editor.mode, Mode::Insert);
+        assert_eq!(editor.text().line(0), ">one two!\r\n");
+
+        // An empty line has nowhere to go.
+        editor.move_down();
+        editor.move_to_line_end();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.move_to_line_start();
+        assert_eq!(editor.selected_pos(), (0, 1));
+    }
+
+    #[test]
+    fn cursor_chars() {
+        let mut editor = editor("ab\r\ncd\n\nef");
+        for (idx, pos) in [
+            (0, (0, 0)),
+            (2, (2, 0)),
+            (4, (0, 1)),
+            (6, (2, 1)),
+            (7, (0, 2)),
+            (8, (0, 3)),
+            (10, (2, 3)),
+        ] {
+            editor.set_cursor_char(idx);
+            assert_eq!(editor.selected_pos(), pos);
+            assert_eq!(editor.cursor_char(), idx);
+        }
+
+        // The `\n` of a `\r\n` is at the end of the line too.
+        editor.set_cursor_char(3);
+        assert_eq!(editor.selected_pos(), (2, 0));
+        // Past the end of the buffer is clamped to the end.
+        editor.set_cursor_char(100);
+        assert_eq!(editor.selected_pos(), (2, 3));
+        assert_eq!(editor.cursor_char(), 10);
+    }
+
+    #[test]
+    fn searching_for_the_word_under_the_cursor() {
+        let mut editor = editor("foo bar\nfoobar foo\nbar foo\n");
+        editor.search_word_under_cursor(false).unwrap();
+        assert_eq!(editor.selected_pos(), (7, 1));
+        assert_eq!(editor.search().unwrap().query, "foo");
+
+        // `n` carries on with the same search.
+        editor.search_next().unwrap();
+        assert_eq!(editor.selected_pos(), (4, 2));
+        editor.search_next().unwrap();
+        assert_eq!(editor.selected_pos(), (0, 0));
+        editor.search_prev().unwrap();
+        assert_eq!(editor.selected_pos(), (4, 2));
+
+        // `#` goes backwards, and so does `n` after it.
+        editor.selected_pos = (0, 2);
+        editor.search_word_under_cursor(true).unwrap();
+        assert_eq!(editor.selected_pos(), (4, 0));
+        editor.search_next().unwrap();
+        assert_eq!(editor.selected_pos(), (0, 2));
+
+        // Nothing happens off of a word.
+        editor.selected_pos = (3, 0);
+        editor.search_word_under_cursor(false).unwrap();
+        assert_eq!(editor.selected_pos(), (3, 0));
+        assert_eq!(editor.search().unwrap().query, "bar");
+    }
+
+    #[test]
+    fn searching_forward() {
+        let mut editor = editor("one two\nthree one\ntwo one\n");
+        editor.selected_pos = (1, 0);
+        editor.set_mode(Mode::Search);
+        for c in "one".chars() {
+            editor.command_push(c);
+        }
+        editor.run_command_line().unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.selected_pos(), (6, 1));
+        assert_eq!(editor.take_output(), None);
+        editor.search_next().unwrap();
+        assert_eq!(editor.selected_pos(), (4, 2));
+
+        // Going past the end wraps around to the top, and says so.
+        editor.search_next().unwrap();
+        assert_eq!(editor.selected_pos(), (0, 0));
+        assert_eq!(
+            editor.take_output().as_deref(),
+            Some("Search hit BOTTOM, continuing at TOP")
+        );
+        editor.search_prev().unwrap();
+        assert_eq!(editor.selected_pos(), (4, 2));
+        assert_eq!(
+            editor.take_output().as_deref(),
+            Some("Search hit TOP, continuing at BOTTOM")
+        );
+
+        // An empty search repeats the last one, forwards even after `#`.
+        editor.search_word_under_cursor(true).unwrap();
+        assert_eq!(editor.selected_pos(), (6, 1));
+        editor.search_for(String::new()).unwrap();
+        assert_eq!(editor.selected_pos(), (4, 2));
+        assert!(editor.search_for(String::from("four")).is_err());
+    }
+
+    #[test]
+    fn registers() {
+        let mut editor = editor("one two");
+        editor.set_mode(Mode::Visual);
+        editor.move_right();
+        editor.move_right();
+        editor.select_register('a').unwrap();
+        editor.yank_selection();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.register('a'), Some("one"));
+        assert_eq!(editor.register('"'), Some("one"));
+        assert!(editor.select_register('!').is_err());
+
+        editor.execute(Command::Registers).unwrap();
+        assert_eq!(
+            editor.take_output().unwrap(),
+            "--- Registers ---\n\"\"   one\n\"a   one"
+        );
+        assert_eq!(editor.take_output(), None);
+    }
+
+    #[test]
+    fn editing_a_register() {
+        let mut editor = editor("x\n");
+        editor.registers.store(
+            'q',
+            Register {
+                text: "hello\nworld".to_owned(),
+                linewise: false,
+            },
+        );
+
+        // Paste the register in to the buffer.
+        editor.select_register('q').unwrap();
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "xhello\nworld\n");
+        assert_eq!(editor.selected_pos(), (4, 1));
+
+        // Change "world" to "there".
+        editor.selected_pos = (0, 1);
+        editor.set_mode(Mode::Visual);
+        editor.selected_pos = (4, 1);
+        editor.replace_all_selection();
+        type_text(&mut editor, "there");
+
+        // Yank it back in to the register.
+        editor.set_cursor_char(1);
+        editor.set_mode(Mode::Visual);
+        editor.selected_pos = (4, 1);
+        editor.select_register('q').unwrap();
+        editor.yank_selection();
+        assert_eq!(editor.register('q'), Some("hello\nthere"));
+
+        editor.select_register('z').unwrap();
+        assert!(editor.paste().is_err());
+    }
+
+    #[test]
+    fn yanking_and_pasting_lines() {
+        // Lines go below the cursor's line whatever column it is in.
+        let mut editor = editor("one\r\ntwo\r\nthree");
+        editor.selected_pos = (2, 0);
+        editor.yank_line();
+        assert_eq!(editor.register('"'), Some("one\r\n"));
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "one\r\none\r\ntwo\r\nthree");
+        assert_eq!(editor.selected_pos(), (0, 1));
+
+        // The last line has no newline, so it gets one in the register.
+        editor.go_to_line(3);
+        editor.yank_line();
+        assert_eq!(editor.register('"'), Some("three\n"));
+        editor.go_to_line(0);
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "one\r\nthree\none\r\ntwo\r\nthree");
+        assert_eq!(editor.selected_pos(), (0, 1));
+    }
+
+    #[test]
+    fn deleting_chars() {
+        let mut editor = editor("abc\nd");
+        editor.delete_char();
+        assert_eq!(editor.text(), "bc\nd");
+        assert_eq!(editor.selected_pos(), (0, 0));
+        assert_eq!(editor.register('"'), Some("a"));
+
+        // Deleting the last char of the line moves back on to the one before.
+        editor.selected_pos = (1, 0);
+        editor.delete_char();
+        assert_eq!(editor.text(), "b\nd");
+        assert_eq!(editor.selected_pos(), (0, 0));
+        editor.delete_char();
+        assert_eq!(editor.text(), "\nd");
+        assert_eq!(editor.selected_pos(), (0, 0));
+        // The newline is never deleted.
+        editor.delete_char();
+        assert_eq!(editor.text(), "\nd");
+
+        // `xp` swaps two chars.
+        let mut editor = self::editor("ab");
+        editor.delete_char();
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "ba");
+    }
+
+    #[test]
+    fn replacing_chars() {
+        let mut editor = editor("cat\n");
+        editor.selected_pos = (2, 0);
+        editor.replace_char('r');
+        assert_eq!(editor.text(), "car\n");
+        assert_eq!(editor.selected_pos(), (2, 0));
+        assert_eq!(editor.mode, Mode::Normal);
+        // There is nothing to replace past the end of the line.
+        editor.selected_pos = (3, 0);
+        editor.replace_char('t');
+        assert_eq!(editor.text(), "car\n");
+
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "cat\n");
+    }
+
+    #[test]
+    fn snapping_to_graphemes() {
+        let line = Rope::from_str("ae\u{301}\u{302}b");
+        let starts: Vec<usize> = (0..6).map(|x| grapheme_start(line.slice(..), x)).collect();
+        assert_eq!(starts, [0, 1, 1, 1, 4, 5]);
+
+        // Deleting the accent under the cursor leaves it in the middle of what's left.
+        let mut editor = editor("xe\u{301}\u{302}");
+        editor.selected_pos = (3, 0);
+        editor.delete_char();
+        assert_eq!(editor.text(), "xe\u{301}");
+        assert_eq!(editor.selected_pos(), (1, 0));
+
+        // Deleting only one of the accents of a cluster.
+        let mut editor = self::editor("ae\u{301}\u{302}b");
+        editor.delete_range(2..3);
+        assert_eq!(editor.text(), "ae\u{302}b");
+        assert_eq!(editor.selected_pos(), (1, 0));
+        editor.selected_pos = (2, 0);
+        editor.commit();
+        assert_eq!(editor.selected_pos(), (1, 0));
+    }
+
+    #[test]
+    fn transposing_chars() {
+        let mut editor = editor("abc\n");
+        editor.selected_pos = (2, 0);
+        editor.transpose_chars();
+        assert_eq!(editor.text(), "acb\n");
+        assert_eq!(editor.selected_pos(), (3, 0));
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "abc\n");
+
+        // Nothing happens at the start of a line.
+        editor.selected_pos = (0, 0);
+        editor.transpose_chars();
+        assert_eq!(editor.text(), "abc\n");
+        assert!(!editor.is_modified());
+
+        // In insert mode, it is part of what was typed.
+        editor.set_mode(Mode::Insert);
+        editor.push('d');
+        editor.push('c');
+        editor.transpose_chars();
+        assert_eq!(editor.selected_pos(), (3, 0));
+        editor.set_mode(Mode::Normal);
+        assert_eq!(editor.text(), "dacbc\n");
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "abc\n");
+    }
+
+    #[test]
+    fn joining_lines() {
+        let mut editor = editor("fn main() {\n    body();\n}");
+        editor.join_lines();
+        assert_eq!(editor.text(), "fn main() { body();\n}");
+        assert_eq!(editor.selected_pos(), (11, 0));
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "fn main() {\n    body();\n}");
+
+        editor.goto_bottom();
+        editor.join_lines();
+        assert_eq!(editor.text(), "fn main() {\n    body();\n}");
+        assert_eq!(editor.selected_pos(), (0, 2));
+
+        // The cursor can stay in its column instead.
+        editor.settings.join_keeps_column = true;
+        editor.selected_pos = (3, 0);
+        editor.join_lines();
+        assert_eq!(editor.text(), "fn main() { body();\n}");
+        assert_eq!(editor.selected_pos(), (3, 0));
+        editor.undo().unwrap();
+        editor.settings.join_keeps_column = false;
+        editor.selected_pos = (3, 0);
+        editor.join_lines();
+        assert_eq!(editor.selected_pos(), (11, 0));
+    }
+
+    #[test]
+    fn counted_motions() {
+        let mut editor = editor("one two\nthree\nfour five six\nseven");
+        editor.move_down_by(2);
+        assert_eq!(editor.selected_pos(), (0, 2));
+        // Counts past the ends of the buffer stop there.
+        editor.move_down_by(usize::MAX);
+        assert_eq!(editor.selected_pos(), (0, 3));
+        editor.move_up_by(100);
+        assert_eq!(editor.selected_pos(), (0, 0));
+
+        editor.repeat_motion(4, Editor::word_start);
+        assert_eq!(editor.selected_pos(), (5, 2));
+        editor.repeat_motion(usize::MAX, Editor::move_right);
+        assert_eq!(editor.selected_pos(), (13, 2));
+        editor.repeat_motion(usize::MAX, Editor::word_start);
+        assert_eq!(editor.selected_pos(), (0, 3));
+
+        editor.goto_line_number(2);
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.goto_line_number(0);
+        assert_eq!(editor.selected_pos(), (0, 0));
+        editor.goto_line_number(50);
+        assert_eq!(editor.selected_pos(), (0, 3));
+    }
+
+    #[test]
+    fn going_to_line_numbers() {
+        let mut editor = editor("one\ntwo\nthree\n");
+        editor.selected_pos = (2, 0);
+        editor.execute("3".parse().unwrap()).unwrap();
+        assert_eq!(editor.selected_pos(), (0, 2));
+        // Lines outside the buffer are clamped to it.
+        editor.execute("0".parse().unwrap()).unwrap();
+        assert_eq!(editor.selected_pos(), (0, 0));
+        editor.execute("400".parse().unwrap()).unwrap();
+        assert_eq!(editor.selected_pos(), (0, 2));
+        let mut empty = Editor::new_empty();
+        empty.execute("2".parse().unwrap()).unwrap();
+        assert_eq!(empty.selected_pos(), (0, 0));
+    }
+
+    #[test]
+    fn deleting_lines() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.selected_pos = (2, 1);
+        editor.delete_line();
+        assert_eq!(editor.text(), "one\nthree");
+        assert_eq!(editor.selected_pos(), (0, 1));
+        assert_eq!(editor.register('"'), Some("two\n"));
+
+        // Deleting the last line moves up to the one before, taking its newline too.
+        editor.delete_line();
+        assert_eq!(editor.text(), "one");
+        assert_eq!(editor.selected_pos(), (0, 0));
+        editor.delete_line();
+        assert_eq!(editor.text(), "");
+        editor.delete_line();
+        assert_eq!(editor.register('"'), Some("one\n"));
+
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "one");
+    }
+
+    #[test]
+    fn named_registers() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.select_register('a').unwrap();
+        editor.yank_line();
+        editor.move_down();
+        editor.select_register('b').unwrap();
+        editor.yank_line();
+        assert_eq!(editor.register('"'), Some("two\n"));
+
+        editor.move_down();
+        editor.select_register('a').unwrap();
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree\none");
+        assert_eq!(editor.selected_pos(), (0, 3));
+        editor.select_register('b').unwrap();
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree\none\ntwo");
+
+        // `"A` adds on to `"a`.
+        editor.selected_pos = (0, 2);
+        editor.select_register('A').unwrap();
+        editor.yank_line();
+        assert_eq!(editor.register('a'), Some("one\nthree\n"));
+        editor.selected_pos = (0, 0);
+        editor.select_register('a').unwrap();
+        editor.paste().unwrap();
+        assert_eq!(editor.text(), "one\none\nthree\ntwo\nthree\none\ntwo");
+    }
+
+    #[test]
+    fn inserting_registers() {
+        let mut editor = editor("(one)");
+        editor.registers.store(
+            'a',
+            Register {
+                text: "two\nthree".to_owned(),
+                linewise: false,
+            },
+        );
+        editor.set_mode(Mode::Visual);
+        editor.set_cursor_char(4);
+        editor.yank_selection();
+
+        editor.set_mode(Mode::Insert);
+        editor.set_cursor_char(4);
+        editor.insert_register('"');
+        assert_eq!(editor.text(), "(one(one))");
+        assert_eq!(editor.selected_pos(), (9, 0));
+        editor.set_cursor_char(1);
+        editor.insert_register('a');
+        assert_eq!(editor.text(), "(two\nthreeone(one))");
+        assert_eq!(editor.selected_pos(), (5, 1));
+        editor.insert_register('z');
+        assert_eq!(editor.selected_pos(), (5, 1));
+    }
+
+    #[test]
+    fn duplicating_lines() {
+        let mut editor = editor("one\ntwo");
+        editor.move_right();
+        editor.duplicate_line(1);
+        assert_eq!(editor.text(), "one\none\ntwo");
+        assert_eq!(editor.selected_pos(), (1, 1));
+
+        editor.move_down();
+        editor.duplicate_line(3);
+        assert_eq!(editor.text(), "one\none\ntwo\ntwo\ntwo\ntwo");
+        assert_eq!(editor.selected_pos(), (1, 5));
+        assert_eq!(editor.register(registers::UNNAMED), None);
+
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "one\none\ntwo");
+    }
+
+    #[test]
+    fn moving_lines() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.move_right();
+        editor.move_line_down();
+        assert_eq!(editor.text(), "two\none\nthree");
+        assert_eq!(editor.selected_pos(), (1, 1));
+        editor.move_line_down();
+        assert_eq!(editor.text(), "two\nthree\none");
+        assert_eq!(editor.selected_pos(), (1, 2));
+        // There is nothing below the last line to move past.
+        editor.move_line_down();
+        assert_eq!(editor.text(), "two\nthree\none");
+
+        editor.move_line_up();
+        assert_eq!(editor.text(), "two\none\nthree");
+        assert_eq!(editor.selected_pos(), (1, 1));
+        editor.move_line_up();
+        editor.move_line_up();
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+        assert_eq!(editor.selected_pos(), (1, 0));
+    }
+
+    #[test]
+    fn moving_selected_lines() {
+        let mut editor = editor("one\ntwo\nthree\n");
+        editor.set_mode(Mode::Visual);
+        editor.move_down();
+        editor.move_line_down();
+        assert_eq!(editor.text(), "three\none\ntwo\n");
+        assert_eq!(editor.mode, Mode::Visual);
+        assert_eq!(editor.text().slice(editor.selection().unwrap()), "one\nt");
+        // The empty line after the trailing newline isn't a line to move past.
+        editor.move_line_down();
+        assert_eq!(editor.text(), "three\none\ntwo\n");
+        editor.move_line_up();
+        assert_eq!(editor.text(), "one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn modified_buffers() {
+        let file = TempFile::new("modified");
+        let fname = file.path();
+        let mut editor = editor("one");
+        editor.buffers.get_mut(&0).unwrap().file = Some(fname.to_owned());
+        assert!(!editor.is_modified());
+
+        editor.set_mode(Mode::Insert);
+        editor.push('!');
+        assert!(editor.is_modified());
+        assert!(editor.any_modified());
+        editor.write(false).unwrap();
+        assert!(!editor.is_modified());
+
+        type_text(&mut editor, "?");
+        assert!(editor.is_modified());
+        editor.undo().unwrap();
+        assert!(!editor.is_modified());
+        editor.undo().unwrap();
+        assert!(editor.is_modified());
+        editor.redo().unwrap();
+        assert!(!editor.is_modified());
+    }
+
+    #[test]
+    fn reloading() {
+        let file = TempFile::new("reloading");
+        let fname = file.path();
+        std::fs::write(fname, "one\ntwo\nthree\n").unwrap();
+        let mut editor = Editor::open(fname).unwrap();
+        editor.go_to_line(2);
+        editor.move_right();
+        type_text(&mut editor, "x");
+        assert!(editor.is_modified());
+
+        std::fs::write(fname, "new\n").unwrap();
+        editor.execute(Command::Reload).unwrap();
+        assert_eq!(editor.text(), "new\n");
+        assert!(!editor.is_modified());
+        assert_eq!(editor.selected_pos(), (0, 1));
+
+        std::fs::remove_file(fname).unwrap();
+        assert!(editor.execute(Command::Reload).is_err());
+        assert_eq!(editor.text(), "new\n");
+    }
+
+    #[test]
+    fn showing_diffs() {
+        let file = TempFile::new("diff");
+        let fname = file.path();
+        std::fs::write(fname, "one\ntwo\nthree\n").unwrap();
+        let mut editor = Editor::open(fname).unwrap();
+        editor.execute(Command::Diff).unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert!(editor.take_output().is_some());
+
+        editor.go_to_line(1);
+        type_text(&mut editor, "2");
+        editor.execute(Command::Diff).unwrap();
+        let list = editor.list().unwrap();
+        assert_eq!(list.items, ["  one", "- two", "+ 2two", "  three"]);
+        assert_eq!(
+            list.changes(),
+            [Change::Same, Change::Removed, Change::Added, Change::Same]
+        );
+        editor.move_in_list(false);
+        editor.move_in_list(false);
+        editor.move_in_list(false);
+        editor.pick_from_list().unwrap();
+        assert_eq!(editor.selected_pos(), (0, 2));
+    }
+
+    #[test]
+    fn autowriting() {
+        let file = TempFile::new("autowrite");
+        let fname = file.path();
+        let mut editor = editor("one");
+        editor.buffers.get_mut(&0).unwrap().file = Some(fname.to_owned());
+        editor.add_buffer(Buffer::empty());
+        editor.settings.autowrite = true;
+
+        type_text(&mut editor, "1");
+        editor.next_buffer().unwrap();
+        assert_eq!(editor.selected_buf, 1);
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "1one");
+        assert!(!editor.buffers[&0].modified);
+
+        // The scratch buffer has no file to be written to.
+        type_text(&mut editor, "scratch");
+        editor.prev_buffer().unwrap();
+        assert_eq!(editor.selected_buf, 0);
+        assert_eq!(editor.selected_pos(), (1, 0));
+        assert!(editor.any_modified());
+        editor.autowrite_all().unwrap();
+        assert!(editor.buffers[&1].modified);
+
+        editor.settings.autowrite = false;
+        type_text(&mut editor, "2");
+        editor.next_buffer().unwrap();
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "1one");
+    }
+
+    #[test]
+    fn noticing_changes_on_disk() {
+        let file = TempFile::new("changed-on-disk");
+        let fname = file.path();
+        std::fs::write(fname, "one").unwrap();
+        let mut editor = Editor::open(fname).unwrap();
+        assert_eq!(editor.check_changed_on_disk(), None);
+
+        let file = std::fs::File::options().write(true).open(fname).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert!(editor
+            .check_changed_on_disk()
+            .unwrap()
+            .ends_with("has changed on disk since it was read"));
+        assert_eq!(editor.check_changed_on_disk(), None);
+
+        // Writing it ourselves isn't a change by something else.
+        editor.write(false).unwrap();
+        assert_eq!(editor.check_changed_on_disk(), None);
+    }
+
+    #[test]
+    fn firing_hooks() {
+        let file = TempFile::new("hooks");
+        let fname = file.path();
+        std::fs::write(fname, "one\ntwo").unwrap();
+        let mut editor = Editor::open(fname).unwrap();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        for kind in [
+            HookKind::BufOpen,
+            HookKind::BufWrite,
+            HookKind::ModeChange,
+            HookKind::CursorMove,
+        ] {
+            let events = events.clone();
+            editor.add_hook(kind, move |editor, event| {
+                events.lock().unwrap().push((event.clone(), editor.mode));
+            });
+        }
+        let take = || std::mem::take(&mut *events.lock().unwrap());
+
+        editor.set_mode(Mode::Insert);
+        editor.set_mode(Mode::Insert);
+        assert_eq!(
+            take(),
+            [(
+                HookEvent::ModeChange {
+                    from: Mode::Normal,
+                    to: Mode::Insert
+                },
+                Mode::Insert
+            )]
+        );
+
+        editor.push('x');
+        editor.set_mode(Mode::Normal);
+        take();
+        editor.write(false).unwrap();
+        assert_eq!(
+            take(),
+            [(
+                HookEvent::BufWrite {
+                    fname: fname.to_owned()
+                },
+                Mode::Normal
+            )]
+        );
+
+        editor.move_down();
+        editor.move_down();
+        assert_eq!(
+            take(),
+            [(
+                HookEvent::CursorMove {
+                    from: (1, 0),
+                    to: (1, 1)
+                },
+                Mode::Normal
+            )]
+        );
+
+        let other = TempFile::new("hooks-other");
+        std::fs::write(other.path(), "three").unwrap();
+        editor.go_to_location(other.path(), (0, 0)).unwrap();
+        assert_eq!(
+            take()[0],
+            (
+                HookEvent::BufOpen {
+                    fname: other.path().to_owned()
+                },
+                Mode::Normal
+            )
+        );
+    }
+
+    #[test]
+    fn listing_maps() {
+        let mut editor = editor("");
+        let mut run = |command: &str| {
+            editor.execute(command.parse().unwrap()).unwrap();
+            editor.take_output().unwrap_or_default()
+        };
+        run("noremap ,w :w<CR>");
+        assert!(run("map").contains(",w           * :w<CR>"));
+        assert_eq!(
+            run("verbose map ,w"),
+            ",w           * :w<CR>\n\tLast set from :map"
+        );
+        assert_eq!(
+            run("verbose map q"),
+            "No mapping found\nq is built in as nothing"
+        );
+        assert_eq!(
+            run("verbose map gd"),
+            "No mapping found\ngd is built in as GoToDefinition"
+        );
+    }
+
+    #[test]
+    fn running_autocmds() {
+        let markdown = TempFile::new("autocmd.md");
+        let markdown = markdown.path();
+        let rust = TempFile::new("autocmd.rs");
+        let rust = rust.path();
+        let mut editor = editor("text");
+        editor.settings.filter_limit = 0;
+        for command in [
+            "autocmd BufWrite *.md set filterlimit=1",
+            "autocmd BufWrite *.md set filterlimit=2",
+        ] {
+            editor.execute(command.parse().unwrap()).unwrap();
+        }
+        editor.write_as(rust, false).unwrap();
+        assert_eq!(editor.settings.filter_limit, 0);
+
+        // Both run, in the order they were added.
+        editor.write_as(markdown, false).unwrap();
+        assert_eq!(editor.settings.filter_limit, 2);
+
+        editor
+            .execute("autocmd BufWrite * frobnicate".parse().unwrap())
+            .unwrap();
+        editor.write(false).unwrap();
+        assert!(editor.take_output().unwrap().contains("frobnicate"));
+    }
+
+    #[test]
+    fn protecting_changed_files() {
+        let file = TempFile::new("overwrite");
+        let fname = file.path();
+        std::fs::write(fname, "one").unwrap();
+        let mut editor = Editor::open(fname).unwrap();
+        let touch = || {
+            let file = std::fs::File::options().write(true).open(fname).unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(60))
+                .unwrap();
+        };
+
+        // Touching the file without changing it doesn't count.
+        touch();
+        editor.write(false).unwrap();
+
+        type_text(&mut editor, "mine ");
+        std::fs::write(fname, "theirs").unwrap();
+        touch();
+        let err = editor.execute("w".parse().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("add ! to override"));
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "theirs");
+        assert!(editor.is_modified());
+        // Autowriting doesn't clobber it either.
+        editor.settings.autowrite = true;
+        assert!(editor.autowrite_all().is_err());
+
+        editor.execute("w!".parse().unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "mine one");
+        assert!(!editor.is_modified());
+
+        // Writing to another file which already exists needs ! as well.
+        let mut other = self::editor("other");
+        assert!(other.write_as(fname, false).is_err());
+        assert_eq!(other.active_fname(), None);
+        other.write_as(fname, true).unwrap();
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "other");
+    }
+
+    #[test]
+    fn splitting_windows() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.move_down();
+        editor.execute("split".parse().unwrap()).unwrap();
+        assert_eq!(editor.layout().windows().len(), 2);
+        assert_eq!(editor.layout().focused(), 0);
+        editor.move_down();
+        editor.next_window();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.next_window();
+        assert_eq!(editor.selected_pos(), (0, 2));
+
+        // Each window keeps to its own buffer.
+        editor.new_buffer().unwrap();
+        type_text(&mut editor, "new");
+        editor.focus_window(1);
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+        assert_eq!(editor.layout().windows()[0].buffer, 1);
+        assert_eq!(editor.buffer_text(1).unwrap(), "new");
+
+        // Closing a window keeps its buffer, and closing a buffer replaces it in every window.
+        editor.focus_window(0);
+        editor.execute("close".parse().unwrap()).unwrap();
+        assert_eq!(editor.layout().windows().len(), 1);
+        assert_eq!(editor.layout().focused(), 0);
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+        assert_eq!(editor.selected_pos(), (0, 1));
+        assert!(editor.buffer_text(1).is_some());
+        assert!(editor.close_window().is_err());
+        editor.split_window();
+        editor.close_buffer(0).unwrap();
+        assert!(editor
+            .layout()
+            .windows()
+            .iter()
+            .all(|window| window.buffer == 1));
+    }
+
+    #[test]
+    fn keeping_only_one_window() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.split_window();
+        editor.new_buffer().unwrap();
+        type_text(&mut editor, "new");
+        editor.split_window();
+        editor.focus_window(1);
+        assert_eq!(editor.layout().windows().len(), 3);
+        assert_eq!(editor.text(), "new");
+
+        editor.execute("only".parse().unwrap()).unwrap();
+        assert_eq!(editor.layout().windows().len(), 1);
+        assert_eq!(editor.layout().focused(), 0);
+        assert_eq!(editor.layout().weights(), [1]);
+        assert_eq!(editor.layout().windows()[0].buffer, 1);
+        assert_eq!(editor.text(), "new");
+        // The buffers of the closed windows stay open.
+        assert_eq!(editor.buffer_text(0).unwrap(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn quitting_windows() {
+        let mut editor = editor("one");
+        editor.split_window();
+        type_text(&mut editor, "1");
+        assert!(!editor.quit_window().unwrap());
+        assert_eq!(editor.layout().windows().len(), 1);
+        assert!(editor.quit_window().is_err());
+        editor.undo().unwrap();
+        assert!(editor.quit_window().unwrap());
+    }
+
+    #[test]
+    fn quit_commands() {
+        let mut editor = editor("one");
+        editor.split_window();
+        type_text(&mut editor, "1");
+        editor.execute("q".parse().unwrap()).unwrap();
+        assert_eq!(editor.layout().windows().len(), 1);
+        assert!(!editor.quitting());
+        assert!(editor.execute("q".parse().unwrap()).is_err());
+        assert!(!editor.quitting());
+        editor.execute("q!".parse().unwrap()).unwrap();
+        assert!(editor.quitting());
+
+        // `:wq` writes before quitting.
+        let file = TempFile::new("wq");
+        let fname = file.path();
+        std::fs::write(fname, "one").unwrap();
+        let mut editor = Editor::open(fname).unwrap();
+        type_text(&mut editor, "1");
+        editor.execute("wq".parse().unwrap()).unwrap();
+        assert!(editor.quitting());
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "1one");
+    }
+
+    #[test]
+    fn new_buffers() {
+        let file = TempFile::new("enew");
+        let fname = file.path();
+        let mut editor = editor("one");
+        editor.execute(Command::NewBuffer).unwrap();
+        assert_eq!(editor.selected_buf, 1);
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.active_fname(), None);
+
+        type_text(&mut editor, "notes");
+        // It has no file to be written to until it is given one.
+        let err = editor.execute("w".parse().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "No file name");
+        assert!(editor.is_modified());
+        editor
+            .execute(format!("w {fname}").parse().unwrap())
+            .unwrap();
+        assert_eq!(editor.active_fname(), Some(fname));
+        assert_eq!(std::fs::read_to_string(fname).unwrap(), "notes");
+        editor.prev_buffer().unwrap();
+        assert_eq!(editor.text(), "one");
+    }
+
+    #[test]
+    fn closing_buffers() {
+        let mut editor = editor("zero");
+        editor.new_buffer().unwrap();
+        editor.new_buffer().unwrap();
+        assert_eq!(editor.selected_buf, 2);
+        editor.close_buffer(2).unwrap();
+        // The last buffer was closed, so the one before it is selected.
+        assert_eq!(editor.selected_buf, 1);
+        editor.new_buffer().unwrap();
+        assert_eq!(editor.selected_buf, 3);
+
+        editor.prev_buffer().unwrap();
+        editor.close_buffer(1).unwrap();
+        assert_eq!(editor.selected_buf, 3);
+        assert!(editor.close_buffer(1).is_err());
+        editor.close_buffer(0).unwrap();
+        assert_eq!(editor.selected_buf, 3);
+
+        editor.close_buffer(3).unwrap();
+        assert_eq!(editor.selected_buf, 4);
+        assert_eq!(editor.buffers.len(), 1);
+        assert_eq!(editor.text(), "");
+    }
+
+    #[test]
+    fn deleting_buffers() {
+        let bd = |force| Command::DeleteBuffer { force };
+        let mut editor = editor("zero");
+        editor.new_buffer().unwrap();
+        editor.new_buffer().unwrap();
+        editor.prev_buffer().unwrap();
+        editor.execute(bd(false)).unwrap();
+        assert_eq!(editor.selected_buf, 2);
+        editor.execute(bd(false)).unwrap();
+        assert_eq!(editor.selected_buf, 0);
+
+        type_text(&mut editor, "changed ");
+        let err = editor.execute(bd(false)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No write since last change for buffer 0 (add ! to override)"
+        );
+        assert_eq!(editor.text(), "changed zero");
+        // Closing the last buffer leaves a scratch buffer in its place.
+        editor.execute(bd(true)).unwrap();
+        assert_eq!(editor.buffers.len(), 1);
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.active_fname(), None);
+    }
+
+    #[test]
+    fn auto_indenting() {
+        let mut editor = editor("\t  one");
+        editor.settings.autoindent = true;
+        editor.set_mode(Mode::Insert);
+        editor.selected_pos = (6, 0);
+        editor.newline();
+        editor.push('x');
+        assert_eq!(editor.text(), "\t  one\n\t  x");
+
+        // Pasted lines already have their indentation.
+        let mut detector = PasteDetector::default();
+        let now = Instant::now();
+        for i in 0..config::PASTE_BURST_KEYS as u32 {
+            editor.set_pasting(detector.key(now + Duration::from_millis(1) * i));
+        }
+        editor.newline();
+        editor.push('y');
+        assert_eq!(editor.text(), "\t  one\n\t  x\ny");
+
+        editor.set_pasting(detector.key(now + Duration::from_secs(1)));
+        editor.selected_pos = (4, 1);
+        editor.newline();
+        assert_eq!(editor.selected_pos(), (3, 2));
+    }
+
+    #[test]
+    fn appending() {
+        let mut editor = editor("ab\n\nce\u{301}");
+        editor.append();
+        assert_eq!(editor.mode, Mode::Insert);
+        assert_eq!(editor.selected_pos(), (1, 0));
+        editor.set_mode(Mode::Normal);
+
+        // `a` on the last char goes past it, where only insert mode can be.
+        editor.selected_pos = (1, 0);
+        editor.append();
+        type_text(&mut editor, "!");
+        assert_eq!(editor.text(), "ab!\n\nce\u{301}");
+        editor.set_mode(Mode::Normal);
+
+        editor.move_down();
+        editor.append();
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.set_mode(Mode::Normal);
+
+        // The accent goes with the letter before it.
+        editor.selected_pos = (1, 2);
+        editor.append();
+        assert_eq!(editor.selected_pos(), (3, 2));
+        editor.set_mode(Mode::Normal);
+
+        editor.selected_pos = (0, 0);
+        editor.append_at_line_end();
+        assert_eq!(editor.selected_pos(), (3, 0));
+        editor.move_down();
+        editor.set_mode(Mode::Normal);
+        editor.append_at_line_end();
+        assert_eq!(editor.selected_pos(), (0, 1));
+    }
+
+    #[test]
+    fn inserting_at_the_first_non_blank() {
+        let mut editor = editor("\t  x = 1;\n  \n");
+        editor.move_to_line_end();
+        editor.insert_at_first_non_blank();
+        assert_eq!(editor.mode, Mode::Insert);
+        type_text(&mut editor, "let ");
+        assert_eq!(editor.text(), "\t  let x = 1;\n  \n");
+        editor.set_mode(Mode::Normal);
+
+        // A line of only spaces is inserted in to at the start.
+        editor.move_down();
+        editor.move_to_line_end();
+        editor.insert_at_first_non_blank();
+        assert_eq!(editor.selected_pos(), (0, 1));
+    }
+
+    #[test]
+    fn opening_lines() {
+        let mut editor = editor("one\n  two");
+        editor.open_above();
+        assert_eq!(editor.mode, Mode::Insert);
+        assert_eq!(editor.selected_pos(), (0, 0));
+        type_text(&mut editor, "zero");
+        assert_eq!(editor.text(), "zero\none\n  two");
+
+        editor.set_mode(Mode::Normal);
+        editor.go_to_line(2);
+        editor.open_below();
+        assert_eq!(editor.selected_pos(), (0, 3));
+        type_text(&mut editor, "three");
+        assert_eq!(editor.text(), "zero\none\n  two\nthree");
+        editor.set_mode(Mode::Normal);
+        editor.undo().unwrap();
+        assert_eq!(editor.text(), "zero\none\n  two");
+
+        // The new lines take the indentation of the cursor's line.
+        editor.settings.autoindent = true;
+        editor.go_to_line(2);
+        editor.open_below();
+        type_text(&mut editor, "x");
+        editor.set_mode(Mode::Normal);
+        editor.open_above();
+        type_text(&mut editor, "y");
+        assert_eq!(editor.text(), "zero\none\n  two\n  y\n  x");
+    }
+
+    #[test]
+    fn listing_jumps() {
+        let mut editor = editor("one\ntwo one\nthree\n\none more");
+        editor.search_word_under_cursor(false).unwrap();
+        editor.search_next().unwrap();
+        editor.search_next().unwrap();
+        assert_eq!(editor.selected_pos(), (0, 0));
+
+        editor.execute(Command::Jumps).unwrap();
+        assert_eq!(editor.mode, Mode::List);
+        let list = editor.list().unwrap();
+        assert_eq!(
+            list.items,
+            [
+                "   2     1    0 one",
+                "   1     2    4 two one",
+                ">  0     5    0 one more",
+            ]
+        );
+        assert_eq!(list.selected, 2);
+
+        editor.move_in_list(true);
+        editor.move_in_list(true);
+        editor.move_in_list(true);
+        editor.move_in_list(false);
+        editor.pick_from_list().unwrap();
+        assert_eq!(editor.mode, Mode::Normal);
+        assert!(editor.list().is_none());
+        assert_eq!(editor.selected_pos(), (4, 1));
+    }
+
+    #[test]
+    fn listing_changes() {
+        let mut editor = editor("one\ntwo\nthree");
+        assert!(editor.show_changes().is_err());
+        type_text(&mut editor, "1");
+        editor.move_down();
+        editor.move_down();
+        type_text(&mut editor, "3");
+        editor.execute(Command::Changes).unwrap();
+        assert_eq!(
+            editor.list().unwrap().items,
+            ["   1     1    1 1one", ">  0     3    2 t3hree"]
+        );
+        editor.move_in_list(true);
+        editor.pick_from_list().unwrap();
+        assert_eq!(editor.selected_pos(), (1, 0));
+
+        // Leaving the list without picking anything stays put.
+        editor.show_changes().unwrap();
+        editor.set_mode(Mode::Normal);
+        assert!(editor.list().is_none());
+        assert_eq!(editor.selected_pos(), (1, 0));
+    }
+
+    #[test]
+    fn read_only_buffers() {
+        let mut editor = Editor::from_reader("one\ntwo\n".as_bytes()).unwrap();
+        assert_eq!(editor.text(), "one\ntwo\n");
+        editor.go_to_line(7);
+        assert_eq!(editor.selected_pos(), (0, 1));
+        editor.settings.readonly = true;
+        assert!(editor.write(false).is_err());
+    }
+
+    #[test]
+    fn warning_about_mixed_indentation() {
+        let mut editor = editor("fn a() {\n\tone\n    two\n}");
+        assert_eq!(editor.mixed_indentation(), Some(2));
+        assert_eq!(
+            editor.indentation_warning().unwrap(),
+            "Mixed tabs and spaces in indentation, first at line 3"
+        );
+        editor
+            .execute(Command::Set(vec!["nomixedindent".to_owned()]))
+            .unwrap();
+        assert_eq!(editor.mixed_indentation(), None);
+        editor
+            .execute(Command::Set(vec!["mixedindent".to_owned()]))
+            .unwrap();
+
+        // Edits are checked once they are made.
+        let mut editor = self::editor("\tone\n");
+        assert_eq!(editor.mixed_indentation(), None);
+        editor.move_down();
+        type_text(&mut editor, "  two");
+        assert_eq!(editor.mixed_indentation(), Some(1));
+        editor.undo().unwrap();
+        assert_eq!(editor.mixed_indentation(), None);
+    }
+
+    #[test]
+    fn running_commands() {
+        let mut editor = editor("");
+        editor.set_dictionary(Dictionary::default());
+        editor.set_mode(Mode::Command);
+        for c in "set spelx".chars() {
+            editor.command_push(c);
+        }
+        editor.command_backspace();
+        editor.command_push('l');
+        assert_eq!(editor.command_line(), "set spell");
+        editor.run_command_line().unwrap();
+        assert!(editor.settings.spell);
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.command_line(), "");
+
+        editor.set_mode(Mode::Command);
+        editor.command_push('x');
+        assert!(editor.run_command_line().is_err());
+        assert_eq!(editor.mode, Mode::Normal);
+
+        // Backspacing an empty command line leaves it.
+        editor.set_mode(Mode::Command);
+        editor.command_push('x');
+        editor.command_backspace();
+        assert_eq!(editor.mode, Mode::Command);
+        editor.command_backspace();
+        assert_eq!(editor.mode, Mode::Normal);
+    }
 }
@@ -0,0 +1,109 @@
+//! Registers hold text which has been deleted or yanked so it can be put back later.
+//!
+//! Like vim, registers are named `a` to `z` and `0` to `9`. Using an uppercase name adds on to
+//! the end of the lowercase register rather than replacing it. The unnamed register `"` always
+//! holds whatever was last put in any register.
+
+use std::collections::HashMap;
+
+/// The name of the register used when no other is chosen.
+pub const UNNAMED: char = '"';
+
+/// The contents of a single register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    /// The text in the register.
+    pub text: String,
+    /// Whether the text is made of whole lines, so it is put back on lines of its own.
+    pub linewise: bool,
+}
+
+/// Every register which has something in it.
+#[derive(Debug, Clone, Default)]
+pub struct Registers {
+    /// The registers, by name.
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    /// Get the register called `name`, if anything has been put in it.
+    ///
+    /// Uppercase names get the same register as their lowercase versions.
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name.to_ascii_lowercase())
+    }
+
+    /// Put `register` in the register called `name`, as well as the unnamed register.
+    ///
+    /// If `name` is uppercase, `register` is added to the end of the lowercase register instead.
+    /// Adding whole lines to a register makes all of it whole lines.
+    pub fn store(&mut self, name: char, register: Register) {
+        let register = match self.registers.get_mut(&name.to_ascii_lowercase()) {
+            Some(existing) if name.is_ascii_uppercase() => {
+                if register.linewise && !existing.linewise && !existing.text.ends_with('\n') {
+                    existing.text.push('\n');
+                }
+                existing.text.push_str(&register.text);
+                existing.linewise |= register.linewise;
+                existing.clone()
+            }
+            _ => {
+                self.registers
+                    .insert(name.to_ascii_lowercase(), register.clone());
+                register
+            }
+        };
+        self.registers.insert(UNNAMED, register);
+    }
+
+    /// List the contents of every register which has something in it, like vim's `:registers`.
+    ///
+    /// Newlines are shown as `^J` so each register fits on one line.
+    pub fn list(&self) -> String {
+        let mut names: Vec<char> = self.registers.keys().copied().collect();
+        // The unnamed register comes first.
+        names.sort_by_key(|&name| (name != UNNAMED, name));
+        let mut listing = String::from("--- Registers ---");
+        for name in names {
+            let contents = self.registers[&name].text.replace('\n', "^J");
+            listing.push_str(&format!("\n\"{name}   {contents}"));
+        }
+        listing
+    }
+}
+
+/// Whether there is a register called `name`.
+pub fn is_valid_name(name: char) -> bool {
+    name.is_ascii_alphanumeric() || name == UNNAMED
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A register holding `text`.
+    fn register(text: &str, linewise: bool) -> Register {
+        Register {
+            text: text.to_owned(),
+            linewise,
+        }
+    }
+
+    #[test]
+    fn appending() {
+        let mut registers = Registers::default();
+        registers.store('a', register("one", false));
+        registers.store('A', register(" two", false));
+        assert_eq!(registers.get('a'), Some(&register("one two", false)));
+        registers.store('A', register("three\n", true));
+        assert_eq!(
+            registers.get('a'),
+            Some(&register("one two\nthree\n", true))
+        );
+        assert_eq!(registers.get(UNNAMED), registers.get('a'));
+
+        // Appending to an empty register just fills it.
+        registers.store('B', register("four", false));
+        assert_eq!(registers.get('b'), Some(&register("four", false)));
+    }
+}
@@ -0,0 +1,191 @@
+//! Searching through a buffer for some text.
+
+use ropey::RopeSlice;
+use std::ops::Range;
+
+/// What to search for and which way to go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    /// The text being searched for.
+    pub query: String,
+    /// Whether the query only matches whole words, like vim's `\<word\>`.
+    pub whole_word: bool,
+    /// Whether the search goes towards the start of the buffer.
+    pub backward: bool,
+}
+
+impl Search {
+    /// Find the char ranges of every match of the search in `text`.
+    pub fn matches(&self, text: RopeSlice) -> Vec<Range<usize>> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+        let string = text.to_string();
+        let len = self.query.chars().count();
+        string
+            .match_indices(&self.query)
+            .map(|(byte, _)| {
+                let start = text.byte_to_char(byte);
+                start..start + len
+            })
+            .filter(|range| !self.whole_word || is_whole_word(text, range))
+            .collect()
+    }
+
+    /// Find the first match after `cursor` in the direction of the search, wrapping around the
+    /// ends of `text`.
+    ///
+    /// If `reverse` is set, the search goes the other way.
+    pub fn next(&self, text: RopeSlice, cursor: usize, reverse: bool) -> Option<Range<usize>> {
+        let matches = self.matches(text);
+        if self.backward != reverse {
+            matches
+                .iter()
+                .rev()
+                .find(|range| range.start < cursor)
+                .or(matches.last())
+                .cloned()
+        } else {
+            matches
+                .iter()
+                .find(|range| range.start > cursor)
+                .or(matches.first())
+                .cloned()
+        }
+    }
+}
+
+/// Whether `c` can be part of a word, like vim's default `iskeyword`.
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Find the word made of [`is_word_char`] chars which `idx` is in.
+pub fn word_at(text: RopeSlice, idx: usize) -> Option<Range<usize>> {
+    if !text.get_char(idx).is_some_and(is_word_char) {
+        return None;
+    }
+    let start = idx
+        - text
+            .chars_at(idx)
+            .reversed()
+            .take_while(|&c| is_word_char(c))
+            .count();
+    let end = idx + text.chars_at(idx).take_while(|&c| is_word_char(c)).count();
+    Some(start..end)
+}
+
+/// The char index of the end of the word after `idx` in `text`, like vim's `e`, or `idx` itself
+/// if there are no more words.
+///
+/// A word is a run of [`is_word_char`] chars, or a run of other chars which aren't whitespace,
+/// so `foo.bar` has three words. If `idx` is already at the end of a word, the end of the next
+/// one is found, even if that is on a later line.
+pub fn word_end(text: RopeSlice, idx: usize) -> usize {
+    let class = |c: char| (c.is_whitespace(), is_word_char(c));
+    let len = text.len_chars();
+    let mut end = idx + 1;
+    while end < len && text.char(end).is_whitespace() {
+        end += 1;
+    }
+    if end >= len {
+        return idx;
+    }
+    let kind = class(text.char(end));
+    while end + 1 < len && class(text.char(end + 1)) == kind {
+        end += 1;
+    }
+    end
+}
+
+/// The char index of the start of the word after `idx` in `text`, like vim's `w`, or `idx`
+/// itself if there are no more words.
+///
+/// Words are the same as for [`word_end`], and blank lines are crossed to get to the next one.
+pub fn word_start(text: RopeSlice, idx: usize) -> usize {
+    let class = |c: char| (c.is_whitespace(), is_word_char(c));
+    let len = text.len_chars();
+    let Some(first) = text.get_char(idx) else {
+        return idx;
+    };
+    let mut start = idx;
+    while start < len && class(text.char(start)) == class(first) && !first.is_whitespace() {
+        start += 1;
+    }
+    while start < len && text.char(start).is_whitespace() {
+        start += 1;
+    }
+    if start >= len {
+        return idx;
+    }
+    start
+}
+
+/// Whether `range` of `text` isn't part of a larger word.
+fn is_whole_word(text: RopeSlice, range: &Range<usize>) -> bool {
+    let before = range.start.checked_sub(1).and_then(|i| text.get_char(i));
+    let after = text.get_char(range.end);
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ropey::Rope;
+
+    #[test]
+    fn finding_word_ends() {
+        let text = Rope::from_str("foo.bar  baz\n  \n(qux)");
+        let text = text.slice(..);
+        assert_eq!(word_end(text, 0), 2);
+        // The punctuation is a word of its own.
+        assert_eq!(word_end(text, 2), 3);
+        assert_eq!(word_end(text, 3), 6);
+        assert_eq!(word_end(text, 6), 11);
+        // Blank lines are crossed to get to the next word.
+        assert_eq!(word_end(text, 11), 16);
+        assert_eq!(word_end(text, 16), 19);
+        assert_eq!(word_end(text, 19), 20);
+        assert_eq!(word_end(text, 20), 20);
+    }
+
+    #[test]
+    fn finding_word_starts() {
+        let text = Rope::from_str("foo.bar  baz\n  \n(qux)");
+        let text = text.slice(..);
+        assert_eq!(word_start(text, 0), 3);
+        assert_eq!(word_start(text, 1), 3);
+        assert_eq!(word_start(text, 3), 4);
+        assert_eq!(word_start(text, 4), 9);
+        // Blank lines are crossed to get to the next word.
+        assert_eq!(word_start(text, 9), 16);
+        assert_eq!(word_start(text, 12), 16);
+        assert_eq!(word_start(text, 16), 17);
+        assert_eq!(word_start(text, 17), 20);
+        assert_eq!(word_start(text, 20), 20);
+    }
+
+    #[test]
+    fn finding_words() {
+        let text = Rope::from_str("let foo_bar = 1;");
+        assert_eq!(word_at(text.slice(..), 6), Some(4..11));
+        assert_eq!(word_at(text.slice(..), 4), Some(4..11));
+        assert_eq!(word_at(text.slice(..), 11), None);
+    }
+
+    #[test]
+    fn whole_word_matches() {
+        let text = Rope::from_str("cat concat cat_ cat");
+        let mut search = Search {
+            query: "cat".to_owned(),
+            whole_word: false,
+            backward: false,
+        };
+        assert_eq!(search.matches(text.slice(..)).len(), 4);
+        search.whole_word = true;
+        assert_eq!(search.matches(text.slice(..)), vec![0..3, 16..19]);
+        assert_eq!(search.next(text.slice(..), 0, false), Some(16..19));
+        assert_eq!(search.next(text.slice(..), 16, false), Some(0..3));
+        assert_eq!(search.next(text.slice(..), 16, true), Some(0..3));
+    }
+}
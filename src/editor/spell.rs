@@ -0,0 +1,196 @@
+//! A very basic spell checker.
+//!
+//! Words are checked against a [`Dictionary`], which is just a list of known words. Anything not
+//! in the [`Dictionary`] is considered to be misspelled.
+//!
+//! Words can be added to (or explicitly marked as misspelled in) a user dictionary which is kept
+//! in its own file. The user dictionary has one word per line, with misspelled words marked by a
+//! trailing `/!`.
+
+use anyhow::Context;
+use ropey::RopeSlice;
+use std::{
+    collections::HashSet,
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// A set of correctly spelled words.
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    /// All the known words.
+    words: HashSet<String>,
+    /// Words which have been marked as misspelled.
+    ///
+    /// These are flagged even if they are also in `words`.
+    bad_words: HashSet<String>,
+    /// The user dictionary which added words are saved to.
+    user_file: Option<PathBuf>,
+}
+
+impl Dictionary {
+    /// Read a [`Dictionary`] from a file containing one word per line.
+    pub fn load(fname: &str) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(fname)
+            .with_context(|| format!("Reading dictionary `{fname}` failed."))?;
+        Ok(contents.lines().collect())
+    }
+
+    /// Use `path` as the user dictionary, reading in any words already saved to it.
+    ///
+    /// The file doesn't need to exist yet. It will be created when a word is first added.
+    pub fn with_user_file(mut self, path: PathBuf) -> anyhow::Result<Self> {
+        if path.exists() {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Reading user dictionary `{}` failed.", path.display()))?;
+            for line in contents.lines().map(str::trim) {
+                match line.strip_suffix("/!") {
+                    Some(word) => {
+                        self.words.remove(word);
+                        self.bad_words.insert(word.to_owned());
+                    }
+                    None if !line.is_empty() => {
+                        self.bad_words.remove(line);
+                        self.words.insert(line.to_owned());
+                    }
+                    None => {}
+                }
+            }
+        }
+        self.user_file = Some(path);
+        Ok(self)
+    }
+
+    /// Check whether `word` is spelled correctly.
+    ///
+    /// A word is also accepted if its lowercase form is known, so capitalized words at the start
+    /// of a sentence aren't flagged.
+    pub fn contains(&self, word: &str) -> bool {
+        !self.bad_words.contains(word)
+            && (self.words.contains(word) || self.words.contains(&word.to_lowercase()))
+    }
+
+    /// Add `word` as a correctly spelled word, saving it to the user dictionary.
+    pub fn add_good(&mut self, word: &str) -> anyhow::Result<()> {
+        self.bad_words.remove(word);
+        self.words.insert(word.to_owned());
+        self.save(word)
+    }
+
+    /// Mark `word` as misspelled, saving it to the user dictionary.
+    pub fn add_bad(&mut self, word: &str) -> anyhow::Result<()> {
+        self.words.remove(word);
+        self.bad_words.insert(word.to_owned());
+        self.save(&format!("{word}/!"))
+    }
+
+    /// Append `line` to the user dictionary, if there is one.
+    fn save(&self, line: &str) -> anyhow::Result<()> {
+        let Some(path) = &self.user_file else {
+            return Ok(());
+        };
+        append_line(path, line)
+            .with_context(|| format!("Writing to user dictionary `{}` failed.", path.display()))
+    }
+
+    /// Find the char ranges of every misspelled word in `text`.
+    pub fn misspellings(&self, text: RopeSlice) -> Vec<Range<usize>> {
+        words(text)
+            .filter(|range| !self.contains(&text.slice(range.clone()).to_string()))
+            .collect()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for Dictionary {
+    fn from_iter<T: IntoIterator<Item = &'a str>>(iter: T) -> Self {
+        Self {
+            words: iter
+                .into_iter()
+                .map(str::trim)
+                .filter(|word| !word.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Append `line` to the file at `path`, creating it (and its directory) if needed.
+fn append_line(path: &Path, line: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)?;
+    writeln!(file, "{line}")
+}
+
+/// Find the char ranges of all the words in `text`.
+///
+/// A word is a run of alphabetic characters. Apostrophes are allowed inside of a word so
+/// contractions like "don't" are kept together.
+pub fn words(text: RopeSlice<'_>) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut chars = text.chars().enumerate().peekable();
+    std::iter::from_fn(move || {
+        let (start, _) = chars.find(|(_, c)| c.is_alphabetic())?;
+        let mut end = start + 1;
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_alphabetic() {
+                end = i + 1;
+            } else if c != '\'' {
+                break;
+            }
+            chars.next();
+        }
+        Some(start..end)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempFile;
+    use ropey::Rope;
+
+    #[test]
+    fn finding_words() {
+        let text = Rope::from_str("Don't  panic, it's 42 o'clock'\nbye");
+        let words: Vec<_> = words(text.slice(..))
+            .map(|range| text.slice(range).to_string())
+            .collect();
+        assert_eq!(words, ["Don't", "panic", "it's", "o'clock", "bye"]);
+    }
+
+    #[test]
+    fn flags_unknown_words() {
+        let dictionary: Dictionary = ["the", "quick", "fox"].into_iter().collect();
+        let text = Rope::from_str("The quikc fox");
+        assert_eq!(dictionary.misspellings(text.slice(..)), vec![4..9]);
+    }
+
+    #[test]
+    fn user_dictionary() {
+        let file = TempFile::new("spell.add");
+
+        let mut dictionary = ["fox"]
+            .into_iter()
+            .collect::<Dictionary>()
+            .with_user_file(file.path().into())
+            .unwrap();
+        dictionary.add_good("quikc").unwrap();
+        dictionary.add_bad("fox").unwrap();
+        assert!(dictionary.contains("quikc"));
+        assert!(!dictionary.contains("fox"));
+
+        let reloaded = ["fox"]
+            .into_iter()
+            .collect::<Dictionary>()
+            .with_user_file(file.path().into())
+            .unwrap();
+        assert!(reloaded.contains("quikc"));
+        assert!(!reloaded.contains("fox"));
+    }
+}
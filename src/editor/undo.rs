@@ -0,0 +1,457 @@
+//! Undo history for a buffer.
+//!
+//! The history is kept as a tree of states, like vim's undo tree. Undoing moves to the parent of
+//! the current state and redoing moves back down to the child which was most recently visited.
+//! Making a change after undoing starts a new branch rather than throwing away the changes which
+//! were undone, and those can still be reached by stepping through the states in the order they
+//! were made with [`UndoTree::earlier`] and [`UndoTree::later`].
+//!
+//! The history of a file is saved to an undo file when it is written so it can be picked back up
+//! the next time the file is opened. The undo file holds the original text followed by the
+//! change each state made to its parent, and is only used if its newest state still matches
+//! what is in the file.
+
+use crate::config;
+use anyhow::{bail, Context};
+use ropey::Rope;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// The first line of every undo file.
+const HEADER: &str = "not-vim undo file";
+
+/// The state of a buffer after a single change.
+#[derive(Debug, Clone)]
+pub struct State {
+    /// The text of the buffer.
+    pub text: Rope,
+    /// Where the cursor was, in (x, y) format.
+    pub cursor: (usize, usize),
+    /// When the change was made.
+    pub time: SystemTime,
+    /// The state this one was changed from, or [`None`] for the original state.
+    parent: Option<usize>,
+    /// The child of this state which redoing moves to.
+    redo: Option<usize>,
+}
+
+/// The full history of changes made to a buffer.
+///
+/// States are numbered in the order they were made, starting with the original state at 0.
+#[derive(Debug, Clone)]
+pub struct UndoTree {
+    /// Every state the buffer has been in, in the order they were made.
+    states: Vec<State>,
+    /// The index of the state the buffer is currently in.
+    current: usize,
+}
+
+impl UndoTree {
+    /// Start the history of a buffer whose original contents are `text`.
+    pub fn new(text: Rope) -> Self {
+        Self {
+            states: vec![State {
+                text,
+                cursor: (0, 0),
+                time: SystemTime::now(),
+                parent: None,
+                redo: None,
+            }],
+            current: 0,
+        }
+    }
+
+    /// The state the buffer is currently in.
+    pub fn current(&self) -> &State {
+        &self.states[self.current]
+    }
+
+    /// The index of the state the buffer is currently in.
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    /// Record that the buffer was changed to `text` at `time`.
+    ///
+    /// Nothing is recorded if `text` is the same as the current state. Returns whether a new
+    /// state was recorded.
+    pub fn record(&mut self, text: Rope, cursor: (usize, usize), time: SystemTime) -> bool {
+        if text == self.current().text {
+            return false;
+        }
+        let new = self.states.len();
+        self.states.push(State {
+            text,
+            cursor,
+            time,
+            parent: Some(self.current),
+            redo: None,
+        });
+        self.states[self.current].redo = Some(new);
+        self.current = new;
+        true
+    }
+
+    /// Move back to the state the current one was changed from.
+    pub fn undo(&mut self) -> Option<&State> {
+        let parent = self.current().parent?;
+        Some(self.go_to(parent))
+    }
+
+    /// Move forward to the state most recently undone from the current one.
+    pub fn redo(&mut self) -> Option<&State> {
+        let child = self.current().redo?;
+        Some(self.go_to(child))
+    }
+
+    /// Move back `count` states in the order they were made, regardless of branches.
+    ///
+    /// Returns [`None`] if already at the original state.
+    pub fn earlier(&mut self, count: usize) -> Option<&State> {
+        if self.current == 0 {
+            return None;
+        }
+        Some(self.go_to(self.current.saturating_sub(count)))
+    }
+
+    /// Move forward `count` states in the order they were made, regardless of branches.
+    ///
+    /// Returns [`None`] if already at the newest state.
+    pub fn later(&mut self, count: usize) -> Option<&State> {
+        let newest = self.states.len() - 1;
+        if self.current == newest {
+            return None;
+        }
+        Some(self.go_to(self.current.saturating_add(count).min(newest)))
+    }
+
+    /// Move back to the state the buffer was in `duration` before the current state was made,
+    /// regardless of branches.
+    ///
+    /// This always moves back at least one state, and stops at the original state. Returns
+    /// [`None`] if already at the original state.
+    pub fn earlier_by(&mut self, duration: Duration) -> Option<&State> {
+        if self.current == 0 {
+            return None;
+        }
+        let time = self.current().time;
+        let target = time.checked_sub(duration).unwrap_or(SystemTime::UNIX_EPOCH);
+        let index = self.states[..self.current]
+            .iter()
+            .rposition(|state| state.time <= target)
+            .unwrap_or(0);
+        Some(self.go_to(index.min(self.current - 1)))
+    }
+
+    /// Move forward to the state the buffer was in `duration` after the current state was made,
+    /// regardless of branches.
+    ///
+    /// This always moves forward at least one state, and stops at the newest state. Returns
+    /// [`None`] if already at the newest state.
+    pub fn later_by(&mut self, duration: Duration) -> Option<&State> {
+        let newest = self.states.len() - 1;
+        if self.current == newest {
+            return None;
+        }
+        let time = self.current().time;
+        let index = match time.checked_add(duration) {
+            Some(target) => self
+                .states
+                .iter()
+                .rposition(|state| state.time <= target)
+                .unwrap_or(0),
+            None => newest,
+        };
+        Some(self.go_to(index.max(self.current + 1)))
+    }
+
+    /// Make `index` the current state.
+    ///
+    /// The states leading to it are updated so redoing from any of them follows the path to it.
+    fn go_to(&mut self, index: usize) -> &State {
+        let mut child = index;
+        while let Some(parent) = self.states[child].parent {
+            self.states[parent].redo = Some(child);
+            child = parent;
+        }
+        self.current = index;
+        self.current()
+    }
+    /// Save the history to the undo file at `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut out = format!("{HEADER}\n{} {}\n", self.current, self.states.len());
+        for state in &self.states {
+            let parent_text = state.parent.map(|parent| &self.states[parent].text);
+            let (start, removed, inserted) = diff(parent_text, &state.text);
+            let millis = state
+                .time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            out.push_str(&format!(
+                "{} {} {} {} {millis} {start} {removed} {}\n{inserted}\n",
+                index_to_str(state.parent),
+                index_to_str(state.redo),
+                state.cursor.0,
+                state.cursor.1,
+                inserted.chars().count(),
+            ));
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::File::create(path)?.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    /// Load the history saved to the undo file at `path`.
+    ///
+    /// Returns [`None`] if the history doesn't end up at `text`, which happens when the file was
+    /// changed without the undo file being updated.
+    pub fn load(path: &Path, text: &Rope) -> anyhow::Result<Option<Self>> {
+        let contents = std::fs::read_to_string(path)?;
+        let tree = parse(&contents).context("Malformed undo file")?;
+        Ok(Some(tree).filter(|tree| tree.current().text == *text))
+    }
+}
+
+/// The undo file the history of the file `fname` is saved to.
+///
+/// Undo files are named after the absolute path of the file with every `/` replaced by `%`, like
+/// vim's.
+pub fn file_for(fname: &str) -> Option<PathBuf> {
+    let path = std::fs::canonicalize(fname).ok()?;
+    let name = path.to_string_lossy().replace('/', "%");
+    Some(config::state_dir()?.join(config::UNDO_DIRECTORY).join(name))
+}
+
+/// Find the change made to `old` to get `new`.
+///
+/// The change is given as the char index it starts at, how many chars were removed and the text
+/// which was inserted in their place. No `old` text is treated as empty.
+fn diff(old: Option<&Rope>, new: &Rope) -> (usize, usize, String) {
+    let Some(old) = old else {
+        return (0, 0, new.to_string());
+    };
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let max_suffix = old.len_chars().min(new.len_chars()) - prefix;
+    let suffix = old
+        .chars_at(old.len_chars())
+        .reversed()
+        .zip(new.chars_at(new.len_chars()).reversed())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let inserted = new.slice(prefix..new.len_chars() - suffix).to_string();
+    (prefix, old.len_chars() - suffix - prefix, inserted)
+}
+
+/// Parse the contents of an undo file written by [`UndoTree::save`].
+fn parse(contents: &str) -> anyhow::Result<UndoTree> {
+    let Some(rest) = contents
+        .strip_prefix(HEADER)
+        .and_then(|rest| rest.strip_prefix('\n'))
+    else {
+        bail!("Missing header");
+    };
+    let (line, mut rest) = rest.split_once('\n').context("Missing state count")?;
+    let [current, count] = numbers(line)?;
+
+    // The count comes from the file, so it can't be trusted to allocate for.
+    let mut states: Vec<State> = Vec::new();
+    for _ in 0..count {
+        let (line, after) = rest.split_once('\n').context("Missing state")?;
+        let (parent, line) = line.split_once(' ').context("Missing parent")?;
+        let (redo, line) = line.split_once(' ').context("Missing redo")?;
+        let parent = str_to_index(parent)?;
+        let [x, y, millis, start, removed, inserted_len] = numbers(line)?;
+        let split = after
+            .char_indices()
+            .nth(inserted_len)
+            .map_or(after.len(), |(i, _)| i);
+        let (inserted, after) = after.split_at(split);
+        rest = after.strip_prefix('\n').context("Missing newline")?;
+
+        let mut text = match parent {
+            Some(parent) => states
+                .get(parent)
+                .context("States out of order")?
+                .text
+                .clone(),
+            None => Rope::new(),
+        };
+        let Some(end) = start
+            .checked_add(removed)
+            .filter(|&end| end <= text.len_chars())
+        else {
+            bail!("Change out of bounds");
+        };
+        let time = SystemTime::UNIX_EPOCH
+            .checked_add(Duration::from_millis(millis as u64))
+            .context("Time out of bounds")?;
+        text.remove(start..end);
+        text.insert(start, inserted);
+        states.push(State {
+            text,
+            cursor: (x, y),
+            time,
+            parent,
+            redo: str_to_index(redo)?,
+        });
+    }
+    if current >= states.len() || states.iter().any(|state| state.redo >= Some(states.len())) {
+        bail!("State out of bounds");
+    }
+    Ok(UndoTree { states, current })
+}
+
+/// Parse a line of `N` space-separated numbers.
+fn numbers<const N: usize>(line: &str) -> anyhow::Result<[usize; N]> {
+    let mut numbers = [0; N];
+    let mut words = line.split(' ');
+    for number in &mut numbers {
+        *number = words.next().context("Missing number")?.parse()?;
+    }
+    Ok(numbers)
+}
+
+/// Write an optional index to an undo file, using `-` for [`None`].
+fn index_to_str(index: Option<usize>) -> String {
+    index.map_or_else(|| "-".to_owned(), |index| index.to_string())
+}
+
+/// Read an optional index written by [`index_to_str`].
+fn str_to_index(s: &str) -> anyhow::Result<Option<usize>> {
+    Ok(match s {
+        "-" => None,
+        _ => Some(s.parse()?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempFile;
+
+    /// Record `text` in `tree` with a dummy cursor and time.
+    fn record(tree: &mut UndoTree, text: &str) {
+        tree.record(Rope::from_str(text), (0, 0), SystemTime::now());
+    }
+
+    /// The text of the current state of `tree`.
+    fn text(tree: &UndoTree) -> String {
+        tree.current().text.to_string()
+    }
+
+    #[test]
+    fn linear_undo() {
+        let mut tree = UndoTree::new(Rope::from_str("a"));
+        record(&mut tree, "ab");
+        record(&mut tree, "abc");
+        // Unchanged text isn't a new state.
+        assert!(!tree.record(Rope::from_str("abc"), (0, 0), SystemTime::now()));
+
+        assert_eq!(tree.undo().unwrap().text, "ab");
+        assert_eq!(tree.undo().unwrap().text, "a");
+        assert!(tree.undo().is_none());
+        assert_eq!(tree.redo().unwrap().text, "ab");
+        assert_eq!(tree.redo().unwrap().text, "abc");
+        assert!(tree.redo().is_none());
+    }
+
+    #[test]
+    fn recovering_abandoned_branches() {
+        let mut tree = UndoTree::new(Rope::from_str("one"));
+        record(&mut tree, "one two");
+        tree.undo();
+        record(&mut tree, "one three");
+        assert_eq!(text(&tree), "one three");
+
+        // Undo and redo stay on the newest branch.
+        tree.undo();
+        assert_eq!(tree.redo().unwrap().text, "one three");
+
+        // Stepping back through time reaches the abandoned branch.
+        assert_eq!(tree.earlier(1).unwrap().text, "one two");
+        assert_eq!(tree.earlier(1).unwrap().text, "one");
+        assert!(tree.earlier(1).is_none());
+        // Redoing now follows the branch which was last visited.
+        assert_eq!(tree.redo().unwrap().text, "one two");
+        assert_eq!(tree.later(5).unwrap().text, "one three");
+        assert!(tree.later(1).is_none());
+    }
+
+    #[test]
+    fn moving_through_time() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut tree = UndoTree::new(Rope::from_str(""));
+        tree.states[0].time = start;
+        tree.record(Rope::from_str("a"), (0, 0), at(10));
+        tree.record(Rope::from_str("ab"), (0, 0), at(70));
+        tree.record(Rope::from_str("abc"), (0, 0), at(100));
+
+        // A minute before the newest change, the first change had been made.
+        let minute = Duration::from_secs(60);
+        assert_eq!(tree.earlier_by(minute).unwrap().text, "a");
+        assert_eq!(tree.later_by(minute).unwrap().text, "ab");
+        // Going further than the history goes stops at either end.
+        assert_eq!(tree.earlier_by(minute * 60).unwrap().text, "");
+        assert!(tree.earlier_by(minute).is_none());
+        assert_eq!(tree.later_by(minute * 60).unwrap().text, "abc");
+        assert!(tree.later_by(minute).is_none());
+        // Something always changes, even if the step is too small to reach another state.
+        assert_eq!(tree.earlier_by(Duration::from_secs(1)).unwrap().text, "ab");
+    }
+
+    #[test]
+    fn saving_and_loading() {
+        let file = TempFile::new("saving.undo");
+        let path = Path::new(file.path());
+        let mut tree = UndoTree::new(Rope::from_str("one\n"));
+        record(&mut tree, "one two\n");
+        tree.undo();
+        record(&mut tree, "zero one\nthree é\n");
+        tree.save(path).unwrap();
+
+        let text = Rope::from_str("zero one\nthree é\n");
+        let mut loaded = UndoTree::load(path, &text).unwrap().unwrap();
+        assert_eq!(loaded.current().text, text);
+        assert_eq!(loaded.undo().unwrap().text, "one\n");
+        assert_eq!(loaded.redo().unwrap().text, text);
+        assert_eq!(loaded.earlier(1).unwrap().text, "one two\n");
+
+        // The file has changed since the undo file was saved.
+        let changed = Rope::from_str("something else\n");
+        assert!(UndoTree::load(path, &changed).unwrap().is_none());
+
+        // A corrupt file can't claim more states than it holds.
+        assert!(parse(&format!("{HEADER}\n0 {}\n", usize::MAX)).is_err());
+        // Nor changes which overflow the text.
+        let huge = usize::MAX;
+        assert!(parse(&format!("{HEADER}\n0 1\n- - 0 0 0 1 {huge} 0\n\n")).is_err());
+    }
+
+    #[test]
+    fn diffs() {
+        let old = Rope::from_str("abcabc");
+        assert_eq!(
+            diff(Some(&old), &Rope::from_str("abXbc")),
+            (2, 2, "X".to_owned())
+        );
+        assert_eq!(
+            diff(Some(&old), &Rope::from_str("abcabcabc")),
+            (6, 0, "abc".to_owned())
+        );
+        assert_eq!(diff(Some(&old), &Rope::from_str("")), (0, 6, String::new()));
+        assert_eq!(diff(None, &old), (0, 0, "abcabc".to_owned()));
+    }
+}
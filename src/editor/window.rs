@@ -0,0 +1,223 @@
+//! Windows are views onto buffers, several of which can be shown at once by splitting the screen.
+//!
+//! Like vim's `:split`, the windows are stacked one above the other. One of them is focused, and
+//! that is the one being edited. Each window has its own cursor, even when several show the same
+//! buffer.
+//!
+//! Each window has a weight, and the screen is shared out between the windows in proportion to
+//! their weights. The front end decides what that means in rows.
+
+use anyhow::bail;
+
+/// A view onto a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    /// A number identifying the window, which is never reused for another window.
+    pub id: usize,
+    /// The ID of the buffer shown in the window.
+    pub buffer: usize,
+    /// Where the cursor is in the window, as of the window last being focused.
+    pub cursor: (usize, usize),
+    /// How much of the screen the window gets, relative to the other windows.
+    pub weight: usize,
+}
+
+/// The windows on the screen, from top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// The windows, from top to bottom. There is always at least one.
+    windows: Vec<Window>,
+    /// The index of the focused window.
+    focused: usize,
+    /// The ID to give the next window.
+    next_id: usize,
+}
+
+impl Layout {
+    /// Create a layout with one window showing `buffer`.
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            windows: vec![Window {
+                id: 0,
+                buffer,
+                cursor: (0, 0),
+                weight: 1,
+            }],
+            focused: 0,
+            next_id: 1,
+        }
+    }
+
+    /// The windows, from top to bottom.
+    pub fn windows(&self) -> &[Window] {
+        &self.windows
+    }
+
+    /// The index of the focused window in [`Layout::windows`].
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    /// The focused window.
+    pub fn focused_window(&mut self) -> &mut Window {
+        &mut self.windows[self.focused]
+    }
+
+    /// Split the focused window in two, with the new half above it showing the same place in the
+    /// same buffer. The new window is focused.
+    ///
+    /// The two halves share the weight the focused window had.
+    pub fn split(&mut self) {
+        // Doubling the other weights keeps them whole while the focused one is halved.
+        for (i, window) in self.windows.iter_mut().enumerate() {
+            if i != self.focused {
+                window.weight *= 2;
+            }
+        }
+        let window = Window {
+            id: self.next_id,
+            ..self.windows[self.focused]
+        };
+        self.next_id += 1;
+        self.windows.insert(self.focused, window);
+        self.simplify_weights();
+    }
+
+    /// Close the focused window, focusing the one below it, or the one above if it was at the
+    /// bottom.
+    ///
+    /// The last window can't be closed.
+    pub fn close(&mut self) -> anyhow::Result<()> {
+        if self.windows.len() == 1 {
+            bail!("Cannot close last window");
+        }
+        let closed = self.windows.remove(self.focused);
+        self.focused = self.focused.min(self.windows.len() - 1);
+        self.windows[self.focused].weight += closed.weight;
+        self.simplify_weights();
+        Ok(())
+    }
+
+    /// Close every window but the focused one, which takes up the whole screen, like vim's
+    /// `:only`.
+    pub fn only(&mut self) {
+        let window = Window {
+            weight: 1,
+            ..self.windows[self.focused]
+        };
+        self.windows = vec![window];
+        self.focused = 0;
+    }
+
+    /// The weight of each window, from top to bottom.
+    pub fn weights(&self) -> Vec<usize> {
+        self.windows.iter().map(|window| window.weight).collect()
+    }
+
+    /// Set the weight of each window, from top to bottom.
+    ///
+    /// Weights are at least 1, and windows past the end of `weights` keep their weight.
+    pub fn set_weights(&mut self, weights: &[usize]) {
+        for (window, &weight) in self.windows.iter_mut().zip(weights) {
+            window.weight = weight.max(1);
+        }
+        self.simplify_weights();
+    }
+
+    /// Give every window the same weight, like vim's `Ctrl-w =`.
+    pub fn equalize(&mut self) {
+        self.set_weights(&vec![1; self.windows.len()]);
+    }
+
+    /// Divide the weights by their greatest common divisor, so they don't grow without end.
+    fn simplify_weights(&mut self) {
+        let divisor = self
+            .windows
+            .iter()
+            .map(|window| window.weight)
+            .reduce(gcd)
+            .unwrap_or(1);
+        for window in &mut self.windows {
+            window.weight /= divisor.max(1);
+        }
+    }
+
+    /// Focus the window at `index` in [`Layout::windows`], or the bottom one if that is past the
+    /// end.
+    pub fn focus(&mut self, index: usize) {
+        self.focused = index.min(self.windows.len() - 1);
+    }
+
+    /// Show `new` with the cursor at `cursor` in every window which shows `old`.
+    pub fn replace_buffer(&mut self, old: usize, new: usize, cursor: (usize, usize)) {
+        for window in &mut self.windows {
+            if window.buffer == old {
+                window.buffer = new;
+                window.cursor = cursor;
+            }
+        }
+    }
+}
+
+/// The greatest common divisor of `a` and `b`.
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn splitting_and_closing() {
+        let mut layout = Layout::new(3);
+        assert!(layout.close().is_err());
+        layout.focused_window().cursor = (1, 2);
+        layout.split();
+        layout.split();
+        assert_eq!(layout.windows().len(), 3);
+        assert_eq!(layout.focused(), 0);
+        assert_eq!(layout.windows()[0].id, 2);
+        assert!(layout
+            .windows()
+            .iter()
+            .all(|window| window.buffer == 3 && window.cursor == (1, 2)));
+
+        layout.focus(5);
+        assert_eq!(layout.focused(), 2);
+        layout.close().unwrap();
+        assert_eq!(layout.focused(), 1);
+        layout.focus(0);
+        layout.close().unwrap();
+        assert_eq!(layout.focused(), 0);
+        assert_eq!(layout.windows()[0].id, 1);
+
+        layout.split();
+        layout.only();
+        assert_eq!(layout.windows().len(), 1);
+        assert_eq!(layout.windows()[0].id, 3);
+        assert_eq!(layout.weights(), [1]);
+    }
+
+    #[test]
+    fn weighing_windows() {
+        let mut layout = Layout::new(0);
+        layout.split();
+        assert_eq!(layout.weights(), [1, 1]);
+        layout.split();
+        assert_eq!(layout.weights(), [1, 1, 2]);
+
+        layout.set_weights(&[6, 0]);
+        assert_eq!(layout.weights(), [6, 1, 2]);
+        layout.close().unwrap();
+        assert_eq!(layout.weights(), [7, 2]);
+        layout.equalize();
+        assert_eq!(layout.weights(), [1, 1]);
+        layout.set_weights(&[4, 6]);
+        assert_eq!(layout.weights(), [2, 3]);
+    }
+}
@@ -0,0 +1,318 @@
+//! Key mappings which can be added while the editor is running using `:map` and `:noremap`.
+//!
+//! A mapping replaces a sequence of keys typed in normal or visual mode with another sequence of
+//! keys. The keys of a recursive mapping (`:map`) are themselves checked for mappings, while the
+//! keys of a non-recursive mapping (`:noremap`) always have their built-in meanings.
+//!
+//! Keys are written like vim's, with special keys in angle brackets: `<Esc>`, `<CR>`, `<C-r>`.
+
+use crate::config::{self, Key, KeyCode, KeyModifiers};
+use anyhow::bail;
+use std::collections::HashMap;
+
+/// What a sequence of keys has been mapped to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    /// The keys typed in place of the mapped ones.
+    pub rhs: Vec<Key>,
+    /// Whether `rhs` is checked for mappings too.
+    pub recursive: bool,
+    /// Where the mapping was added from.
+    pub source: MapSource,
+}
+
+/// Where a [`Mapping`] was added from, as shown by `:verbose map`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapSource {
+    /// [`config::MAPPINGS`].
+    Config,
+    /// `:map` or `:noremap`.
+    User,
+}
+
+/// All the key mappings which have been added.
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    /// The mappings, keyed by the sequence of keys they replace.
+    mappings: HashMap<Vec<Key>, Mapping>,
+}
+
+impl Keymap {
+    /// The mappings in [`config::MAPPINGS`].
+    ///
+    /// Mappings whose keys can't be parsed are left out.
+    pub fn from_config() -> Self {
+        let mappings = config::MAPPINGS
+            .iter()
+            .filter_map(|&(lhs, rhs, recursive)| {
+                let mapping = Mapping {
+                    rhs: parse_keys(rhs).ok()?,
+                    recursive,
+                    source: MapSource::Config,
+                };
+                Some((parse_keys(lhs).ok()?, mapping))
+            })
+            .collect();
+        Self { mappings }
+    }
+
+    /// Make typing `lhs` act as if `rhs` was typed instead.
+    pub fn map(&mut self, lhs: Vec<Key>, rhs: Vec<Key>, recursive: bool) {
+        let mapping = Mapping {
+            rhs,
+            recursive,
+            source: MapSource::User,
+        };
+        self.mappings.insert(lhs, mapping);
+    }
+
+    /// Remove the mapping for `lhs`.
+    pub fn unmap(&mut self, lhs: &[Key]) -> anyhow::Result<()> {
+        if self.mappings.remove(lhs).is_none() {
+            bail!("No such mapping");
+        }
+        Ok(())
+    }
+
+    /// Find the mapping at the start of `keys`, returning the length of its left-hand side along
+    /// with it.
+    pub fn find(&self, keys: &[Key]) -> Option<(usize, &Mapping)> {
+        (1..=keys.len())
+            .rev()
+            .find_map(|len| Some((len, self.mappings.get(&keys[..len])?)))
+    }
+
+    /// The mapping for exactly `lhs`, if there is one.
+    pub fn get(&self, lhs: &[Key]) -> Option<&Mapping> {
+        self.mappings.get(lhs)
+    }
+
+    /// List the mappings whose left-hand side starts with `prefix`, or all of them when there
+    /// isn't one, like vim's `:map`.
+    ///
+    /// Each line has the keys, a `*` if the mapping isn't recursive, and what they are mapped to.
+    /// With `verbose`, each is followed by a line saying where it was added from.
+    pub fn list(&self, prefix: Option<&[Key]>, verbose: bool) -> String {
+        let mut mappings: Vec<(String, &Mapping)> = self
+            .mappings
+            .iter()
+            .filter(|(lhs, _)| prefix.is_none_or(|prefix| lhs.starts_with(prefix)))
+            .map(|(lhs, mapping)| (format_keys(lhs), mapping))
+            .collect();
+        if mappings.is_empty() {
+            return "No mapping found".to_owned();
+        }
+        mappings.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let lines: Vec<String> = mappings
+            .into_iter()
+            .map(|(lhs, mapping)| {
+                let noremap = if mapping.recursive { ' ' } else { '*' };
+                let mut line = format!("{lhs:<12} {noremap} {}", format_keys(&mapping.rhs));
+                if verbose {
+                    line.push_str(match mapping.source {
+                        MapSource::Config => "\n\tLast set from config",
+                        MapSource::User => "\n\tLast set from :map",
+                    });
+                }
+                line
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// Whether `keys` could become a mapping if more keys were typed.
+    pub fn is_prefix(&self, keys: &[Key]) -> bool {
+        self.mappings
+            .keys()
+            .any(|lhs| lhs.len() > keys.len() && lhs.starts_with(keys))
+    }
+}
+
+/// Parse a sequence of keys written like vim's, such as `dd` or `<C-w>j`.
+///
+/// A `<` which doesn't start a special key is just a `<`.
+pub fn parse_keys(s: &str) -> anyhow::Result<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some((name, after)) = rest[1..].split_once('>') {
+                if let Some(key) = special_key(name) {
+                    keys.push(key?);
+                    rest = after;
+                    continue;
+                }
+            }
+        }
+        keys.push(Key::from(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    if keys.is_empty() {
+        bail!("No keys given");
+    }
+    Ok(keys)
+}
+
+/// Write out a sequence of keys the way [`parse_keys`] reads them, such as `<C-w>j`.
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter().map(|&key| format_key(key)).collect()
+}
+
+/// Write out a single key, in angle brackets if it is a special key.
+fn format_key(key: Key) -> String {
+    let name = match key.code {
+        KeyCode::Char('<') => "lt".to_owned(),
+        KeyCode::Char(' ') => "Space".to_owned(),
+        KeyCode::Char('|') => "Bar".to_owned(),
+        KeyCode::Char(c) if key.modifiers.is_empty() => return c.to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Esc => "Esc".to_owned(),
+        KeyCode::Enter => "CR".to_owned(),
+        KeyCode::Backspace => "BS".to_owned(),
+        KeyCode::Tab => "Tab".to_owned(),
+        KeyCode::Left => "Left".to_owned(),
+        KeyCode::Right => "Right".to_owned(),
+        KeyCode::Up => "Up".to_owned(),
+        KeyCode::Down => "Down".to_owned(),
+        KeyCode::Home => "Home".to_owned(),
+        KeyCode::End => "End".to_owned(),
+        KeyCode::Delete => "Del".to_owned(),
+        code => format!("{code:?}"),
+    };
+    let mut modifiers = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        modifiers.push_str("C-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        modifiers.push_str("A-");
+    }
+    if key.modifiers.contains(KeyModifiers::SHIFT) {
+        modifiers.push_str("S-");
+    }
+    format!("<{modifiers}{name}>")
+}
+
+/// Parse the name of a special key, like `Esc` or `C-r`.
+///
+/// Returns [`None`] if `name` doesn't look like a key at all, so the brackets are taken
+/// literally.
+fn special_key(name: &str) -> Option<anyhow::Result<Key>> {
+    if name.is_empty() || name.contains(char::is_whitespace) || name.contains('<') {
+        return None;
+    }
+    let mut modifiers = KeyModifiers::NONE;
+    let mut base = name;
+    while let Some((modifier, rest)) = base.split_once('-').filter(|(_, rest)| !rest.is_empty()) {
+        modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "c" => KeyModifiers::CONTROL,
+            "a" | "m" => KeyModifiers::ALT,
+            "s" => KeyModifiers::SHIFT,
+            _ => return Some(Err(anyhow::anyhow!("Unknown modifier in <{name}>"))),
+        };
+        base = rest;
+    }
+    let mut chars = base.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match base.to_ascii_lowercase().as_str() {
+            "esc" => KeyCode::Esc,
+            "cr" | "enter" | "return" => KeyCode::Enter,
+            "bs" => KeyCode::Backspace,
+            "tab" => KeyCode::Tab,
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            "bar" => KeyCode::Char('|'),
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "del" => KeyCode::Delete,
+            _ => return Some(Err(anyhow::anyhow!("Unknown key <{name}>"))),
+        },
+    };
+    // Match the keys the terminal reports: shifted chars are just the uppercase char, and
+    // control chars are reported in lowercase.
+    let code = match code {
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+            modifiers -= KeyModifiers::SHIFT;
+            KeyCode::Char(c.to_ascii_uppercase())
+        }
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+            KeyCode::Char(c.to_ascii_lowercase())
+        }
+        code => code,
+    };
+    Some(Ok(Key { code, modifiers }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing_keys() {
+        assert_eq!(parse_keys("dd").unwrap(), [Key::from('d'), Key::from('d')]);
+        assert_eq!(
+            parse_keys("<C-w>j<Esc>").unwrap(),
+            [
+                Key {
+                    code: KeyCode::Char('w'),
+                    modifiers: KeyModifiers::CONTROL,
+                },
+                Key::from('j'),
+                Key {
+                    code: KeyCode::Esc,
+                    modifiers: KeyModifiers::NONE,
+                },
+            ]
+        );
+        assert_eq!(
+            parse_keys("<lt><").unwrap(),
+            [Key::from('<'), Key::from('<')]
+        );
+        assert_eq!(parse_keys("a<b").unwrap().len(), 3);
+        assert!(parse_keys("<Nope>").is_err());
+        assert!(parse_keys("").is_err());
+    }
+
+    #[test]
+    fn finding_mappings() {
+        let mut keymap = Keymap::default();
+        keymap.map(parse_keys(",w").unwrap(), parse_keys("w").unwrap(), false);
+        assert!(keymap.is_prefix(&parse_keys(",").unwrap()));
+        assert!(!keymap.is_prefix(&parse_keys(",w").unwrap()));
+        let (len, mapping) = keymap.find(&parse_keys(",wx").unwrap()).unwrap();
+        assert_eq!(len, 2);
+        assert_eq!(mapping.rhs, [Key::from('w')]);
+        assert!(keymap.find(&parse_keys("x,w").unwrap()).is_none());
+    }
+
+    #[test]
+    fn formatting_keys() {
+        for keys in ["dd", "<C-w>j<Esc>", "<lt>a<Space><CR>", "<C-A-x><Left>"] {
+            assert_eq!(format_keys(&parse_keys(keys).unwrap()), keys);
+        }
+    }
+
+    #[test]
+    fn listing_mappings() {
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.list(None, false), "No mapping found");
+        keymap.map(
+            parse_keys(",w").unwrap(),
+            parse_keys(":w<CR>").unwrap(),
+            false,
+        );
+        keymap.map(parse_keys("Q").unwrap(), parse_keys("gq").unwrap(), true);
+        assert_eq!(
+            keymap.list(None, false),
+            ",w           * :w<CR>\nQ              gq"
+        );
+        assert_eq!(
+            keymap.list(Some(&parse_keys(",").unwrap()), true),
+            ",w           * :w<CR>\n\tLast set from :map"
+        );
+    }
+}
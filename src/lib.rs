@@ -1,3 +1,15 @@
+pub mod command;
 pub mod config;
 pub mod editor;
+pub mod keymap;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod paste;
+pub mod progress;
+pub mod settings;
+#[cfg(test)]
+mod test_util;
+// Lets the test helpers shared with the binary name this crate the same way it does.
+#[cfg(test)]
+extern crate self as not_vim;
 pub use editor::Editor;
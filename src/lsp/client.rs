@@ -0,0 +1,253 @@
+//! A running language server, and what the editor has told it about.
+
+use super::protocol::{self, PositionEncoding};
+use crate::editor::diagnostic::Diagnostic;
+use anyhow::{bail, Context};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for the server to answer the `initialize` request before giving up on it.
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something the server has sent which the editor should act on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The diagnostics for the document at the path have changed, and are now these.
+    ///
+    /// The columns of their positions are in the [`Client::position_encoding`].
+    Diagnostics(PathBuf, Vec<Diagnostic>),
+    /// The answer to a go-to-definition request, as the path and `(column, line)` position of
+    /// each place the definition is.
+    Definition(Vec<(PathBuf, (usize, usize))>),
+}
+
+/// A document the server has been told about.
+#[derive(Debug)]
+struct Document {
+    /// The text the server was last sent.
+    text: Rope,
+    /// The version the text was last sent with.
+    version: i64,
+}
+
+/// A language server running in a child process, talking over its stdin and stdout.
+#[derive(Debug)]
+pub struct Client {
+    /// The server process.
+    process: Child,
+    /// Where messages to the server are written.
+    stdin: ChildStdin,
+    /// Messages read from the server by the reader thread, or the error which stopped it.
+    messages: Receiver<anyhow::Result<Value>>,
+    /// The language ID documents are opened with.
+    language_id: String,
+    /// What columns of positions are counted in, as agreed with the server.
+    encoding: PositionEncoding,
+    /// The documents the server has been told about, by absolute path.
+    documents: HashMap<PathBuf, Document>,
+    /// The ID to give the next request.
+    next_id: u64,
+    /// The ID of the go-to-definition request still waiting for an answer, if there is one.
+    pending_definition: Option<u64>,
+}
+
+impl Client {
+    /// Start `command` as the language server for documents in `language_id` under `root`,
+    /// waiting for it to finish initializing.
+    pub fn start(command: &str, language_id: &str, root: &Path) -> anyhow::Result<Self> {
+        let mut process = Command::new(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Could not start language server {command}"))?;
+        let (Some(stdin), Some(stdout)) = (process.stdin.take(), process.stdout.take()) else {
+            bail!("Could not talk to language server {command}");
+        };
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            loop {
+                let message = protocol::read_message(&mut reader);
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            process,
+            stdin,
+            messages,
+            language_id: language_id.to_owned(),
+            encoding: PositionEncoding::Utf16,
+            documents: HashMap::new(),
+            next_id: 0,
+            pending_definition: None,
+        };
+        let id = client.next_id();
+        client.send(&protocol::initialize(id, root))?;
+        let result = client.wait_for_response(id)?;
+        client.encoding = PositionEncoding::from_initialize_result(&result);
+        client.send(&protocol::notification("initialized", json!({})))?;
+        Ok(client)
+    }
+
+    /// The language ID documents are opened with.
+    pub fn language_id(&self) -> &str {
+        &self.language_id
+    }
+
+    /// What columns of positions sent to and from the server are counted in.
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.encoding
+    }
+
+    /// Take the ID for a new request.
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Send `message` to the server.
+    fn send(&mut self, message: &Value) -> anyhow::Result<()> {
+        self.stdin.write_all(&protocol::encode(message))?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// Wait for the response to the request with ID `id`, ignoring anything else the server
+    /// sends in the meantime.
+    fn wait_for_response(&mut self, id: u64) -> anyhow::Result<Value> {
+        let deadline = Instant::now() + INITIALIZE_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let message = match self.messages.recv_timeout(timeout) {
+                Ok(message) => message?,
+                Err(RecvTimeoutError::Timeout) => bail!("The language server did not respond"),
+                Err(RecvTimeoutError::Disconnected) => bail!("The language server stopped"),
+            };
+            if message.get("id").and_then(Value::as_u64) == Some(id) {
+                if let Some(error) = message.get("error") {
+                    bail!("The language server failed to start: {}", error["message"]);
+                }
+                return Ok(message["result"].clone());
+            }
+        }
+    }
+
+    /// Tell the server the document at `path` now contains `text`.
+    ///
+    /// The first time, the document is opened. After that, only what changed since the last time
+    /// is sent.
+    pub fn sync(&mut self, path: &Path, text: &Rope) -> anyhow::Result<()> {
+        let message = match self.documents.get_mut(path) {
+            // Comparing is much quicker than working out what changed.
+            Some(document) if document.text == *text => return Ok(()),
+            Some(document) => {
+                let Some(message) = protocol::did_change(
+                    path,
+                    document.version + 1,
+                    &document.text,
+                    text,
+                    self.encoding,
+                ) else {
+                    return Ok(());
+                };
+                document.version += 1;
+                document.text = text.clone();
+                message
+            }
+            None => {
+                let message = protocol::did_open(path, &self.language_id, 0, text);
+                self.documents.insert(
+                    path.to_owned(),
+                    Document {
+                        text: text.clone(),
+                        version: 0,
+                    },
+                );
+                message
+            }
+        };
+        self.send(&message)
+    }
+
+    /// Ask the server where the definition of what is at the `(column, line)` position `pos` in
+    /// the document at `path` is, after syncing `text` as its contents.
+    ///
+    /// The answer comes later as an [`Event::Definition`]. Only the latest request is answered.
+    pub fn request_definition(
+        &mut self,
+        path: &Path,
+        text: &Rope,
+        pos: (usize, usize),
+    ) -> anyhow::Result<()> {
+        self.sync(path, text)?;
+        let id = self.next_id();
+        self.pending_definition = Some(id);
+        self.send(&protocol::definition(id, path, pos))
+    }
+
+    /// Handle everything the server has sent since the last call, returning what the editor
+    /// should act on.
+    ///
+    /// Fails if the server has stopped.
+    pub fn events(&mut self) -> anyhow::Result<Vec<Event>> {
+        let mut events = Vec::new();
+        loop {
+            let message = match self.messages.try_recv() {
+                Ok(message) => message?,
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => bail!("The language server stopped"),
+            };
+            let id = message.get("id").cloned();
+            match message.get("method").and_then(Value::as_str) {
+                Some("textDocument/publishDiagnostics") => {
+                    if let Some((path, diagnostics)) =
+                        protocol::parse_diagnostics(&message["params"])
+                    {
+                        events.push(Event::Diagnostics(path, diagnostics));
+                    }
+                }
+                // The server is asking for something. None of it is supported, but the server
+                // may wait for an answer.
+                Some(_) => {
+                    if let Some(id) = id {
+                        self.send(&json!({ "jsonrpc": "2.0", "id": id, "result": null }))?;
+                    }
+                }
+                None => {
+                    let id = id.as_ref().and_then(Value::as_u64);
+                    if id.is_some() && id == self.pending_definition {
+                        self.pending_definition = None;
+                        let locations = protocol::parse_locations(&message["result"]);
+                        events.push(Event::Definition(locations));
+                    }
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // The server is meant to exit once told to, but it doesn't matter much if it doesn't.
+        let id = self.next_id();
+        let _ = self.send(&protocol::request(id, "shutdown", Value::Null));
+        let _ = self.send(&protocol::notification("exit", Value::Null));
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
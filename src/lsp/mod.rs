@@ -0,0 +1,10 @@
+//! A minimal client for the Language Server Protocol, used for diagnostics and going to
+//! definitions.
+//!
+//! The server for a buffer is chosen by its file extension, from
+//! [`LANGUAGE_SERVERS`](crate::config::LANGUAGE_SERVERS).
+
+mod client;
+pub mod protocol;
+
+pub use client::{Client, Event};
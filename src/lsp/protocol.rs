@@ -0,0 +1,414 @@
+//! The messages sent between the editor and a language server.
+//!
+//! Messages are JSON-RPC, each sent with a `Content-Length` header saying how long its body is.
+//! Positions are sent as a line and a column, counted in the [`PositionEncoding`] agreed with the
+//! server. Positions here are always in the server's encoding, except where they are worked out
+//! from text.
+
+use crate::editor::diagnostic::{Diagnostic, DiagnosticSeverity};
+use anyhow::{bail, Context};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::{
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+/// What columns of positions are counted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    /// Bytes of UTF-8, which is what the editor's text is stored as.
+    Utf8,
+    /// Code units of UTF-16, which every server supports and is used unless another is agreed.
+    Utf16,
+}
+
+impl PositionEncoding {
+    /// The encoding the server chose in its answer to the `initialize` request.
+    pub fn from_initialize_result(result: &Value) -> Self {
+        match result["capabilities"]["positionEncoding"].as_str() {
+            Some("utf-8") => Self::Utf8,
+            _ => Self::Utf16,
+        }
+    }
+}
+
+/// Frame `message` with the header it is sent with.
+pub fn encode(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut encoded = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    encoded.extend_from_slice(body.as_bytes());
+    encoded
+}
+
+/// Read the next message from `reader`, waiting until the whole of it has arrived.
+pub fn read_message(reader: &mut impl BufRead) -> anyhow::Result<Value> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("The language server closed its output");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let length = length.context("A message from the language server had no Content-Length")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// A request with the ID `id`, which the server answers with a response with the same ID.
+pub fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+/// A notification, which the server doesn't answer.
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// The `initialize` request, which must be the first thing sent to the server.
+///
+/// The client only says it can handle what it uses: diagnostics and going to definitions. UTF-8
+/// positions are preferred, since the text doesn't need converting to count them.
+pub fn initialize(id: u64, root: &Path) -> Value {
+    request(
+        id,
+        "initialize",
+        json!({
+            "processId": std::process::id(),
+            "rootUri": path_to_uri(root),
+            "capabilities": {
+                "general": { "positionEncodings": ["utf-8", "utf-16"] },
+                "textDocument": {
+                    "synchronization": { "dynamicRegistration": false },
+                    "publishDiagnostics": { "relatedInformation": false },
+                    "definition": { "linkSupport": true },
+                },
+            },
+        }),
+    )
+}
+
+/// The `textDocument/didOpen` notification, telling the server about a document it should now
+/// keep track of.
+pub fn did_open(path: &Path, language_id: &str, version: i64, text: &Rope) -> Value {
+    notification(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": path_to_uri(path),
+                "languageId": language_id,
+                "version": version,
+                "text": text.to_string(),
+            },
+        }),
+    )
+}
+
+/// The `textDocument/didChange` notification for the document at `path` changing from `old` to
+/// `new`, or [`None`] if nothing changed.
+///
+/// The change is sent as the one range of `old` which was replaced, rather than the whole text.
+pub fn did_change(
+    path: &Path,
+    version: i64,
+    old: &Rope,
+    new: &Rope,
+    encoding: PositionEncoding,
+) -> Option<Value> {
+    let change = content_change(old, new, encoding)?;
+    Some(notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": path_to_uri(path), "version": version },
+            "contentChanges": [change],
+        }),
+    ))
+}
+
+/// The smallest range of `old` which can be replaced to make `new`, along with what replaces it.
+fn content_change(old: &Rope, new: &Rope, encoding: PositionEncoding) -> Option<Value> {
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix == old.len_chars() && prefix == new.len_chars() {
+        return None;
+    }
+    // The common suffix can't overlap the common prefix in either text.
+    let max_suffix = old.len_chars().min(new.len_chars()) - prefix;
+    let suffix = old
+        .chars_at(old.len_chars())
+        .reversed()
+        .zip(new.chars_at(new.len_chars()).reversed())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let end = old.len_chars() - suffix;
+    Some(json!({
+        "range": {
+            "start": position(old, prefix, encoding),
+            "end": position(old, end, encoding),
+        },
+        "text": new.slice(prefix..new.len_chars() - suffix).to_string(),
+    }))
+}
+
+/// The `textDocument/definition` request, for what is at the `(column, line)` position `pos` in
+/// the document at `path`.
+pub fn definition(id: u64, path: &Path, (column, line): (usize, usize)) -> Value {
+    request(
+        id,
+        "textDocument/definition",
+        json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": { "line": line, "character": column },
+        }),
+    )
+}
+
+/// The protocol's position of the char at `char_idx` in `text`.
+fn position(text: &Rope, char_idx: usize, encoding: PositionEncoding) -> Value {
+    let line = text.char_to_line(char_idx);
+    let start = text.line_to_char(line);
+    let column = match encoding {
+        PositionEncoding::Utf8 => text.char_to_byte(char_idx) - text.char_to_byte(start),
+        PositionEncoding::Utf16 => text.char_to_utf16_cu(char_idx) - text.char_to_utf16_cu(start),
+    };
+    json!({ "line": line, "character": column })
+}
+
+/// Read a position sent by the server as a `(column, line)` position.
+fn parse_position(position: &Value) -> Option<(usize, usize)> {
+    let line = position.get("line")?.as_u64()?;
+    let character = position.get("character")?.as_u64()?;
+    Some((character as usize, line as usize))
+}
+
+/// Read the parameters of a `textDocument/publishDiagnostics` notification as the path of the
+/// document and the diagnostics for it.
+pub fn parse_diagnostics(params: &Value) -> Option<(PathBuf, Vec<Diagnostic>)> {
+    let path = uri_to_path(params.get("uri")?.as_str()?)?;
+    let diagnostics = params
+        .get("diagnostics")?
+        .as_array()?
+        .iter()
+        .filter_map(|diagnostic| {
+            let range = diagnostic.get("range")?;
+            // Diagnostics without a severity are up to the client, so treat them as errors.
+            let severity = match diagnostic.get("severity").and_then(Value::as_u64) {
+                Some(2) => DiagnosticSeverity::Warning,
+                Some(3) => DiagnosticSeverity::Information,
+                Some(4) => DiagnosticSeverity::Hint,
+                _ => DiagnosticSeverity::Error,
+            };
+            Some(Diagnostic {
+                start: parse_position(range.get("start")?)?,
+                end: parse_position(range.get("end")?)?,
+                severity,
+                message: diagnostic.get("message")?.as_str()?.to_owned(),
+            })
+        })
+        .collect();
+    Some((path, diagnostics))
+}
+
+/// Read the result of a `textDocument/definition` request as the path and `(column, line)`
+/// position of each place the definition is.
+///
+/// The result can be a single location, a list of them or a list of links, or `null` if nothing
+/// was found.
+pub fn parse_locations(result: &Value) -> Vec<(PathBuf, (usize, usize))> {
+    let locations = match result {
+        Value::Array(locations) => locations.as_slice(),
+        Value::Null => &[],
+        location => std::slice::from_ref(location),
+    };
+    locations
+        .iter()
+        .filter_map(|location| {
+            let (uri, range) = match location.get("targetUri") {
+                Some(uri) => (uri, location.get("targetSelectionRange")?),
+                None => (location.get("uri")?, location.get("range")?),
+            };
+            let path = uri_to_path(uri.as_str()?)?;
+            Some((path, parse_position(range.get("start")?)?))
+        })
+        .collect()
+}
+
+/// The `file://` URI for `path`, which should be absolute.
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+/// The path of a `file://` URI, or [`None`] if it isn't one.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut path = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+            path.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            path.push(encoded[i]);
+            i += 1;
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(path).ok()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn framing_messages() {
+        let message = notification("exit", Value::Null);
+        let encoded = encode(&message);
+        assert!(encoded.starts_with(b"Content-Length: 47\r\n\r\n{"));
+        let mut reader = &encoded[..];
+        assert_eq!(read_message(&mut reader).unwrap(), message);
+        assert!(read_message(&mut reader).is_err());
+    }
+
+    #[test]
+    fn notifying_changes() {
+        let path = Path::new("/src/main.rs");
+        let old = Rope::from_str("fn main() {\n    foo();\n}\n");
+        let mut new = old.clone();
+        new.insert(16, "bar");
+        new.remove(19..22);
+        assert_eq!(
+            did_change(path, 3, &old, &new, PositionEncoding::Utf16),
+            Some(json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": "file:///src/main.rs", "version": 3 },
+                    "contentChanges": [{
+                        "range": {
+                            "start": { "line": 1, "character": 4 },
+                            "end": { "line": 1, "character": 7 },
+                        },
+                        "text": "bar",
+                    }],
+                },
+            }))
+        );
+        assert_eq!(
+            did_change(path, 4, &new, &new, PositionEncoding::Utf16),
+            None
+        );
+
+        // Repeated text is only counted once, at the start of the change.
+        let change = |old, new, encoding| {
+            content_change(&Rope::from_str(old), &Rope::from_str(new), encoding).unwrap()
+        };
+        assert_eq!(
+            change("aa", "aaa", PositionEncoding::Utf16)["range"]["start"]["character"],
+            2
+        );
+        assert_eq!(change("a\nb", "a\n", PositionEncoding::Utf16)["text"], "");
+
+        // Columns after an emoji count it as two UTF-16 code units, or four bytes.
+        let start =
+            |encoding| change("😀a", "😀b", encoding)["range"]["start"]["character"].clone();
+        assert_eq!(start(PositionEncoding::Utf16), 2);
+        assert_eq!(start(PositionEncoding::Utf8), 4);
+    }
+
+    #[test]
+    fn negotiating_position_encodings() {
+        let result = |encoding| json!({ "capabilities": { "positionEncoding": encoding } });
+        assert_eq!(
+            PositionEncoding::from_initialize_result(&result("utf-8")),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            PositionEncoding::from_initialize_result(&result("utf-32")),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(
+            PositionEncoding::from_initialize_result(&json!({ "capabilities": {} })),
+            PositionEncoding::Utf16
+        );
+    }
+
+    #[test]
+    fn reading_diagnostics() {
+        let params = json!({
+            "uri": "file:///src/my%20file.rs",
+            "diagnostics": [
+                {
+                    "range": {
+                        "start": { "line": 2, "character": 1 },
+                        "end": { "line": 2, "character": 5 },
+                    },
+                    "severity": 2,
+                    "message": "unused variable",
+                },
+                { "message": "no range" },
+            ],
+        });
+        let (path, diagnostics) = parse_diagnostics(&params).unwrap();
+        assert_eq!(path, Path::new("/src/my file.rs"));
+        assert_eq!(
+            diagnostics,
+            [Diagnostic {
+                start: (1, 2),
+                end: (5, 2),
+                severity: DiagnosticSeverity::Warning,
+                message: "unused variable".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reading_locations() {
+        let location = json!({
+            "uri": "file:///a.rs",
+            "range": {
+                "start": { "line": 4, "character": 3 },
+                "end": { "line": 4, "character": 6 },
+            },
+        });
+        let link = json!({
+            "targetUri": "file:///b.rs",
+            "targetRange": location["range"],
+            "targetSelectionRange": {
+                "start": { "line": 1, "character": 0 },
+                "end": { "line": 1, "character": 2 },
+            },
+        });
+        assert_eq!(
+            parse_locations(&location),
+            [(PathBuf::from("/a.rs"), (3, 4))]
+        );
+        assert_eq!(
+            parse_locations(&json!([link])),
+            [(PathBuf::from("/b.rs"), (0, 1))]
+        );
+        assert_eq!(parse_locations(&Value::Null), []);
+    }
+}
@@ -1,22 +1,223 @@
 //! Command-line arguments being passed to the program.
 //!
 //! [`Args::parse_args`] will parse the command-line arguments as an [`Args`] and return it.
-//! TODO: If the arguments get too complex, should we swap to using clap?
+//!
+//! Flags are written like `--readonly`, `--line=10` or `--line 10`, and most have a short form
+//! like `-R` or `-l10`. Short flags which don't take a value can be combined, as in `-Rh`. A lone
+//! `-` reads the text to edit from stdin, and everything after `--` is a file.
 
+use anyhow::{bail, Context};
 use std::env;
 
+/// How to use the program, shown by `--help`.
+pub const USAGE: &str = "\
+Usage: notvim [OPTIONS] [FILE]... [-- FILE...]
+
+Options:
+  -R, --readonly     Don't allow the file to be written
+  -l, --line <LINE>  Start with the cursor on line LINE
+  -h, --help         Show this help
+  -V, --version      Show the version
+  -                  Read the text to edit from stdin";
+
 /// The command-line arguments passed into the program.
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct Args {
-    /// The file to be edited.
-    pub file: Option<String>,
+    /// The files to be edited.
+    pub files: Vec<String>,
+    /// Whether the text to edit should be read from stdin.
+    pub stdin: bool,
+    /// Whether writing the file is disallowed.
+    pub readonly: bool,
+    /// The line to start the cursor on, counting from 1.
+    pub line: Option<usize>,
+    /// Whether to show [`USAGE`] instead of editing.
+    pub help: bool,
+    /// Whether to show the version instead of editing.
+    pub version: bool,
 }
 
 impl Args {
     /// Interpret the command-line arguments as an [`Args`].
-    pub fn parse_args() -> Self {
-        let mut args = env::args();
-        args.next(); // skip program name
+    pub fn parse_args() -> anyhow::Result<Self> {
+        Self::parse(env::args().skip(1)) // skip program name
+    }
+
+    /// Interpret `args`, not including the program name, as an [`Args`].
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                // Everything after `--` is a file, even if it looks like a flag.
+                parsed.files.extend(args.by_ref());
+            } else if arg == "-" {
+                parsed.stdin = true;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_owned())),
+                    None => (long, None),
+                };
+                parsed.set_flag(Flag::from_long(name)?, value, &mut args)?;
+            } else if let Some(shorts) = arg.strip_prefix('-') {
+                for (i, short) in shorts.char_indices() {
+                    let flag = Flag::from_short(short)?;
+                    // A flag which takes a value takes the rest of the argument, if there is any.
+                    let rest = &shorts[i + short.len_utf8()..];
+                    if flag.takes_value() && !rest.is_empty() {
+                        parsed.set_flag(flag, Some(rest.to_owned()), &mut args)?;
+                        break;
+                    }
+                    parsed.set_flag(flag, None, &mut args)?;
+                }
+            } else {
+                parsed.files.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Set `flag`, using `value` or the next of `args` as its value if it takes one.
+    fn set_flag(
+        &mut self,
+        flag: Flag,
+        value: Option<String>,
+        args: &mut impl Iterator<Item = String>,
+    ) -> anyhow::Result<()> {
+        let value = match (flag.takes_value(), value) {
+            (true, Some(value)) => value,
+            (true, None) => args
+                .next()
+                .with_context(|| format!("--{} needs a value", flag.long()))?,
+            (false, Some(_)) => bail!("--{} doesn't take a value", flag.long()),
+            (false, None) => String::new(),
+        };
+        match flag {
+            Flag::Readonly => self.readonly = true,
+            Flag::Line => {
+                let line = value
+                    .parse()
+                    .with_context(|| format!("Invalid line number `{value}`"))?;
+                self.line = Some(line);
+            }
+            Flag::Help => self.help = true,
+            Flag::Version => self.version = true,
+        }
+        Ok(())
+    }
+}
+
+/// A flag which can be passed to the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flag {
+    /// `--readonly`.
+    Readonly,
+    /// `--line`.
+    Line,
+    /// `--help`.
+    Help,
+    /// `--version`.
+    Version,
+}
+
+impl Flag {
+    /// The flag with the long name `name`, written after `--`.
+    fn from_long(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "readonly" => Self::Readonly,
+            "line" => Self::Line,
+            "help" => Self::Help,
+            "version" => Self::Version,
+            _ => bail!("Unknown flag --{name}\n\n{USAGE}"),
+        })
+    }
+
+    /// The flag with the short name `name`, written after `-`.
+    fn from_short(name: char) -> anyhow::Result<Self> {
+        Ok(match name {
+            'R' => Self::Readonly,
+            'l' => Self::Line,
+            'h' => Self::Help,
+            'V' => Self::Version,
+            _ => bail!("Unknown flag -{name}\n\n{USAGE}"),
+        })
+    }
+
+    /// The long name of the flag.
+    fn long(self) -> &'static str {
+        match self {
+            Self::Readonly => "readonly",
+            Self::Line => "line",
+            Self::Help => "help",
+            Self::Version => "version",
+        }
+    }
+
+    /// Whether the flag is followed by a value.
+    fn takes_value(self) -> bool {
+        self == Self::Line
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parse `args` as if they were split up by the shell.
+    fn parse(args: &str) -> anyhow::Result<Args> {
+        Args::parse(args.split_whitespace().map(str::to_owned))
+    }
+
+    /// Parse `args`, which should be valid.
+    fn valid(args: &str) -> Args {
+        parse(args).expect("the arguments should be valid")
+    }
+
+    #[test]
+    fn parsing_flags_and_files() {
+        assert_eq!(
+            valid("one.txt --readonly --line=3 two.txt"),
+            Args {
+                files: vec!["one.txt".to_owned(), "two.txt".to_owned()],
+                readonly: true,
+                line: Some(3),
+                ..Args::default()
+            }
+        );
+        assert_eq!(
+            valid("-Rl 12 -"),
+            Args {
+                stdin: true,
+                readonly: true,
+                line: Some(12),
+                ..Args::default()
+            }
+        );
+        assert_eq!(valid("-l4").line, Some(4));
+        assert!(valid("-hV").help);
+        assert_eq!(valid(""), Args::default());
+    }
+
+    #[test]
+    fn separating_files_from_flags() {
+        assert_eq!(valid("-- -foo").files, ["-foo"]);
+        assert_eq!(
+            valid("--readonly -- --weird.txt - --").files,
+            ["--weird.txt", "-", "--"]
+        );
+        let args = valid("--readonly -- --line=3");
+        assert!(args.readonly);
+        assert_eq!(args.line, None);
+        assert!(!args.stdin);
+    }
 
-        Self { file: args.next() }
+    #[test]
+    fn parsing_bad_flags() {
+        let err = parse("--nope").expect_err("--nope isn't a flag");
+        assert!(err.to_string().starts_with("Unknown flag --nope"));
+        assert!(parse("-x").is_err());
+        assert!(parse("--line").is_err());
+        assert!(parse("--line=ten").is_err());
+        assert!(parse("--readonly=yes").is_err());
     }
 }
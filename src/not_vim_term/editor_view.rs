@@ -1,9 +1,31 @@
 //! Separates the mechanics of drawing an [`Editor`] from the internals of the editing itself.
 
-use std::ops::{Deref, DerefMut};
+use ropey::RopeSlice;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    ops::{Deref, DerefMut, Range},
+    time::Instant,
+};
 
-use crate::tui::{rect::Bottom, Color, Frame, Rect, Style, Text};
-use not_vim::editor::Editor;
+use crate::tui::{
+    rect::{Bottom, Stacked},
+    Color, Frame, HexView, ListView, Modifier, Rect, Style, Text, BYTES_PER_ROW,
+};
+use not_vim::{
+    config::{
+        Message, WrapMode, MESSAGE_TIMEOUT, MIN_NUMBER_WIDTH, MIN_WINDOW_HEIGHT, START_SCREEN,
+        WRAP_MODE,
+    },
+    editor::{
+        diagnostic::{self, DiagnosticSeverity},
+        diff::Change,
+        line_count, trim_newlines,
+        window::Window,
+        Editor, List, Mode, SelectionKind,
+    },
+    progress::Progress,
+    settings::Settings,
+};
 
 /// An [`Editor`] which can be [`render`]ed.
 ///
@@ -16,8 +38,30 @@ pub struct EditorView {
     pub editor: Editor,
     /// The bottom status bar of the editor.
     status_bar: StatusBar,
-    /// The position of the top-right corner of the view rectangle in the editor.
-    view_pos: (usize, usize),
+    /// The position in the editor of the top-left corner of each window, by window ID.
+    view_positions: HashMap<usize, (usize, usize)>,
+    /// The width and height of the terminal the editor is drawn to.
+    size: (u16, u16),
+    /// Whether something has changed since the view was last drawn.
+    redraw: bool,
+    /// The lines of the focused buffer which have changed since the view was last drawn, if
+    /// they may be all that has. See [`EditorView::redraw_lines`].
+    changed_lines: Option<Range<usize>>,
+    /// What the view depended on besides the text when it was last drawn.
+    drawn: Option<DrawnState>,
+    /// The index of the entry of the list being picked from which is in the top row.
+    list_offset: usize,
+    /// The row of bytes in the top row of the focused window when it is shown as hex.
+    hex_row: usize,
+    /// Half the height of the focused window when [`Settings::scroll`] was last kept up to date
+    /// with it, to tell whether `:set scroll` has changed it since.
+    ///
+    /// [`Settings::scroll`]: not_vim::settings::Settings::scroll
+    auto_scroll: usize,
+    /// Messages waiting to be shown in the status bar, with the one being shown first.
+    ///
+    /// Messages with more than one line are shown above the status bar instead.
+    messages: VecDeque<StatusMessage>,
 }
 
 impl EditorView {
@@ -26,7 +70,15 @@ impl EditorView {
         Self {
             editor,
             status_bar: StatusBar::default(),
-            view_pos: (0, 0),
+            view_positions: HashMap::new(),
+            size: (0, 0),
+            redraw: true,
+            changed_lines: None,
+            drawn: None,
+            list_offset: 0,
+            hex_row: 0,
+            auto_scroll: 0,
+            messages: VecDeque::new(),
         }
     }
 
@@ -42,43 +94,689 @@ impl EditorView {
 
     /// See [`frame`].
     ///
+    /// With more than one window, each is drawn with a bar showing its file name below it. The
+    /// cursor is put where it is in the focused window with [`Frame::set_cursor`].
+    ///
     /// [`frame`]: crate::tui::frame
     pub fn render(&self, frame: &mut Frame, region: Rect) {
-        let regions = region.partition(Bottom);
-        let bottom_bar = regions[0];
-        let editor_area = regions[1];
-        self.status_bar.render(frame, bottom_bar, {
-            let pos = self.editor.selected_pos();
-            (pos.0 as u16, pos.1 as u16)
+        let regions = self.regions(region);
+        if let (Some(panel), Some(lines)) = (regions.message_panel, self.messages.front()) {
+            MessagePanel(lines).render(frame, panel);
+        }
+        if let (Some(panel), Some(list)) = (regions.list_panel, self.editor.list()) {
+            ListPanel {
+                list,
+                offset: self.list_offset,
+            }
+            .render(frame, panel);
+        }
+        self.render_bottom_bar(frame, &regions);
+
+        let layout = self.editor.layout();
+        let split = layout.windows().len() > 1;
+        for (i, (window, mut area)) in layout.windows().iter().zip(regions.windows).enumerate() {
+            let focused = i == layout.focused();
+            if split {
+                let parts = area.partition(Bottom);
+                WindowBar {
+                    fname: self.editor.buffer_fname(window.buffer),
+                    focused,
+                }
+                .render(frame, parts[0]);
+                area = parts[1];
+            }
+            if focused {
+                self.render_focused(frame, area, self.view_pos().1);
+                if self.shows_start_screen() {
+                    StartScreen.render(frame, area);
+                }
+            } else {
+                self.render_unfocused(frame, window, area);
+            }
+        }
+        self.render_cursor(frame, region);
+    }
+
+    /// Draw only the rows of the focused window showing `lines`, and the status bar, over what
+    /// was drawn last time, like [`Terminal::update`]. See [`EditorView::take_redraw`] for when
+    /// this is all that needs drawing.
+    ///
+    /// [`Terminal::update`]: crate::tui::Terminal::update
+    pub fn render_lines(&self, frame: &mut Frame, region: Rect, lines: Range<usize>) {
+        let regions = self.regions(region);
+        let mut area = regions.windows[self.editor.layout().focused()];
+        if self.editor.layout().windows().len() > 1 {
+            area = area.partition(Bottom)[1];
+        }
+        let first_line = self.view_pos().1;
+        let start = lines.start.max(first_line) - first_line;
+        let end = lines
+            .end
+            .saturating_sub(first_line)
+            .min(area.height as usize);
+        if start < end {
+            let rows = Rect {
+                top: area.top + start as u16,
+                height: (end - start) as u16,
+                ..area
+            };
+            frame.clear(rows);
+            self.render_focused(frame, rows, first_line + start);
+        }
+        frame.clear(regions.bottom_bar);
+        if let Some(command_line) = regions.command_line {
+            frame.clear(command_line);
+        }
+        self.render_bottom_bar(frame, &regions);
+        self.render_cursor(frame, region);
+    }
+
+    /// Whether the [`StartScreen`] is drawn over the focused window.
+    fn shows_start_screen(&self) -> bool {
+        START_SCREEN && self.editor.is_blank()
+    }
+
+    /// The char shown before the command line, if it is being typed in.
+    fn prompt(&self) -> Option<char> {
+        match self.editor.mode {
+            Mode::Command => Some(':'),
+            Mode::Search => Some('/'),
+            _ => None,
+        }
+    }
+
+    /// Draw the status bar, or whatever is shown in its place, and the command line below it if
+    /// it is being typed in.
+    fn render_bottom_bar(&self, frame: &mut Frame, regions: &Regions) {
+        if let (Some(prompt), Some(region)) = (self.prompt(), regions.command_line) {
+            CommandLine(prompt, self.editor.command_line()).render(frame, region);
+        }
+        if let Some(question) = self.editor.question() {
+            Question(question).render(frame, regions.bottom_bar);
+        } else {
+            // A message with its own panel isn't shown in the status bar too.
+            let message = self
+                .messages
+                .front()
+                .filter(|_| regions.message_panel.is_none());
+            self.status_bar.render(
+                frame,
+                regions.bottom_bar,
+                &self.editor,
+                self.view_pos().0,
+                message,
+            );
+        }
+    }
+
+    /// Put the cursor where it is in the focused window.
+    fn render_cursor(&self, frame: &mut Frame, region: Rect) {
+        // The command line and questions put the cursor at the end of their text instead.
+        if let Some((x, y)) = self.cursor_pos(region).filter(|_| {
+            !matches!(
+                self.editor.mode,
+                Mode::Command | Mode::Search | Mode::Confirm
+            )
+        }) {
+            frame.set_cursor(x, y);
+        }
+    }
+
+    /// Draw the text of the focused window, with the selection, misspellings and diagnostics
+    /// highlighted.
+    ///
+    /// If the buffer has any diagnostics, they get signs in a column to the left of the text.
+    ///
+    /// `first_line` is the line in the top row of `region`, which is below the top of the window
+    /// when only some of its rows are being drawn.
+    fn render_focused(&self, frame: &mut Frame, region: Rect, first_line: usize) {
+        if self.editor.hex_view() {
+            let offset = self.hex_row * BYTES_PER_ROW;
+            let bytes = self
+                .editor
+                .bytes(offset..offset + region.height as usize * BYTES_PER_ROW);
+            HexView {
+                bytes: &bytes,
+                offset,
+                cursor: self.editor.cursor_byte(),
+            }
+            .render(frame, region);
+            return;
+        }
+        let (sign_column, region) = region.split_horizontal(self.sign_column_width());
+        SignColumn {
+            signs: diagnostic::signs(self.editor.diagnostics()),
+            first_line,
+        }
+        .render(frame, sign_column);
+        let (number_column, region) = region.split_horizontal(self.number_column_width());
+        NumberColumn {
+            first_line,
+            lines: self
+                .editor
+                .line_count()
+                .max(self.editor.selected_pos().1 + 1),
+            cursor_line: self.editor.selected_pos().1,
+            absolute: self.editor.settings.number,
+            relative: self.editor.settings.relativenumber,
+        }
+        .render(frame, number_column);
+
+        let offset = self.editor.text().line_to_char(first_line);
+        let mut text = Text::from(self.editor.text().slice(offset..));
+        text.wrap(WRAP_MODE);
+        text.scroll(self.view_pos().0);
+        for range in self
+            .editor
+            .misspellings(first_line..first_line + region.height as usize)
+        {
+            text.highlight(
+                range.start - offset..range.end - offset,
+                Style::default().add_modifier(Modifier::UNDERLINED),
+            );
+        }
+        for diagnostic in self.editor.diagnostics() {
+            let start = pos_to_char(self.editor.text(), diagnostic.start);
+            let end = pos_to_char(self.editor.text(), diagnostic.end);
+            text.highlight(
+                start.saturating_sub(offset)..end.saturating_sub(offset),
+                Style::default()
+                    .fg(diagnostic_color(diagnostic.severity))
+                    .add_modifier(Modifier::UNDERLINED),
+            );
+        }
+        if let Some(range) = self.editor.selection() {
+            text.highlight(
+                range.start.saturating_sub(offset)..range.end.saturating_sub(offset),
+                Style::default().add_modifier(Modifier::REVERSED),
+            );
+        }
+        text.render(frame, region);
+        if self.editor.settings.indent_guides {
+            let lines = first_line..first_line + region.height as usize;
+            let selection = self.editor.selection().unwrap_or_default();
+            let guides = self
+                .editor
+                .indent_guides(lines.clone())
+                .into_iter()
+                .zip(lines)
+                .map(|(columns, y)| {
+                    // Guides would hide that the indentation is selected.
+                    let start = self.editor.text().line_to_char(y);
+                    columns
+                        .into_iter()
+                        .filter(|x| !selection.contains(&(start + x)))
+                        .collect()
+                })
+                .collect();
+            IndentGuides {
+                guides,
+                first_column: self.view_pos().0,
+            }
+            .render(frame, region);
+        }
+        if let (SelectionKind::Line, Some((first, last))) =
+            (self.editor.selection_kind(), self.editor.selected_lines())
+        {
+            // Whole lines are highlighted right across the window, even where they have no text.
+            let rows = first.max(first_line) - first_line..(last + 1).saturating_sub(first_line);
+            for y in rows.take_while(|&y| y < region.height as usize) {
+                frame.set_style(
+                    Style::default().add_modifier(Modifier::REVERSED),
+                    Rect {
+                        top: region.top + y as u16,
+                        height: 1,
+                        ..region
+                    },
+                );
+            }
+        }
+        // The empty line after a final newline is only shown while the cursor is on it.
+        let lines = self
+            .editor
+            .line_count()
+            .max(self.editor.selected_pos().1 + 1);
+        // Lines past the bottom of the region can't leave room for filler.
+        let shown = lines.saturating_sub(first_line).min(region.height as usize);
+        Filler {
+            first_row: text.rows(shown, region.width),
+        }
+        .render(frame, region);
+    }
+
+    /// Draw the text of a `window` which isn't focused.
+    fn render_unfocused(&self, frame: &mut Frame, window: &Window, region: Rect) {
+        let Some(text) = self.editor.buffer_text(window.buffer) else {
+            return;
+        };
+        // The buffer may have been made shorter from another window.
+        let (first_column, first_line) = self.window_view_pos(window.id);
+        let first_line = first_line.min(text.len_lines() - 1);
+        // At least one line is always shown, even if it is empty.
+        let lines = line_count(text).max(1);
+        let mut text = Text::from(text.slice(text.line_to_char(first_line)..));
+        text.wrap(WRAP_MODE);
+        text.scroll(first_column);
+        text.render(frame, region);
+        // Lines past the bottom of the region can't leave room for filler.
+        let shown = lines.saturating_sub(first_line).min(region.height as usize);
+        Filler {
+            first_row: text.rows(shown, region.width),
+        }
+        .render(frame, region);
+    }
+
+    /// Work out where each part of the view goes when it is rendered to `region`.
+    fn regions(&self, region: Rect) -> Regions {
+        let mut command_line = None;
+        let mut region = region;
+        if self.prompt().is_some() {
+            let parts = region.partition(Bottom);
+            command_line = Some(parts[0]);
+            region = parts[1];
+        }
+        let parts = region.partition(Bottom);
+        let mut editor_area = parts[1];
+        let mut message_panel = None;
+        if let Some(message) = self
+            .messages
+            .front()
+            .filter(|message| message.text.contains('\n'))
+        {
+            let height = (message.text.lines().count() as u16).min(editor_area.height);
+            let (rest, panel) = editor_area.split_vertical(editor_area.height - height);
+            message_panel = Some(panel);
+            editor_area = rest;
+        }
+        let mut list_panel = None;
+        if let Some(list) = self.editor.list() {
+            // The title takes up a row above the entries.
+            let height = (list.items.len() as u16 + 1).min(editor_area.height);
+            let (rest, panel) = editor_area.split_vertical(editor_area.height - height);
+            list_panel = Some(panel);
+            editor_area = rest;
+        }
+        Regions {
+            bottom_bar: parts[0],
+            command_line,
+            message_panel,
+            list_panel,
+            windows: editor_area.partition(Stacked {
+                weights: &self.editor.layout().weights(),
+                min: MIN_WINDOW_HEIGHT,
+            }),
+        }
+    }
+
+    /// The rows each window takes up on the screen, from top to bottom.
+    fn window_heights(&self) -> Vec<u16> {
+        let windows = self.regions(self.screen()).windows;
+        windows.iter().map(|area| area.height).collect()
+    }
+
+    /// The whole of the terminal the editor is drawn to.
+    fn screen(&self) -> Rect {
+        Rect {
+            top: 0,
+            left: 0,
+            height: self.size.1,
+            width: self.size.0,
+        }
+    }
+
+    /// Make the focused window a row taller, or shorter if `grow` isn't set, like vim's
+    /// `Ctrl-w +` and `Ctrl-w -`.
+    ///
+    /// The row is taken from or given to the nearest window below, or above if there is none
+    /// below. No window is made shorter than [`MIN_WINDOW_HEIGHT`].
+    pub fn resize_window(&mut self, grow: bool) {
+        let mut heights = self.window_heights();
+        let focused = self.editor.layout().focused();
+        let mut others = (focused + 1..heights.len()).chain((0..focused).rev());
+        let neighbour = if grow {
+            others.find(|&i| heights[i] > MIN_WINDOW_HEIGHT)
+        } else {
+            others
+                .next()
+                .filter(|_| heights[focused] > MIN_WINDOW_HEIGHT)
+        };
+        let Some(neighbour) = neighbour else {
+            return;
+        };
+        let (taller, shorter) = if grow {
+            (focused, neighbour)
+        } else {
+            (neighbour, focused)
+        };
+        heights[taller] += 1;
+        heights[shorter] -= 1;
+        // Now the weights are rows, the windows keep their proportions if the screen is resized.
+        let weights: Vec<usize> = heights.into_iter().map(usize::from).collect();
+        self.editor.set_window_weights(&weights);
+        self.redraw = true;
+    }
+
+    /// How many columns the sign column to the left of the focused window takes up.
+    ///
+    /// The column is only there when the focused buffer has diagnostics.
+    fn sign_column_width(&self) -> u16 {
+        if self.editor.diagnostics().is_empty() {
+            0
+        } else {
+            2
+        }
+    }
+
+    /// How many columns the line numbers to the left of the focused window take up, including
+    /// the space after them.
+    ///
+    /// This is wide enough for the number of the last line, so it doesn't change as the cursor
+    /// moves even when the numbers are relative. It is 0 when no numbers are shown.
+    fn number_column_width(&self) -> u16 {
+        let settings = &self.editor.settings;
+        if !settings.number && !settings.relativenumber {
+            return 0;
+        }
+        let digits = self.editor.line_count().max(1).to_string().len() as u16;
+        (digits + 1).max(MIN_NUMBER_WIDTH)
+    }
+
+    /// Where the cursor is drawn when the view is rendered to `region`, or [`None`] if it is
+    /// scrolled out of view.
+    fn cursor_pos(&self, region: Rect) -> Option<(u16, u16)> {
+        let area = self.regions(region).windows[self.editor.layout().focused()];
+        if self.editor.hex_view() {
+            let byte = self.editor.cursor_byte();
+            let row = (byte / BYTES_PER_ROW).checked_sub(self.hex_row)?;
+            return Some((area.left + HexView::column(byte), area.top + row as u16));
+        }
+        let (x, y) = self.selected_pos();
+        let view_pos = self.view_pos();
+        Some((
+            area.left
+                + self.sign_column_width()
+                + self.number_column_width()
+                + x.checked_sub(view_pos.0 as u16)?,
+            area.top + y.checked_sub(view_pos.1 as u16)?,
+        ))
+    }
+
+    /// Show `message` in the status bar, styled by its `severity`.
+    ///
+    /// The message goes away when [`EditorView::clear_message`] is called, or on its own
+    /// [`MESSAGE_TIMEOUT`] after it is first shown. Messages with more than one line don't go
+    /// away on their own, so they can be read. If a message is already being shown, `message`
+    /// waits for it to go away first.
+    pub fn show_message(&mut self, message: String, severity: Severity) {
+        self.messages.push_back(StatusMessage {
+            text: message,
+            severity,
+            expires: None,
         });
+        if self.messages.len() == 1 {
+            self.start_message_timer(Instant::now());
+        }
+        self.redraw = true;
+    }
+
+    /// Stop showing every message from [`EditorView::show_message`].
+    pub fn clear_message(&mut self) {
+        if !self.messages.is_empty() {
+            self.messages.clear();
+            self.redraw = true;
+        }
+    }
+
+    /// When the message being shown will go away on its own, if it will.
+    pub fn message_expiry(&self) -> Option<Instant> {
+        self.messages.front()?.expires
+    }
 
-        let mut text = Text::from({
-            let text = self.editor.text();
-            let idx = text.line_to_char(self.view_pos.1);
-            text.slice(idx..)
+    /// Stop showing messages which have been shown for [`MESSAGE_TIMEOUT`] by `now`, moving on to
+    /// the next ones.
+    pub fn expire_messages(&mut self, now: Instant) {
+        while self.message_expiry().is_some_and(|expires| expires <= now) {
+            self.messages.pop_front();
+            self.start_message_timer(now);
+            self.redraw = true;
+        }
+    }
+
+    /// Start the timer for the message being shown to go away at `now`.
+    fn start_message_timer(&mut self, now: Instant) {
+        if let Some(message) = self.messages.front_mut() {
+            if !message.text.contains('\n') {
+                message.expires = Some(now + MESSAGE_TIMEOUT);
+            }
+        }
+    }
+
+    /// Handles the resizing of the editor view, which needs redrawing afterwards.
+    pub fn resize(&mut self, new_size: (u16, u16)) {
+        self.size = new_size;
+        self.update_scroll();
+        self.redraw = true;
+    }
+
+    /// Keep [`Settings::scroll`] at half the height of the focused window, unless it has been set
+    /// to something else since it last was.
+    ///
+    /// [`Settings::scroll`]: not_vim::settings::Settings::scroll
+    fn update_scroll(&mut self) {
+        let half = self.focused_height() / 2;
+        if self.editor.settings.scroll == self.auto_scroll {
+            self.editor.settings.scroll = half;
+        }
+        self.auto_scroll = half;
+    }
+
+    /// Move the cursor and the focused window down [`Settings::scroll`] lines, or up if `down`
+    /// isn't set, like vim's `Ctrl-d` and `Ctrl-u`.
+    ///
+    /// The cursor stops at the first or last line. The window stops scrolling down once the last
+    /// line is in the bottom row.
+    ///
+    /// [`Settings::scroll`]: not_vim::settings::Settings::scroll
+    pub fn scroll_half_page(&mut self, down: bool) {
+        let amount = self.editor.settings.scroll.max(1);
+        let y = self.editor.selected_pos().1;
+        let last = self.editor.line_count().saturating_sub(1);
+        let height = self.focused_height();
+        let id = self.focused_window_id();
+        let view_pos = self.view_positions.entry(id).or_default();
+        if down {
+            let bottom = last.saturating_sub(height.saturating_sub(1));
+            view_pos.1 = (view_pos.1 + amount).min(bottom).max(view_pos.1);
+            self.editor.go_to_line((y + amount).min(last));
+        } else {
+            view_pos.1 = view_pos.1.saturating_sub(amount);
+            self.editor.go_to_line(y.saturating_sub(amount));
+        }
+        self.redraw = true;
+    }
+
+    /// The width and height of the terminal the editor is drawn to.
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Mark the view as needing to be drawn again, after the editor has changed.
+    pub fn request_redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Mark `lines` of the focused buffer as needing to be drawn again, after they were edited.
+    ///
+    /// Unlike [`EditorView::request_redraw`], this lets [`EditorView::take_redraw`] draw just
+    /// those lines if nothing else has changed.
+    pub fn redraw_lines(&mut self, lines: Range<usize>) {
+        self.changed_lines = Some(match self.changed_lines.take() {
+            Some(changed) => changed.start.min(lines.start)..changed.end.max(lines.end),
+            None => lines,
         });
-        text.wrap(not_vim::config::WRAP_MODE);
-        text.render(frame, editor_area);
     }
 
-    /// Handles the resizing of the editor view.
+    /// The lines of the focused buffer which handling `message` will change, if it won't change
+    /// anything else which is drawn besides the cursor and the status bar.
     ///
-    /// Currently this involves moving the screen when the cursor goes off the end of the screen on
-    /// the top or bottom.
-    pub fn resize(&mut self, new_size: (u16, u16)) {
+    /// This is only worked out for typing in insert mode. Adding or removing a line break moves
+    /// every line after it, so they all change.
+    pub fn lines_changed_by(&self, message: &Message) -> Option<Range<usize>> {
+        // Typing in to a blank buffer hides the whole start screen.
+        if self.editor.mode != Mode::Insert || self.shows_start_screen() {
+            return None;
+        }
+        let (x, y) = self.editor.selected_pos();
+        match message {
+            Message::Char(_) | Message::TransposeChars => Some(y..y + 1),
+            Message::Backspace if x > 0 => Some(y..y + 1),
+            Message::Backspace => Some(y.saturating_sub(1)..usize::MAX),
+            Message::Enter => Some(y..usize::MAX),
+            _ => None,
+        }
+    }
+
+    /// What needs to be drawn again, if anything, marking it as drawn. The focused window is
+    /// scrolled to the cursor first, since that decides what is shown.
+    ///
+    /// Only [`Redraw::Lines`] need drawing if they were the only thing to change, through
+    /// [`EditorView::redraw_lines`], and no window besides the focused one shows them. Otherwise
+    /// the whole view is drawn.
+    pub fn take_redraw(&mut self) -> Option<Redraw> {
+        let changed_lines = self.changed_lines.take();
+        let redraw = std::mem::take(&mut self.redraw);
+        if !redraw && changed_lines.is_none() {
+            return None;
+        }
+        self.scroll_to_cursor();
+        let state = self.drawn_state();
+        let unchanged = self.drawn.as_ref() == Some(&state);
+        self.drawn = Some(state);
+        let layout = self.editor.layout();
+        let buffer = layout.windows()[layout.focused()].buffer;
+        let shared = layout
+            .windows()
+            .iter()
+            .filter(|window| window.buffer == buffer)
+            .count()
+            > 1;
+        // Guides on blank lines depend on the lines around them.
+        let lines_only =
+            unchanged && !shared && !self.editor.hex_view() && !self.editor.settings.indent_guides;
+        match changed_lines {
+            Some(lines) if !redraw && lines_only => Some(Redraw::Lines(lines)),
+            _ => Some(Redraw::All),
+        }
+    }
+
+    /// What the view depends on now besides the text of the focused buffer, the cursor and the
+    /// status bar.
+    fn drawn_state(&self) -> DrawnState {
+        let layout = self.editor.layout();
+        DrawnState {
+            size: self.size,
+            mode: self.editor.mode,
+            settings: self.editor.settings.clone(),
+            windows: layout.windows().to_vec(),
+            focused: layout.focused(),
+            view_pos: self.view_pos(),
+            gutter: (self.sign_column_width(), self.number_column_width()),
+            relative_to: self
+                .editor
+                .settings
+                .relativenumber
+                .then_some(self.editor.selected_pos().1),
+            selection: self.editor.selection(),
+            message: self.messages.front().map(|message| message.text.clone()),
+            list: self.editor.list().is_some().then_some(self.list_offset),
+        }
+    }
+
+    /// Move the focused window when the cursor goes off the end of it on any side. It is only
+    /// scrolled sideways when lines aren't wrapped.
+    ///
+    /// The list being picked from is scrolled in the same way when its selection goes out of view.
+    pub fn scroll_to_cursor(&mut self) {
+        match (self.editor.list(), self.regions(self.screen()).list_panel) {
+            (Some(list), Some(panel)) => {
+                let mut view = ListPanel {
+                    list,
+                    offset: self.list_offset,
+                }
+                .view();
+                // The title takes up the top row.
+                view.scroll_into_view(panel.height.saturating_sub(1));
+                self.list_offset = view.offset;
+            }
+            _ => self.list_offset = 0,
+        }
+
+        let windows = self.editor.layout().windows();
+        self.view_positions
+            .retain(|&id, _| windows.iter().any(|window| window.id == id));
+        let height = self.focused_height();
+        self.update_scroll();
+
+        let row = self.editor.cursor_byte() / BYTES_PER_ROW;
+        if row < self.hex_row {
+            self.hex_row = row;
+        }
+        if height > 0 && row >= self.hex_row + height {
+            self.hex_row = row + 1 - height;
+        }
+
+        let width = self.focused_width();
         let editor_pos = self.editor.selected_pos();
-        if editor_pos.1 < self.view_pos.1 {
-            self.view_pos.1 = editor_pos.1;
+        let id = self.focused_window_id();
+        let view_pos = self.view_positions.entry(id).or_default();
+        if editor_pos.1 < view_pos.1 {
+            view_pos.1 = editor_pos.1;
+        }
+        if height > 0 && editor_pos.1 >= view_pos.1 + height {
+            view_pos.1 = editor_pos.1 + 1 - height;
         }
-        // +1 because of line at the bottom for status bar.
-        if editor_pos.1 - self.view_pos.1 + 1 >= new_size.1 as usize {
-            self.view_pos.1 = editor_pos.1 + 2 - new_size.1 as usize;
+        if matches!(WRAP_MODE, WrapMode::Wrap) {
+            view_pos.0 = 0;
+        } else {
+            if editor_pos.0 < view_pos.0 {
+                view_pos.0 = editor_pos.0;
+            }
+            if width > 0 && editor_pos.0 >= view_pos.0 + width {
+                view_pos.0 = editor_pos.0 + 1 - width;
+            }
         }
     }
 
-    /// Get the current view position of the editor view
+    /// How many columns of text the focused window has, besides its sign and number columns.
+    fn focused_width(&self) -> usize {
+        let width = self.regions(self.screen()).windows[self.editor.layout().focused()].width;
+        width.saturating_sub(self.sign_column_width() + self.number_column_width()) as usize
+    }
+
+    /// How many rows of text the focused window has.
+    fn focused_height(&self) -> usize {
+        let height = self.window_heights()[self.editor.layout().focused()];
+        if self.editor.layout().windows().len() > 1 {
+            // The bar with the file name takes up the bottom row.
+            return height.saturating_sub(1) as usize;
+        }
+        height as usize
+    }
+
+    /// Get the current view position of the focused window.
     pub fn view_pos(&self) -> (usize, usize) {
-        self.view_pos
+        self.window_view_pos(self.focused_window_id())
+    }
+
+    /// The view position of the window `id`.
+    fn window_view_pos(&self, id: usize) -> (usize, usize) {
+        self.view_positions.get(&id).copied().unwrap_or_default()
+    }
+
+    /// The ID of the focused window.
+    fn focused_window_id(&self) -> usize {
+        let layout = self.editor.layout();
+        layout.windows()[layout.focused()].id
     }
 }
 
@@ -95,6 +793,253 @@ impl DerefMut for EditorView {
     }
 }
 
+/// What needs to be drawn again, from [`EditorView::take_redraw`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redraw {
+    /// Everything, with [`EditorView::render`].
+    All,
+    /// Only these lines of the focused buffer, with [`EditorView::render_lines`].
+    Lines(Range<usize>),
+}
+
+/// Everything besides the text of the focused buffer, the cursor and the status bar which decides
+/// what an [`EditorView`] draws, to tell whether it has changed since the view was last drawn.
+#[derive(Debug, Clone, PartialEq)]
+struct DrawnState {
+    /// See [`EditorView::size`].
+    size: (u16, u16),
+    /// The mode the editor was in.
+    mode: Mode,
+    /// The runtime settings.
+    settings: Settings,
+    /// The windows on the screen.
+    windows: Vec<Window>,
+    /// The index of the focused window.
+    focused: usize,
+    /// See [`EditorView::view_pos`].
+    view_pos: (usize, usize),
+    /// The widths of the sign column and the line numbers.
+    gutter: (u16, u16),
+    /// The line relative line numbers count from, if they are shown.
+    relative_to: Option<usize>,
+    /// See [`Editor::selection`].
+    selection: Option<Range<usize>>,
+    /// The message being shown.
+    message: Option<String>,
+    /// Which entry of the list being picked from is in its top row, if there is one.
+    list: Option<usize>,
+}
+
+/// Where each part of an [`EditorView`] is drawn.
+struct Regions {
+    /// The status bar, or the question in [`Mode::Confirm`].
+    bottom_bar: Rect,
+    /// The row below the status bar for the command line, in [`Mode::Command`] and
+    /// [`Mode::Search`].
+    command_line: Option<Rect>,
+    /// The panel for a message too long for the status bar, if one is being shown.
+    message_panel: Option<Rect>,
+    /// The panel for the list being picked from, if there is one.
+    list_panel: Option<Rect>,
+    /// The area of each window, from top to bottom.
+    windows: Vec<Rect>,
+}
+
+/// The bar below a window showing the name of its file, when there is more than one window.
+struct WindowBar<'a> {
+    /// The file shown in the window, if it has one.
+    fname: Option<&'a str>,
+    /// Whether the window is focused, which makes the bar stand out.
+    focused: bool,
+}
+
+impl WindowBar<'_> {
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        let style = if self.focused {
+            Style::default().fg(Color::Black).bg(Color::White)
+        } else {
+            Style::default().fg(Color::White).bg(Color::DarkGrey)
+        };
+        frame.set_style(style, frame.size());
+        for (x, c) in self.fname.unwrap_or("[No Name]").chars().enumerate() {
+            frame.set_char(c, x as u16, 0);
+        }
+    }
+}
+
+/// The `~` drawn at the start of each row of a window past the end of its buffer, like vim.
+struct Filler {
+    /// The first row past the end of the buffer.
+    first_row: usize,
+}
+
+impl Filler {
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        for y in self.first_row..region.height as usize {
+            frame.set_char('~', 0, y as u16);
+            frame.set_style(
+                Style::default().add_modifier(Modifier::DIM),
+                Rect {
+                    top: y as u16,
+                    left: 0,
+                    height: 1,
+                    width: 1,
+                },
+            );
+        }
+    }
+}
+
+/// The `│` drawn at each level of indentation when [`Settings::indent_guides`] is on.
+///
+/// [`Settings::indent_guides`]: not_vim::settings::Settings::indent_guides
+struct IndentGuides {
+    /// The columns of the guides in each row, from the top.
+    guides: Vec<Vec<usize>>,
+    /// The column shown at the left of the window, which is more than 0 when scrolled sideways.
+    first_column: usize,
+}
+
+impl IndentGuides {
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        let size = frame.size();
+        for (y, columns) in self.guides.iter().enumerate().take(size.height as usize) {
+            let columns = columns
+                .iter()
+                .filter_map(|x| x.checked_sub(self.first_column))
+                .take_while(|&x| x < size.width as usize);
+            for x in columns {
+                frame.set_char('│', x as u16, y as u16);
+                frame.set_style(
+                    Style::default().add_modifier(Modifier::DIM),
+                    Rect {
+                        top: y as u16,
+                        left: x as u16,
+                        height: 1,
+                        width: 1,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// The column to the left of the focused window showing which lines have diagnostics.
+struct SignColumn {
+    /// The sign for each line with diagnostics, by line.
+    signs: BTreeMap<usize, DiagnosticSeverity>,
+    /// The line shown in the top row.
+    first_line: usize,
+}
+
+impl SignColumn {
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        let rows = self.first_line..self.first_line + region.height as usize;
+        for (&line, &severity) in self.signs.range(rows) {
+            let y = (line - self.first_line) as u16;
+            frame.set_char(severity.sign(), 0, y);
+            frame.set_style(
+                Style::default().fg(diagnostic_color(severity)),
+                Rect {
+                    top: y,
+                    height: 1,
+                    ..frame.size()
+                },
+            );
+        }
+    }
+}
+
+/// The column to the left of the focused window showing the number of each line.
+struct NumberColumn {
+    /// The line shown in the top row.
+    first_line: usize,
+    /// How many lines there are to number.
+    lines: usize,
+    /// The line the cursor is on.
+    cursor_line: usize,
+    /// Whether lines show their line number. See [`Settings::number`].
+    ///
+    /// [`Settings::number`]: not_vim::settings::Settings::number
+    absolute: bool,
+    /// Whether lines show how far they are from the cursor. See [`Settings::relativenumber`].
+    ///
+    /// [`Settings::relativenumber`]: not_vim::settings::Settings::relativenumber
+    relative: bool,
+}
+
+impl NumberColumn {
+    /// See [`frame`].
+    ///
+    /// Numbers are right-aligned, except the cursor's line number when the others are relative,
+    /// which is left-aligned like vim.
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        let size = frame.size();
+        // The last column is a space between the numbers and the text.
+        let width = (size.width as usize).saturating_sub(1);
+        let last = self.lines.min(self.first_line + size.height as usize);
+        for (y, line) in (self.first_line..last).enumerate() {
+            let label = match (self.absolute, self.relative) {
+                (true, true) if line == self.cursor_line => format!("{:<width$}", line + 1),
+                (_, true) => format!("{:>width$}", line.abs_diff(self.cursor_line)),
+                _ => format!("{:>width$}", line + 1),
+            };
+            for (x, c) in label.chars().enumerate() {
+                frame.set_char(c, x as u16, y as u16);
+            }
+            if line != self.cursor_line {
+                frame.set_style(
+                    Style::default().fg(Color::DarkGrey),
+                    Rect {
+                        top: y as u16,
+                        height: 1,
+                        ..size
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// The colour diagnostics of `severity` are shown in.
+fn diagnostic_color(severity: DiagnosticSeverity) -> Color {
+    match severity {
+        DiagnosticSeverity::Error => Color::Red,
+        DiagnosticSeverity::Warning => Color::DarkYellow,
+        DiagnosticSeverity::Information => Color::Blue,
+        DiagnosticSeverity::Hint => Color::DarkGrey,
+    }
+}
+
+/// The char index of the `(x, y)` position `pos` in `text`, clamped to the end of the line and of
+/// the text.
+fn pos_to_char(text: RopeSlice, (x, y): (usize, usize)) -> usize {
+    if y >= text.len_lines() {
+        return text.len_chars();
+    }
+    let line_len = trim_newlines(text.line(y)).len_chars();
+    text.line_to_char(y) + x.min(line_len)
+}
+
 /// Placeholder struct for the bottom status bar of the editor.
 ///
 /// Does not contain any information about the contents of the status_bar, but rather contains the
@@ -106,12 +1051,910 @@ impl StatusBar {
     /// See [`frame`].
     ///
     /// [`frame`]: crate::tui::frame
-    fn render(&self, frame: &mut Frame, region: Rect, position: (u16, u16)) {
-        let bottom = region.top + region.height - 1;
+    ///
+    /// If there is a `message`, it is shown at the left of the status bar, coloured by its
+    /// [`Severity`]. The cursor position
+    /// of `editor` is shown at the right, followed by `[+]` if the buffer is modified. If the
+    /// buffer has mixed indentation, a sign with the first offending line is shown before the
+    /// position. So is the column at the left of the window when `first_column` shows it is
+    /// scrolled sideways, like `< col 41`.
+    fn render(
+        &self,
+        frame: &mut Frame,
+        region: Rect,
+        editor: &Editor,
+        first_column: usize,
+        message: Option<&StatusMessage>,
+    ) {
+        let mut frame = frame.sub(region);
+        let region = frame.size();
         frame.set_style(Style::default().fg(Color::Black).bg(Color::White), region);
-        let position = format!("{}:{}", position.1 + 1, position.0 + 1);
+        if let Some(message) = message {
+            let width = message.text.chars().count() as u16;
+            let style = match message.severity.color() {
+                Some(color) => Style::default().fg(Color::White).bg(color),
+                None => Style::default().fg(Color::Black).bg(Color::White),
+            };
+            frame.set_style(style, Rect { width, ..region });
+            for (x, c) in message.text.chars().enumerate() {
+                frame.set_char(c, x as u16, 0);
+            }
+        }
+        let mut signs = Vec::new();
+        if let Some(line) = editor.mixed_indentation() {
+            signs.push(format!("[mixed indent {}]", line + 1));
+        }
+        if first_column > 0 {
+            signs.push(format!("< col {}", first_column + 1));
+        }
+        if !signs.is_empty() {
+            let sign = signs.join(" ");
+            let start = region
+                .width
+                .saturating_sub(16 + sign.chars().count() as u16);
+            for (x, c) in sign.chars().enumerate() {
+                frame.set_char(c, start + x as u16, 0);
+            }
+        }
+        let (x, y) = editor.selected_pos();
+        let mut position = format!("{}:{}", y + 1, x + 1);
+        if editor.is_modified() {
+            position.push_str(" [+]");
+        }
         for (x, c) in position.chars().enumerate() {
-            frame.set_char(c, region.width - 15 + x as u16, bottom)
+            frame.set_char(c, region.width.saturating_sub(15) + x as u16, 0)
+        }
+    }
+}
+
+/// The command line being typed in while in [`Mode::Command`] or [`Mode::Search`], after its
+/// prompt.
+struct CommandLine<'a>(char, &'a str);
+
+impl CommandLine<'_> {
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        for (x, c) in std::iter::once(self.0).chain(self.1.chars()).enumerate() {
+            frame.set_char(c, x as u16, 0);
+        }
+        frame.set_cursor(self.1.chars().count() as u16 + 1, 0);
+    }
+}
+
+/// The version of the editor and a few keys to get started, shown in the middle of a window over
+/// a blank buffer. See [`START_SCREEN`].
+struct StartScreen;
+
+impl StartScreen {
+    /// The keys shown, along with what they do.
+    const KEYS: [(&'static str, &'static str); 3] = [
+        ("i", "start typing"),
+        (":w name<Enter>", "write to a file"),
+        (":q<Enter>", "quit"),
+    ];
+
+    /// See [`frame`]. Nothing is drawn if it doesn't all fit.
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let key_width = Self::KEYS
+            .iter()
+            .map(|(key, _)| key.len())
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec![
+            format!("not-vim {}", env!("CARGO_PKG_VERSION")),
+            String::new(),
+        ];
+        lines.extend(
+            Self::KEYS
+                .iter()
+                .map(|(key, action)| format!("{key:key_width$}  {action}")),
+        );
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u16;
+        let height = lines.len() as u16;
+        if width > region.width || height > region.height {
+            return;
+        }
+        let mut frame = frame.sub(region);
+        let top = (region.height - height) / 2;
+        let left = (region.width - width) / 2;
+        for (y, line) in lines.iter().enumerate() {
+            // The title is centred over the keys.
+            let indent = match y {
+                0 => (width - line.len() as u16) / 2,
+                _ => 0,
+            };
+            for (x, c) in line.chars().enumerate() {
+                frame.set_char(c, left + indent + x as u16, top + y as u16);
+            }
+        }
+    }
+}
+
+/// The question asked in [`Mode::Confirm`] before running a command.
+struct Question<'a>(&'a str);
+
+impl Question<'_> {
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        for (x, c) in self.0.chars().enumerate() {
+            frame.set_char(c, x as u16, 0);
+        }
+        frame.set_cursor(self.0.chars().count() as u16 + 1, 0);
+    }
+}
+
+/// How serious a message from [`EditorView::show_message`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Something the user asked to see, like the output of a command.
+    Info,
+    /// Something the user should know about, but which didn't stop anything from happening.
+    Warning,
+    /// Something which went wrong.
+    Error,
+}
+
+impl Severity {
+    /// The colour messages of this severity are shown in, if they stand out from the status bar.
+    fn color(self) -> Option<Color> {
+        match self {
+            Self::Info => None,
+            Self::Warning => Some(Color::DarkYellow),
+            Self::Error => Some(Color::Red),
+        }
+    }
+}
+
+/// A message from [`EditorView::show_message`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StatusMessage {
+    /// The text of the message.
+    text: String,
+    /// How serious the message is.
+    severity: Severity,
+    /// When the message goes away on its own, once it is being shown.
+    expires: Option<Instant>,
+}
+
+/// A message too long to fit in the status bar, shown one line per row.
+struct MessagePanel<'a>(&'a StatusMessage);
+
+impl MessagePanel<'_> {
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        let style = match self.0.severity.color() {
+            Some(color) => Style::default().fg(color),
+            None => Style::default(),
+        };
+        frame.set_style(style, frame.size());
+        for (y, line) in self.0.text.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                frame.set_char(c, x as u16, y as u16);
+            }
+        }
+    }
+}
+
+/// A [`List`] being picked from in [`Mode::List`], shown below its title.
+///
+/// The selected entry is highlighted. If the entries don't all fit, the list is scrolled so the
+/// selected one can be seen.
+struct ListPanel<'a> {
+    /// The list being picked from.
+    list: &'a List,
+    /// The index of the entry in the top row, as of the list last being scrolled.
+    offset: usize,
+}
+
+impl<'a> ListPanel<'a> {
+    /// The entries of the list, without the title.
+    fn view(&self) -> ListView<'a> {
+        ListView {
+            items: &self.list.items,
+            styles: &[],
+            selected: self.list.selected,
+            offset: self.offset,
+        }
+    }
+
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    fn render(&self, frame: &mut Frame, region: Rect) {
+        let (title, entries) = region.split_vertical(1);
+        let mut title_frame = frame.sub(title);
+        title_frame.set_style(Style::default(), title_frame.size());
+        for (x, c) in self.list.title.chars().enumerate() {
+            title_frame.set_char(c, x as u16, 0);
+        }
+        // Lines of a diff are coloured by whether they were added or removed.
+        let styles: Vec<Style> = self
+            .list
+            .changes()
+            .into_iter()
+            .map(|change| match change {
+                Change::Same => Style::default(),
+                Change::Added => Style::default().fg(Color::Green),
+                Change::Removed => Style::default().fg(Color::Red),
+            })
+            .collect();
+        ListView {
+            styles: &styles,
+            ..self.view()
+        }
+        .render(frame, entries);
+    }
+}
+
+/// A bar showing the [`Progress`] of some long-running operation.
+#[derive(Debug)]
+pub struct ProgressBar<'a> {
+    /// What the operation is.
+    pub label: &'a str,
+    /// How far along the operation is.
+    pub progress: Progress,
+}
+
+impl ProgressBar<'_> {
+    /// See [`frame`].
+    ///
+    /// The bar is drawn in the bottom row of `region`, styled the same as the status bar.
+    ///
+    /// [`frame`]: crate::tui::frame
+    pub fn render(&self, frame: &mut Frame, region: Rect) {
+        let region = region.partition(Bottom)[0];
+        let mut frame = frame.sub(region);
+        let width = frame.size().width;
+        frame.set_style(
+            Style::default().fg(Color::Black).bg(Color::White),
+            frame.size(),
+        );
+
+        let percent = format!(" {:>3}%", self.progress.percent());
+        let bar_width = width.saturating_sub(self.label.chars().count() as u16 + 8) as usize;
+        let filled = bar_width * self.progress.percent() as usize / 100;
+        let bar = std::iter::once('[')
+            .chain(std::iter::repeat_n('#', filled))
+            .chain(std::iter::repeat_n(' ', bar_width - filled))
+            .chain(std::iter::once(']'));
+        let line = self
+            .label
+            .chars()
+            .chain([' '])
+            .chain(bar)
+            .chain(percent.chars());
+        for (x, c) in line.enumerate() {
+            frame.set_char(c, x as u16, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_util::TempFile, tui::TestScreen};
+    use not_vim::editor::diagnostic::Diagnostic;
+
+    #[test]
+    fn rendering_long_messages() {
+        let mut screen = TestScreen::new(20, 5);
+        let mut view = EditorView::new(Editor::new_empty());
+        view.show_message("one\ntwo".to_owned(), Severity::Info);
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(screen.row(2).trim_end(), "one");
+        assert_eq!(screen.row(3).trim_end(), "two");
+        assert!(!screen.row(4).contains("one"));
+
+        // Long messages stay until they are cleared, so they can be read.
+        assert_eq!(view.message_expiry(), None);
+        view.expire_messages(Instant::now() + MESSAGE_TIMEOUT * 2);
+        assert!(view.messages.front().is_some());
+    }
+
+    #[test]
+    fn expiring_messages() {
+        let mut screen = TestScreen::new(20, 3);
+        let mut view = EditorView::new(Editor::new_empty());
+        let start = Instant::now();
+        view.show_message("oops".to_owned(), Severity::Error);
+        view.show_message("fyi".to_owned(), Severity::Info);
+        let draw = |screen: &mut TestScreen, view: &EditorView| {
+            screen.draw(|f| {
+                view.render(f, f.size());
+            });
+        };
+        draw(&mut screen, &view);
+        assert!(screen.row(2).starts_with("oops"));
+        assert_eq!(
+            screen.style(0, 2),
+            Style::default().fg(Color::White).bg(Color::Red)
+        );
+        assert_eq!(
+            screen.style(4, 2),
+            Style::default().fg(Color::Black).bg(Color::White)
+        );
+
+        // The next message waits for the first to expire.
+        view.take_redraw();
+        view.expire_messages(start);
+        assert!(view.take_redraw().is_none());
+        let expired = view.message_expiry().expect("the message should expire");
+        view.expire_messages(expired);
+        assert!(view.take_redraw().is_some());
+        draw(&mut screen, &view);
+        assert!(screen.row(2).starts_with("fyi"));
+        assert_eq!(
+            screen.style(0, 2),
+            Style::default().fg(Color::Black).bg(Color::White)
+        );
+
+        view.expire_messages(expired + MESSAGE_TIMEOUT);
+        assert_eq!(view.message_expiry(), None);
+        draw(&mut screen, &view);
+        assert!(!screen.row(2).contains("fyi"));
+    }
+
+    #[test]
+    fn rendering_the_command_line() {
+        let mut screen = TestScreen::new(20, 4);
+        let mut view = EditorView::new(
+            Editor::from_reader("one\ntwo\nthree".as_bytes()).expect("the text should be read"),
+        );
+        view.set_mode(Mode::Command);
+        for c in "set hex".chars() {
+            view.command_push(c);
+        }
+        let cursor = screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(screen.row(3).trim_end(), ":set hex");
+        assert_eq!(cursor, Some((8, 3)));
+        // The status bar is pushed up, and the text makes room for it.
+        assert_eq!(
+            screen.style(0, 2),
+            Style::default().fg(Color::Black).bg(Color::White)
+        );
+        assert_eq!(screen.row(1).trim_end(), "two");
+        assert!(!screen.row(2).contains("three"));
+
+        view.set_mode(Mode::Search);
+        view.command_push('t');
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(screen.row(3).trim_end(), "/t");
+
+        view.set_mode(Mode::Normal);
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(screen.row(2).trim_end(), "three");
+    }
+
+    #[test]
+    fn rendering_the_start_screen() {
+        let mut screen = TestScreen::new(40, 9);
+        let mut view = EditorView::new(Editor::new_empty());
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        let title = format!("not-vim {}", env!("CARGO_PKG_VERSION"));
+        assert!(screen.row(1).contains(&title));
+        assert!(screen.row(5).contains(":q<Enter>"));
+
+        // Typing hides it, even after the text is deleted again.
+        view.set_mode(Mode::Insert);
+        assert_eq!(view.lines_changed_by(&Message::Char('a')), None);
+        view.push('a');
+        view.backspace();
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert!((0..9).all(|y| !screen.row(y).contains(&title)));
+
+        // A buffer with text in it doesn't show it.
+        let view = EditorView::new(
+            Editor::from_reader("one".as_bytes()).expect("the text should be read"),
+        );
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert!(!screen.row(1).contains(&title));
+    }
+
+    #[test]
+    fn rendering_the_mixed_indentation_sign() {
+        let mut screen = TestScreen::new(40, 3);
+        let mut view = EditorView::new(Editor::new_empty());
+        for c in "\ta\n  b".chars() {
+            match c {
+                '\n' => view.newline(),
+                c => view.push(c),
+            }
+        }
+        view.commit();
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert!(screen.row(2).contains("[mixed indent 2]"));
+        assert!(screen.row(2).contains("2:4 [+]"));
+    }
+
+    #[test]
+    fn rendering_lists() {
+        let mut screen = TestScreen::new(30, 6);
+        let mut view = EditorView::new(Editor::new_empty());
+        view.set_mode(Mode::Insert);
+        view.push('a');
+        view.set_mode(Mode::Normal);
+        view.show_changes().expect("a change was made");
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(screen.row(3).trim_end(), " change line  col text");
+        assert_eq!(screen.row(4).trim_end(), ">  0     1    1 a");
+        assert_eq!(
+            screen.style(0, 4),
+            Style::default().add_modifier(Modifier::REVERSED)
+        );
+        assert_eq!(screen.style(0, 3), Style::default());
+    }
+
+    #[test]
+    fn rendering_split_windows() {
+        let mut screen = TestScreen::new(10, 7);
+        let mut view = EditorView::new(
+            Editor::from_reader("one\ntwo\nthree".as_bytes()).expect("the text is UTF-8"),
+        );
+        view.resize((10, 7));
+        view.move_down();
+        view.split_window();
+        view.move_down();
+        view.scroll_to_cursor();
+        let draw = |screen: &mut TestScreen, view: &EditorView| {
+            screen.draw(|f| {
+                view.render(f, f.size());
+            })
+        };
+        // The top window is a row short of the whole file, so it scrolls.
+        assert_eq!(draw(&mut screen, &view), Some((0, 1)));
+        assert_eq!(screen.row(0).trim_end(), "two");
+        assert_eq!(screen.row(2).trim_end(), "[No Name]");
+        assert_eq!(screen.row(3).trim_end(), "one");
+        assert_eq!(screen.row(5).trim_end(), "[No Name]");
+        assert_eq!(
+            screen.style(0, 2),
+            Style::default().fg(Color::Black).bg(Color::White)
+        );
+        assert_eq!(
+            screen.style(0, 5),
+            Style::default().fg(Color::White).bg(Color::DarkGrey)
+        );
+
+        view.next_window();
+        view.scroll_to_cursor();
+        assert_eq!(draw(&mut screen, &view), Some((0, 4)));
+        assert_eq!(
+            screen.style(0, 5),
+            Style::default().fg(Color::Black).bg(Color::White)
+        );
+
+        // Closing one of two windows leaves the other focused with the whole screen.
+        view.close_window().expect("there are two windows");
+        view.scroll_to_cursor();
+        assert_eq!(draw(&mut screen, &view), Some((0, 1)));
+        assert_eq!(screen.row(0).trim_end(), "two");
+        assert_eq!(screen.row(1).trim_end(), "three");
+        assert!(!screen.row(2).contains("[No Name]"));
+        assert!(screen.row(6).contains("3:1"));
+    }
+
+    #[test]
+    fn resizing_windows() {
+        let mut view = EditorView::new(Editor::new_empty());
+        view.resize((10, 11));
+        view.split_window();
+        view.split_window();
+        // The second split halved the top window.
+        assert_eq!(view.window_heights(), [3, 2, 5]);
+
+        // The window below can't give up any rows, so the one below that does.
+        view.resize_window(true);
+        assert_eq!(view.window_heights(), [4, 2, 4]);
+        view.resize_window(true);
+        assert_eq!(view.window_heights(), [5, 2, 3]);
+        view.resize_window(false);
+        assert_eq!(view.window_heights(), [4, 3, 3]);
+
+        // The proportions are kept when the screen changes size.
+        view.resize((10, 21));
+        assert_eq!(view.window_heights(), [8, 6, 6]);
+
+        view.focus_window(2);
+        for _ in 0..10 {
+            view.resize_window(false);
+        }
+        assert_eq!(view.window_heights()[2], MIN_WINDOW_HEIGHT);
+        view.equalize_windows();
+        assert_eq!(view.window_heights(), [7, 7, 6]);
+        assert_eq!(view.layout().weights(), [1, 1, 1]);
+    }
+
+    #[test]
+    fn rendering_binary_files() {
+        let file = TempFile::new("binary");
+        let fname = file.path();
+        std::fs::write(fname, b"\x7fELF\0\x1b[2J").expect("the file should be writable");
+        let view = EditorView::new(Editor::open(fname).expect("the file should be readable"));
+        assert!(view.is_binary());
+        assert!(view.file_info().contains("\" [binary] 1 line"));
+        let mut screen = TestScreen::new(20, 2);
+        let mut draw = |view: &EditorView| {
+            screen.draw(|f| {
+                view.render(f, f.size());
+            });
+            screen.row(0).trim_end().to_owned()
+        };
+        if not_vim::config::HEX_BINARY {
+            assert_eq!(draw(&view), "00000000: 7f45 4c46");
+        } else {
+            assert_eq!(draw(&view), "·ELF··[2J");
+        }
+
+        // Control chars in text files can't reach the terminal either.
+        let view = EditorView::new(
+            Editor::from_reader("a\x1b[2Jb\tc".as_bytes()).expect("the text should be read"),
+        );
+        assert_eq!(draw(&view), "a·[2Jb\tc");
+    }
+
+    #[test]
+    fn rendering_filler_lines() {
+        let mut view = EditorView::new(
+            Editor::from_reader("one\ntwo\nthree\n".as_bytes()).expect("the text should be read"),
+        );
+        let mut screen = TestScreen::new(10, 11);
+        let mut draw = |view: &EditorView| {
+            screen.draw(|f| {
+                view.render(f, f.size());
+            });
+            (0..10)
+                .map(|y| screen.row(y).trim_end().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let rows = draw(&view);
+        assert_eq!(rows[..3], ["one", "two", "three"]);
+        assert!(rows[3..].iter().all(|row| row == "~"));
+
+        // The empty line after the final newline is shown once the cursor is on it.
+        for _ in 0..3 {
+            view.move_down();
+        }
+        assert_eq!(draw(&view)[3..5], ["", "~"]);
+        assert_eq!(
+            screen.style(0, 4),
+            Style::default().add_modifier(Modifier::DIM)
+        );
+    }
+
+    #[test]
+    fn rendering_line_numbers() {
+        let text: String = (1..=200).map(|i| format!("line {i}\n")).collect();
+        let mut view =
+            EditorView::new(Editor::from_reader(text.as_bytes()).expect("the text should be read"));
+        view.settings.number = true;
+        view.resize((20, 5));
+        let mut screen = TestScreen::new(20, 5);
+        let mut draw = |view: &mut EditorView| {
+            view.scroll_to_cursor();
+            let cursor = screen.draw(|f| {
+                view.render(f, f.size());
+            });
+            let rows = (0..4).map(|y| screen.row(y).trim_end().to_owned());
+            (rows.collect::<Vec<_>>(), cursor)
+        };
+        let (rows, cursor) = draw(&mut view);
+        assert_eq!(rows[..2], ["  1 line 1", "  2 line 2"]);
+        assert_eq!(cursor, Some((4, 0)));
+
+        // The text stays put as relative offsets go from one digit to three.
+        view.settings.relativenumber = true;
+        view.move_down();
+        let (rows, cursor) = draw(&mut view);
+        assert_eq!(rows[..2], ["  1 line 1", "2   line 2"]);
+        assert_eq!(cursor, Some((4, 1)));
+        view.go_to_line(150);
+        let (rows, cursor) = draw(&mut view);
+        assert_eq!(rows[3], "151 line 151");
+        assert_eq!(cursor, Some((4, 3)));
+        view.go_to_line(199);
+        let (rows, _) = draw(&mut view);
+        assert_eq!(rows[0], "  3 line 197");
+    }
+
+    #[test]
+    fn rendering_indent_guides() {
+        let mut view = EditorView::new(
+            Editor::from_reader("if a {\n        b\n\n    c\n}\n".as_bytes())
+                .expect("the text should be read"),
+        );
+        view.settings.indent_guides = true;
+        view.settings.tab_width = 4;
+        let mut screen = TestScreen::new(20, 6);
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(screen.row(0).trim_end(), "if a {");
+        assert_eq!(screen.row(1).trim_end(), "│   │   b");
+        // The blank line carries on the guides of the line below it.
+        assert_eq!(screen.row(2).trim_end(), "│");
+        assert_eq!(screen.row(3).trim_end(), "│   c");
+        assert_eq!(screen.row(4).trim_end(), "}");
+        assert_eq!(
+            screen.style(4, 1),
+            Style::default().add_modifier(Modifier::DIM)
+        );
+    }
+
+    #[test]
+    fn scrolling_half_pages() {
+        let text: String = (1..=100).map(|i| format!("line {i}\n")).collect();
+        let mut view =
+            EditorView::new(Editor::from_reader(text.as_bytes()).expect("the text should be read"));
+        // The window has 20 rows above the status bar.
+        view.resize((20, 21));
+        assert_eq!(view.settings.scroll, 10);
+        view.scroll_half_page(true);
+        assert_eq!(view.editor.selected_pos(), (0, 10));
+        assert_eq!(view.view_pos(), (0, 10));
+
+        view.editor
+            .execute("set scroll=5".parse().expect("the command should parse"))
+            .expect("the setting should be set");
+        view.scroll_half_page(true);
+        assert_eq!(view.editor.selected_pos(), (0, 15));
+        assert_eq!(view.view_pos(), (0, 15));
+        // Setting it stops resizing from changing it.
+        view.resize((20, 11));
+        assert_eq!(view.settings.scroll, 5);
+
+        // Neither the cursor nor the window go past the ends of the buffer.
+        view.go_to_line(97);
+        view.resize((20, 21));
+        view.scroll_to_cursor();
+        view.scroll_half_page(true);
+        assert_eq!(view.editor.selected_pos(), (0, 99));
+        assert_eq!(view.view_pos(), (0, 80));
+        view.go_to_line(2);
+        view.scroll_to_cursor();
+        view.scroll_half_page(false);
+        assert_eq!(view.editor.selected_pos(), (0, 0));
+        assert_eq!(view.view_pos(), (0, 0));
+
+        // Without being set, it follows the height of the window.
+        let mut view =
+            EditorView::new(Editor::from_reader(text.as_bytes()).expect("the text should be read"));
+        view.resize((20, 11));
+        assert_eq!(view.settings.scroll, 5);
+        view.resize((20, 31));
+        assert_eq!(view.settings.scroll, 15);
+    }
+
+    #[test]
+    fn scrolling_sideways() {
+        let line = "0123456789".repeat(6);
+        let mut view = EditorView::new(
+            Editor::from_reader(format!("{line}\ntwo\n").as_bytes())
+                .expect("the text should be read"),
+        );
+        view.resize((40, 3));
+        let mut screen = TestScreen::new(40, 3);
+        let mut draw = |view: &mut EditorView| {
+            view.scroll_to_cursor();
+            screen.draw(|f| {
+                view.render(f, f.size());
+            });
+            (screen.row(0), screen.row(2))
+        };
+        let (text, status) = draw(&mut view);
+        assert_eq!(text, line[..39].to_owned() + ">");
+        assert!(!status.contains('<'));
+
+        // Going past the right of the window scrolls the text, the cursor and the status bar.
+        view.move_to_line_end();
+        let (text, status) = draw(&mut view);
+        assert_eq!(view.view_pos(), (20, 0));
+        assert_eq!(text, &line[20..]);
+        assert!(status.contains("< col 21"));
+        assert_eq!(view.cursor_pos(view.screen()), Some((39, 0)));
+
+        // Going back to the left scrolls back.
+        view.move_to_line_start();
+        let (text, status) = draw(&mut view);
+        assert_eq!(view.view_pos(), (0, 0));
+        assert_eq!(text, line[..39].to_owned() + ">");
+        assert!(!status.contains('<'));
+    }
+
+    #[test]
+    fn redrawing_only_changed_lines() {
+        let mut view = EditorView::new(
+            Editor::from_reader("one\ntwo\nthree\n".as_bytes()).expect("the text should be read"),
+        );
+        view.resize((20, 5));
+        let mut screen = TestScreen::new(20, 5);
+        view.move_down();
+        view.set_mode(Mode::Insert);
+        assert_eq!(view.take_redraw(), Some(Redraw::All));
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        // Rows which are drawn again lose their mark.
+        screen.update(|f| {
+            for y in 0..4 {
+                f.set_char('#', 19, y);
+            }
+        });
+
+        let type_char = |view: &mut EditorView, c| {
+            let lines = view
+                .lines_changed_by(&Message::Char(c))
+                .expect("typing should only change lines");
+            view.push(c);
+            view.redraw_lines(lines);
+        };
+        type_char(&mut view, 'x');
+        assert_eq!(view.take_redraw(), Some(Redraw::Lines(1..2)));
+        let cursor = screen.update(|f| {
+            view.render_lines(f, f.size(), 1..2);
+        });
+        assert_eq!(screen.row(0), "one                #");
+        assert_eq!(screen.row(1).trim_end(), "xtwo");
+        assert_eq!(screen.row(2), "three              #");
+        assert_eq!(screen.row(3), "~                  #");
+        assert!(screen.row(4).contains("2:2 [+]"));
+        assert_eq!(cursor, Some((1, 1)));
+        assert_eq!(view.take_redraw(), None);
+
+        // Anything else changing means drawing everything again.
+        type_char(&mut view, 'y');
+        view.request_redraw();
+        assert_eq!(view.take_redraw(), Some(Redraw::All));
+        view.split_window();
+        view.request_redraw();
+        view.take_redraw();
+        type_char(&mut view, 'z');
+        assert_eq!(view.take_redraw(), Some(Redraw::All));
+    }
+
+    /// Compares drawing everything with drawing only the line typed on, on a big screen. Run it
+    /// with `cargo test --release --bin notvim -- --ignored --nocapture redrawing`.
+    #[test]
+    #[ignore = "a benchmark, which is only meaningful in release builds"]
+    fn redrawing_lines_benchmark() {
+        const DRAWS: u32 = 1000;
+        let text = "the quick brown fox jumps over the lazy dog\n".repeat(10_000);
+        let mut view =
+            EditorView::new(Editor::from_reader(text.as_bytes()).expect("the text should be read"));
+        view.resize((250, 80));
+        view.set_mode(Mode::Insert);
+        view.take_redraw();
+        let mut screen = TestScreen::new(250, 80);
+
+        let start = Instant::now();
+        for _ in 0..DRAWS {
+            screen.draw(|f| {
+                view.render(f, f.size());
+            });
+        }
+        let all = start.elapsed() / DRAWS;
+        let start = Instant::now();
+        for _ in 0..DRAWS {
+            screen.update(|f| {
+                view.render_lines(f, f.size(), 0..1);
+            });
+        }
+        let lines = start.elapsed() / DRAWS;
+        println!("drawing everything: {all:?}, drawing one line: {lines:?}");
+    }
+
+    #[test]
+    fn rendering_hex() {
+        let mut view = EditorView::new(
+            Editor::from_reader("one\ntwo\nthree\nfour\nfive\n".as_bytes())
+                .expect("the text should be read"),
+        );
+        view.settings.hex = true;
+        // The window is a single row above the status bar.
+        view.resize((70, 2));
+        for _ in 0..4 {
+            view.move_down();
+        }
+        view.scroll_to_cursor();
+        let mut screen = TestScreen::new(70, 2);
+        let cursor = screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        // The cursor is on the `f` of `five`, byte 0x13, so the first row is scrolled past.
+        assert_eq!(
+            screen.row(0).trim_end(),
+            "00000010: 7572 0a66 6976 650a                      ur.five."
+        );
+        assert_eq!(cursor, Some((17, 0)));
+    }
+
+    #[test]
+    fn rendering_diagnostics() {
+        let file = TempFile::new("diagnostics");
+        let fname = file.path();
+        std::fs::write(fname, "let x;\nlet y = x;\n").expect("the file should be writable");
+        let mut view = EditorView::new(Editor::open(fname).expect("the file should be readable"));
+        let diagnostic = |start, end, severity| Diagnostic {
+            start,
+            end,
+            severity,
+            message: String::new(),
+        };
+        view.set_diagnostics(
+            &std::path::absolute(fname).expect("the path should be absolute"),
+            vec![
+                diagnostic((4, 0), (5, 0), DiagnosticSeverity::Warning),
+                diagnostic((8, 1), (9, 1), DiagnosticSeverity::Error),
+            ],
+        );
+        view.move_down();
+        let mut screen = TestScreen::new(20, 4);
+        let cursor = screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(screen.row(0).trim_end(), "W let x;");
+        assert_eq!(screen.row(1).trim_end(), "E let y = x;");
+        assert_eq!(screen.style(0, 0), Style::default().fg(Color::DarkYellow));
+        assert_eq!(
+            screen.style(10, 1),
+            Style::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::UNDERLINED)
+        );
+        assert_eq!(screen.style(9, 1), Style::default());
+        // The text is pushed right by the sign column, and so is the cursor.
+        assert_eq!(cursor, Some((2, 1)));
+    }
+
+    #[test]
+    fn rendering_progress() {
+        let mut screen = TestScreen::new(20, 3);
+        for (done, bar) in [
+            (0, "load [        ]   0%"),
+            (50, "load [####    ]  50%"),
+            (100, "load [########] 100%"),
+        ] {
+            screen.draw(|f| {
+                ProgressBar {
+                    label: "load",
+                    progress: Progress { done, total: 100 },
+                }
+                .render(f, f.size());
+            });
+            assert_eq!(screen.row(2).trim_end(), bar);
+            assert_eq!(screen.row(1).trim_end(), "");
+            assert_eq!(
+                screen.style(19, 2),
+                Style::default().fg(Color::Black).bg(Color::White)
+            );
         }
     }
 }
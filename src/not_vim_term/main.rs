@@ -12,25 +12,27 @@
 use anyhow::Context;
 use args::Args;
 use crossterm::{
-    cursor::SetCursorStyle,
-    event::{read, Event, KeyEventKind},
+    cursor::{Hide, SetCursorStyle, Show},
+    event::{poll, read, DisableFocusChange, EnableFocusChange, Event, KeyEventKind},
     execute,
-    terminal::{
-        self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
-    },
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use editor_view::EditorView;
+use editor_view::{EditorView, ProgressBar, Redraw, Severity};
 use gag::Hold;
 use not_vim::{
-    config::{translate_event, Message},
+    config::{Message, Translator},
     editor::Mode,
+    paste::PasteDetector,
     Editor,
 };
-use std::io;
+use std::{io, ops::ControlFlow, sync::mpsc, thread, time::Instant};
 use tui::Terminal;
 
 mod args;
 mod editor_view;
+#[cfg(test)]
+#[path = "../test_util.rs"]
+mod test_util;
 mod tui;
 
 /// Unit struct which, when dropped, executes LeaveAlternateScreen on stdout.
@@ -45,6 +47,8 @@ impl Drop for AlternateScreenGuard {
         let _ = execute!(
             io::stdout(),
             LeaveAlternateScreen,
+            DisableFocusChange,
+            Show,
             SetCursorStyle::DefaultUserShape
         );
     }
@@ -52,81 +56,153 @@ impl Drop for AlternateScreenGuard {
 
 fn main() {
     if let Err(err) = try_main() {
-        eprintln!("{err:#?}");
+        eprintln!("{err:#}");
     }
 }
 
 /// This is the main function which is extracted out for better error handling.
 fn try_main() -> anyhow::Result<()> {
-    let args = Args::parse_args();
+    let args = Args::parse_args()?;
+    if args.help {
+        println!("{}", args::USAGE);
+        return Ok(());
+    }
+    if args.version {
+        println!("notvim {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
 
     enable_raw_mode().context("Failed to enter raw mode.")?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen).context("Failed to enter alternate screen")?;
     execute!(stdout, SetCursorStyle::SteadyBlock).context("Failed to set cursor style")?;
+    execute!(stdout, EnableFocusChange).context("Failed to enable focus change events")?;
     let _stderr_hold = Hold::stderr().context("Failed to obtain hold on stderr")?;
     let _asg = AlternateScreenGuard;
 
     let mut term = Terminal::new();
-    let editor = match args.file {
-        Some(fname) => {
-            Editor::open(&fname).context("Could not create an editor from the file given")?
+    let mut files = args.files.into_iter();
+    let mut editor = if args.stdin {
+        Editor::from_reader(io::stdin())?
+    } else {
+        match files.next() {
+            Some(fname) => open_with_progress(&mut term, fname)
+                .context("Could not create an editor from the file given")?,
+            None => Editor::new_empty(),
         }
-        None => Editor::new(),
     };
+    editor.settings.readonly = args.readonly;
+    editor.set_mode(not_vim::config::INITIAL_MODE);
+    if let Some(line) = args.line {
+        editor.go_to_line(line.saturating_sub(1));
+    }
     let mut editor_view = EditorView::new(editor);
+    if let Err(err) = editor_view.start_language_server() {
+        editor_view.show_message(format!("{err:#}"), Severity::Warning);
+    }
+    if let Some(warning) = editor_view.indentation_warning() {
+        editor_view.show_message(warning, Severity::Warning);
+    }
+    if editor_view.is_binary() {
+        editor_view.show_message(
+            "Binary file, which can only be viewed".to_owned(),
+            Severity::Warning,
+        );
+    }
+    if files.len() > 0 {
+        editor_view.show_message(
+            format!(
+                "Only one file can be edited at a time, so {} were not opened",
+                files.len()
+            ),
+            Severity::Warning,
+        );
+    }
+    let mut translator = Translator::default();
+    let mut paste_detector = PasteDetector::default();
 
-    loop {
-        term.resize();
-        let size = terminal::size().expect("unable to get the dimensions of the terminal");
-        editor_view.resize(size);
-        term.draw(|f| {
-            editor_view.render(f, f.size());
-            let selected_pos = editor_view.selected_pos();
-            let view_pos = editor_view.view_pos();
-            Some((
-                selected_pos.0 - view_pos.0 as u16,
-                selected_pos.1 - view_pos.1 as u16,
-            ))
-        })?;
+    editor_view.resize(tui::terminal_size());
+    'main: loop {
+        match editor_view.take_redraw() {
+            Some(Redraw::All) => {
+                term.resize(editor_view.size());
+                term.draw(|f| editor_view.render(f, f.size()))?;
+            }
+            Some(Redraw::Lines(lines)) => {
+                term.update(|f| editor_view.render_lines(f, f.size(), lines.clone()))?;
+            }
+            None => {}
+        }
 
-        let Event::Key(event) = read().context("Could not read an event from the terminal")? else {
-            continue;
-        };
-        if !matches!(event.kind, KeyEventKind::Press | KeyEventKind::Repeat) {
-            continue;
-        }
-
-        let message = translate_event(editor_view.editor.mode, event.into());
-        match message {
-            Message::Quit => {
-                break;
-            }
-            Message::Write => {
-                editor_view
-                    .write()
-                    .with_context(|| match editor_view.active_fname() {
-                        Some(fname) => format!("Could not write to file {}", fname),
-                        None => String::from("No file to write to"),
-                    })?;
-            }
-            Message::Enter => editor_view.newline(),
-            Message::Backspace => editor_view.backspace(),
-            Message::Left => editor_view.move_left(),
-            Message::Right => editor_view.move_right(),
-            Message::Up => editor_view.move_up(),
-            Message::Down => editor_view.move_down(),
-            Message::Char(c) => editor_view.push(c),
-            Message::Mode(m) => {
-                editor_view.mode = m;
-                match m {
-                    Mode::Normal => {
-                        execute!(stdout, crossterm::cursor::SetCursorStyle::SteadyBlock)?
+        // Wake up to clear the message once it expires, or to hear from the language server, if
+        // no event comes first.
+        let message_timeout = editor_view
+            .message_expiry()
+            .map(|expires| expires.saturating_duration_since(Instant::now()));
+        let server_timeout = editor_view
+            .language_server_running()
+            .then_some(not_vim::config::LANGUAGE_SERVER_POLL_INTERVAL);
+        if let Some(timeout) = message_timeout.into_iter().chain(server_timeout).min() {
+            if !poll(timeout).context("Could not poll the terminal for events")? {
+                editor_view.expire_messages(Instant::now());
+                poll_language_server(&mut editor_view);
+                continue;
+            }
+        }
+
+        let prev_mode = editor_view.mode;
+        match read().context("Could not read an event from the terminal")? {
+            Event::Key(event)
+                if matches!(event.kind, KeyEventKind::Press | KeyEventKind::Repeat) =>
+            {
+                editor_view.clear_message();
+                editor_view.set_pasting(paste_detector.key(Instant::now()));
+                translator.push(event.into());
+                while let Some(message) = translator.next(editor_view.mode, &editor_view.keymap) {
+                    // Typing only needs the lines it changes drawn again.
+                    let lines = editor_view.lines_changed_by(&message);
+                    editor_view.record(message);
+                    let flow = handle_message(&mut editor_view, message)?;
+                    match lines {
+                        Some(lines) => editor_view.redraw_lines(lines),
+                        None => editor_view.request_redraw(),
+                    }
+                    if flow.is_break() {
+                        break 'main;
                     }
-                    Mode::Insert => execute!(stdout, crossterm::cursor::SetCursorStyle::SteadyBar)?,
                 }
             }
-            Message::None => {}
+            Event::Resize(width, height) => {
+                if handle_message(&mut editor_view, Message::Resize(width, height))?.is_break() {
+                    break 'main;
+                }
+            }
+            Event::FocusLost => {
+                if not_vim::config::HIDE_UNFOCUSED_CURSOR {
+                    execute!(stdout, Hide)?;
+                }
+                if handle_message(&mut editor_view, Message::FocusLost)?.is_break() {
+                    break 'main;
+                }
+            }
+            Event::FocusGained => {
+                execute!(stdout, Show)?;
+                if handle_message(&mut editor_view, Message::FocusGained)?.is_break() {
+                    break 'main;
+                }
+            }
+            _ => continue,
+        }
+
+        if editor_view.mode != prev_mode {
+            match editor_view.mode {
+                Mode::Normal | Mode::Visual | Mode::List => {
+                    execute!(stdout, crossterm::cursor::SetCursorStyle::SteadyBlock)?
+                }
+                Mode::Insert | Mode::Command | Mode::Search | Mode::Confirm => {
+                    execute!(stdout, crossterm::cursor::SetCursorStyle::SteadyBar)?
+                }
+            }
         }
     }
 
@@ -140,3 +216,405 @@ fn try_main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Act on anything the language server has sent, showing any error.
+fn poll_language_server(editor_view: &mut EditorView) {
+    match editor_view.poll_language_server() {
+        Ok(true) => editor_view.request_redraw(),
+        Ok(false) => {}
+        Err(err) => editor_view.show_message(format!("{err:#}"), Severity::Error),
+    }
+}
+
+/// Carry out the action for `message`.
+///
+/// Breaks if the editor should quit.
+fn handle_message(
+    editor_view: &mut EditorView,
+    message: Message,
+) -> anyhow::Result<ControlFlow<()>> {
+    // A count only applies to the command right after it.
+    let count = match message {
+        Message::Count(count) => {
+            editor_view.set_count(count);
+            return Ok(ControlFlow::Continue(()));
+        }
+        _ => editor_view.take_count(),
+    };
+    let times = count.unwrap_or(1);
+    match message {
+        Message::Quit => {
+            if let Err(err) = editor_view.quit(false) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+            if editor_view.quitting() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        Message::Write => {
+            if let Err(err) = editor_view.write(false) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        // Typing in normal mode only happens when editing modelessly, and starts inserting.
+        Message::Char(_) | Message::Enter | Message::Backspace
+            if editor_view.mode == Mode::Normal =>
+        {
+            editor_view.set_mode(Mode::Insert);
+            return handle_message(editor_view, message);
+        }
+        Message::Enter => editor_view.newline(),
+        Message::Backspace => editor_view.backspace(),
+        Message::Left => editor_view.repeat_motion(times, Editor::move_left),
+        Message::Right => editor_view.repeat_motion(times, Editor::move_right),
+        Message::LineStart => editor_view.move_to_line_start(),
+        Message::FirstNonBlank => editor_view.move_to_first_non_blank(),
+        Message::LineEnd => editor_view.move_to_line_end(),
+        Message::Up => editor_view.move_up_by(times),
+        Message::Down => editor_view.move_down_by(times),
+        Message::GotoPercent(percent) => editor_view.goto_percent(percent),
+        Message::GotoTop => editor_view.goto_top(),
+        Message::GotoBottom => match count {
+            Some(line) => editor_view.goto_line_number(line),
+            None => editor_view.goto_bottom(),
+        },
+        Message::WordStart => editor_view.repeat_motion(times, Editor::word_start),
+        Message::WordEnd => editor_view.repeat_motion(times, Editor::word_end),
+        Message::MatchBracket => editor_view.go_to_matching_bracket(),
+        Message::HalfPageDown => editor_view.scroll_half_page(true),
+        Message::HalfPageUp => editor_view.scroll_half_page(false),
+        Message::Char(c) => editor_view.push(c),
+        Message::Mode(m) => editor_view.set_mode(m),
+        Message::VisualLine => editor_view.visual_line_mode(),
+        Message::SelectAll => editor_view.select_all(),
+        Message::CommandChar(c) => editor_view.command_push(c),
+        Message::CommandBackspace => editor_view.command_backspace(),
+        Message::RunCommand => {
+            if let Err(err) = editor_view.run_command_line() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            } else if let Some(output) = editor_view.take_output() {
+                editor_view.show_message(output, Severity::Info);
+            }
+            if editor_view.quitting() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        Message::Confirm(yes) => {
+            if let Err(err) = editor_view.confirm(yes) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            } else if let Some(output) = editor_view.take_output() {
+                editor_view.show_message(output, Severity::Info);
+            }
+            if editor_view.quitting() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        Message::NextMisspelling => editor_view.next_misspelling(),
+        Message::PrevMisspelling => editor_view.prev_misspelling(),
+        Message::SpellGood => {
+            if let Err(err) = editor_view.spell_good() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::SpellBad => {
+            if let Err(err) = editor_view.spell_bad() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::GoToDefinition => {
+            if let Err(err) = editor_view.go_to_definition() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::FileInfo => editor_view.show_message(editor_view.file_info(), Severity::Info),
+        Message::CursorInfo => editor_view.show_message(editor_view.cursor_info(), Severity::Info),
+        Message::Undo => {
+            if let Err(err) = editor_view.undo() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::Redo => {
+            if let Err(err) = editor_view.redo() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::Earlier => {
+            if let Err(err) = editor_view.earlier(1) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::Later => {
+            if let Err(err) = editor_view.later(1) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::DeleteSelection => editor_view.delete_selection(),
+        Message::ReplaceSelection => editor_view.replace_all_selection(),
+        Message::KeepSelection => editor_view.keep_selection(),
+        Message::SearchWordForward => {
+            if let Err(err) = editor_view.search_word_under_cursor(false) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            } else if let Some(output) = editor_view.take_output() {
+                editor_view.show_message(output, Severity::Info);
+            }
+        }
+        Message::SearchWordBackward => {
+            if let Err(err) = editor_view.search_word_under_cursor(true) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            } else if let Some(output) = editor_view.take_output() {
+                editor_view.show_message(output, Severity::Info);
+            }
+        }
+        Message::SearchNext => {
+            if let Err(err) = editor_view.search_next() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            } else if let Some(output) = editor_view.take_output() {
+                editor_view.show_message(output, Severity::Info);
+            }
+        }
+        Message::SearchPrev => {
+            if let Err(err) = editor_view.search_prev() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            } else if let Some(output) = editor_view.take_output() {
+                editor_view.show_message(output, Severity::Info);
+            }
+        }
+        Message::YankSelection => editor_view.yank_selection(),
+        Message::YankLine => editor_view.yank_line(),
+        Message::DeleteLine => editor_view.delete_line(),
+        Message::DeleteChar => editor_view.delete_char(),
+        Message::OpenBelow => editor_view.open_below(),
+        Message::OpenAbove => editor_view.open_above(),
+        Message::Append => editor_view.append(),
+        Message::AppendEnd => editor_view.append_at_line_end(),
+        Message::InsertLineStart => editor_view.insert_at_first_non_blank(),
+        Message::JoinLines => editor_view.join_lines(),
+        Message::TransposeChars => editor_view.transpose_chars(),
+        Message::Repeat => {
+            for message in editor_view.last_change().to_vec() {
+                if handle_message(editor_view, message)?.is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+        }
+        Message::ReplaceChar(c) => editor_view.replace_char(c),
+        Message::DuplicateLine => editor_view.duplicate_line(times),
+        Message::MoveLineDown => editor_view.move_line_down(),
+        Message::MoveLineUp => editor_view.move_line_up(),
+        Message::Paste => {
+            if let Err(err) = editor_view.paste() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::SelectRegister(name) => {
+            if let Err(err) = editor_view.select_register(name) {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::InsertRegister(name) => editor_view.insert_register(name),
+        Message::RecursiveMapping => {
+            editor_view.show_message("Recursive mapping".to_owned(), Severity::Error)
+        }
+        Message::SplitWindow => editor_view.split_window(),
+        Message::CloseWindow => {
+            if let Err(err) = editor_view.close_window() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::OnlyWindow => editor_view.only_window(),
+        Message::QuitWindow => match editor_view.quit_window() {
+            Ok(true) => return Ok(ControlFlow::Break(())),
+            Ok(false) => {}
+            Err(err) => editor_view.show_message(format!("{err:#}"), Severity::Error),
+        },
+        Message::NextWindow => editor_view.next_window(),
+        Message::WindowDown => {
+            let below = editor_view.layout().focused() + 1;
+            editor_view.focus_window(below);
+        }
+        Message::WindowUp => {
+            let above = editor_view.layout().focused().saturating_sub(1);
+            editor_view.focus_window(above);
+        }
+        Message::EqualizeWindows => editor_view.equalize_windows(),
+        Message::GrowWindow => editor_view.resize_window(true),
+        Message::ShrinkWindow => editor_view.resize_window(false),
+        Message::ListUp => editor_view.move_in_list(true),
+        Message::ListDown => editor_view.move_in_list(false),
+        Message::ListPick => {
+            if let Err(err) = editor_view.pick_from_list() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::Resize(width, height) => editor_view.resize((width, height)),
+        Message::FocusGained => {
+            if let Some(warning) = editor_view.check_changed_on_disk() {
+                editor_view.show_message(warning, Severity::Warning);
+            }
+        }
+        Message::FocusLost => {
+            if let Err(err) = editor_view.autowrite_all() {
+                editor_view.show_message(format!("{err:#}"), Severity::Error);
+            }
+        }
+        Message::None | Message::Count(_) => {}
+    }
+    Ok(ControlFlow::Continue(()))
+}
+
+/// Open `fname` on another thread, drawing a progress bar while it is read in.
+fn open_with_progress(term: &mut Terminal, fname: String) -> anyhow::Result<Editor> {
+    let label = format!("Opening {fname}");
+    let (sender, receiver) = mpsc::channel();
+    let opening = thread::spawn(move || {
+        Editor::open_with_progress(&fname, |progress| {
+            // If nobody is listening, there's nothing to report to.
+            let _ = sender.send(progress);
+        })
+    });
+
+    // The sender is dropped (ending this loop) once the file has been read in.
+    for progress in receiver {
+        term.resize(tui::terminal_size());
+        term.draw(|f| {
+            ProgressBar {
+                label: &label,
+                progress,
+            }
+            .render(f, f.size());
+        })?;
+    }
+
+    opening
+        .join()
+        .map_err(|_| anyhow::anyhow!("Panicked while opening the file"))?
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use test_util::TempFile;
+    use tui::TestScreen;
+
+    #[test]
+    fn resizing() {
+        let mut editor_view = EditorView::new(Editor::new_empty());
+        editor_view.take_redraw();
+        let flow = handle_message(&mut editor_view, Message::Resize(30, 8));
+        assert!(flow.is_ok_and(|flow| flow.is_continue()));
+        assert_eq!(editor_view.size(), (30, 8));
+        assert!(editor_view.take_redraw().is_some());
+        assert!(editor_view.take_redraw().is_none());
+    }
+
+    #[test]
+    fn quitting_with_changes() {
+        let mut editor_view = EditorView::new(Editor::new_empty());
+        editor_view.push('x');
+        let flow = handle_message(&mut editor_view, Message::Quit);
+        assert!(flow.is_ok_and(|flow| flow.is_continue()));
+        let flow = handle_message(&mut editor_view, Message::Write);
+        assert!(flow.is_ok_and(|flow| flow.is_continue()));
+
+        editor_view.undo().expect("the push should be undone");
+        let flow = handle_message(&mut editor_view, Message::Quit);
+        assert!(flow.is_ok_and(|flow| flow.is_break()));
+    }
+
+    #[test]
+    fn repeating_changes() {
+        let mut editor_view = EditorView::new(
+            Editor::from_reader("one\ntwo\n".as_bytes()).expect("the text should be read"),
+        );
+        let mut send = |messages: &[Message]| {
+            for &message in messages {
+                editor_view.record(message);
+                let flow = handle_message(&mut editor_view, message);
+                assert!(flow.is_ok_and(|flow| flow.is_continue()));
+            }
+            editor_view.text().to_string()
+        };
+        let typed = [
+            Message::Mode(Mode::Insert),
+            Message::Char('x'),
+            Message::Char('y'),
+            Message::Mode(Mode::Normal),
+        ];
+        assert_eq!(send(&typed), "xyone\ntwo\n");
+        assert_eq!(
+            send(&[Message::Down, Message::LineStart, Message::Repeat]),
+            "xyone\nxytwo\n"
+        );
+        assert_eq!(send(&[Message::DeleteChar]), "xyone\nxywo\n");
+        assert_eq!(send(&[Message::Repeat]), "xyone\nxyo\n");
+        // Each repeat is undone on its own.
+        assert_eq!(send(&[Message::Undo]), "xyone\nxywo\n");
+    }
+
+    #[test]
+    fn counting_motions() {
+        let mut editor_view = EditorView::new(
+            Editor::from_reader("one\ntwo\nthree\nfour".as_bytes())
+                .expect("the text should be read"),
+        );
+        let mut send = |messages: &[Message]| {
+            for &message in messages {
+                let flow = handle_message(&mut editor_view, message);
+                assert!(flow.is_ok_and(|flow| flow.is_continue()));
+            }
+            editor_view.cursor_char()
+        };
+        assert_eq!(send(&[Message::Count(2), Message::Down]), 8);
+        assert_eq!(send(&[Message::Count(3), Message::Right]), 11);
+        // The count is only used once.
+        assert_eq!(send(&[Message::Up]), 7);
+        assert_eq!(send(&[Message::Count(2), Message::Mode(Mode::Normal)]), 7);
+        assert_eq!(send(&[Message::Down]), 11);
+        assert_eq!(send(&[Message::Count(1), Message::GotoBottom]), 0);
+        assert_eq!(send(&[Message::Count(usize::MAX), Message::Down]), 14);
+    }
+
+    #[test]
+    fn counting_duplicates() {
+        let mut editor_view = EditorView::new(
+            Editor::from_reader("one\ntwo".as_bytes()).expect("the text should be read"),
+        );
+        for message in [Message::Count(3), Message::DuplicateLine] {
+            let flow = handle_message(&mut editor_view, message);
+            assert!(flow.is_ok_and(|flow| flow.is_continue()));
+        }
+        assert_eq!(editor_view.text(), "one\none\none\none\ntwo");
+        assert_eq!(editor_view.selected_pos(), (0, 3));
+    }
+
+    #[test]
+    fn regaining_focus() {
+        let file = TempFile::new("focus");
+        let fname = file.path();
+        std::fs::write(fname, "one").expect("the file should be writable");
+        let mut editor_view =
+            EditorView::new(Editor::open(fname).expect("the file should be readable"));
+        editor_view.take_redraw();
+        let focus_gained = |editor_view: &mut EditorView| {
+            let flow = handle_message(editor_view, Message::FocusGained);
+            assert!(flow.is_ok_and(|flow| flow.is_continue()));
+        };
+        focus_gained(&mut editor_view);
+        assert!(editor_view.take_redraw().is_none());
+
+        std::fs::write(fname, "two").expect("the file should be writable");
+        std::fs::File::options()
+            .write(true)
+            .open(fname)
+            .and_then(|file| file.set_modified(SystemTime::now() + Duration::from_secs(60)))
+            .expect("the file's mtime should be settable");
+        focus_gained(&mut editor_view);
+        assert!(editor_view.take_redraw().is_some());
+        let mut screen = TestScreen::new(80, 3);
+        screen.draw(|f| {
+            editor_view.render(f, f.size());
+        });
+        assert!(screen.row(2).contains("has changed on disk"));
+    }
+}
@@ -6,57 +6,153 @@
 //!
 //! This is currently achieved by mainly using the [`Frame::set_char`] method, which allows you to,
 //! one character at a time, draw out the content being displayed.
+//! Coordinates given to a [`Frame`] are local to it, so a renderer should take a [`Frame::sub`]
+//! of the region it was given and draw starting from `(0, 0)`.
 //! Example implimentation of `render` on [`String`]:
 //! ```
 //! impl String {
 //!     fn render(&self, frame: &mut Frame, region: Rect) {
+//!         let mut frame = frame.sub(region);
 //!         for (i, c) in self.chars().enumerate() {
-//!             frame.set_char(c, region.left + i, region.top);
+//!             frame.set_char(c, i, 0);
 //!         }
 //!     }
 //! }
 //! ```
 //!
 
-use super::{Buffer, Rect, Style};
+use super::{Buffer, Cell, Rect, Style};
 
 /// An abstraction around drawing to a region of a [`Buffer`].
 pub struct Frame<'a> {
     /// The underlying [`Buffer`] being drawn to.
-    pub(super) buffer: &'a mut Buffer,
+    buffer: &'a mut Buffer,
+    /// The region of the [`Buffer`] this frame covers, in the [`Buffer`]'s coordinates.
+    ///
+    /// Always lies within the area of the [`Buffer`].
+    region: Rect,
+}
+
+impl<'a> Frame<'a> {
+    /// Create a [`Frame`] covering the entirety of `buffer`.
+    pub(super) fn new(buffer: &'a mut Buffer) -> Self {
+        let region = buffer.area;
+        Self { buffer, region }
+    }
 }
 
 impl Frame<'_> {
+    /// Get a [`Frame`] which only covers `region` of this one.
+    ///
+    /// `region` is given in this frame's coordinates and is clipped to this frame. The new
+    /// [`Frame`] has its own coordinates, so `(0, 0)` is the top-left of `region`.
+    pub fn sub(&mut self, region: Rect) -> Frame<'_> {
+        let region = Rect {
+            top: self.region.top.saturating_add(region.top),
+            left: self.region.left.saturating_add(region.left),
+            ..region
+        };
+        let region = region.intersection(&self.region).unwrap_or(Rect {
+            height: 0,
+            width: 0,
+            ..self.region
+        });
+        Frame {
+            buffer: self.buffer,
+            region,
+        }
+    }
+
     /// Sets the char at a single location in the frame.
+    ///
+    /// Writes outside of the frame are silently clipped. Use [`Frame::try_set_char`] to find out
+    /// whether the write landed.
     pub fn set_char(&mut self, c: char, x: u16, y: u16) {
-        // Should these panic or should the function return a Result?
-        if x >= self.buffer.area.width {
-            return;
-            // todo!("panic message");
-        }
-        if y >= self.buffer.area.height {
-            return;
-            // todo!("panic message");
+        self.try_set_char(c, x, y);
+    }
+
+    /// Sets the char at a single location in the frame.
+    ///
+    /// Returns `false` (leaving the frame untouched) if the location is outside of the frame.
+    pub fn try_set_char(&mut self, c: char, x: u16, y: u16) -> bool {
+        match self.index(x, y) {
+            Some(i) => {
+                self.buffer.content[i].symbol = c;
+                true
+            }
+            None => false,
         }
+    }
 
-        let i = x as usize + self.buffer.area.width as usize * y as usize;
-        self.buffer.content[i].symbol = c;
+    /// Report that the cursor should be shown at a location in the frame once it is drawn.
+    ///
+    /// The last location reported wins. Locations outside of the frame are ignored, like writes
+    /// with [`Frame::set_char`].
+    pub fn set_cursor(&mut self, x: u16, y: u16) {
+        if self.size().contains(x, y) {
+            self.buffer.cursor = Some((self.region.left + x, self.region.top + y));
+        }
     }
 
-    /// Get the [`Rect`] representing the size of the [`Buffer`] being written to.
+    /// Get the [`Rect`] representing the size of the frame.
+    ///
+    /// This is in the frame's own coordinates, so `top` and `left` are always 0.
     pub fn size(&self) -> Rect {
-        self.buffer.area
+        Rect {
+            top: 0,
+            left: 0,
+            ..self.region
+        }
     }
 
     /// Set the [`Style`] of all the [`Cell`]s in the underlying [`Buffer`] in the region specified.
     ///
+    /// Any part of `region` outside of the frame is ignored.
+    ///
     /// [`Cell`]: super::Cell
     pub fn set_style(&mut self, style: Style, region: Rect) {
-        for y in region.top..region.top + region.height {
-            for x in region.left..region.left + region.width {
-                let i = x as usize + self.buffer.area.width as usize * y as usize;
-                self.buffer.content[i].style = style;
+        let Some(region) = region.intersection(&self.size()) else {
+            return;
+        };
+        for y in region.top..region.bottom() {
+            for x in region.left..region.right() {
+                if let Some(i) = self.index(x, y) {
+                    self.buffer.content[i].style = style;
+                }
             }
         }
     }
+
+    /// Blank all the [`Cell`]s in the region specified, as they are at the start of a
+    /// [`Terminal::draw`].
+    ///
+    /// This is for drawing over part of what was drawn before with [`Terminal::update`]. Any part
+    /// of `region` outside of the frame is ignored.
+    ///
+    /// [`Terminal::draw`]: super::Terminal::draw
+    /// [`Terminal::update`]: super::Terminal::update
+    pub fn clear(&mut self, region: Rect) {
+        let Some(region) = region.intersection(&self.size()) else {
+            return;
+        };
+        for y in region.top..region.bottom() {
+            for x in region.left..region.right() {
+                if let Some(i) = self.index(x, y) {
+                    self.buffer.content[i] = Cell::default();
+                }
+            }
+        }
+    }
+
+    /// Convert a position in the frame's coordinates into an index into the [`Buffer`].
+    ///
+    /// Returns [`None`] if the position is outside of the frame.
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        if !self.size().contains(x, y) {
+            return None;
+        }
+        let x = (self.region.left + x - self.buffer.area.left) as usize;
+        let y = (self.region.top + y - self.buffer.area.top) as usize;
+        Some(x + self.buffer.area.width as usize * y)
+    }
 }
@@ -0,0 +1,110 @@
+//! A [`HexView`] draws bytes like `xxd`, with the offset, hex and ASCII of each row.
+
+use super::{Frame, Modifier, Rect, Style};
+
+/// How many bytes are shown in each row.
+pub const BYTES_PER_ROW: usize = 16;
+
+/// The column the hex of a row starts at, after the offset and `": "`.
+const HEX_START: usize = 10;
+
+/// The column the ASCII of a row starts at, after the hex and two spaces.
+const ASCII_START: usize = HEX_START + BYTES_PER_ROW * 5 / 2 + 1;
+
+/// Rows of bytes, each drawn as `00000010: 6865 6c6c 6f0a  hello.` with the bytes in pairs.
+#[derive(Debug, Clone, Copy)]
+pub struct HexView<'a> {
+    /// The bytes shown, starting at the top row.
+    pub bytes: &'a [u8],
+    /// The offset of the first of [`HexView::bytes`], which should be the start of a row.
+    pub offset: usize,
+    /// The offset of the byte the cursor is on, which is highlighted in the ASCII.
+    pub cursor: usize,
+}
+
+impl HexView<'_> {
+    /// The column the hex of the byte at `offset` starts at, which is where the cursor is drawn
+    /// when it is on the byte.
+    pub fn column(offset: usize) -> u16 {
+        let i = offset % BYTES_PER_ROW;
+        (HEX_START + i * 2 + i / 2) as u16
+    }
+
+    /// See [`frame`].
+    ///
+    /// [`frame`]: crate::tui::frame
+    pub fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        frame.set_style(Style::default(), frame.size());
+        let rows = self
+            .bytes
+            .chunks(BYTES_PER_ROW)
+            .take(frame.size().height as usize);
+        for (y, row) in rows.enumerate() {
+            let start = self.offset + y * BYTES_PER_ROW;
+            let mut line = format!("{start:08x}: ");
+            for (i, byte) in row.iter().enumerate() {
+                line.push_str(&format!("{byte:02x}"));
+                if i % 2 == 1 {
+                    line.push(' ');
+                }
+            }
+            let line = format!("{line:<ASCII_START$}");
+            let ascii = row.iter().map(|&byte| match byte {
+                b' '..=b'~' => byte as char,
+                _ => '.',
+            });
+            for (x, c) in line.chars().chain(ascii).enumerate() {
+                frame.set_char(c, x as u16, y as u16);
+            }
+            if (start..start + row.len()).contains(&self.cursor) {
+                let x = ASCII_START + self.cursor - start;
+                frame.set_style(
+                    Style::default().add_modifier(Modifier::REVERSED),
+                    Rect {
+                        top: y as u16,
+                        left: x as u16,
+                        height: 1,
+                        width: 1,
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tui::TestScreen;
+
+    #[test]
+    fn rendering_rows() {
+        let bytes: Vec<u8> = (0..20).map(|i| b'a' + i).chain([0, b'\n', 0xff]).collect();
+        let view = HexView {
+            bytes: &bytes,
+            offset: 0x20,
+            cursor: 0x31,
+        };
+        let mut screen = TestScreen::new(70, 3);
+        screen.draw(|f| {
+            view.render(f, f.size());
+        });
+        assert_eq!(
+            screen.row(0).trim_end(),
+            "00000020: 6162 6364 6566 6768 696a 6b6c 6d6e 6f70  abcdefghijklmnop"
+        );
+        assert_eq!(
+            screen.row(1).trim_end(),
+            "00000030: 7172 7374 000a ff                        qrst..."
+        );
+        assert_eq!(screen.row(2).trim_end(), "");
+        // The cursor is on the second byte of the second row.
+        assert_eq!(
+            screen.style(ASCII_START as u16 + 1, 1),
+            Style::default().add_modifier(Modifier::REVERSED)
+        );
+        assert_eq!(HexView::column(0x31), 12);
+        assert_eq!(HexView::column(0x33), 17);
+    }
+}
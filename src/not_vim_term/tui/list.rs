@@ -0,0 +1,139 @@
+//! A [`ListView`] draws a list of items to pick from, scrolled so the selected one can be seen.
+
+use super::{Frame, Modifier, Rect, Style};
+
+/// A scrollable list of items with one of them selected.
+///
+/// The list only scrolls when the selection would otherwise be out of view, so moving the
+/// selection within the visible rows doesn't move the rest of the list.
+#[derive(Debug, Clone, Copy)]
+pub struct ListView<'a> {
+    /// The items, one per row.
+    pub items: &'a [String],
+    /// The style of each item, for as many of them as have one.
+    pub styles: &'a [Style],
+    /// The index of the selected item.
+    pub selected: usize,
+    /// The index of the item in the top row.
+    pub offset: usize,
+}
+
+impl ListView<'_> {
+    /// The index of the item which should be in the top row when `rows` rows are shown.
+    ///
+    /// This is [`ListView::offset`], moved as little as possible to bring the selection into view
+    /// and to not leave rows empty at the bottom.
+    fn first_visible(&self, rows: usize) -> usize {
+        let offset = self.offset.min(self.items.len().saturating_sub(rows));
+        if self.selected < offset {
+            self.selected
+        } else if rows > 0 && self.selected >= offset + rows {
+            self.selected + 1 - rows
+        } else {
+            offset
+        }
+    }
+
+    /// Scroll so the selected item is shown when `rows` rows are shown.
+    ///
+    /// This should be called whenever the selection changes, so the scroll is remembered.
+    pub fn scroll_into_view(&mut self, rows: u16) {
+        self.offset = self.first_visible(rows as usize);
+    }
+
+    /// See [`frame`].
+    ///
+    /// If [`ListView::scroll_into_view`] hasn't been called since the selection changed, the
+    /// list is drawn as if it had been.
+    ///
+    /// [`frame`]: crate::tui::frame
+    pub fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        let size = frame.size();
+        frame.set_style(Style::default(), size);
+        let first = self.first_visible(size.height as usize);
+        let items = self.items.iter().skip(first).take(size.height as usize);
+        for (y, item) in items.enumerate() {
+            if let Some(&style) = self.styles.get(first + y) {
+                frame.set_style(
+                    style,
+                    Rect {
+                        top: y as u16,
+                        height: 1,
+                        ..size
+                    },
+                );
+            }
+            for (x, c) in item.chars().enumerate() {
+                frame.set_char(c, x as u16, y as u16);
+            }
+        }
+        if let Some(row) = self.selected.checked_sub(first) {
+            frame.set_style(
+                Style::default().add_modifier(Modifier::REVERSED),
+                Rect {
+                    top: row as u16,
+                    height: 1,
+                    ..size
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tui::TestScreen;
+
+    /// The style of the selected row.
+    fn selected() -> Style {
+        Style::default().add_modifier(Modifier::REVERSED)
+    }
+
+    #[test]
+    fn scrolling_to_the_selection() {
+        let items: Vec<String> = (0..10).map(|i| format!("item {i}")).collect();
+        let mut list = ListView {
+            items: &items,
+            styles: &[],
+            selected: 1,
+            offset: 0,
+        };
+        let mut screen = TestScreen::new(10, 3);
+        let draw = |screen: &mut TestScreen, list: &ListView| {
+            screen.draw(|f| {
+                list.render(f, f.size());
+            });
+        };
+        list.scroll_into_view(3);
+        draw(&mut screen, &list);
+        assert_eq!(screen.row(0).trim_end(), "item 0");
+        assert_eq!(screen.style(0, 1), selected());
+
+        // Selecting past the bottom scrolls just far enough to show it.
+        list.selected = 5;
+        list.scroll_into_view(3);
+        assert_eq!(list.offset, 3);
+        draw(&mut screen, &list);
+        assert_eq!(screen.row(0).trim_end(), "item 3");
+        assert_eq!(screen.row(2).trim_end(), "item 5");
+        assert_eq!(screen.style(0, 2), selected());
+        assert_eq!(screen.style(0, 0), Style::default());
+
+        // Moving within the visible rows doesn't scroll.
+        list.selected = 4;
+        list.scroll_into_view(3);
+        assert_eq!(list.offset, 3);
+
+        list.selected = 0;
+        list.scroll_into_view(3);
+        assert_eq!(list.offset, 0);
+
+        // Fewer rows than were scrolled for are still drawn with the selection in view.
+        list.selected = 9;
+        list.scroll_into_view(5);
+        draw(&mut screen, &list);
+        assert_eq!(screen.row(2).trim_end(), "item 9");
+    }
+}
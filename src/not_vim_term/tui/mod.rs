@@ -3,15 +3,19 @@
 //! Contains information about [`Buffer`]s and individual [`Cell`]s.
 
 pub mod frame;
+mod hex;
+mod list;
 pub mod rect;
 mod text;
 
 pub use crossterm::style::Color;
 use crossterm::{cursor::MoveTo, queue, style::Print};
 pub use frame::Frame;
+pub use hex::{HexView, BYTES_PER_ROW};
+pub use list::ListView;
 pub use rect::Rect;
 use std::io::{self, StdoutLock, Write};
-pub use text::{Style, Text};
+pub use text::{Modifier, Style, Text};
 
 /// All the information regarding the content of a single cell of a terminal.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -40,9 +44,21 @@ struct Buffer {
     content: Vec<Cell>,
     /// The area the [`Buffer`] is representing.
     area: Rect,
+    /// Where the cursor should be shown, as reported with [`Frame::set_cursor`].
+    cursor: Option<(u16, u16)>,
 }
 
 impl Buffer {
+    /// Create a blank [`Buffer`] covering `area`.
+    fn new(area: Rect) -> Self {
+        let content = vec![Cell::default(); area.area()];
+        Self {
+            content,
+            area,
+            cursor: None,
+        }
+    }
+
     /// Takes another [`Buffer`] and returns a vector of all the [`Cell`]s which are different between
     /// `self` and the other [`Buffer`].
     ///
@@ -67,15 +83,13 @@ impl Buffer {
     /// if the new area is larger than the previous area.
     fn resize(&mut self, new_area: Rect) {
         self.area = new_area;
-        self.content.resize(
-            new_area.width as usize * new_area.height as usize,
-            Cell::default(),
-        );
+        self.content.resize(new_area.area(), Cell::default());
     }
 
-    /// Fill the entire buffer with blank spaces.
+    /// Fill the entire buffer with blank spaces, forgetting where the cursor was.
     fn clear(&mut self) {
         self.content.fill_with(Cell::default);
+        self.cursor = None;
     }
 }
 
@@ -84,11 +98,7 @@ impl Buffer {
 /// The coordinates are found by mapping the vector in a row-major fashion to the area described by
 /// `area`.
 fn enumerate_2d(items: &[Cell], area: Rect) -> impl Iterator<Item = (Cell, u16, u16)> + '_ {
-    assert_eq!(
-        items.len(),
-        area.width as usize * area.height as usize,
-        "{area:?}"
-    );
+    assert_eq!(items.len(), area.area(), "{area:?}");
     items.iter().enumerate().map(move |(i, item)| {
         (
             *item,
@@ -98,12 +108,18 @@ fn enumerate_2d(items: &[Cell], area: Rect) -> impl Iterator<Item = (Cell, u16,
     })
 }
 
+/// The width and height of the terminal being written to.
+///
+/// If there is no terminal to ask, this falls back like [`Rect`]'s size does, to the `COLUMNS` and
+/// `LINES` environment variables or a default size, rather than failing.
+pub fn terminal_size() -> (u16, u16) {
+    let size = Rect::get_size();
+    (size.width, size.height)
+}
+
 impl Default for Buffer {
     fn default() -> Self {
-        let area = Rect::get_size();
-
-        let content = vec![Cell::default(); area.height as usize * area.width as usize];
-        Self { content, area }
+        Self::new(Rect::get_size())
     }
 }
 
@@ -188,8 +204,13 @@ impl Terminal {
     // }
 
     /// Resize the [`Terminal`] to reflect the actual size of the terminal.
-    pub fn resize(&mut self) {
-        let area = Rect::get_size();
+    pub fn resize(&mut self, (width, height): (u16, u16)) {
+        let area = Rect {
+            top: 0,
+            left: 0,
+            height,
+            width,
+        };
         self.current_buf_mut().resize(area);
     }
 
@@ -217,13 +238,279 @@ impl Terminal {
     // Concise description stolen from tui.
     /// Synchronizes terminal size, calls the rendering closure, flushes the current internal state and prepares for the next draw call.
     ///
-    /// If the return value is [`Some`], then this will set the position of the cursor to the
-    /// returned coordinates.
-    pub fn draw(&mut self, draw: impl Fn(&mut Frame) -> Option<(u16, u16)>) -> anyhow::Result<()> {
+    /// If the rendering closure reports where the cursor goes with [`Frame::set_cursor`], the
+    /// cursor is moved there.
+    pub fn draw(&mut self, draw: impl Fn(&mut Frame)) -> anyhow::Result<()> {
         self.current_buf_mut().clear();
-        let final_position = draw(&mut Frame {
-            buffer: self.current_buf_mut(),
-        });
+        draw(&mut Frame::new(self.current_buf_mut()));
+        let final_position = self.current_buf().cursor;
+        self.flush(final_position)
+    }
+
+    /// Like [`Terminal::draw`], but starting from what was drawn last time rather than a blank
+    /// screen, so the rendering closure only has to draw what has changed. It should
+    /// [`Frame::clear`] anything it draws over.
+    ///
+    /// Only the cursor is forgotten, so it has to be reported again.
+    pub fn update(&mut self, draw: impl Fn(&mut Frame)) -> anyhow::Result<()> {
+        self.current_buf_mut().cursor = None;
+        draw(&mut Frame::new(self.current_buf_mut()));
+        let final_position = self.current_buf().cursor;
         self.flush(final_position)
     }
 }
+
+/// An in-memory screen which can be drawn to in tests.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct TestScreen(Buffer);
+
+#[cfg(test)]
+impl TestScreen {
+    /// Create a blank screen of the given size.
+    pub fn new(width: u16, height: u16) -> Self {
+        Self(Buffer::new(Rect {
+            top: 0,
+            left: 0,
+            height,
+            width,
+        }))
+    }
+
+    /// Draw to the screen in the same way as [`Terminal::draw`], returning where the cursor was
+    /// put with [`Frame::set_cursor`].
+    pub fn draw(&mut self, draw: impl Fn(&mut Frame)) -> Option<(u16, u16)> {
+        self.0.clear();
+        draw(&mut Frame::new(&mut self.0));
+        self.0.cursor
+    }
+
+    /// Draw over what is on the screen in the same way as [`Terminal::update`], returning where
+    /// the cursor was put.
+    pub fn update(&mut self, draw: impl Fn(&mut Frame)) -> Option<(u16, u16)> {
+        self.0.cursor = None;
+        draw(&mut Frame::new(&mut self.0));
+        self.0.cursor
+    }
+
+    /// Get the symbols in row `y` of the screen.
+    pub fn row(&self, y: u16) -> String {
+        let width = self.0.area.width as usize;
+        let start = y as usize * width;
+        self.0.content[start..start + width]
+            .iter()
+            .map(|cell| cell.symbol)
+            .collect()
+    }
+
+    /// Get the [`Style`] of the cell at `(x, y)`.
+    pub fn style(&self, x: u16, y: u16) -> Style {
+        self.0.content[y as usize * self.0.area.width as usize + x as usize].style
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn area(width: u16, height: u16) -> Rect {
+        Rect {
+            top: 0,
+            left: 0,
+            height,
+            width,
+        }
+    }
+
+    #[test]
+    fn set_char_writes_symbol() {
+        let mut buffer = Buffer::new(area(4, 3));
+        let mut frame = Frame::new(&mut buffer);
+        frame.set_char('a', 1, 2);
+        frame.set_char('b', 4, 0);
+        frame.set_char('c', 0, 3);
+
+        assert_eq!(buffer.content[2 * 4 + 1].symbol, 'a');
+        assert_eq!(
+            buffer
+                .content
+                .iter()
+                .filter(|cell| cell.symbol != ' ')
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn try_set_char_reports_bounds() {
+        let mut buffer = Buffer::new(area(4, 3));
+        let mut frame = Frame::new(&mut buffer);
+        assert!(frame.try_set_char('a', 0, 0));
+        assert!(frame.try_set_char('b', 3, 2));
+        assert!(!frame.try_set_char('c', 4, 0));
+        assert!(!frame.try_set_char('d', 0, 3));
+        assert!(!frame.try_set_char('e', u16::MAX, u16::MAX));
+
+        assert_eq!(buffer.content[0].symbol, 'a');
+        assert_eq!(buffer.content[2 * 4 + 3].symbol, 'b');
+        assert_eq!(
+            buffer
+                .content
+                .iter()
+                .filter(|cell| cell.symbol != ' ')
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn sub_frame_uses_local_coordinates() {
+        let mut buffer = Buffer::new(area(5, 4));
+        let mut frame = Frame::new(&mut buffer);
+        let mut sub = frame.sub(Rect {
+            top: 1,
+            left: 2,
+            height: 2,
+            width: 2,
+        });
+        assert_eq!(sub.size(), area(2, 2));
+        assert!(sub.try_set_char('a', 0, 0));
+        assert!(sub.try_set_char('b', 1, 1));
+        assert!(!sub.try_set_char('c', 2, 0));
+        assert!(!sub.try_set_char('d', 0, 2));
+        sub.set_style(Style::default().fg(Color::Red), area(10, 10));
+
+        assert_eq!(buffer.content[5 + 2].symbol, 'a');
+        assert_eq!(buffer.content[2 * 5 + 3].symbol, 'b');
+        for (cell, x, y) in enumerate_2d(&buffer.content, buffer.area) {
+            let inside = (2..4).contains(&x) && (1..3).contains(&y);
+            assert_eq!(cell.symbol != ' ', inside && x == y + 1, "({x}, {y})");
+            assert_eq!(cell.style != Style::default(), inside, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn sub_frame_is_clipped_to_parent() {
+        let mut buffer = Buffer::new(area(5, 4));
+        let mut frame = Frame::new(&mut buffer);
+        let mut outer = frame.sub(Rect {
+            top: 2,
+            left: 3,
+            height: 10,
+            width: 10,
+        });
+        assert_eq!(outer.size(), area(2, 2));
+        let mut inner = outer.sub(Rect {
+            top: 1,
+            left: 1,
+            height: 5,
+            width: 5,
+        });
+        assert_eq!(inner.size(), area(1, 1));
+        assert!(inner.try_set_char('a', 0, 0));
+        assert!(!inner.try_set_char('b', 1, 0));
+        assert_eq!(buffer.content[3 * 5 + 4].symbol, 'a');
+    }
+
+    #[test]
+    fn set_style_only_touches_region() {
+        let mut buffer = Buffer::new(area(4, 3));
+        let style = Style::default().fg(Color::Red);
+        let mut frame = Frame::new(&mut buffer);
+        frame.set_char('x', 1, 1);
+        frame.set_style(
+            style,
+            Rect {
+                top: 1,
+                left: 1,
+                height: 2,
+                width: 2,
+            },
+        );
+
+        for (cell, x, y) in enumerate_2d(&buffer.content, buffer.area) {
+            let inside = (1..3).contains(&x) && (1..3).contains(&y);
+            assert_eq!(cell.style == style, inside, "({x}, {y})");
+        }
+        // Styling doesn't change the symbols.
+        assert_eq!(buffer.content[4 + 1].symbol, 'x');
+    }
+
+    #[test]
+    fn clear_only_touches_region() {
+        let mut buffer = Buffer::new(area(4, 3));
+        let mut frame = Frame::new(&mut buffer);
+        for y in 0..3 {
+            for x in 0..4 {
+                frame.set_char('x', x, y);
+            }
+        }
+        frame.set_style(Style::default().fg(Color::Red), frame.size());
+        let mut sub = frame.sub(Rect {
+            top: 1,
+            left: 1,
+            height: 5,
+            width: 2,
+        });
+        sub.clear(sub.size());
+
+        for (cell, x, y) in enumerate_2d(&buffer.content, buffer.area) {
+            let inside = (1..3).contains(&x) && (1..3).contains(&y);
+            assert_eq!(cell == Cell::default(), inside, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn diff_reports_changed_cells() {
+        let old = Buffer::new(area(3, 2));
+        let mut new = old.clone();
+        let mut frame = Frame::new(&mut new);
+        frame.set_char('a', 2, 0);
+        frame.set_style(
+            Style::default().bg(Color::Blue),
+            Rect {
+                top: 1,
+                left: 0,
+                height: 1,
+                width: 1,
+            },
+        );
+
+        let diff = new.diff(&old);
+        assert_eq!(diff.len(), 2);
+        assert_eq!((diff[0].0.symbol, diff[0].1, diff[0].2), ('a', 2, 0));
+        assert_eq!(
+            (diff[1].0.style, diff[1].1, diff[1].2),
+            (Style::default().bg(Color::Blue), 0, 1)
+        );
+    }
+
+    #[test]
+    fn diff_after_resize_redraws_everything() {
+        let old = Buffer::new(area(3, 2));
+        let mut new = old.clone();
+        new.resize(area(2, 2));
+        assert_eq!(new.diff(&old).len(), 4);
+        assert!(new.diff(&new.clone()).is_empty());
+    }
+
+    #[test]
+    fn reporting_the_cursor() {
+        let mut screen = TestScreen::new(6, 4);
+        let cursor = screen.draw(|f| {
+            let mut sub = f.sub(Rect {
+                top: 1,
+                left: 2,
+                height: 2,
+                width: 2,
+            });
+            sub.set_cursor(1, 1);
+            // Places outside of the frame are ignored.
+            sub.set_cursor(2, 0);
+        });
+        assert_eq!(cursor, Some((3, 2)));
+
+        // Each draw starts off without a cursor.
+        assert_eq!(screen.draw(|_| {}), None);
+    }
+}
@@ -4,6 +4,11 @@
 //! A [`Rect`] represents a region of the terminal screen.
 
 use crossterm::terminal;
+use std::{cmp::Reverse, io};
+
+/// The width and height assumed for the terminal when its size can't be found out, and the
+/// `COLUMNS` and `LINES` environment variables don't give it either.
+const FALLBACK_SIZE: (u16, u16) = (80, 24);
 
 /// A simple struct representing a rectangular region of the terminal.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -20,9 +25,31 @@ pub struct Rect {
 
 impl Rect {
     /// Get a [`Rect`] representing the current size of the terminal being written to.
+    ///
+    /// If there is no terminal to ask, like when the output is piped, the size comes from the
+    /// `COLUMNS` and `LINES` environment variables, or [`FALLBACK_SIZE`] without them.
     pub(super) fn get_size() -> Self {
-        let (width, height) =
-            terminal::size().expect("unable to get the dimensions of the terminal");
+        Self::size_or_fallback(terminal::size(), |name| std::env::var(name).ok())
+    }
+
+    /// The [`Rect`] for a terminal of `size`, falling back to the sizes in the environment
+    /// variables `var` looks up, then [`FALLBACK_SIZE`], if it couldn't be found.
+    fn size_or_fallback(
+        size: io::Result<(u16, u16)>,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let (width, height) = size.unwrap_or_else(|_| {
+            let dimension = |name, fallback| {
+                var(name)
+                    .and_then(|value| value.trim().parse().ok())
+                    .filter(|&n| n > 0)
+                    .unwrap_or(fallback)
+            };
+            (
+                dimension("COLUMNS", FALLBACK_SIZE.0),
+                dimension("LINES", FALLBACK_SIZE.1),
+            )
+        });
         Self {
             top: 0,
             left: 0,
@@ -31,6 +58,30 @@ impl Rect {
         }
     }
 
+    /// The number of cells in the [`Rect`].
+    pub fn area(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Whether the [`Rect`] has no cells in it, because it has no width or no height.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The column just past the right side of the [`Rect`].
+    ///
+    /// This stops at [`u16::MAX`] for a [`Rect`] which goes past the last column there can be.
+    pub fn right(&self) -> u16 {
+        self.left.saturating_add(self.width)
+    }
+
+    /// The row just past the bottom side of the [`Rect`].
+    ///
+    /// This stops at [`u16::MAX`] for a [`Rect`] which goes past the last row there can be.
+    pub fn bottom(&self) -> u16 {
+        self.top.saturating_add(self.height)
+    }
+
     /// Take a [`Partition`]er and use it to split the current [`Rect`].
     ///
     /// This is mainly a convenience function and so
@@ -47,6 +98,81 @@ impl Rect {
     pub fn partition<S: Partition>(self, partition: S) -> Vec<Rect> {
         partition.partition(self)
     }
+
+    /// Split the [`Rect`] into a left and right part, with the first `at` columns going to the
+    /// left part.
+    ///
+    /// If `at` is beyond the right edge, the right part is empty.
+    #[allow(dead_code)]
+    pub fn split_horizontal(&self, at: u16) -> (Rect, Rect) {
+        let at = at.min(self.width);
+        (
+            Rect { width: at, ..*self },
+            Rect {
+                left: self.left + at,
+                width: self.width - at,
+                ..*self
+            },
+        )
+    }
+
+    /// Split the [`Rect`] into a top and bottom part, with the first `at` rows going to the top
+    /// part.
+    ///
+    /// If `at` is beyond the bottom edge, the bottom part is empty.
+    pub fn split_vertical(&self, at: u16) -> (Rect, Rect) {
+        let at = at.min(self.height);
+        (
+            Rect {
+                height: at,
+                ..*self
+            },
+            Rect {
+                top: self.top + at,
+                height: self.height - at,
+                ..*self
+            },
+        )
+    }
+
+    /// Get the region covered by both `self` and `other`.
+    ///
+    /// Returns [`None`] if they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let left = self.left.max(other.left);
+        let top = self.top.max(other.top);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= left || bottom <= top {
+            return None;
+        }
+        Some(Rect {
+            top,
+            left,
+            height: bottom - top,
+            width: right - left,
+        })
+    }
+
+    /// Check whether the point `(x, y)` lies within the [`Rect`].
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        (self.left..self.right()).contains(&x) && (self.top..self.bottom()).contains(&y)
+    }
+
+    /// Shrink the [`Rect`] by `margin` on every side.
+    ///
+    /// If the margin is too large to fit, the result is an empty [`Rect`] in the middle.
+    #[allow(dead_code)]
+    pub fn inner(&self, margin: u16) -> Rect {
+        let horizontal = margin.min(self.width / 2);
+        let vertical = margin.min(self.height / 2);
+        Rect {
+            top: self.top + vertical,
+            left: self.left + horizontal,
+            height: self.height - 2 * vertical,
+            width: self.width - 2 * horizontal,
+        }
+    }
 }
 
 // TODO: Is there some way to return something like [Rect; 4]
@@ -95,17 +221,62 @@ pub struct Bottom;
 
 impl Partition for Bottom {
     fn partition(&self, area: Rect) -> Vec<Rect> {
-        vec![
-            Rect {
-                top: area.top + area.height - 1,
-                height: 1,
-                ..area
-            },
-            Rect {
-                height: area.height - 1,
-                ..area
-            },
-        ]
+        let (rest, bottom) = area.split_vertical(area.height.saturating_sub(1));
+        vec![bottom, rest]
+    }
+}
+
+/// A [`Partition`]er which splits a [`Rect`] into parts stacked from top to bottom, with heights
+/// in proportion to their weights.
+///
+/// Rows left over from rounding go to the parts which lost the most to it, the top ones first
+/// when that is even. As far as the height allows, every part is at least `min` rows tall, with
+/// the rows needed for that taken from the tallest parts.
+///
+/// See [`Partition`] for more information about how to use this struct.
+pub struct Stacked<'a> {
+    /// The weight of each part, from top to bottom.
+    pub weights: &'a [usize],
+    /// The smallest height a part should have.
+    pub min: u16,
+}
+
+impl Partition for Stacked<'_> {
+    fn partition(&self, area: Rect) -> Vec<Rect> {
+        if self.weights.is_empty() {
+            return vec![area];
+        }
+        let count = self.weights.len();
+        let height = area.height as usize;
+        let total = self.weights.iter().sum::<usize>().max(1);
+        let mut heights: Vec<usize> = self.weights.iter().map(|w| height * w / total).collect();
+        let mut by_remainder: Vec<usize> = (0..count).collect();
+        by_remainder.sort_by_key(|&i| Reverse(height * self.weights[i] % total));
+        let left_over = height - heights.iter().sum::<usize>();
+        for &i in by_remainder.iter().take(left_over) {
+            heights[i] += 1;
+        }
+
+        let min = (self.min as usize).min(height / count);
+        for i in 0..count {
+            while heights[i] < min {
+                let Some(tallest) = (0..count).max_by_key(|&j| (heights[j], Reverse(j))) else {
+                    break;
+                };
+                heights[tallest] -= 1;
+                heights[i] += 1;
+            }
+        }
+
+        let mut rest = area;
+        heights
+            .into_iter()
+            .map(|rows| {
+                let (part, below) = rest.split_vertical(rows as u16);
+                rest = below;
+                part
+            })
+            .collect()
     }
 }
 
@@ -113,6 +284,71 @@ impl Partition for Bottom {
 mod test {
     use super::*;
 
+    #[test]
+    fn falling_back_to_a_default_size() {
+        let size = |rect: Rect| (rect.width, rect.height);
+        let no_tty = || Err(io::Error::other("not a terminal"));
+        assert_eq!(
+            size(Rect::size_or_fallback(Ok((100, 50)), |_| None)),
+            (100, 50)
+        );
+        assert_eq!(
+            size(Rect::size_or_fallback(no_tty(), |_| None)),
+            FALLBACK_SIZE
+        );
+
+        let env = |name: &str| match name {
+            "COLUMNS" => Some("120".to_string()),
+            "LINES" => Some("nonsense".to_string()),
+            _ => None,
+        };
+        assert_eq!(size(Rect::size_or_fallback(no_tty(), env)), (120, 24));
+        let env = |name: &str| match name {
+            "COLUMNS" => Some("120".to_string()),
+            "LINES" => Some(" 40\n".to_string()),
+            _ => None,
+        };
+        assert_eq!(size(Rect::size_or_fallback(no_tty(), env)), (120, 40));
+        // The environment is only used when the terminal can't be asked.
+        assert_eq!(size(Rect::size_or_fallback(Ok((100, 50)), env)), (100, 50));
+    }
+
+    #[test]
+    fn using_stacked() {
+        let area = Rect {
+            top: 1,
+            left: 2,
+            height: 7,
+            width: 3,
+        };
+        let heights = |weights: &[usize], min| {
+            let parts = area.partition(Stacked { weights, min });
+            assert!(parts.windows(2).all(|w| w[0].top + w[0].height == w[1].top));
+            parts.iter().map(|part| part.height).collect::<Vec<_>>()
+        };
+        assert_eq!(heights(&[1], 0), [7]);
+        assert_eq!(heights(&[1, 1, 1], 0), [3, 2, 2]);
+        assert_eq!(heights(&[4, 1, 2], 0), [4, 1, 2]);
+        assert_eq!(heights(&[1, 2], 0), [2, 5]);
+        assert_eq!(heights(&[10, 1, 1], 2), [3, 2, 2]);
+        assert_eq!(heights(&[1; 9], 2), [1, 1, 1, 1, 1, 1, 1, 0, 0]);
+        assert_eq!(
+            area.partition(Stacked {
+                weights: &[],
+                min: 1
+            }),
+            [area]
+        );
+        assert_eq!(
+            area.partition(Stacked {
+                weights: &[1, 1],
+                min: 1
+            })[1]
+                .top,
+            5
+        );
+    }
+
     #[test]
     fn using_bottom() {
         let initial_rect = Rect {
@@ -141,4 +377,205 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn splitting() {
+        let rect = Rect {
+            top: 1,
+            left: 2,
+            height: 4,
+            width: 6,
+        };
+        assert_eq!(
+            rect.split_horizontal(2),
+            (
+                Rect {
+                    top: 1,
+                    left: 2,
+                    height: 4,
+                    width: 2,
+                },
+                Rect {
+                    top: 1,
+                    left: 4,
+                    height: 4,
+                    width: 4,
+                }
+            )
+        );
+        assert_eq!(
+            rect.split_vertical(3),
+            (
+                Rect {
+                    top: 1,
+                    left: 2,
+                    height: 3,
+                    width: 6,
+                },
+                Rect {
+                    top: 4,
+                    left: 2,
+                    height: 1,
+                    width: 6,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn splitting_out_of_bounds() {
+        let rect = Rect {
+            top: 1,
+            left: 2,
+            height: 4,
+            width: 6,
+        };
+        assert_eq!(
+            rect.split_horizontal(10),
+            (
+                rect,
+                Rect {
+                    left: 8,
+                    width: 0,
+                    ..rect
+                }
+            )
+        );
+        assert_eq!(
+            rect.split_vertical(4),
+            (
+                rect,
+                Rect {
+                    top: 5,
+                    height: 0,
+                    ..rect
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn intersections() {
+        let a = Rect {
+            top: 0,
+            left: 0,
+            height: 4,
+            width: 4,
+        };
+        let overlapping = Rect {
+            top: 2,
+            left: 3,
+            height: 4,
+            width: 4,
+        };
+        assert_eq!(
+            a.intersection(&overlapping),
+            Some(Rect {
+                top: 2,
+                left: 3,
+                height: 2,
+                width: 1,
+            })
+        );
+        assert_eq!(a.intersection(&overlapping), overlapping.intersection(&a));
+
+        let disjoint = Rect {
+            top: 0,
+            left: 4,
+            height: 4,
+            width: 4,
+        };
+        assert_eq!(a.intersection(&disjoint), None);
+
+        let nested = Rect {
+            top: 1,
+            left: 1,
+            height: 2,
+            width: 2,
+        };
+        assert_eq!(a.intersection(&nested), Some(nested));
+    }
+
+    #[test]
+    fn measuring() {
+        let rect = Rect {
+            top: 1,
+            left: 2,
+            height: 3,
+            width: 4,
+        };
+        assert_eq!(rect.area(), 12);
+        assert!(!rect.is_empty());
+        assert_eq!(rect.right(), 6);
+        assert_eq!(rect.bottom(), 4);
+
+        let flat = Rect { height: 0, ..rect };
+        assert_eq!(flat.area(), 0);
+        assert!(flat.is_empty());
+        assert_eq!(flat.bottom(), flat.top);
+        assert!(Rect { width: 0, ..rect }.is_empty());
+        assert!(Rect::default().is_empty());
+
+        let huge = Rect {
+            top: u16::MAX,
+            left: u16::MAX,
+            height: u16::MAX,
+            width: u16::MAX,
+        };
+        assert_eq!(huge.area(), u16::MAX as usize * u16::MAX as usize);
+        assert_eq!((huge.right(), huge.bottom()), (u16::MAX, u16::MAX));
+    }
+
+    #[test]
+    fn containment() {
+        let rect = Rect {
+            top: 1,
+            left: 2,
+            height: 2,
+            width: 3,
+        };
+        assert!(rect.contains(2, 1));
+        assert!(rect.contains(4, 2));
+        assert!(!rect.contains(5, 2));
+        assert!(!rect.contains(4, 3));
+        assert!(!rect.contains(1, 1));
+        assert!(!Rect::default().contains(0, 0));
+    }
+
+    #[test]
+    fn shrinking() {
+        let rect = Rect {
+            top: 1,
+            left: 2,
+            height: 5,
+            width: 8,
+        };
+        assert_eq!(
+            rect.inner(1),
+            Rect {
+                top: 2,
+                left: 3,
+                height: 3,
+                width: 6,
+            }
+        );
+        assert_eq!(
+            rect.inner(3),
+            Rect {
+                top: 3,
+                left: 5,
+                height: 1,
+                width: 2,
+            }
+        );
+        assert_eq!(
+            rect.inner(100),
+            Rect {
+                top: 3,
+                left: 6,
+                height: 1,
+                width: 0,
+            }
+        );
+    }
 }
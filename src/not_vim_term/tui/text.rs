@@ -12,6 +12,7 @@ use crossterm::{
     Command,
 };
 use ropey::RopeSlice;
+use std::ops::Range;
 
 /// A piece of text which can be drawn to the terminal.
 pub struct Text<'a> {
@@ -23,6 +24,14 @@ pub struct Text<'a> {
     ///
     /// [`WrapMode::NoWrap(None)`]: WrapMode::NoWrap
     wrap_mode: WrapMode,
+    /// Char ranges of the text which are drawn with a particular [`Style`].
+    ///
+    /// If ranges overlap, the one added last wins.
+    highlights: Vec<(Range<usize>, Style)>,
+    /// The column of each line drawn at the left, which is more than 0 when scrolled sideways.
+    ///
+    /// Wrapped text is never scrolled sideways, so this is only used without wrapping.
+    first_column: usize,
 }
 
 impl<'a> Text<'a> {
@@ -33,19 +42,45 @@ impl<'a> Text<'a> {
         self.wrap_mode = wrap_mode;
     }
 
+    /// Scroll the text sideways so `first_column` is drawn at the left, when it isn't wrapped.
+    pub fn scroll(&mut self, first_column: usize) {
+        self.first_column = first_column;
+    }
+
+    /// Draw the chars in `range` with `style`.
+    pub fn highlight(&mut self, range: Range<usize>, style: Style) {
+        self.highlights.push((range, style));
+    }
+
+    /// How many rows the first `lines` lines of the text take up when drawn `width` columns wide.
+    pub fn rows(&self, lines: usize, width: u16) -> usize {
+        let lines = self.text.lines().take(lines).map(trim_newlines);
+        match self.wrap_mode {
+            WrapMode::Wrap if width > 0 => lines
+                .map(|line| line.len_chars() / width as usize + 1)
+                .sum(),
+            _ => lines.count(),
+        }
+    }
+
     /// See [`frame`].
     ///
     /// [`frame`]: crate::tui::frame
     pub fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
+        if frame.size().is_empty() {
+            return;
+        }
         match self.wrap_mode {
-            WrapMode::Wrap => self.render_wrap(frame, region),
-            WrapMode::NoWrap(Some(c)) => self.render_no_wrap_with_char(frame, region, c),
-            WrapMode::NoWrap(None) => self.render_no_wrap(frame, region),
+            WrapMode::Wrap => self.render_wrap(&mut frame),
+            WrapMode::NoWrap(Some(c)) => self.render_no_wrap_with_char(&mut frame, c),
+            WrapMode::NoWrap(None) => self.render_no_wrap(&mut frame),
         }
     }
 
     /// Renders the text in the case where `self.wrap_mode` is set to [`WrapMode::Wrap`].
-    fn render_no_wrap(&self, frame: &mut Frame, region: Rect) {
+    fn render_no_wrap(&self, frame: &mut Frame) {
+        let region = frame.size();
         for (y, line) in self
             .text
             .lines()
@@ -53,9 +88,10 @@ impl<'a> Text<'a> {
             .map(trim_newlines)
             .enumerate()
         {
-            for (x, c) in line.chars().take(region.width as usize).enumerate() {
-                let (x, y) = (x as u16, y as u16);
-                frame.set_char(c, x + region.left, y + region.top);
+            let line_start = self.text.line_to_char(y) + self.first_column;
+            let chars = line.chars().skip(self.first_column);
+            for (x, c) in chars.take(region.width as usize).enumerate() {
+                self.draw_char(frame, c, line_start + x, x as u16, y as u16);
             }
         }
     }
@@ -63,7 +99,8 @@ impl<'a> Text<'a> {
     /// Renders the text in the case where `self.wrap_mode` is set to [`WrapMode::NoWrap(Some(c))`], where c is passed in as a parameter here.
     ///
     /// [`WrapMode::NoWrap(Some(c))`]: WrapMode::NoWrap
-    fn render_no_wrap_with_char(&self, frame: &mut Frame, region: Rect, c: char) {
+    fn render_no_wrap_with_char(&self, frame: &mut Frame, c: char) {
+        let region = frame.size();
         for (y, line) in self
             .text
             .lines()
@@ -71,12 +108,13 @@ impl<'a> Text<'a> {
             .map(trim_newlines)
             .enumerate()
         {
-            for (x, c) in line.chars().take(region.width as usize).enumerate() {
-                let (x, y) = (x as u16, y as u16);
-                frame.set_char(c, x + region.left, y + region.top);
+            let line_start = self.text.line_to_char(y) + self.first_column;
+            let chars = line.chars().skip(self.first_column);
+            for (x, c) in chars.take(region.width as usize).enumerate() {
+                self.draw_char(frame, c, line_start + x, x as u16, y as u16);
             }
-            if line.len_chars() > region.width as usize {
-                frame.set_char(c, region.width - 1 + region.left, y as u16 + region.top);
+            if line.len_chars() > self.first_column + region.width as usize {
+                frame.set_char(c, region.width - 1, y as u16);
             }
         }
     }
@@ -84,18 +122,21 @@ impl<'a> Text<'a> {
     /// Renders the text in the case where `self.wrap_mode` is set to [`WrapMode::NoWrap(None)`].
     ///
     /// [`WrapMode::NoWrap(None)`]: WrapMode::NoWrap
-    fn render_wrap(&self, frame: &mut Frame, region: Rect) {
+    fn render_wrap(&self, frame: &mut Frame) {
+        let region = frame.size();
         let mut y = 0;
 
-        for line in self
+        for (line_no, line) in self
             .text
             .lines()
             .take(region.height as usize)
             .map(trim_newlines)
+            .enumerate()
         {
+            let line_start = self.text.line_to_char(line_no);
             let mut x = 0;
-            for c in line.chars() {
-                frame.set_char(c, x + region.left, y + region.top);
+            for (i, c) in line.chars().enumerate() {
+                self.draw_char(frame, c, line_start + i, x, y);
 
                 x += 1;
                 if x == region.width {
@@ -110,6 +151,32 @@ impl<'a> Text<'a> {
             }
         }
     }
+
+    /// Draw the char `c` found at char index `idx` of the text to `(x, y)`, applying any
+    /// highlight which covers it.
+    ///
+    /// Control chars other than tabs are drawn as `·`, since the terminal would act on them
+    /// rather than show them.
+    fn draw_char(&self, frame: &mut Frame, c: char, idx: usize, x: u16, y: u16) {
+        let c = if c.is_control() && c != '\t' { '·' } else { c };
+        frame.set_char(c, x, y);
+        if let Some((_, style)) = self
+            .highlights
+            .iter()
+            .rev()
+            .find(|(range, _)| range.contains(&idx))
+        {
+            frame.set_style(
+                *style,
+                Rect {
+                    top: y,
+                    left: x,
+                    height: 1,
+                    width: 1,
+                },
+            );
+        }
+    }
 }
 
 impl<'a, T> From<T> for Text<'a>
@@ -120,6 +187,8 @@ where
         Self {
             text: value.into(),
             wrap_mode: WrapMode::NoWrap(None),
+            highlights: Vec::new(),
+            first_column: 0,
         }
     }
 }
@@ -149,8 +218,9 @@ impl SingleText<'_> {
     ///
     /// [`frame`]: crate::tui::frame
     pub fn render(&self, frame: &mut Frame, region: Rect) {
+        let mut frame = frame.sub(region);
         for (x, c) in self.text.chars().enumerate() {
-            frame.set_char(c, x as u16 + region.left, region.top);
+            frame.set_char(c, x as u16, 0);
         }
     }
 }
@@ -209,7 +279,6 @@ impl Style {
     /// Take self and add a [`Modifier`] on to it.
     ///
     /// Returns Self to allow method chaining.
-    #[allow(dead_code)]
     pub fn add_modifier(mut self, modifier: Modifier) -> Self {
         self.modifiers |= modifier;
         self
@@ -0,0 +1,60 @@
+//! Telling pasted text apart from typing in terminals which don't support bracketed paste.
+//!
+//! Pasting into such a terminal sends the text as if it was typed very quickly, so a burst of
+//! keys arriving closer together than anyone can type is taken to be a paste. Things like
+//! auto-indent are turned off during a paste since the pasted text already has its indentation.
+
+use crate::config::{PASTE_BURST_GAP, PASTE_BURST_KEYS};
+use std::time::Instant;
+
+/// Watches when keys arrive to notice when text is being pasted.
+#[derive(Debug, Clone, Default)]
+pub struct PasteDetector {
+    /// When the last key arrived.
+    last: Option<Instant>,
+    /// How many keys in a row have arrived within [`PASTE_BURST_GAP`] of each other.
+    burst: usize,
+}
+
+impl PasteDetector {
+    /// Record a key arriving at `now`, returning whether it is part of a paste.
+    ///
+    /// A paste is [`PASTE_BURST_KEYS`] or more keys in a row, each within [`PASTE_BURST_GAP`] of
+    /// the one before. It ends as soon as there is a longer gap.
+    pub fn key(&mut self, now: Instant) -> bool {
+        let fast = self
+            .last
+            .is_some_and(|last| now.saturating_duration_since(last) <= PASTE_BURST_GAP);
+        self.burst = if fast { self.burst + 1 } else { 1 };
+        self.last = Some(now);
+        self.burst >= PASTE_BURST_KEYS
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn detecting_bursts() {
+        let mut detector = PasteDetector::default();
+        let start = Instant::now();
+        let typing = Duration::from_millis(150);
+        for i in 0..20 {
+            assert!(!detector.key(start + typing * i));
+        }
+
+        let start = start + typing * 20;
+        let pasting: Vec<bool> = (0..PASTE_BURST_KEYS as u32 + 2)
+            .map(|i| detector.key(start + Duration::from_millis(1) * i))
+            .collect();
+        assert!(!pasting[0]);
+        assert!(pasting[PASTE_BURST_KEYS - 1..]
+            .iter()
+            .all(|&pasting| pasting));
+
+        let after = start + Duration::from_secs(1);
+        assert!(!detector.key(after));
+    }
+}
@@ -0,0 +1,110 @@
+//! Reporting the progress of operations which can take a noticeable amount of time.
+//!
+//! Long operations take a callback which is given a [`Progress`] every so often, so whatever is
+//! running them can show how far along they are.
+
+use std::{fmt, io::Read};
+
+/// How far along an operation is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// How much of the work has been done.
+    pub done: u64,
+    /// How much work there is in total.
+    pub total: u64,
+}
+
+impl Progress {
+    /// How far along the operation is, from 0 to 100.
+    ///
+    /// An operation with no work to do is already finished.
+    pub fn percent(&self) -> u8 {
+        if self.total == 0 {
+            return 100;
+        }
+        (self.done.min(self.total) * 100 / self.total) as u8
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.percent())
+    }
+}
+
+/// A reader which reports the [`Progress`] of reading `total` bytes through it.
+///
+/// To avoid flooding whatever is listening, progress is only reported when the percentage
+/// changes.
+pub struct ProgressReader<R, F> {
+    /// The reader being read from.
+    inner: R,
+    /// The progress so far.
+    progress: Progress,
+    /// The percentage last reported, if anything has been.
+    reported: Option<u8>,
+    /// Called with the progress whenever it changes.
+    report: F,
+}
+
+impl<R, F: FnMut(Progress)> ProgressReader<R, F> {
+    /// Create a [`ProgressReader`] which expects to read `total` bytes from `inner`.
+    pub fn new(inner: R, total: u64, report: F) -> Self {
+        Self {
+            inner,
+            progress: Progress { done: 0, total },
+            reported: None,
+            report,
+        }
+    }
+}
+
+impl<R: Read, F: FnMut(Progress)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.done += n as u64;
+        if n == 0 {
+            // Finished, even if there was less to read than expected.
+            self.progress.total = self.progress.done;
+        }
+        let percent = self.progress.percent();
+        if self.reported != Some(percent) {
+            self.reported = Some(percent);
+            (self.report)(self.progress);
+        }
+        Ok(n)
+    }
+}
+
+impl<R, F> fmt::Debug for ProgressReader<R, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReader")
+            .field("progress", &self.progress)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percentages() {
+        assert_eq!(Progress { done: 0, total: 0 }.percent(), 100);
+        assert_eq!(Progress { done: 1, total: 3 }.percent(), 33);
+        assert_eq!(Progress { done: 5, total: 3 }.percent(), 100);
+        assert_eq!(Progress { done: 1, total: 2 }.to_string(), "50%");
+    }
+
+    #[test]
+    fn reading_reports_progress() {
+        let data = [0u8; 1000];
+        let mut reported = Vec::new();
+        let mut reader = ProgressReader::new(&data[..], 1000, |progress: Progress| {
+            reported.push(progress.percent())
+        });
+        let mut buf = [0; 250];
+        while reader.read(&mut buf).unwrap() != 0 {}
+        assert_eq!(reported, [25, 50, 75, 100]);
+    }
+}
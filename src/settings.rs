@@ -0,0 +1,264 @@
+//! Settings which can be changed while the editor is running using `:set`.
+//!
+//! The initial values come from [`config`].
+//!
+//! [`config`]: crate::config
+
+use anyhow::{bail, Context};
+
+/// All the runtime settings of the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Whether misspelled words are highlighted.
+    pub spell: bool,
+    /// Whether to warn about lines which mix tabs and spaces in their indentation.
+    pub mixed_indent: bool,
+    /// Whether writing the buffer is disallowed.
+    pub readonly: bool,
+    /// Whether new lines start with the same indentation as the line before them.
+    pub autoindent: bool,
+    /// Whether modified buffers are written when switching away from them or when the terminal
+    /// loses focus.
+    pub autowrite: bool,
+    /// Whether each line is shown with its line number.
+    pub number: bool,
+    /// Whether each line is shown with how many lines it is from the cursor. With
+    /// [`Settings::number`] too, the cursor's line shows its line number.
+    pub relativenumber: bool,
+    /// Whether a guide is drawn at each level of indentation.
+    pub indent_guides: bool,
+    /// How many columns of spaces make up a level of indentation.
+    pub tab_width: usize,
+    /// How many lines `Ctrl-d` and `Ctrl-u` move. This is kept at half the height of the window
+    /// unless it is set to something else.
+    pub scroll: usize,
+    /// The pairs of chars `%` jumps between, and which [`Settings::autopairs`] closes.
+    pub match_pairs: Vec<(char, char)>,
+    /// Whether typing the first char of one of [`Settings::match_pairs`] in insert mode types the
+    /// second after it too.
+    pub autopairs: bool,
+    /// Whether backspacing at the start of a line joins it on to the line before, rather than
+    /// doing nothing.
+    pub backspace_joins_lines: bool,
+    /// Whether joining lines with `J` leaves the cursor in the column it was in, rather than
+    /// where the lines were joined.
+    pub join_keeps_column: bool,
+    /// The motions which wrap on to the previous or next line at the ends of a line, given by
+    /// their keys: `h` for moving left and `l` for moving right. The arrow keys move the same way.
+    pub which_wrap: Vec<char>,
+    /// Whether the buffer is shown as hex, with the offset, bytes and ASCII of each row.
+    pub hex: bool,
+    /// The most output (in bytes) a filter command like `:%!` may produce before it is aborted.
+    pub filter_limit: usize,
+    /// How many lines a command like `:g/pattern/d` or `:%!` may change before it asks for
+    /// confirmation first. At 0, it never asks.
+    pub confirm_lines: usize,
+}
+
+impl Settings {
+    /// Apply a single `:set` argument.
+    ///
+    /// Boolean settings are turned on with `name`, off with `noname` and toggled with `name!`.
+    /// Numeric settings are changed with `name=value`, as are lists of pairs like
+    /// `matchpairs=(:),<:>` and lists of keys like `whichwrap=h,l`.
+    pub fn set(&mut self, arg: &str) -> anyhow::Result<()> {
+        if let Some((name, value)) = arg.split_once('=') {
+            if let Some(pairs) = self.pairs(name) {
+                *pairs = parse_pairs(name, value)?;
+                return Ok(());
+            }
+            if let Some((keys, allowed)) = self.keys(name) {
+                *keys = parse_keys(name, value, allowed)?;
+                return Ok(());
+            }
+            let Some(number) = self.number(name) else {
+                if self.flag(name).is_some() {
+                    bail!("Setting `{name}` doesn't take a value");
+                }
+                bail!("Unknown setting `{name}`");
+            };
+            *number = value
+                .parse()
+                .with_context(|| format!("Invalid value for `{name}`: {value}"))?;
+            return Ok(());
+        }
+        let name = arg;
+        if self.number(name).is_some() || self.pairs(name).is_some() || self.keys(name).is_some() {
+            bail!("Setting `{name}` needs a value");
+        }
+
+        let (name, toggle) = match name.strip_suffix('!') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let (name, on) = match name.strip_prefix("no") {
+            Some(name) if self.flag(name).is_some() => (name, false),
+            _ => (name, true),
+        };
+        let Some(flag) = self.flag(name) else {
+            bail!("Unknown setting `{name}`");
+        };
+        *flag = if toggle { !*flag } else { on };
+        Ok(())
+    }
+
+    /// Get the boolean setting called `name`.
+    fn flag(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "spell" => Some(&mut self.spell),
+            "mixedindent" => Some(&mut self.mixed_indent),
+            "readonly" => Some(&mut self.readonly),
+            "autoindent" => Some(&mut self.autoindent),
+            "autowrite" => Some(&mut self.autowrite),
+            "autopairs" => Some(&mut self.autopairs),
+            "backspacejoins" => Some(&mut self.backspace_joins_lines),
+            "hex" => Some(&mut self.hex),
+            "indentguides" => Some(&mut self.indent_guides),
+            "joinkeepscolumn" => Some(&mut self.join_keeps_column),
+            "number" => Some(&mut self.number),
+            "relativenumber" => Some(&mut self.relativenumber),
+            _ => None,
+        }
+    }
+
+    /// Get the numeric setting called `name`.
+    fn number(&mut self, name: &str) -> Option<&mut usize> {
+        match name {
+            "confirmlines" => Some(&mut self.confirm_lines),
+            "filterlimit" => Some(&mut self.filter_limit),
+            "scroll" => Some(&mut self.scroll),
+            "tabwidth" => Some(&mut self.tab_width),
+            _ => None,
+        }
+    }
+
+    /// Get the setting called `name` which is a list of pairs of chars.
+    fn pairs(&mut self, name: &str) -> Option<&mut Vec<(char, char)>> {
+        match name {
+            "matchpairs" => Some(&mut self.match_pairs),
+            _ => None,
+        }
+    }
+
+    /// Get the setting called `name` which is a list of keys, along with the keys it may contain.
+    fn keys(&mut self, name: &str) -> Option<(&mut Vec<char>, &'static [char])> {
+        match name {
+            "whichwrap" => Some((&mut self.which_wrap, &['h', 'l'])),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the value of a setting like `whichwrap`, which is a comma-separated list of keys out of
+/// `allowed`.
+fn parse_keys(name: &str, value: &str, allowed: &[char]) -> anyhow::Result<Vec<char>> {
+    value
+        .split(',')
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if allowed.contains(&c) => Ok(c),
+                _ => bail!("Invalid key for `{name}`: {key}"),
+            }
+        })
+        .collect()
+}
+
+/// Parse the value of a setting like `matchpairs`, which is a comma-separated list of two
+/// different chars with a `:` between them.
+fn parse_pairs(name: &str, value: &str) -> anyhow::Result<Vec<(char, char)>> {
+    value
+        .split(',')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let chars: Vec<char> = pair.chars().collect();
+            match chars[..] {
+                [open, ':', close] if open != close => Ok((open, close)),
+                _ => bail!("Invalid pair for `{name}`: {pair}"),
+            }
+        })
+        .collect()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            spell: crate::config::SPELL,
+            mixed_indent: crate::config::MIXED_INDENT,
+            readonly: false,
+            autoindent: crate::config::AUTOINDENT,
+            autowrite: crate::config::AUTOWRITE,
+            hex: false,
+            indent_guides: crate::config::INDENT_GUIDES,
+            tab_width: crate::config::TAB_WIDTH,
+            match_pairs: crate::config::MATCH_PAIRS.to_vec(),
+            autopairs: crate::config::AUTOPAIRS,
+            backspace_joins_lines: crate::config::BACKSPACE_JOINS_LINES,
+            join_keeps_column: crate::config::JOIN_KEEPS_COLUMN,
+            which_wrap: crate::config::WHICH_WRAP.to_vec(),
+            scroll: 0,
+            number: crate::config::NUMBER,
+            relativenumber: crate::config::RELATIVE_NUMBER,
+            filter_limit: crate::config::FILTER_LIMIT,
+            confirm_lines: crate::config::CONFIRM_LINES,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn setting_flags() {
+        let mut settings = Settings {
+            spell: false,
+            ..Default::default()
+        };
+        settings.set("spell").unwrap();
+        assert!(settings.spell);
+        settings.set("nospell").unwrap();
+        assert!(!settings.spell);
+        settings.set("spell!").unwrap();
+        assert!(settings.spell);
+        assert!(settings.set("spell=3").is_err());
+        assert!(settings.set("notasetting").is_err());
+    }
+
+    #[test]
+    fn setting_numbers() {
+        let mut settings = Settings::default();
+        settings.set("filterlimit=10").unwrap();
+        assert_eq!(settings.filter_limit, 10);
+        assert!(settings.set("filterlimit").is_err());
+        assert!(settings.set("filterlimit=ten").is_err());
+        assert!(settings.set("nofilterlimit").is_err());
+        assert_eq!(settings.filter_limit, 10);
+    }
+
+    #[test]
+    fn setting_pairs() {
+        let mut settings = Settings::default();
+        settings.set("matchpairs=(:),{:},<:>").unwrap();
+        assert_eq!(settings.match_pairs, [('(', ')'), ('{', '}'), ('<', '>')]);
+        settings.set("matchpairs=").unwrap();
+        assert!(settings.match_pairs.is_empty());
+        assert!(settings.set("matchpairs").is_err());
+        assert!(settings.set("matchpairs=(:").is_err());
+        assert!(settings.set("matchpairs=(-)").is_err());
+        assert!(settings.set("matchpairs=|:|").is_err());
+    }
+
+    #[test]
+    fn setting_keys() {
+        let mut settings = Settings::default();
+        settings.set("whichwrap=h,l").unwrap();
+        assert_eq!(settings.which_wrap, ['h', 'l']);
+        settings.set("whichwrap=").unwrap();
+        assert!(settings.which_wrap.is_empty());
+        assert!(settings.set("whichwrap").is_err());
+        assert!(settings.set("whichwrap=x").is_err());
+        assert!(settings.set("whichwrap=hl").is_err());
+    }
+}
@@ -0,0 +1,35 @@
+//! Helpers shared by the tests.
+
+/// A path in the temporary directory for a test to write to, unique to the test and this process.
+///
+/// The file is removed along with its undo file when this is dropped, so they are cleaned up even
+/// if the test fails.
+#[derive(Debug)]
+pub(crate) struct TempFile(String);
+
+impl TempFile {
+    /// A path for the test using `name`, which is kept at the end so it can have an extension.
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("not-vim-{}-{name}", std::process::id()));
+        Self(
+            path.to_str()
+                .expect("the temporary directory should be UTF-8")
+                .to_owned(),
+        )
+    }
+
+    /// The path of the file.
+    pub(crate) fn path(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // The undo file is found from the file, so it has to go first.
+        if let Some(undo_file) = not_vim::editor::undo::file_for(&self.0) {
+            let _ = std::fs::remove_file(undo_file);
+        }
+        let _ = std::fs::remove_file(&self.0);
+    }
+}